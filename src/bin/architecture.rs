@@ -1,24 +1,28 @@
+use cargo_invoke::filter::Filter;
+use cargo_invoke::model::{module_order, module_path_for_file, Vis};
 use regex::Regex;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet};
 use std::env;
 use std::fs::{self, File};
 use std::io::{self, Write};
 use std::path::{Path, PathBuf};
 
+/// Everything extracted from the sources. Sorted collections and a sorted
+/// directory walk keep the written files the same from run to run.
 #[derive(Debug)]
 struct CodeComponents {
-    use_statements: HashSet<String>,
-    // Key = struct name, Value = struct definition
-    structs: HashMap<String, String>,
-    // We'll store each impl block with function signatures
-    impls: Vec<String>,
+    use_statements: BTreeSet<String>,
+    // Key = module path and struct name (`crate::net::Config`), Value = struct definition
+    structs: BTreeMap<String, String>,
+    // Each impl block with function signatures, with the module it is in
+    impls: Vec<(String, String)>,
 }
 
 impl CodeComponents {
     fn new() -> Self {
         CodeComponents {
-            use_statements: HashSet::new(),
-            structs: HashMap::new(),
+            use_statements: BTreeSet::new(),
+            structs: BTreeMap::new(),
             impls: Vec::new(),
         }
     }
@@ -27,26 +31,38 @@ impl CodeComponents {
         self.use_statements.extend(statements);
     }
 
-    fn add_structs(&mut self, structs: Vec<String>) {
-        // We'll parse out the struct name to keep them unique in a HashMap
+    fn add_structs(&mut self, module: &str, structs: Vec<String>) {
+        // Same-named structs in different modules are different types, so key
+        // by path and record the module in a marker comment for the readers.
         let struct_name_regex =
             Regex::new(r"(?m)^(?:#\[[^\]]+\]\s*)*(?:pub\s+)?struct\s+(\w+)").unwrap();
         for s in structs {
             if let Some(capt) = struct_name_regex.captures(&s) {
-                let name = capt[1].to_string();
-                self.structs.insert(name, s.clone());
+                let key = format!("{}::{}", module, &capt[1]);
+                self.structs
+                    .insert(key, format!("// module: {}\n{}", module, s));
             }
         }
     }
 
-    fn add_impls(&mut self, mut impls: Vec<String>) {
-        self.impls.append(&mut impls);
+    fn merge(&mut self, other: CodeComponents) {
+        self.use_statements.extend(other.use_statements);
+        self.structs.extend(other.structs);
+        self.impls.extend(other.impls);
+    }
+
+    fn add_impls(&mut self, module: &str, impls: Vec<String>) {
+        self.impls
+            .extend(impls.into_iter().map(|i| (module.to_string(), i)));
     }
 }
 
 struct CodeExtractor {
     use_regex: Regex,
+    // group(1) = `pub`, group(2) = struct name
     struct_regex: Regex,
+    // A private field line inside a struct body, dropped by `--pub-only`
+    private_field_regex: Regex,
     // We'll capture entire impl block (with group(1) = struct name, group(2) = block contents)
     impl_regex: Regex,
     // A regex for function signatures, capturing function name and optional return type
@@ -58,7 +74,10 @@ impl CodeExtractor {
         CodeExtractor {
             use_regex: Regex::new(r"^\s*use\s+[^;]+;").unwrap(),
             struct_regex: Regex::new(
-                r"(?m)^(?:#\[[^\]]+\]\s*)*(?:pub\s+)?struct\s+\w+(?:<[^>]+>)?\s*\{[^}]*\}"
+                r"(?m)^(?:#\[[^\]]+\]\s*)*(pub\s+)?struct\s+(\w+)(?:<[^>]+>)?\s*\{[^}]*\}"
+            ).unwrap(),
+            private_field_regex: Regex::new(
+                r"(?m)^[ \t]*(?:pub\s*\([^)]*\)\s*)?[A-Za-z_]\w*\s*:[^\n]*\n",
             ).unwrap(),
             impl_regex: Regex::new(
                 r"(?ms)^\s*(?:#\[[^\]]*\])*\s*impl(?:<[^>]+>)?\s+(?:\w+(?:<[^>]+>)?\s+for\s+)?(\w+)(?:<[^>]+>)?\s*(?:where\s+[^{]*?)?\s*\{(.*)\n\s*\}\s*"
@@ -69,8 +88,9 @@ impl CodeExtractor {
                                     }
     }
 
-    fn extract_components(&self, content: &str) -> CodeComponents {
+    fn extract_components(&self, content: &str, module: &str, filter: &Filter) -> CodeComponents {
         let mut components = CodeComponents::new();
+        let mut dropped = BTreeSet::new();
 
         // **a. Extract Use Statements**
         for use_cap in self.use_regex.find_iter(content) {
@@ -78,14 +98,30 @@ impl CodeExtractor {
         }
 
         // **b. Extract Struct Definitions**
-        for struct_cap in self.struct_regex.find_iter(content) {
-            components.add_structs(vec![struct_cap.as_str().to_string()]);
+        for struct_cap in self.struct_regex.captures_iter(content) {
+            let name = &struct_cap[2];
+            if !filter.allows_type(module, name, struct_cap.get(1).is_some()) {
+                dropped.insert(name.to_string());
+                continue;
+            }
+            let mut definition = struct_cap[0].to_string();
+            if filter.pub_only {
+                definition = self
+                    .private_field_regex
+                    .replace_all(&definition, "")
+                    .into_owned();
+            }
+            components.add_structs(module, vec![definition]);
         }
 
         // **c. Extract Impl Blocks**
         for impl_cap in self.impl_regex.captures_iter(content) {
             let struct_name = impl_cap.get(1).map_or("???", |m| m.as_str());
             let impl_content = impl_cap.get(2).unwrap().as_str();
+            // Impls carry no visibility; they follow their struct when it is here
+            if dropped.contains(struct_name) || !filter.allows_type(module, struct_name, true) {
+                continue;
+            }
 
             let mut impl_block = String::new();
             impl_block.push_str(&format!("impl {} {{\n", struct_name));
@@ -99,6 +135,14 @@ impl CodeExtractor {
                 if fn_name == "new" {
                     continue;
                 }
+                let vis = if fn_cap[0].trim_start().starts_with("pub") {
+                    Vis::Public
+                } else {
+                    Vis::Private
+                };
+                if !filter.allows_member(vis) {
+                    continue;
+                }
 
                 // Extract the entire function signature
                 let fn_sig_match = fn_cap.get(0).unwrap().as_str().trim();
@@ -107,8 +151,8 @@ impl CodeExtractor {
                 impl_block.push_str(&format!("    {};\n", fn_sig));
             }
 
-            impl_block.push_str("}");
-            components.add_impls(vec![impl_block]);
+            impl_block.push('}');
+            components.add_impls(module, vec![impl_block]);
         }
 
         components
@@ -116,7 +160,11 @@ impl CodeExtractor {
 }
 
 // Recursively process directories
-fn process_directory(dir_path: &Path) -> io::Result<CodeComponents> {
+fn process_directory(
+    src_dir: &Path,
+    dir_path: &Path,
+    filter: &Filter,
+) -> io::Result<CodeComponents> {
     println!("Processing directory: {:?}", dir_path);
     let extractor = CodeExtractor::new();
     let mut all_components = CodeComponents::new();
@@ -133,25 +181,27 @@ fn process_directory(dir_path: &Path) -> io::Result<CodeComponents> {
         return Ok(all_components);
     }
 
-    for entry in fs::read_dir(dir_path)? {
-        let entry = entry?;
-        let path = entry.path();
+    let mut paths = fs::read_dir(dir_path)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    paths.sort();
+
+    for path in paths {
         println!("Found entry: {:?}", path);
         if path.is_dir() {
             println!("Processing subdirectory: {:?}", path);
-            let sub_components = process_directory(&path)?;
-            all_components.add_use_statements(sub_components.use_statements.into_iter().collect());
-            all_components
-                .add_structs(sub_components.structs.into_iter().map(|(_, v)| v).collect());
-            all_components.add_impls(sub_components.impls);
+            let sub_components = process_directory(src_dir, &path, filter)?;
+            all_components.merge(sub_components);
         } else if path.extension().and_then(|s| s.to_str()) == Some("rs") {
             println!("Processing Rust file: {:?}", path);
             let content = fs::read_to_string(&path)?;
-            let components = extractor.extract_components(&content);
+            let module = module_path_for_file(src_dir, &path);
+            if !filter.allows_module(&module, false) {
+                continue;
+            }
+            let components = extractor.extract_components(&content, &module, filter);
             println!("Found {} impls in {:?}", components.impls.len(), path);
-            all_components.add_use_statements(components.use_statements.into_iter().collect());
-            all_components.add_structs(components.structs.into_iter().map(|(_, v)| v).collect());
-            all_components.add_impls(components.impls);
+            all_components.merge(components);
         }
     }
 
@@ -162,7 +212,9 @@ fn main() -> io::Result<()> {
     println!("Running architecture extraction...");
 
     // Get command line arguments
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().collect();
+    let filter = Filter::from_args(&mut args)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
 
     // Get the base directory from command line argument or use current directory
     let base_dir = if args.len() > 1 {
@@ -186,11 +238,11 @@ fn main() -> io::Result<()> {
     fs::create_dir_all(&arch_dir)?;
 
     // Extract components
-    let components = process_directory(&src_dir)?;
+    let components = process_directory(&src_dir, &src_dir, &filter)?;
 
     // Debug: Print captured impls
     println!("Captured impls:");
-    for impl_block in &components.impls {
+    for (_, impl_block) in &components.impls {
         println!("{}", impl_block);
     }
 
@@ -213,7 +265,7 @@ fn main() -> io::Result<()> {
 // Helper functions for writing files
 fn write_file(
     path: &Path,
-    use_statements: &HashSet<String>,
+    use_statements: &BTreeSet<String>,
     content: &[String],
     is_prelude: bool,
     sort: bool,
@@ -221,9 +273,7 @@ fn write_file(
     let mut file = File::create(path)?;
 
     if is_prelude {
-        let mut sorted_uses: Vec<_> = use_statements.iter().collect();
-        sorted_uses.sort();
-        for stmt in sorted_uses {
+        for stmt in use_statements {
             writeln!(file, "{}", stmt)?;
         }
     } else {
@@ -231,7 +281,7 @@ fn write_file(
 
         let mut content_to_write = content.to_vec();
         if sort {
-            content_to_write.sort_by(|a, b| extract_name(a).cmp(&extract_name(b)));
+            content_to_write.sort_by_key(|a| extract_name(a));
         }
 
         for item in &content_to_write {
@@ -244,7 +294,7 @@ fn write_file(
 
 fn extract_name(s: &str) -> String {
     for line in s.lines() {
-        if line.trim_start().starts_with('#') {
+        if line.trim_start().starts_with('#') || line.trim_start().starts_with("//") {
             continue;
         }
         if line.contains("struct") || line.contains("impl") {
@@ -269,32 +319,99 @@ fn write_architecture_files(arch_dir: &Path, components: &CodeComponents) -> io:
         false,
     )?;
 
-    // structs.rs
-    let mut sorted_structs: Vec<&String> = components.structs.values().collect();
-    if sorted_structs.len() > 1 {
-        sorted_structs.sort_by(|a, b| extract_name(a).cmp(&extract_name(b)));
-    }
-    let struct_contents: Vec<String> = sorted_structs.into_iter().cloned().collect();
+    // structs.rs, by module path and then name
+    let mut sorted_structs: Vec<(&String, &String)> = components.structs.iter().collect();
+    sorted_structs.sort_by(|(a, a_def), (b, b_def)| {
+        let module = |path: &'_ str| path.rsplit_once("::").map_or("", |(m, _)| m).to_string();
+        module_order(&module(a), &module(b))
+            .then_with(|| extract_name(a_def).cmp(&extract_name(b_def)))
+            .then_with(|| a.cmp(b))
+    });
+    let struct_contents: Vec<String> = sorted_structs.into_iter().map(|(_, s)| s.clone()).collect();
 
     write_file(
         &arch_dir.join("structs.rs"),
-        &HashSet::new(),
+        &BTreeSet::new(),
         &struct_contents,
         false,
         false,
     )?;
 
-    // impls.rs
+    // impls.rs, by module path and then type name; blocks for the same type
+    // keep their source order
     let mut sorted_impls = components.impls.clone();
-    sorted_impls.sort_by(|a, b| extract_name(a).cmp(&extract_name(b)));
+    sorted_impls.sort_by(|(a_module, a), (b_module, b)| {
+        module_order(a_module, b_module).then_with(|| extract_name(a).cmp(&extract_name(b)))
+    });
+    let impl_contents: Vec<String> = sorted_impls.into_iter().map(|(_, i)| i).collect();
 
     write_file(
         &arch_dir.join("impls.rs"),
-        &HashSet::new(),
-        &sorted_impls,
+        &BTreeSet::new(),
+        &impl_contents,
         false,
         false,
     )?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_drops_structs_impls_and_private_members() {
+        let source = r#"
+pub struct Server {
+    pub port: u16,
+    pub(crate) secret: String,
+    handle: Handle,
+}
+
+struct Handle {
+    id: u64,
+}
+
+impl Server {
+    pub fn start(&self) -> Handle {
+    }
+    fn restart(&self) {
+    }
+}
+"#;
+        let extractor = CodeExtractor::new();
+
+        let all = extractor.extract_components(source, "crate::net", &Filter::default());
+        assert_eq!(all.structs.len(), 2);
+        assert_eq!(all.impls.len(), 1);
+
+        let excluded = Filter {
+            exclude: vec!["crate::net::Server".to_string()],
+            ..Filter::default()
+        };
+        let components = extractor.extract_components(source, "crate::net", &excluded);
+        assert_eq!(
+            components.structs.keys().collect::<Vec<_>>(),
+            ["crate::net::Handle"]
+        );
+        assert!(components.impls.is_empty());
+
+        let pub_only = Filter {
+            pub_only: true,
+            ..Filter::default()
+        };
+        let components = extractor.extract_components(source, "crate::net", &pub_only);
+        assert_eq!(
+            components.structs["crate::net::Server"],
+            "// module: crate::net\npub struct Server {\n    pub port: u16,\n}"
+        );
+        assert_eq!(
+            components.impls,
+            [(
+                "crate::net".to_string(),
+                "impl Server {\n    pub fn start(&self) -> Handle;\n}".to_string()
+            )]
+        );
+    }
+}
@@ -0,0 +1,37 @@
+use anyhow::{Context, Result};
+use cargo_invoke::filter::Filter;
+use cargo_invoke::model::CrateModel;
+use cargo_invoke::theme::Theme;
+use cargo_invoke::topology::{NodeKind, Topology};
+use std::fs;
+use std::path::Path;
+
+fn main() -> Result<()> {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let filter = Filter::from_args(&mut args)?;
+    let theme = Theme::from_args(&mut args)?;
+
+    let mut model = CrateModel::load(Path::new("src"))?;
+    filter.apply(&mut model);
+    let topology = Topology::from_model(&model);
+
+    let count =
+        |kind: fn(&NodeKind) -> bool| topology.nodes.values().filter(|n| kind(&n.kind)).count();
+    println!(
+        "Found {} async functions, {} spawned tasks/threads and {} channels",
+        count(|k| matches!(k, NodeKind::Function { is_async: true })),
+        count(|k| matches!(k, NodeKind::Task | NodeKind::Thread)),
+        count(|k| matches!(k, NodeKind::Channel)),
+    );
+
+    fs::create_dir_all("diagrams").context("Failed to create 'diagrams/' directory.")?;
+    let output_path = Path::new("diagrams/async_topology.mermaid");
+    fs::write(output_path, topology.to_mermaid())
+        .with_context(|| format!("Failed to write {}", output_path.display()))?;
+    if let Some(theme) = &theme {
+        theme.apply_to_file(output_path)?;
+    }
+    println!("Generated diagram at {}", output_path.display());
+
+    Ok(())
+}
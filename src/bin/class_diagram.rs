@@ -1,412 +1,543 @@
-use std::collections::{HashMap, HashSet};
+use cargo_invoke::filter::Filter;
+use cargo_invoke::focus::{Focus, FOCUS_STYLE};
+use cargo_invoke::mermaid::{self, class_member_type};
+use cargo_invoke::model::{
+    node_names, type_order, CrateModel, Method, Module, NodeName, TypeDef, TypeKind, Variant,
+};
+use cargo_invoke::theme::Theme;
+use cargo_invoke::types::{display_type, TypeAnalyzer, TypeRef};
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs::{self, File};
 use std::io::Write;
 use std::path::Path;
-use syn::{
-    parse_file, Fields, FnArg, GenericArgument, ImplItem, ItemEnum, ItemImpl, ItemStruct, Pat,
-    PathArguments, ReturnType, Type, TypePath,
-};
-
-#[derive(Debug)]
-struct StructInfo {
-    name: String,
-    fields: Vec<FieldInfo>,
-    methods: Vec<MethodInfo>,
-}
+use syn::{FnArg, Pat, ReturnType, Type};
 
-#[derive(Debug)]
-struct EnumInfo {
-    name: String,
-    variants: Vec<String>,
-}
-
-#[derive(Debug, Hash, Eq, PartialEq, Clone)]
+/// Types are identified by their full path, e.g. `crate::modules::net::Config`.
+#[derive(Debug, Hash, Eq, PartialEq, Ord, PartialOrd, Clone)]
 struct Relationship {
     from: String,
     to: String,
-    relationship_type: String,
+    kind: RelationshipKind,
+    /// How many `to` values one `from` holds: `1`, `0..1` or `*`. Only set for fields.
+    multiplicity: Option<&'static str>,
+    label: String,
 }
 
-#[derive(Debug)]
-struct FieldInfo {
-    name: String,
-    type_name: String,
+/// UML relationship kinds, strongest first.
+#[derive(Debug, Hash, Eq, PartialEq, Ord, PartialOrd, Clone, Copy)]
+enum RelationshipKind {
+    /// An owned field: the part lives and dies with the whole.
+    Composition,
+    /// A field behind `Rc`/`Arc`/`Weak`, a reference or a raw pointer.
+    Aggregation,
+    /// A trait implemented by the type.
+    Realization,
+    /// A type only seen in method parameters or return types.
+    Dependency,
 }
 
-#[derive(Debug)]
-struct MethodInfo {
-    name: String,
-    params: Vec<ParamInfo>,
-    return_type: String,
-}
-
-#[derive(Debug)]
-struct ParamInfo {
-    name: String,
-    type_name: String,
-}
-
-#[derive(Debug)]
-struct TypeInfo {
-    base_type: String,
-    generic_params: Vec<String>,
+impl RelationshipKind {
+    fn arrow(self) -> &'static str {
+        match self {
+            RelationshipKind::Composition => "*--",
+            RelationshipKind::Aggregation => "o--",
+            RelationshipKind::Realization => "..|>",
+            RelationshipKind::Dependency => "..>",
+        }
+    }
 }
 
-fn main() -> std::io::Result<()> {
-    let src_dir = Path::new("src/modules");
-    let mut structs = Vec::new();
-    let mut enums = Vec::new();
+fn main() -> anyhow::Result<()> {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let focus = Focus::from_args(&mut args)?;
+    let filter = Filter::from_args(&mut args)?;
+    let theme = Theme::from_args(&mut args)?;
+
+    // The whole crate is loaded so `use crate::...` paths and impls in other
+    // files resolve, but only types under src/modules are drawn.
+    let src_dir = Path::new("src");
+    let scope = src_dir.join("modules");
+    let mut model = if scope.exists() {
+        CrateModel::load(src_dir)?
+    } else {
+        CrateModel::default()
+    };
+    filter.apply(&mut model);
+    let analyzer = TypeAnalyzer::from_model(&model);
 
     fs::create_dir_all("diagrams")?;
-    visit_dirs(src_dir, &mut structs, &mut enums)?;
 
-    let relationships = extract_relationships(&structs, &enums);
+    let mut types: BTreeMap<String, (&Module, &TypeDef)> = model
+        .types()
+        .filter(|(m, _)| m.file.starts_with(&scope))
+        .map(|(m, t)| (format!("{}::{}", m.path, t.name), (m, t)))
+        .collect();
 
-    let mermaid = generate_mermaid_diagram(&structs, &enums, &relationships);
-    let mut file = File::create("diagrams/class_diagram.mermaid")?;
-    file.write_all(mermaid.as_bytes())?;
+    let mut relationships = extract_relationships(&model, &analyzer, &types);
+    relationships.retain(|r| filter.allows_reference(&r.to));
 
-    Ok(())
-}
+    // Traits are only drawn when something implements or uses them
+    let referenced: BTreeSet<&String> = relationships.iter().map(|r| &r.to).collect();
+    types.retain(|path, (_, t)| t.kind != TypeKind::Trait || referenced.contains(path));
 
-fn visit_dirs(
-    dir: &Path,
-    structs: &mut Vec<StructInfo>,
-    enums: &mut Vec<EnumInfo>,
-) -> std::io::Result<()> {
-    if dir.is_dir() {
-        for entry in fs::read_dir(dir)? {
-            let entry = entry?;
-            let path = entry.path();
-            if path.is_dir() {
-                visit_dirs(&path, structs, enums)?;
-            } else if path.extension().map_or(false, |ext| ext == "rs") {
-                process_file(&path, structs, enums)?;
-            }
-        }
-    }
-    Ok(())
-}
+    let mut output_path = String::from("diagrams/class_diagram");
+    if let Some(focus) = &focus {
+        let edges = relationships
+            .iter()
+            .map(|r| (r.from.as_str(), r.to.as_str()));
+        let kept = focus.neighbourhood(types.keys().map(String::as_str), edges)?;
 
-fn process_file(
-    path: &Path,
-    structs: &mut Vec<StructInfo>,
-    enums: &mut Vec<EnumInfo>,
-) -> std::io::Result<()> {
-    let content = fs::read_to_string(path)?;
-    let syntax = parse_file(&content).expect("Failed to parse Rust file");
-
-    let mut struct_map: HashMap<String, usize> = HashMap::new();
-
-    // First pass: collect structs and enums
-    for item in syntax.items.iter() {
-        match item {
-            syn::Item::Struct(item_struct) => {
-                let struct_info = process_struct(item_struct);
-                struct_map.insert(struct_info.name.clone(), structs.len());
-                structs.push(struct_info);
-            }
-            syn::Item::Enum(item_enum) => {
-                let enum_info = process_enum(item_enum);
-                enums.push(enum_info);
-            }
-            _ => {}
+        types.retain(|path, _| kept.contains(path));
+        relationships.retain(|r| kept.contains(&r.from) && kept.contains(&r.to));
+        output_path.push_str(&focus.file_suffix());
+    }
+    output_path.push_str(".mermaid");
+
+    let names = node_names(
+        types
+            .keys()
+            .chain(relationships.iter().map(|r| &r.to))
+            .map(String::as_str),
+    );
+    let mut mermaid = generate_mermaid_diagram(&types, &names, &relationships);
+    if let Some(focus) = &focus {
+        mermaid.push_str(&format!("\n    classDef focus {}\n", FOCUS_STYLE));
+        for path in types.keys().filter(|p| focus.matches(p)) {
+            mermaid.push_str(&format!("    cssClass \"{}\" focus\n", names[path].id));
         }
     }
 
-    // Second pass: collect impl blocks
-    for item in syntax.items.iter() {
-        if let syn::Item::Impl(item_impl) = item {
-            if let Type::Path(type_path) = &*item_impl.self_ty {
-                let struct_name = type_path
-                    .path
-                    .segments
-                    .last()
-                    .map(|seg| seg.ident.to_string())
-                    .unwrap_or_default();
-
-                if let Some(&struct_index) = struct_map.get(&struct_name) {
-                    process_impl(item_impl, &mut structs[struct_index]);
-                }
-            }
-        }
+    let mut file = File::create(&output_path)?;
+    file.write_all(mermaid.as_bytes())?;
+    if let Some(theme) = &theme {
+        theme.apply_to_file(Path::new(&output_path))?;
     }
+    println!("Generated diagram at {}", output_path);
 
     Ok(())
 }
 
-fn process_enum(item_enum: &ItemEnum) -> EnumInfo {
-    EnumInfo {
-        name: item_enum.ident.to_string(),
-        variants: item_enum
-            .variants
-            .iter()
-            .map(|variant| variant.ident.to_string())
-            .collect(),
-    }
-}
-
-fn process_struct(item_struct: &ItemStruct) -> StructInfo {
-    let name = item_struct.ident.to_string();
-    let mut fields = Vec::new();
-
-    if let Fields::Named(named_fields) = &item_struct.fields {
-        for field in &named_fields.named {
-            if let Some(ident) = &field.ident {
-                fields.push(FieldInfo {
-                    name: ident.to_string(),
-                    type_name: get_type_name(&field.ty),
-                });
+fn extract_relationships(
+    model: &CrateModel,
+    analyzer: &TypeAnalyzer,
+    types: &BTreeMap<String, (&Module, &TypeDef)>,
+) -> BTreeSet<Relationship> {
+    let mut relationships = BTreeSet::new();
+
+    for (path, (module, ty)) in types {
+        let analyzer = analyzer.in_module(&module.path).with_params(&ty.generics);
+        let mut relate = |to: String, kind, multiplicity, label: &str| {
+            relationships.insert(Relationship {
+                from: path.clone(),
+                to,
+                kind,
+                multiplicity,
+                label: label.to_string(),
+            });
+        };
+        // Resolves a reference from this type's module to a drawn type.
+        let target = |written: &str| {
+            model
+                .resolve(&module.path, written)
+                .map(|(m, t)| format!("{}::{}", m.path, t.name))
+                .filter(|p| types.contains_key(p))
+        };
+
+        // Fields: owned values are composition, shared or borrowed ones aggregation.
+        // Variant payloads count as fields of the enum, labelled with the variant,
+        // and are only present while the enum holds that variant.
+        let fields = ty.fields.iter().map(|f| (f, f.name.as_str(), false)).chain(
+            ty.variants
+                .iter()
+                .flat_map(|v| v.fields.iter().map(move |f| (f, v.name.as_str(), true))),
+        );
+        for (field, label, in_variant) in fields {
+            for type_ref in analyzer.analyze(&field.ty) {
+                let Some(to) = target(&type_ref.path) else {
+                    continue;
+                };
+                let kind = if type_ref.is_shared() || type_ref.is_borrowed() {
+                    RelationshipKind::Aggregation
+                } else {
+                    RelationshipKind::Composition
+                };
+                let multiplicity = match multiplicity(&type_ref) {
+                    "1" if in_variant => "0..1",
+                    many => many,
+                };
+                relate(to, kind, Some(multiplicity), label);
             }
         }
-    }
-
-    StructInfo {
-        name,
-        fields,
-        methods: Vec::new(),
-    }
-}
 
-fn process_impl(item_impl: &ItemImpl, struct_info: &mut StructInfo) {
-    for item in &item_impl.items {
-        if let ImplItem::Fn(method) = item {
-            // Skip new() constructors
-            if method.sig.ident == "new" {
-                continue;
+        // Traits implemented in this crate are realized
+        for trait_name in &ty.traits {
+            let trait_path = trait_name.split('<').next().unwrap_or(trait_name).trim();
+            if let Some(to) = target(trait_path).filter(|p| types[p].1.kind == TypeKind::Trait) {
+                relate(to, RelationshipKind::Realization, None, "");
             }
+        }
 
-            let mut params = Vec::new();
-
+        // Types seen only in signatures are dependencies; returned structs are created
+        for method in ty.methods.iter().filter(|m| m.name != "new") {
             for input in &method.sig.inputs {
-                match input {
-                    FnArg::Typed(pat_type) => {
-                        if let Pat::Ident(pat_ident) = &*pat_type.pat {
-                            params.push(ParamInfo {
-                                name: pat_ident.ident.to_string(),
-                                type_name: get_type_name(&pat_type.ty),
-                            });
-                        }
+                let FnArg::Typed(pat_type) = input else {
+                    continue;
+                };
+                for type_ref in analyzer.analyze(&pat_type.ty) {
+                    if let Some(to) = target(&type_ref.path) {
+                        relate(to, RelationshipKind::Dependency, None, "uses");
                     }
-                    FnArg::Receiver(_) => {}
                 }
             }
-
-            let return_type = match &method.sig.output {
-                ReturnType::Default => "void".to_string(),
-                ReturnType::Type(_, ty) => get_type_name(ty),
-            };
-
-            struct_info.methods.push(MethodInfo {
-                name: method.sig.ident.to_string(),
-                params,
-                return_type,
-            });
-        }
-    }
-}
-
-fn get_type_name(ty: &Type) -> String {
-    match ty {
-        Type::Path(TypePath { path, .. }) => {
-            let mut full_type = String::new();
-
-            if let Some(last_segment) = path.segments.last() {
-                full_type.push_str(&last_segment.ident.to_string());
-
-                if let PathArguments::AngleBracketed(args) = &last_segment.arguments {
-                    let generic_args: Vec<String> = args
-                        .args
-                        .iter()
-                        .filter_map(|arg| {
-                            if let GenericArgument::Type(ty) = arg {
-                                Some(get_type_name(ty))
-                            } else {
-                                None
-                            }
-                        })
-                        .collect();
-
-                    if !generic_args.is_empty() {
-                        full_type.push('<');
-                        full_type.push_str(&generic_args.join(", "));
-                        full_type.push('>');
-                    }
+            for type_ref in analyzer.analyze_return(&method.sig.output) {
+                if let Some(to) = target(&type_ref.path) {
+                    let label = if types[&to].1.kind == TypeKind::Struct {
+                        "creates"
+                    } else {
+                        "uses"
+                    };
+                    relate(to, RelationshipKind::Dependency, None, label);
                 }
             }
-
-            full_type
         }
-        Type::Reference(type_ref) => get_type_name(&type_ref.elem),
-        _ => "unknown".to_string(),
     }
-}
-
-fn extract_relationships(structs: &[StructInfo], enums: &[EnumInfo]) -> HashSet<Relationship> {
-    let mut relationships = HashSet::new();
-    let struct_names: HashSet<_> = structs.iter().map(|s| s.name.as_str()).collect();
-    let enum_names: HashSet<_> = enums.iter().map(|e| e.name.as_str()).collect();
-
-    for struct_info in structs {
-        // Process method return types for creation relationships
-        for method in &struct_info.methods {
-            let return_type_info = parse_type_with_generics(&method.return_type);
-
-            // Handle Result types
-            if return_type_info.base_type == "Result" {
-                relationships.insert(Relationship {
-                    from: struct_info.name.clone(),
-                    to: "Result".to_string(),
-                    relationship_type: "uses".to_string(),
-                });
-
-                // Check if Result contains a struct type
-                for inner_type in &return_type_info.generic_params {
-                    if struct_names.contains(inner_type.as_str()) {
-                        relationships.insert(Relationship {
-                            from: struct_info.name.clone(),
-                            to: inner_type.clone(),
-                            relationship_type: "creates".to_string(),
-                        });
-                    }
-                }
-            }
-            // Handle direct struct return types
-            else if struct_names.contains(return_type_info.base_type.as_str()) {
-                relationships.insert(Relationship {
-                    from: struct_info.name.clone(),
-                    to: return_type_info.base_type.clone(),
-                    relationship_type: "creates".to_string(),
-                });
-            }
-        }
 
-        // Process fields for has/uses relationships
-        for field in &struct_info.fields {
-            let type_info = parse_type_with_generics(&field.type_name);
-
-            // Handle enum types
-            if enum_names.contains(type_info.base_type.as_str()) {
-                relationships.insert(Relationship {
-                    from: struct_info.name.clone(),
-                    to: type_info.base_type.clone(),
-                    relationship_type: "has".to_string(),
-                });
-                continue;
-            }
+    // A dependency adds nothing when the two types are already related structurally
+    let structural: BTreeSet<(String, String)> = relationships
+        .iter()
+        .filter(|r| r.kind != RelationshipKind::Dependency)
+        .map(|r| (r.from.clone(), r.to.clone()))
+        .collect();
+    relationships.retain(|r| {
+        r.kind != RelationshipKind::Dependency
+            || (r.from != r.to && !structural.contains(&(r.from.clone(), r.to.clone())))
+    });
 
-            // Handle struct types
-            if struct_names.contains(type_info.base_type.as_str()) {
-                relationships.insert(Relationship {
-                    from: struct_info.name.clone(),
-                    to: type_info.base_type.clone(),
-                    relationship_type: "has".to_string(),
-                });
-            }
+    relationships
+}
 
-            // Handle special container types
-            match type_info.base_type.as_str() {
-                "Option" | "Vec" | "HashMap" => {
-                    // Add relationship to the container type
-                    relationships.insert(Relationship {
-                        from: struct_info.name.clone(),
-                        to: type_info.base_type.clone(),
-                        relationship_type: "uses".to_string(),
-                    });
-
-                    // Add relationships to the generic type parameters
-                    for generic_type in type_info.generic_params {
-                        if struct_names.contains(generic_type.as_str())
-                            || enum_names.contains(generic_type.as_str())
-                        {
-                            relationships.insert(Relationship {
-                                from: struct_info.name.clone(),
-                                to: generic_type,
-                                relationship_type: "contains".to_string(),
-                            });
-                        }
-                    }
-                }
-                _ => {}
-            }
-        }
+/// Multiplicity of a field's target: `*` behind a collection or map, `0..1`
+/// behind an `Option`, otherwise `1`.
+fn multiplicity(type_ref: &TypeRef) -> &'static str {
+    if type_ref.is_collection() {
+        "*"
+    } else if type_ref.is_optional() {
+        "0..1"
+    } else {
+        "1"
     }
+}
 
-    relationships
+/// `class Id~T~` or, for names declared in several modules, `class Id~T~["net::Config"]`.
+fn class_header(node: &NodeName, ty: &TypeDef) -> String {
+    mermaid::class_header(&node.id, &ty.name, &ty.generics, node.label.as_deref())
 }
 
-fn parse_type_with_generics(type_str: &str) -> TypeInfo {
-    let parts: Vec<&str> = type_str.split('<').collect();
-    let base_type = parts[0].trim().to_string();
-
-    let mut generic_params = Vec::new();
-    if parts.len() > 1 {
-        let generic_part = parts[1].trim_end_matches('>');
-        for param in generic_part.split(',') {
-            let clean_param = param
-                .trim()
-                .trim_start_matches('&')
-                .trim_start_matches("mut ")
-                .to_string();
-            generic_params.push(clean_param);
-        }
+/// A field, parameter or return type as written in a class member.
+fn member_type(ty: &Type) -> String {
+    class_member_type(&display_type(ty))
+}
+
+/// `Created(Order)`, `Active(session: Session)` or a bare `Idle`.
+fn variant_line(variant: &Variant) -> String {
+    if variant.fields.is_empty() {
+        return variant.name.clone();
     }
+    let payload = variant
+        .fields
+        .iter()
+        .map(|f| {
+            if f.name.parse::<usize>().is_ok() {
+                member_type(&f.ty)
+            } else {
+                format!("{}: {}", f.name, member_type(&f.ty))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("{}({})", variant.name, payload)
+}
 
-    TypeInfo {
-        base_type,
-        generic_params,
+/// `<<enum>>`/`<<trait>>` for the type's kind, then `<<derive: ...>>` for its derives.
+fn stereotypes(ty: &TypeDef) -> Vec<String> {
+    let mut stereotypes = Vec::new();
+    match ty.kind {
+        TypeKind::Enum => stereotypes.push("<<enum>>".to_string()),
+        TypeKind::Trait => stereotypes.push("<<trait>>".to_string()),
+        TypeKind::Struct => {}
     }
+    if !ty.derives.is_empty() {
+        stereotypes.push(format!("<<derive: {}>>", ty.derives.join(", ")));
+    }
+    stereotypes
+}
+
+/// `+name(param: Type) Return`, with `$` for associated functions without a
+/// receiver and `*` for trait methods without a default body.
+fn method_line(method: &Method) -> String {
+    let params = method
+        .sig
+        .inputs
+        .iter()
+        .filter_map(|input| match input {
+            FnArg::Typed(pat_type) => match &*pat_type.pat {
+                Pat::Ident(pat_ident) => Some(format!(
+                    "{}: {}",
+                    pat_ident.ident,
+                    member_type(&pat_type.ty)
+                )),
+                _ => None,
+            },
+            FnArg::Receiver(_) => None,
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    let return_type = match &method.sig.output {
+        ReturnType::Default => "void".to_string(),
+        ReturnType::Type(_, ty) => member_type(ty),
+    };
+    let classifier = if method.body.is_none() {
+        "*"
+    } else if method.is_static() {
+        "$"
+    } else {
+        ""
+    };
+    format!(
+        "{}{}({}) {}{}",
+        method.vis.uml_marker(),
+        method.name,
+        params,
+        return_type,
+        classifier
+    )
 }
 
 fn generate_mermaid_diagram(
-    structs: &[StructInfo],
-    enums: &[EnumInfo],
-    relationships: &HashSet<Relationship>,
+    types: &BTreeMap<String, (&Module, &TypeDef)>,
+    names: &BTreeMap<String, NodeName>,
+    relationships: &BTreeSet<Relationship>,
 ) -> String {
     let mut output = String::from("classDiagram\n");
 
-    // Add enums with their variants as attributes
-    for enum_info in enums {
-        output.push_str(&format!("    class {} {{\n", enum_info.name));
-        for variant in &enum_info.variants {
-            output.push_str(&format!("        +{}\n", variant));
-        }
-        output.push_str("    }\n\n");
-    }
+    let mut ordered: Vec<_> = types.iter().collect();
+    ordered.sort_by(|(_, a), (_, b)| type_order(**a, **b));
 
-    // Add structs with their fields and methods
-    for struct_info in structs {
-        output.push_str(&format!("    class {} {{\n", struct_info.name));
+    for (path, (_, ty)) in ordered {
+        output.push_str(&format!("    {} {{\n", class_header(&names[path], ty)));
+        for stereotype in stereotypes(ty) {
+            output.push_str(&format!("        {}\n", stereotype));
+        }
 
-        // Add fields
-        for field in &struct_info.fields {
-            output.push_str(&format!("        +{}: {}\n", field.name, field.type_name));
+        // Variants are always public
+        for variant in &ty.variants {
+            output.push_str(&format!("        +{}\n", variant_line(variant)));
         }
 
-        // Add methods
-        for method in &struct_info.methods {
-            let params = method
-                .params
-                .iter()
-                .map(|p| format!("{}: {}", p.name, p.type_name))
-                .collect::<Vec<_>>()
-                .join(", ");
+        for field in &ty.fields {
             output.push_str(&format!(
-                "        +{}({}) {}\n",
-                method.name, params, method.return_type
+                "        {}{}: {}\n",
+                field.vis.uml_marker(),
+                field.name,
+                member_type(&field.ty)
             ));
         }
 
+        // Methods, skipping new() constructors
+        for method in ty.methods.iter().filter(|m| m.name != "new") {
+            output.push_str(&format!("        {}\n", method_line(method)));
+        }
+
         output.push_str("    }\n\n");
     }
 
-    // Add relationships
+    // Add relationships, with multiplicities on field edges
     for rel in relationships {
-        output.push_str(&format!(
-            "    {} --> {} : {}\n",
-            rel.from, rel.to, rel.relationship_type
-        ));
+        let (from, to) = (&names[&rel.from].id, &names[&rel.to].id);
+        let arrow = match rel.multiplicity {
+            Some(many) => format!("\"1\" {} \"{}\"", rel.kind.arrow(), many),
+            None => rel.kind.arrow().to_string(),
+        };
+        if rel.label.is_empty() {
+            output.push_str(&format!("    {} {} {}\n", from, arrow, to));
+        } else {
+            output.push_str(&format!("    {} {} {} : {}\n", from, arrow, to, rel.label));
+        }
     }
 
     output
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(source: &str) -> String {
+        let model = CrateModel::from_source(Path::new("lib.rs"), source).unwrap();
+        let analyzer = TypeAnalyzer::from_model(&model);
+        let types: BTreeMap<String, (&Module, &TypeDef)> = model
+            .types()
+            .map(|(m, t)| (format!("{}::{}", m.path, t.name), (m, t)))
+            .collect();
+        let relationships = extract_relationships(&model, &analyzer, &types);
+        let names = node_names(types.keys().map(String::as_str));
+        generate_mermaid_diagram(&types, &names, &relationships)
+    }
+
+    #[test]
+    fn test_generics_use_mermaid_syntax() {
+        let diagram = render(
+            r#"
+            pub struct Cache<K, V> {
+                entries: HashMap<K, V>,
+                order: Vec<Option<K>>,
+            }
+            pub struct Slot<T> {
+                pub value: Option<T>,
+            }
+            impl<T> Slot<T> {
+                pub fn take(&mut self, into: Vec<T>) -> Option<Box<T>> { None }
+            }
+            "#,
+        );
+        assert!(
+            diagram.contains("    class Cache[\"Cache<K, V>\"] {\n"),
+            "{}",
+            diagram
+        );
+        assert!(diagram.contains("    class Slot~T~ {\n"), "{}", diagram);
+        assert!(
+            diagram.contains("        -entries: HashMap\n"),
+            "{}",
+            diagram
+        );
+        assert!(
+            diagram.contains("        -order: Vec~Option~K~~\n"),
+            "{}",
+            diagram
+        );
+        assert!(
+            diagram.contains("        +take(into: Vec~T~) Option~Box~T~~\n"),
+            "{}",
+            diagram
+        );
+        // Angle brackets only in the label
+        let members = diagram.replace("[\"Cache<K, V>\"]", "");
+        assert!(!members.contains(['<', '>']), "{}", diagram);
+    }
+
+    fn relationships(
+        source: &str,
+    ) -> Vec<(String, RelationshipKind, Option<&'static str>, String)> {
+        let model = CrateModel::from_source(Path::new("lib.rs"), source).unwrap();
+        let analyzer = TypeAnalyzer::from_model(&model);
+        let types: BTreeMap<String, (&Module, &TypeDef)> = model
+            .types()
+            .map(|(m, t)| (format!("{}::{}", m.path, t.name), (m, t)))
+            .collect();
+        extract_relationships(&model, &analyzer, &types)
+            .into_iter()
+            .map(|r| {
+                let to = r.to.rsplit("::").next().unwrap().to_string();
+                (to, r.kind, r.multiplicity, r.label)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_field_types_map_to_relationship_kinds() {
+        use RelationshipKind::*;
+        let found = relationships(
+            r#"
+            pub trait Vehicle { fn wheels(&self) -> usize; }
+            pub struct Engine;
+            pub struct Wheel;
+            pub struct Driver;
+            pub struct Garage;
+            pub struct Part;
+            pub struct Report;
+            pub struct Tool;
+            pub struct Car {
+                engine: Engine,
+                wheels: Vec<Wheel>,
+                parts: HashMap<u64, Part>,
+                driver: Option<Arc<Driver>>,
+                garage: &'static Garage,
+            }
+            impl Vehicle for Car { fn wheels(&self) -> usize { 4 } }
+            impl Car {
+                pub fn repair(&self, tool: Tool, garage: &Garage) -> Report { Report }
+            }
+            "#,
+        );
+        let expected = [
+            ("Driver", Aggregation, Some("0..1"), "driver"),
+            ("Engine", Composition, Some("1"), "engine"),
+            // Garage is only aggregated: repair() adds no dependency on it
+            ("Garage", Aggregation, Some("1"), "garage"),
+            ("Part", Composition, Some("*"), "parts"),
+            ("Report", Dependency, None, "creates"),
+            ("Tool", Dependency, None, "uses"),
+            ("Vehicle", Realization, None, ""),
+            ("Wheel", Composition, Some("*"), "wheels"),
+        ];
+        let expected: Vec<_> = expected
+            .into_iter()
+            .map(|(to, kind, many, label)| (to.to_string(), kind, many, label.to_string()))
+            .collect();
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn test_relationship_arrows_carry_multiplicities() {
+        let diagram = render(
+            r#"
+            pub struct Line;
+            pub struct Order { lines: Vec<Line>, first: Option<Line> }
+            "#,
+        );
+        assert!(
+            diagram.contains("    Order \"1\" *-- \"*\" Line : lines\n"),
+            "{}",
+            diagram
+        );
+        assert!(
+            diagram.contains("    Order \"1\" *-- \"0..1\" Line : first\n"),
+            "{}",
+            diagram
+        );
+    }
+
+    #[test]
+    fn test_enum_payloads_and_relationships() {
+        let source = r#"
+            pub struct Order;
+            pub struct Session;
+            pub struct Error;
+            pub enum Event {
+                Idle,
+                Created(Order, u64),
+                Active { session: Session, errors: Vec<Error> },
+            }
+        "#;
+        let diagram = render(source);
+        for line in [
+            "    class Event {\n        <<enum>>\n",
+            "        +Idle\n",
+            "        +Created(Order, u64)\n",
+            "        +Active(session: Session, errors: Vec~Error~)\n",
+            // A payload only exists while the enum holds that variant
+            "    Event \"1\" *-- \"0..1\" Order : Created\n",
+            "    Event \"1\" *-- \"0..1\" Session : Active\n",
+            "    Event \"1\" *-- \"*\" Error : Active\n",
+        ] {
+            assert!(
+                diagram.contains(line),
+                "{:?} missing from\n{}",
+                line,
+                diagram
+            );
+        }
+        assert_eq!(relationships(source).len(), 3);
+    }
+}
@@ -1,5 +1,5 @@
-use regex::Regex;
-use std::collections::HashSet;
+use cargo_invoke::mermaid::{Diagram, Node, Statement};
+use cargo_invoke::theme::Theme;
 use std::fs::{self, File};
 use std::io::{self, Write};
 use std::path::Path;
@@ -17,9 +17,16 @@ struct ClassAssignment {
 }
 
 fn main() -> io::Result<()> {
-    let args: Vec<String> = std::env::args().collect();
+    let mut args: Vec<String> = std::env::args().collect();
+    let theme = match Theme::from_args(&mut args) {
+        Ok(theme) => theme,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
+        }
+    };
     if args.len() != 2 {
-        eprintln!("Usage: {} <input_file.mermaid>", args[0]);
+        eprintln!("Usage: {} <input_file.mermaid> [--theme <NAME>]", args[0]);
         std::process::exit(1);
     }
 
@@ -30,7 +37,10 @@ fn main() -> io::Result<()> {
     }
 
     let content = fs::read_to_string(input_path)?;
-    let cleaned_content = clean_mermaid_file(&content)?;
+    let mut cleaned_content = clean_mermaid_file(&content)?;
+    if let Some(theme) = &theme {
+        cleaned_content = theme.apply_to_source(&cleaned_content);
+    }
 
     // Write to new file with "_clean" suffix
     let output_path = input_path.with_file_name(format!(
@@ -44,145 +54,121 @@ fn main() -> io::Result<()> {
 }
 
 fn clean_mermaid_file(content: &str) -> io::Result<String> {
-    let mut output = String::new();
-    let mut nodes = HashSet::new();
+    let mut diagram = Diagram::parse(content);
 
     // Extract components
-    let class_defs = extract_all_class_definitions(content);
-    let class_assignments = extract_all_class_assignments(content);
-    let clean_content = extract_clean_content(content, &mut nodes);
-
-    // Build output
-    output.push_str("flowchart TB\n\n");
-
-    // Add cleaned diagram content
-    output.push_str(&clean_content);
-    output.push_str("\n");
+    let class_defs = extract_all_class_definitions(&diagram);
+    let class_assignments = extract_all_class_assignments(&diagram);
+    clean_content(&mut diagram);
 
     // Add sorted class definitions
-    let mut sorted_defs: Vec<_> = class_defs.iter().collect();
+    let mut sorted_defs = class_defs;
     sorted_defs.sort_by(|a, b| a.name.cmp(&b.name));
+    sorted_defs.dedup_by(|a, b| a.name == b.name);
 
+    diagram.body.push(Statement::Blank.into());
     for def in sorted_defs {
-        output.push_str(&format!("classDef {} {}\n", def.name, def.properties));
+        diagram.body.push(
+            Statement::ClassDef {
+                names: vec![def.name],
+                styles: def.properties,
+            }
+            .into(),
+        );
     }
 
     // Add sorted class assignments
-    let mut sorted_assignments = class_assignments.clone();
+    let mut sorted_assignments = class_assignments;
     sorted_assignments.sort_by(|a, b| {
         a.elements
             .join(",")
             .cmp(&b.elements.join(","))
             .then(a.class_name.cmp(&b.class_name))
     });
+    sorted_assignments.dedup_by(|a, b| a.elements == b.elements && a.class_name == b.class_name);
 
     for assignment in sorted_assignments {
-        output.push_str(&format!(
-            "class {} {}\n",
-            assignment.elements.join(","),
-            assignment.class_name
-        ));
+        diagram.body.push(
+            Statement::ClassAssign {
+                nodes: assignment.elements,
+                class: assignment.class_name,
+            }
+            .into(),
+        );
     }
 
-    Ok(output)
+    Ok(diagram.to_string())
 }
 
-fn extract_all_class_definitions(content: &str) -> Vec<ClassDefinition> {
-    let mut defs = Vec::new();
-    let class_def_re = Regex::new(r"classDef\s+(\w+)\s+([^;\n]+)").unwrap();
-
-    for line in content.lines() {
-        let trimmed = line.trim();
-        if trimmed.starts_with("classDef ") {
-            if let Some(caps) = class_def_re.captures(line) {
-                defs.push(ClassDefinition {
-                    name: caps[1].to_string(),
-                    properties: caps[2].to_string(),
-                });
-            }
-        }
-    }
-
-    defs
+fn extract_all_class_definitions(diagram: &Diagram) -> Vec<ClassDefinition> {
+    diagram
+        .class_defs()
+        .into_iter()
+        .map(|(name, properties)| ClassDefinition {
+            name: name.to_string(),
+            properties: properties.to_string(),
+        })
+        .collect()
 }
 
-fn extract_all_class_assignments(content: &str) -> Vec<ClassAssignment> {
+fn extract_all_class_assignments(diagram: &Diagram) -> Vec<ClassAssignment> {
     let mut assignments = Vec::new();
-    let class_assignment_re = Regex::new(r"^class\s+([\w,]+)\s+(\w+)").unwrap();
-    let inline_style_re = Regex::new(r"(\w+):::(\w+)").unwrap();
-
-    for line in content.lines() {
-        let trimmed = line.trim();
-
-        if trimmed.starts_with("class ") {
-            if let Some(caps) = class_assignment_re.captures(trimmed) {
-                let elements = caps[1].split(',').map(|s| s.trim().to_string()).collect();
-                assignments.push(ClassAssignment {
-                    elements,
-                    class_name: caps[2].to_string(),
-                });
-            }
-        }
 
-        for caps in inline_style_re.captures_iter(trimmed) {
-            assignments.push(ClassAssignment {
-                elements: vec![caps[1].to_string()],
-                class_name: caps[2].to_string(),
-            });
+    for line in diagram.all_lines() {
+        match &line.statement {
+            // `class A,B name` keeps its grouping
+            Statement::ClassAssign { nodes, class } => assignments.push(ClassAssignment {
+                elements: nodes.clone(),
+                class_name: class.clone(),
+            }),
+            // `A:::name` shorthand on nodes and edge endpoints
+            Statement::Node(node) => assignments.extend(inline_assignments(node)),
+            Statement::Edge(edge) => {
+                assignments.extend(inline_assignments(&edge.from));
+                assignments.extend(inline_assignments(&edge.to));
+            }
+            _ => {}
         }
     }
 
     assignments
 }
 
-fn extract_clean_content(content: &str, nodes: &mut HashSet<String>) -> String {
-    let mut cleaned_lines = Vec::new();
-    let mut in_init_block = false;
-    let node_re = Regex::new(r"\[([^\]]+)\]").unwrap();
-
-    for line in content.lines() {
-        let trimmed = line.trim();
+fn inline_assignments(node: &Node) -> Vec<ClassAssignment> {
+    node.classes
+        .iter()
+        .map(|class| ClassAssignment {
+            elements: vec![node.id.clone()],
+            class_name: class.clone(),
+        })
+        .collect()
+}
 
-        // Skip init blocks
-        if trimmed.starts_with("%%{") {
-            in_init_block = true;
-            continue;
-        }
-        if in_init_block {
-            if trimmed.contains("}%%") {
-                in_init_block = false;
-            }
-            continue;
-        }
+/// Drops init blocks, styling, blank lines and commented-out edges or classes,
+/// and replaces `&nbsp;` in labels.
+fn clean_content(diagram: &mut Diagram) {
+    diagram.retain(|statement| match statement {
+        Statement::Directive(_)
+        | Statement::ClassDef { .. }
+        | Statement::ClassAssign { .. }
+        | Statement::Blank => false,
+        Statement::Comment(text) => !(text.contains("-->") || text.contains("class")),
+        _ => true,
+    });
 
-        // Skip class definitions and assignments
-        if trimmed.starts_with("classDef ") || trimmed.starts_with("class ") {
-            continue;
+    diagram.for_each_node_mut(|node| {
+        node.classes.clear();
+        if let Some(label) = &mut node.label {
+            *label = label.replace("&nbsp;", " ");
         }
-
-        // Skip certain comments
-        if trimmed.starts_with("%%") {
-            if trimmed.contains("-->") || trimmed.contains("class") {
-                continue;
+    });
+    diagram.for_each_statement_mut(|statement| match statement {
+        Statement::Edge(edge) => {
+            if let Some(label) = &mut edge.label {
+                *label = label.replace("&nbsp;", " ");
             }
         }
-
-        // Skip flowchart directive
-        if trimmed == "flowchart TB" {
-            continue;
-        }
-
-        // Extract nodes
-        for cap in node_re.captures_iter(trimmed) {
-            nodes.insert(cap[1].to_string());
-        }
-
-        // Clean and add content
-        let cleaned_line = line.replace("&nbsp;", " ");
-        if !cleaned_line.trim().is_empty() {
-            cleaned_lines.push(cleaned_line);
-        }
-    }
-
-    cleaned_lines.join("\n")
+        Statement::Raw(text) => *text = text.replace("&nbsp;", " "),
+        _ => {}
+    });
 }
@@ -3,10 +3,14 @@ use std::io::Write;
 use std::path::Path;
 
 fn concat_rust_files<P: AsRef<Path>>(dir_path: P, output: &mut File) -> std::io::Result<()> {
-    for entry in fs::read_dir(dir_path)? {
-        let entry = entry?;
-        let path = entry.path();
-
+    // Sorted, so the output does not depend on the order the file system
+    // lists entries in
+    let mut paths = fs::read_dir(dir_path)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    paths.sort();
+
+    for path in paths {
         if path.is_dir() {
             concat_rust_files(&path, output)?;
         } else if path.extension().and_then(|s| s.to_str()) == Some("rs") {
@@ -1,142 +1,42 @@
+use cargo_invoke::filter::Filter;
+use cargo_invoke::focus::{Focus, FOCUS_STYLE};
+use cargo_invoke::model::{node_names, CrateModel};
+use cargo_invoke::theme::Theme;
+use cargo_invoke::types::TypeAnalyzer;
 use regex::Regex;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::fs;
 use std::io::{self, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
-/// Simplifies a single type by removing references and wrappers like `Option`, `Arc`, `Box`, etc.,
-/// and returning the *final* type after stripping `::` paths.
-/// e.g., Option<Result<Box<foo::Bar>>> -> Bar
-fn unwrap_type(mut ty: &str) -> String {
-    ty = ty.trim();
-    while ty.starts_with('&') {
-        ty = ty[1..].trim_start();
-    }
-
-    let wrappers = [
-        "Option", "Result", "Box", "Arc", "Rc", "Mutex", "HashSet", "Vec", "HashMap",
-    ];
-    let mut result = ty.to_string();
-
-    'outer: loop {
-        for w in &wrappers {
-            let prefix = format!("{}<", w);
-            if result.starts_with(&prefix) {
-                if let Some(start) = result.find('<') {
-                    let mut depth = 0;
-                    let mut end = start + 1;
-                    for (i, c) in result[start + 1..].chars().enumerate() {
-                        if c == '<' {
-                            depth += 1;
-                        } else if c == '>' {
-                            if depth == 0 {
-                                end = start + 1 + i;
-                                break;
-                            } else {
-                                depth -= 1;
-                            }
-                        }
-                    }
-                    let inside = &result[start + 1..end];
-                    // Recursively unwrap the inside — but we only care about the final type name
-                    // for the single final return. The logic for capturing *all* subtypes is handled below.
-                    result = unwrap_type(inside);
-                    continue 'outer;
-                }
-            }
-        }
-        break;
-    }
-
-    if let Some(colpos) = result.rfind("::") {
-        result = result[colpos + 2..].to_string();
-    }
-    result.trim().to_string()
-}
-
-/// Recursively parses a generic type for all "top-level" arguments.
-/// e.g., `HashMap<A, Vec<B>>` -> ["A", "Vec<B>"] -> then "Vec<B>" -> ["B"] -> so final = ["A", "B"].
-/// We'll return all final unwrapped types (i.e., remove Option, Box, Arc, etc.).
-///
-/// Example:
-/// `HashMap<D, Vec<E>>` -> we get "D" and "Vec<E>" -> unwrap_type("Vec<E>") -> we parse out "E" as well.
-fn extract_all_inner_types(ty: &str) -> Vec<String> {
-    let mut results = Vec::new();
-
-    // Top-level parse: if there's generics, let's isolate them:
-    // e.g. "HashMap<A, Vec<B>>"
-    // We'll find the first '<' and its matching '>' to extract "A, Vec<B>".
-    if let Some(start) = ty.find('<') {
-        let mut depth = 0;
-        let mut end = start + 1;
-        let chars: Vec<char> = ty.chars().collect();
-        for (i, c) in chars[start + 1..].iter().enumerate() {
-            if *c == '<' {
-                depth += 1;
-            } else if *c == '>' {
-                if depth == 0 {
-                    end = start + 1 + i;
-                    break;
-                } else {
-                    depth -= 1;
-                }
-            }
-        }
-
-        // Inside "A, Vec<B>"
-        let inside = &ty[start + 1..end];
-        // Split at top-level commas
-        let mut comma_depth = 0;
-        let mut last_pos = 0;
-        for (i, c) in inside.chars().enumerate() {
-            match c {
-                '<' => comma_depth += 1,
-                '>' => comma_depth -= 1,
-                ',' if comma_depth == 0 => {
-                    // We found a top-level comma
-                    let segment = inside[last_pos..i].trim();
-                    results.push(segment.to_string());
-                    last_pos = i + 1;
-                }
-                _ => {}
-            }
-        }
-        // Final segment
-        let segment = inside[last_pos..].trim();
-        if !segment.is_empty() {
-            results.push(segment.to_string());
-        }
-    }
-
-    // If we found no generics, it's just a single type
-    if results.is_empty() {
-        // Return this as final unwrapped type
-        return vec![unwrap_type(ty)];
-    } else {
-        // We have subtypes. For each subtype, we might have further generics.
-        // So we recursively parse them as well and flatten everything.
-        let mut final_list = Vec::new();
-        for sub in results {
-            // e.g. sub could be "Vec<B>"
-            // parse again
-            let deeper = extract_all_inner_types(&sub);
-            final_list.extend(deeper);
-        }
-        return final_list;
-    }
+/// `crate::net` for `crate::net::Config`; `None` for a bare name.
+fn owner_module(path: &str) -> Option<&str> {
+    path.rsplit_once("::").map(|(module, _)| module)
 }
 
 fn main() -> io::Result<()> {
-    let args: Vec<String> = std::env::args().collect();
+    let mut args: Vec<String> = std::env::args().collect();
+    let focus = Focus::from_args(&mut args)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+    let filter = Filter::from_args(&mut args)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+    let theme = Theme::from_args(&mut args)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
     let generate_png = args.contains(&"--png".to_string());
 
     // 1. Read known classes
     let impls_path = PathBuf::from("architecture/impls.rs");
     let structs_path = PathBuf::from("architecture/structs.rs");
-    let connections_path = PathBuf::from("diagrams/connections.mermaid");
+    let connections_path = match &focus {
+        Some(focus) => PathBuf::from(format!(
+            "diagrams/connections{}.mermaid",
+            focus.file_suffix()
+        )),
+        None => PathBuf::from("diagrams/connections.mermaid"),
+    };
 
-    let mut known_classes = HashSet::new();
+    let mut known_classes = BTreeSet::new();
 
     // Regex to find `impl Something {` or `impl Something for AnotherThing {`
     let impl_name_regex =
@@ -167,27 +67,105 @@ fn main() -> io::Result<()> {
         }
     }
 
+    // `architecture` marks each struct with its module, so same-named structs
+    // from different modules stay distinct (name -> declaring paths) and the
+    // filter sees the real module path
+    let declaration_regex = Regex::new(
+        r"(?m)^(?://\s*module:\s*(\S+)\s*\n)?(?:\s*#\[[^\]]*\])*\s*(pub\s+)?struct\s+(\w+)",
+    )
+    .unwrap();
+    let mut declared: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut in_structs = HashSet::new();
+    let mut allowed = HashSet::new();
+    let mut excluded = HashSet::new();
+    for cap in declaration_regex.captures_iter(&structs_content) {
+        let module = cap.get(1).map_or("", |m| m.as_str());
+        let name = cap[3].to_string();
+        let keep = filter.allows_type(module, &name, cap.get(2).is_some());
+        if !module.is_empty() {
+            let path = format!("{}::{}", module, name);
+            if !keep {
+                excluded.insert(path.clone());
+            }
+            declared.entry(name.clone()).or_default().push(path);
+        }
+        if keep {
+            allowed.insert(name.clone());
+        }
+        in_structs.insert(name);
+    }
+    // Classes only `impls.rs` mentions carry neither module nor visibility
+    known_classes.retain(|c| {
+        allowed.contains(c) || (!in_structs.contains(c) && filter.allows_type("", c, true))
+    });
+    // When the sources are at hand, `use` imports decide which of several
+    // same-named structs a field means
+    let model = Path::new("src")
+        .exists()
+        .then(|| CrateModel::load(Path::new("src")).ok())
+        .flatten();
+    let declared_paths: HashSet<&String> = declared.values().flatten().collect();
+
+    // Resolves a type path written in `owner_module`; ambiguous names resolve
+    // through the module's imports, or else only to a declaration in the same module
+    let resolve = |owner_module: Option<&str>, written: &str| -> Option<String> {
+        let name = written.rsplit("::").next().unwrap_or(written);
+        if !known_classes.contains(name) {
+            return None;
+        }
+        let path = match declared.get(name).map(Vec::as_slice) {
+            None | Some([]) => Some(name.to_string()),
+            Some([only]) => Some(only.clone()),
+            Some(paths) => {
+                let module = owner_module?;
+                let imported = model
+                    .as_ref()
+                    .and_then(|m| m.resolve(module, written))
+                    .map(|(m, t)| format!("{}::{}", m.path, t.name))
+                    .filter(|p| declared_paths.contains(p));
+                imported.or_else(|| {
+                    paths
+                        .iter()
+                        .find(|p| p.strip_suffix(name) == Some(&format!("{}::", module)))
+                        .cloned()
+                })
+            }
+        };
+        // A struct the filter dropped never stands in for a same-named one
+        path.filter(|p| !excluded.contains(p))
+    };
+
     // 2. Parse each struct block to find fields referencing known classes
-    let struct_block_regex =
-        Regex::new(r"(?s)(?:#\[[^\]]+\]\s*)*(?:pub\s+)?struct\s+(\w+)\s*\{([^}]*)\}").unwrap();
-    let field_regex = Regex::new(r"(?m)^\s*(?:pub\s+)?(\w+)\s*:\s*([^,]+)").unwrap();
+    let struct_block_regex = Regex::new(
+        r"(?s)(?://\s*module:\s*(\S+)\s*\n)?(?:#\[[^\]]+\]\s*)*(?:pub\s+)?struct\s+(\w+)\s*\{([^}]*)\}",
+    )
+    .unwrap();
+    let analyzer = TypeAnalyzer::new();
 
     let mut relationships: Vec<(String, String, String)> = Vec::new();
 
     for block_cap in struct_block_regex.captures_iter(&structs_content) {
-        let struct_name = block_cap[1].trim();
-        let struct_body = block_cap[2].trim();
-
-        for field_cap in field_regex.captures_iter(struct_body) {
-            let field_type_raw = field_cap[2].trim();
-
-            // Use extract_all_inner_types to capture all final unwrapped types
-            let types_found = extract_all_inner_types(field_type_raw);
+        let module = block_cap.get(1).map(|m| m.as_str());
+        let struct_name = block_cap[2].trim();
+        let Some(owner) = resolve(module, struct_name) else {
+            continue;
+        };
+        let item: syn::ItemStruct = match syn::parse_str(&block_cap[0]) {
+            Ok(item) => item,
+            Err(e) => {
+                eprintln!("Skipping struct {}: {}", struct_name, e);
+                continue;
+            }
+        };
 
-            // For each final type, if known, record a "has" relationship
-            for t in types_found {
-                if known_classes.contains(&t) {
-                    relationships.push((struct_name.to_string(), "has".to_string(), t));
+        for field in &item.fields {
+            // Every type reachable through generics, tuples, trait objects, ...
+            for type_ref in analyzer.analyze(&field.ty) {
+                if !filter.allows_reference(&type_ref.name) {
+                    continue;
+                }
+                if let Some(t) = resolve(module, &type_ref.path) {
+                    relationships.push((owner.clone(), "has".to_string(), t));
                 }
             }
         }
@@ -205,6 +183,10 @@ fn main() -> io::Result<()> {
         for block_cap in impl_block_regex.captures_iter(&impls_content) {
             let struct_name = block_cap[1].trim();
             let block_body = &block_cap[2];
+            // impls.rs carries no module markers; ambiguous owners are skipped
+            let Some(owner) = resolve(None, struct_name) else {
+                continue;
+            };
 
             for fn_cap in fn_regex.captures_iter(block_body) {
                 let fn_name = fn_cap[1].trim();
@@ -213,38 +195,89 @@ fn main() -> io::Result<()> {
                     continue;
                 }
 
-                let ret_ty_raw = fn_cap.get(2).map_or("", |m| m.as_str());
-                if !ret_ty_raw.is_empty() {
-                    let simplified = unwrap_type(ret_ty_raw);
-                    if known_classes.contains(&simplified) {
-                        relationships.push((
-                            struct_name.to_string(),
-                            fn_name.to_string(),
-                            simplified,
-                        ));
+                let ret_ty_raw = fn_cap.get(2).map_or("", |m| m.as_str()).trim();
+                let Ok(ret_ty) = syn::parse_str::<syn::Type>(ret_ty_raw) else {
+                    continue;
+                };
+                for type_ref in analyzer.analyze(&ret_ty) {
+                    if !filter.allows_reference(&type_ref.name) {
+                        continue;
+                    }
+                    if let Some(target) = resolve(owner_module(&owner), &type_ref.path) {
+                        relationships.push((owner.clone(), fn_name.to_string(), target));
                     }
                 }
             }
         }
     }
 
-    // 4. Write out connections.mermaid
+    // The same field or return type may name a class twice
+    let mut seen = HashSet::new();
+    relationships.retain(|r| seen.insert(r.clone()));
+
+    // 4. Narrow down to the focus neighbourhood, if one was requested
+    let mut nodes: Vec<String> = known_classes
+        .iter()
+        .flat_map(|c| match declared.get(c) {
+            Some(paths) => paths
+                .iter()
+                .filter(|p| !excluded.contains(*p))
+                .cloned()
+                .collect(),
+            None => vec![c.clone()],
+        })
+        .collect();
+    if let Some(focus) = &focus {
+        let edges = relationships
+            .iter()
+            .map(|(a, _, b)| (a.as_str(), b.as_str()));
+        let kept = focus
+            .neighbourhood(nodes.iter().map(String::as_str), edges)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+        relationships.retain(|(a, _, b)| kept.contains(a) && kept.contains(b));
+        nodes.retain(|n| kept.contains(n));
+    }
+
+    // 5. Write out connections.mermaid
+    fs::create_dir_all("diagrams")?;
     let mut file = fs::File::create(&connections_path)?;
     writeln!(file, "graph LR")?;
     writeln!(file)?;
 
+    let names = node_names(nodes.iter().map(String::as_str));
+    let id = |path: &String| names.get(path).map_or(path.clone(), |n| n.id.clone());
+    for node in names.values() {
+        if let Some(label) = &node.label {
+            writeln!(file, "    {}[\"{}\"]", node.id, label)?;
+        }
+    }
+
     for (a, label, b) in &relationships {
-        writeln!(file, "    {} --> |{}| {}", a, label, b)?;
+        writeln!(file, "    {} --> |{}| {}", id(a), label, id(b))?;
+    }
+
+    if let Some(focus) = &focus {
+        writeln!(file)?;
+        writeln!(file, "    classDef focus {}", FOCUS_STYLE)?;
+        for node in nodes.iter().filter(|n| focus.matches(n)) {
+            writeln!(file, "    class {} focus", id(node))?;
+        }
+    }
+    drop(file);
+    if let Some(theme) = &theme {
+        theme
+            .apply_to_file(&connections_path)
+            .map_err(|e| io::Error::other(e.to_string()))?;
     }
 
     println!("Generated diagram at {}", connections_path.display());
     println!("Known classes used for matching: {:?}", known_classes);
 
-    // 5. If --png flag is present, run the PNG generation
+    // 6. If --png flag is present, run the PNG generation
     if generate_png {
         println!("Generating PNG...");
         let status = Command::new("cargo")
-            .args(&[
+            .args([
                 "run",
                 "--bin",
                 "generate_mermaid_png",
@@ -254,10 +287,7 @@ fn main() -> io::Result<()> {
             .status()?;
 
         if !status.success() {
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
-                "Failed to generate PNG",
-            ));
+            return Err(io::Error::other("Failed to generate PNG"));
         }
     }
 
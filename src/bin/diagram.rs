@@ -1,42 +1,76 @@
+use cargo_invoke::filter::Filter;
+use cargo_invoke::mermaid::class_header;
+use cargo_invoke::model::{module_order, node_names};
+use cargo_invoke::theme::Theme;
 use regex::Regex;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet};
 use std::error::Error;
 use std::fs;
 use std::path::PathBuf;
+use std::sync::OnceLock;
 
 #[derive(Debug)]
 struct Field {
     name: String,
     type_name: String,
+    /// `+`, `#` or `-`, from the field's `pub` qualifier.
+    visibility: char,
 }
 
 #[derive(Debug)]
 struct StructDef {
     name: String,
+    /// Names of the generic type and const parameters, e.g. `K` and `V`.
+    generics: Vec<String>,
+    /// Module path from the `// module:` marker `architecture` writes, if any.
+    module: Option<String>,
     fields: Vec<Field>,
 }
 
+impl StructDef {
+    /// `crate::net::Config`, or the bare name when the module is unknown.
+    fn path(&self) -> String {
+        match &self.module {
+            Some(module) => format!("{}::{}", module, self.name),
+            None => self.name.clone(),
+        }
+    }
+}
+
 fn extract_struct_info(content: &str) -> Vec<StructDef> {
     let mut structs = Vec::new();
     // Modified to handle multiline struct definitions better
-    let struct_regex =
-        Regex::new(r"(?m)^\s*pub struct ([A-Za-z_][A-Za-z0-9_]*)\s*\{([\s\S]*?)\}").unwrap();
+    let struct_regex = Regex::new(
+        r"(?m)(?:^//\s*module:\s*(\S+)\s*\n(?:\s*#\[[^\]]*\]\s*\n)*)?^\s*pub struct ([A-Za-z_][A-Za-z0-9_]*)\s*(?:<([^{]*)>)?\s*(?:where[^{]*)?\{([\s\S]*?)\}",
+    )
+    .unwrap();
     // Modified to handle both public and private fields, including comments
-    let field_regex =
-        Regex::new(r"(?m)^\s*(?:pub\s+)?([A-Za-z_][A-Za-z0-9_]*)\s*:\s*([^,\n]+)(?:,|\n|$)")
-            .unwrap();
+    let field_regex = Regex::new(
+        r"(?m)^\s*(pub(?:\s*\([^)]*\))?\s+)?([A-Za-z_][A-Za-z0-9_]*)\s*:\s*([^,\n]+)(?:,|\n|$)",
+    )
+    .unwrap();
 
     for struct_cap in struct_regex.captures_iter(content) {
-        let struct_name = struct_cap[1].to_string();
-        let struct_body = &struct_cap[2];
+        let module = struct_cap.get(1).map(|m| m.as_str().to_string());
+        let struct_name = struct_cap[2].to_string();
+        let generics = struct_cap
+            .get(3)
+            .map(|g| generic_params(g.as_str()))
+            .unwrap_or_default();
+        let struct_body = &struct_cap[4];
 
         // Debug print to verify captures
         println!("Found struct: {}", struct_name);
 
         let mut fields = Vec::new();
         for field_cap in field_regex.captures_iter(struct_body) {
-            let field_name = field_cap[1].to_string();
-            let field_type = field_cap[2].trim().to_string();
+            let visibility = match field_cap.get(1).map(|v| v.as_str().trim()) {
+                Some("pub") => '+',
+                Some(_) => '#',
+                None => '-',
+            };
+            let field_name = field_cap[2].to_string();
+            let field_type = field_cap[3].trim().to_string();
 
             // Debug print to verify field captures
             println!("  Field: {} : {}", field_name, field_type);
@@ -44,39 +78,122 @@ fn extract_struct_info(content: &str) -> Vec<StructDef> {
             fields.push(Field {
                 name: field_name,
                 type_name: field_type,
+                visibility,
             });
         }
 
         structs.push(StructDef {
             name: struct_name,
+            generics,
+            module,
             fields,
         });
     }
 
-    // Rest of the function remains the same...
-    let mut unique_structs: HashMap<String, StructDef> = HashMap::new();
+    // A struct repeated under the same path keeps its most complete definition;
+    // same-named structs from different modules stay separate
+    let mut unique_structs: BTreeMap<String, StructDef> = BTreeMap::new();
     for struct_def in structs {
-        match unique_structs.get(&struct_def.name) {
-            Some(existing) if existing.fields.len() < struct_def.fields.len() => {
-                unique_structs.insert(struct_def.name.clone(), struct_def);
+        match unique_structs.get(&struct_def.path()) {
+            Some(existing) if existing.fields.len() >= struct_def.fields.len() => {}
+            _ => {
+                unique_structs.insert(struct_def.path(), struct_def);
             }
-            None => {
-                unique_structs.insert(struct_def.name.clone(), struct_def);
+        }
+    }
+
+    // Listed by module path, then name
+    let mut structs: Vec<StructDef> = unique_structs.into_values().collect();
+    structs.sort_by(|a, b| {
+        module_order(
+            a.module.as_deref().unwrap_or_default(),
+            b.module.as_deref().unwrap_or_default(),
+        )
+        .then_with(|| a.name.cmp(&b.name))
+    });
+    structs
+}
+
+/// The parameter names in a generic list as written, leaving out lifetimes,
+/// bounds and defaults: `'a, T: Clone + 'a, const N: usize` gives `T` and `N`.
+fn generic_params(list: &str) -> Vec<String> {
+    let mut params = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in list.char_indices().chain([(list.len(), ',')]) {
+        match c {
+            '<' | '(' | '[' => depth += 1,
+            '>' if list[..i].ends_with('-') => {}
+            '>' | ')' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                let param = list[start..i].trim();
+                let param = param.strip_prefix("const ").unwrap_or(param);
+                let name = param.split([':', '=']).next().unwrap_or(param).trim();
+                if !name.is_empty() && !name.starts_with('\'') {
+                    params.push(name.to_string());
+                }
+                start = i + 1;
             }
             _ => {}
         }
     }
+    params
+}
 
-    unique_structs.into_values().collect()
+/// Structs named in a field's type, looked up by each identifier in it. When
+/// several modules declare the name, the one in the field owner's module wins;
+/// otherwise the reference is ambiguous and dropped.
+fn field_targets<'a>(
+    owner: &StructDef,
+    field: &Field,
+    by_name: &BTreeMap<&str, Vec<&'a StructDef>>,
+) -> Vec<&'a StructDef> {
+    static WORD: OnceLock<Regex> = OnceLock::new();
+    let words: BTreeSet<&str> = WORD
+        .get_or_init(|| Regex::new(r"\w+").unwrap())
+        .find_iter(&field.type_name)
+        .map(|m| m.as_str())
+        .collect();
+    words
+        .into_iter()
+        .filter_map(|word| match by_name.get(word)?.as_slice() {
+            [only] => Some(*only),
+            candidates => candidates
+                .iter()
+                .copied()
+                .find(|c| c.module == owner.module),
+        })
+        .collect()
+}
+
+/// Drops the structs and fields the filter rejects. The regex only reads
+/// `pub struct`s, so every struct counts as public.
+fn apply_filter(structs: &mut Vec<StructDef>, filter: &Filter) {
+    structs.retain(|s| filter.allows_type(s.module.as_deref().unwrap_or_default(), &s.name, true));
+    if filter.pub_only {
+        for struct_def in structs {
+            struct_def.fields.retain(|f| f.visibility == '+');
+        }
+    }
 }
 
 fn generate_mermaid(structs: &[StructDef]) -> String {
     let mut mermaid = String::from("classDiagram\n");
-    let mut relationships = HashSet::new();
+    let mut relationships = BTreeSet::new();
+
+    let paths: Vec<String> = structs.iter().map(StructDef::path).collect();
+    let names = node_names(paths.iter().map(String::as_str));
 
     // Generate class definitions
     for struct_def in structs {
-        mermaid.push_str(&format!("    class {} {{\n", struct_def.name));
+        let node = &names[&struct_def.path()];
+        let header = class_header(
+            &node.id,
+            &struct_def.name,
+            &struct_def.generics,
+            node.label.as_deref(),
+        );
+        mermaid.push_str(&format!("    {} {{\n", header));
         for field in &struct_def.fields {
             // Clean up the type name for display
             let clean_type = field
@@ -88,30 +205,30 @@ fn generate_mermaid(structs: &[StructDef]) -> String {
                 .replace("Vec<", "Vec~")
                 .replace("Option<", "Option~")
                 .replace("HashMap<", "Map~");
-
-            // Check if the type contains 'pub' to determine visibility
-            let visibility = if field.type_name.starts_with("pub ") {
-                "+"
-            } else {
-                "-"
-            };
             mermaid.push_str(&format!(
                 "        {}{} {}\n",
-                visibility, clean_type, field.name
+                field.visibility, clean_type, field.name
             ));
         }
         mermaid.push_str("    }\n\n");
     }
 
-    // Rest of the function remains the same...
+    let mut by_name: BTreeMap<&str, Vec<&StructDef>> = BTreeMap::new();
+    for struct_def in structs {
+        by_name
+            .entry(struct_def.name.as_str())
+            .or_default()
+            .push(struct_def);
+    }
     for struct_def in structs {
         for field in &struct_def.fields {
-            for other_struct in structs {
-                if field.type_name.contains(&other_struct.name) {
-                    let relationship =
-                        format!("    {} --> {} : has\n", struct_def.name, other_struct.name);
-                    relationships.insert(relationship);
-                }
+            for target in field_targets(struct_def, field, &by_name) {
+                let relationship = format!(
+                    "    {} --> {} : has\n",
+                    names[&struct_def.path()].id,
+                    names[&target.path()].id
+                );
+                relationships.insert(relationship);
             }
         }
     }
@@ -124,9 +241,14 @@ fn generate_mermaid(structs: &[StructDef]) -> String {
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let args: Vec<String> = std::env::args().collect();
+    let mut args: Vec<String> = std::env::args().collect();
+    let filter = Filter::from_args(&mut args)?;
+    let theme = Theme::from_args(&mut args)?;
     if args.len() != 2 {
-        eprintln!("Usage: {} <input_file>", args[0]);
+        eprintln!(
+            "Usage: {} <input_file> [--include <GLOB>] [--exclude <GLOB>] [--pub-only] [--theme <NAME>]",
+            args[0]
+        );
         std::process::exit(1);
     }
 
@@ -164,7 +286,8 @@ fn main() -> Result<(), Box<dyn Error>> {
     let content = fs::read_to_string(&input_path)?;
 
     // Extract struct info and generate the Mermaid diagram
-    let structs = extract_struct_info(&content);
+    let mut structs = extract_struct_info(&content);
+    apply_filter(&mut structs, &filter);
     let mermaid = generate_mermaid(&structs);
 
     // Prepare the output file path in the 'diagrams' directory
@@ -178,6 +301,9 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     // Write the Mermaid diagram to the output file
     fs::write(&output_path, mermaid)?;
+    if let Some(theme) = &theme {
+        theme.apply_to_file(&output_path)?;
+    }
     println!(
         "Generated class diagram for '{}' and saved to '{}'",
         input_path.display(),
@@ -186,3 +312,51 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generic_headers_match_class_diagram() {
+        let structs = extract_struct_info(
+            "pub struct Cache<'a, K: Hash + Eq, V = ()> where K: 'a {\n    entries: HashMap<K, V>,\n}\n\
+             pub struct Slot<T: Fn() -> u8> {\n    pub value: Option<T>,\n}\n",
+        );
+        assert_eq!(structs[0].generics, ["K", "V"]);
+        assert_eq!(structs[1].generics, ["T"]);
+
+        let mermaid = generate_mermaid(&structs);
+        assert!(
+            mermaid.contains("    class Cache[\"Cache<K, V>\"] {\n"),
+            "{}",
+            mermaid
+        );
+        assert!(mermaid.contains("    class Slot~T~ {\n"), "{}", mermaid);
+    }
+
+    #[test]
+    fn test_filter_uses_module_markers() {
+        let content = "// module: crate::net\npub struct Server {\n    pub port: u16,\n    config: Config,\n}\n\
+                       // module: crate::config\npub struct Config {\n    pub path: String,\n}\n";
+
+        let mut structs = extract_struct_info(content);
+        let filter = Filter {
+            exclude: vec!["crate::config::*".to_string()],
+            ..Filter::default()
+        };
+        apply_filter(&mut structs, &filter);
+        assert_eq!(structs.len(), 1);
+        assert_eq!(structs[0].path(), "crate::net::Server");
+        assert!(!generate_mermaid(&structs).contains("-->"));
+
+        let mut structs = extract_struct_info(content);
+        let filter = Filter {
+            pub_only: true,
+            ..Filter::default()
+        };
+        apply_filter(&mut structs, &filter);
+        let fields: Vec<&str> = structs[1].fields.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(fields, ["port"]);
+    }
+}
@@ -0,0 +1,356 @@
+use anyhow::{anyhow, Result};
+use cargo_invoke::filter::Filter;
+use cargo_invoke::mermaid::{class_header, class_member_type};
+use cargo_invoke::model::{
+    module_order, node_names, tokens_to_string, CrateModel, Module, TypeDef, TypeKind,
+};
+use cargo_invoke::types::TypeAnalyzer;
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::PathBuf;
+
+fn main() -> Result<()> {
+    let mut args: Vec<String> = std::env::args().collect();
+    let filter = Filter::from_args(&mut args)?;
+
+    let mut base_dir = PathBuf::from(".");
+    let mut output_dir = PathBuf::from("docs");
+    let mut mdbook = false;
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--output" => {
+                i += 1;
+                output_dir = PathBuf::from(
+                    args.get(i)
+                        .ok_or_else(|| anyhow!("--output requires a path"))?,
+                );
+            }
+            "--mdbook" => mdbook = true,
+            dir => base_dir = PathBuf::from(dir),
+        }
+        i += 1;
+    }
+
+    let src_dir = base_dir.join("src");
+    if !src_dir.exists() {
+        return Err(anyhow!("Source directory not found: {:?}", src_dir));
+    }
+
+    let mut model = CrateModel::load(&src_dir)?;
+    filter.apply(&mut model);
+    let analyzer = TypeAnalyzer::from_model(&model);
+    fs::create_dir_all(&output_dir)?;
+
+    let mut modules: Vec<&Module> = model.modules.iter().collect();
+    modules.sort_by(|a, b| module_order(&a.path, &b.path));
+
+    for module in &modules {
+        let page = render_module_page(&model, &analyzer, module);
+        fs::write(output_dir.join(page_name(&module.path)), page)?;
+    }
+
+    if mdbook {
+        fs::write(output_dir.join("SUMMARY.md"), render_summary(&modules))?;
+    }
+
+    println!(
+        "Documented {} modules in {}",
+        modules.len(),
+        output_dir.display()
+    );
+    Ok(())
+}
+
+/// `crate::net::server` -> `crate.net.server.md`
+fn page_name(module_path: &str) -> String {
+    format!("{}.md", module_path.replace("::", "."))
+}
+
+/// Heading anchors follow the GitHub/mdBook convention of lower-casing the title.
+fn anchor(name: &str) -> String {
+    name.to_lowercase()
+}
+
+fn render_summary(modules: &[&Module]) -> String {
+    let mut out = String::from("# Summary\n\n");
+    for module in modules {
+        let depth = module.path.matches("::").count();
+        let _ = writeln!(
+            out,
+            "{}- [{}]({})",
+            "  ".repeat(depth),
+            module.path,
+            page_name(&module.path)
+        );
+    }
+    out
+}
+
+fn render_module_page(model: &CrateModel, analyzer: &TypeAnalyzer, module: &Module) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "# Module `{}`\n", module.path);
+    let _ = writeln!(out, "Source: `{}`\n", module.file.display());
+    if !module.docs.is_empty() {
+        let _ = writeln!(out, "{}\n", module.docs);
+    }
+
+    if !module.types.is_empty() {
+        out.push_str("## Diagram\n\n```mermaid\n");
+        out.push_str(&render_module_diagram(model, analyzer, module));
+        out.push_str("```\n\n");
+        out.push_str("## Types\n\n");
+        for ty in &module.types {
+            render_type(&mut out, model, analyzer, module, ty);
+        }
+    }
+
+    if !module.functions.is_empty() {
+        out.push_str("## Functions\n\n");
+        for function in &module.functions {
+            let _ = writeln!(out, "### {}\n", function.name);
+            let _ = writeln!(
+                out,
+                "```rust\n{}{}\n```\n",
+                vis_prefix(function.vis.as_str()),
+                function.signature()
+            );
+            if !function.docs.is_empty() {
+                let _ = writeln!(out, "{}\n", function.docs);
+            }
+        }
+    }
+
+    out
+}
+
+fn vis_prefix(vis: &str) -> String {
+    if vis.is_empty() {
+        String::new()
+    } else {
+        format!("{} ", vis)
+    }
+}
+
+fn render_type(
+    out: &mut String,
+    model: &CrateModel,
+    analyzer: &TypeAnalyzer,
+    module: &Module,
+    ty: &TypeDef,
+) {
+    let _ = writeln!(out, "### {}\n", ty.name);
+
+    let generics = if ty.generics.is_empty() {
+        String::new()
+    } else {
+        format!("<{}>", ty.generics.join(", "))
+    };
+    let _ = write!(
+        out,
+        "`{}{} {}{}`",
+        vis_prefix(ty.vis.as_str()),
+        ty.kind.as_str(),
+        ty.name,
+        generics
+    );
+    if !ty.derives.is_empty() {
+        let _ = write!(out, " · derives: {}", ty.derives.join(", "));
+    }
+    out.push_str("\n\n");
+
+    if !ty.docs.is_empty() {
+        let _ = writeln!(out, "{}\n", ty.docs);
+    }
+
+    if !ty.fields.is_empty() {
+        out.push_str("| Field | Type | Description |\n|---|---|---|\n");
+        for field in &ty.fields {
+            let _ = writeln!(
+                out,
+                "| `{}` | `{}` | {} |",
+                field.name,
+                tokens_to_string(&field.ty).replace('|', "\\|"),
+                first_line(&field.docs)
+            );
+        }
+        out.push('\n');
+    }
+
+    if !ty.variants.is_empty() {
+        out.push_str("| Variant | Payload | Description |\n|---|---|---|\n");
+        for variant in &ty.variants {
+            let payload = variant
+                .fields
+                .iter()
+                .map(|f| tokens_to_string(&f.ty))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let payload = if payload.is_empty() {
+                String::new()
+            } else {
+                format!("`{}`", payload.replace('|', "\\|"))
+            };
+            let _ = writeln!(
+                out,
+                "| `{}` | {} | {} |",
+                variant.name,
+                payload,
+                first_line(&variant.docs)
+            );
+        }
+        out.push('\n');
+    }
+
+    if !ty.methods.is_empty() {
+        out.push_str("**Methods**\n\n");
+        for method in &ty.methods {
+            let _ = write!(
+                out,
+                "- `{}{}`",
+                vis_prefix(method.vis.as_str()),
+                method.signature()
+            );
+            if !method.docs.is_empty() {
+                let _ = write!(out, " — {}", first_line(&method.docs));
+            }
+            out.push('\n');
+        }
+        out.push('\n');
+    }
+
+    if !ty.traits.is_empty() {
+        let _ = writeln!(out, "**Implements:** {}\n", ty.traits.join(", "));
+    }
+
+    let related: Vec<String> = related_types(model, analyzer, module, ty)
+        .into_iter()
+        .map(|(m, t)| {
+            let link = if m == module.path {
+                format!("#{}", anchor(&t))
+            } else {
+                format!("{}#{}", page_name(&m), anchor(&t))
+            };
+            format!("[{}]({})", t, link)
+        })
+        .collect();
+    if !related.is_empty() {
+        let _ = writeln!(out, "**Related:** {}\n", related.join(", "));
+    }
+}
+
+/// The first paragraph of a doc comment, flattened onto one line for tables and lists.
+fn first_line(docs: &str) -> String {
+    docs.split("\n\n")
+        .next()
+        .unwrap_or_default()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace('|', "\\|")
+}
+
+/// Crate-local types referenced from a type's fields, variant payloads and
+/// method signatures, as `(module path, type name)` pairs.
+fn related_types(
+    model: &CrateModel,
+    analyzer: &TypeAnalyzer,
+    module: &Module,
+    ty: &TypeDef,
+) -> BTreeSet<(String, String)> {
+    let analyzer = analyzer.in_module(&module.path).with_params(&ty.generics);
+    let mut refs = Vec::new();
+    for field in ty
+        .fields
+        .iter()
+        .chain(ty.variants.iter().flat_map(|v| &v.fields))
+    {
+        refs.extend(analyzer.analyze(&field.ty));
+    }
+    for method in &ty.methods {
+        refs.extend(analyzer.analyze_signature(&method.sig));
+    }
+
+    refs.into_iter()
+        .filter_map(|r| model.resolve(&module.path, &r.path))
+        .filter(|(m, t)| !(m.path == module.path && t.name == ty.name))
+        .map(|(m, t)| (m.path.clone(), t.name.clone()))
+        .collect()
+}
+
+/// A classDiagram of the module's own types plus the types they reference,
+/// with `click` links to the pages documenting the referenced types.
+fn render_module_diagram(model: &CrateModel, analyzer: &TypeAnalyzer, module: &Module) -> String {
+    let mut out = String::from("classDiagram\n");
+    let mut edges = BTreeSet::new();
+    let mut foreign = BTreeSet::new();
+
+    for ty in &module.types {
+        for (m, t) in related_types(model, analyzer, module, ty) {
+            if m != module.path {
+                foreign.insert((m.clone(), t.clone()));
+            }
+            edges.insert((ty.name.clone(), format!("{}::{}", m, t)));
+        }
+    }
+
+    // Foreign types may share a name with local ones; ids come from full paths.
+    let local = module
+        .types
+        .iter()
+        .map(|t| format!("{}::{}", module.path, t.name));
+    let paths: Vec<String> = local
+        .chain(foreign.iter().map(|(m, t)| format!("{}::{}", m, t)))
+        .collect();
+    let names = node_names(paths.iter().map(String::as_str));
+    let id = |path: &str| names[path].id.clone();
+
+    for ty in &module.types {
+        let node = &names[&format!("{}::{}", module.path, ty.name)];
+        let header = class_header(&node.id, &ty.name, &ty.generics, node.label.as_deref());
+        let _ = writeln!(out, "    {} {{", header);
+        match ty.kind {
+            TypeKind::Enum => out.push_str("        <<enum>>\n"),
+            TypeKind::Trait => out.push_str("        <<trait>>\n"),
+            TypeKind::Struct => {}
+        }
+        for field in &ty.fields {
+            let _ = writeln!(
+                out,
+                "        {} {}",
+                class_member_type(&tokens_to_string(&field.ty)),
+                field.name
+            );
+        }
+        for variant in &ty.variants {
+            let _ = writeln!(out, "        {}", variant.name);
+        }
+        for method in &ty.methods {
+            let _ = writeln!(out, "        {}()", method.name);
+        }
+        out.push_str("    }\n");
+    }
+
+    for (from, to) in &edges {
+        let _ = writeln!(
+            out,
+            "    {} --> {}",
+            id(&format!("{}::{}", module.path, from)),
+            id(to)
+        );
+    }
+    for (m, t) in &foreign {
+        let _ = writeln!(
+            out,
+            "    click {} href \"{}#{}\" \"{}::{}\"",
+            id(&format!("{}::{}", m, t)),
+            page_name(m),
+            anchor(t),
+            m,
+            t
+        );
+    }
+
+    out
+}
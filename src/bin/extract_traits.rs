@@ -1,9 +1,11 @@
+use cargo_invoke::filter::Filter;
+use cargo_invoke::model::{is_cfg_test, module_path_for_file};
 use proc_macro2::TokenStream;
 use quote::{format_ident, quote};
 use std::fs::{self, File};
 use std::io::Write;
 use std::path::Path;
-use syn::{parse_file, Fields, Item, ItemStruct};
+use syn::{parse_file, Fields, Item, ItemStruct, Visibility};
 
 fn transform_struct_to_trait(item_struct: &ItemStruct) -> TokenStream {
     let struct_name = &item_struct.ident;
@@ -36,50 +38,39 @@ fn transform_struct_to_trait(item_struct: &ItemStruct) -> TokenStream {
     }
 }
 
-fn process_file_content(content: String) -> std::io::Result<String> {
-    let syntax_tree = parse_file(&content)
-        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+fn process_file_content(content: String, module: &str, filter: &Filter) -> std::io::Result<String> {
+    let syntax_tree = parse_file(&content).map_err(|e| std::io::Error::other(e.to_string()))?;
 
     let mut output = String::new();
-
-    // List of types to skip
-    let skip_types = [
-        // "Address",
-        // "BlockHash",
-        // "Hash",
-        // "Signature",
-        // "DateTime",
-        // "Duration",
-        // "IpAddr",
-        // "Instant",
-        // "SystemTime",
-    ];
+    if !filter.allows_module(module, is_cfg_test(&syntax_tree.attrs)) {
+        return Ok(output);
+    }
 
     for item in syntax_tree.items {
-        match item {
-            Item::Struct(item_struct) => {
-                // Skip if it's in our skip list
-                if skip_types.contains(&item_struct.ident.to_string().as_str()) {
-                    continue;
-                }
-
-                // Skip if it's a wrapper struct
-                if is_wrapper_struct(&item_struct) {
-                    continue;
-                }
+        if let Item::Struct(item_struct) = item {
+            // Skip types rejected by --include/--exclude/--pub-only/--no-tests
+            let is_public = matches!(item_struct.vis, Visibility::Public(_));
+            if (filter.no_tests && is_cfg_test(&item_struct.attrs))
+                || !filter.allows_type(module, &item_struct.ident.to_string(), is_public)
+            {
+                continue;
+            }
 
-                let trait_def = transform_struct_to_trait(&item_struct);
-                let trait_str = trait_def.to_string();
+            // Skip if it's a wrapper struct
+            if is_wrapper_struct(&item_struct) {
+                continue;
+            }
 
-                // Skip if the trait would be empty
-                if !trait_str.contains("fn") {
-                    continue;
-                }
+            let trait_def = transform_struct_to_trait(&item_struct);
+            let trait_str = trait_def.to_string();
 
-                output.push_str(&trait_str);
-                output.push_str("\n\n");
+            // Skip if the trait would be empty
+            if !trait_str.contains("fn") {
+                continue;
             }
-            _ => {}
+
+            output.push_str(&trait_str);
+            output.push_str("\n\n");
         }
     }
 
@@ -95,16 +86,22 @@ fn is_wrapper_struct(item_struct: &ItemStruct) -> bool {
 }
 
 fn process_directory<P: AsRef<Path>>(
+    root: &Path,
     dir_path: P,
+    filter: &Filter,
     concat_output: &mut File,
     traits_output: &mut File,
 ) -> std::io::Result<()> {
-    for entry in fs::read_dir(dir_path)? {
-        let entry = entry?;
-        let path = entry.path();
-
+    // Sorted, so the output does not depend on the order the file system
+    // lists entries in
+    let mut paths = fs::read_dir(dir_path)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    paths.sort();
+
+    for path in paths {
         if path.is_dir() {
-            process_directory(&path, concat_output, traits_output)?;
+            process_directory(root, &path, filter, concat_output, traits_output)?;
         } else if path.extension().and_then(|s| s.to_str()) == Some("rs") {
             println!("Processing: {:?}", path);
 
@@ -116,8 +113,10 @@ fn process_directory<P: AsRef<Path>>(
             writeln!(concat_output, "{}\n", content)?;
 
             // Transform and write to traits file
+            let module = module_path_for_file(root, &path);
             writeln!(traits_output, "\n// Generated traits for: {:?}", path)?;
-            let traits = process_file_content(content)?;
+            writeln!(traits_output, "// module: {}", module)?;
+            let traits = process_file_content(content, &module, filter)?;
             writeln!(traits_output, "{}\n", traits)?;
         }
     }
@@ -125,6 +124,10 @@ fn process_directory<P: AsRef<Path>>(
 }
 
 fn main() -> std::io::Result<()> {
+    let mut args: Vec<String> = std::env::args().collect();
+    let filter = Filter::from_args(&mut args)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e.to_string()))?;
+
     // Create output files
     let mut concat_output = File::create("modules.rs")?;
     let mut traits_output = File::create("traits.rs")?;
@@ -134,7 +137,8 @@ fn main() -> std::io::Result<()> {
     writeln!(traits_output, "mod generated_traits {{\n")?;
 
     // Process all files
-    process_directory("src/modules", &mut concat_output, &mut traits_output)?;
+    let root = Path::new("src/modules");
+    process_directory(root, root, &filter, &mut concat_output, &mut traits_output)?;
 
     // Close modules
     writeln!(concat_output, "}}")?;
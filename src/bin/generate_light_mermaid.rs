@@ -1,7 +1,9 @@
+use cargo_invoke::filter::Filter;
+use cargo_invoke::model::marked_module;
+use cargo_invoke::theme::Theme;
 use regex::Regex;
-use std::collections::{BTreeSet, HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs;
-use std::io::{self};
 
 #[derive(Debug)]
 struct TraitInfo {
@@ -9,26 +11,39 @@ struct TraitInfo {
     references: BTreeSet<String>,
 }
 
-fn main() -> io::Result<()> {
+fn main() -> anyhow::Result<()> {
+    let mut args: Vec<String> = std::env::args().collect();
+    let filter = Filter::from_args(&mut args)?;
+    let theme = Theme::from_args(&mut args)?;
+
     // 1. Read all contents of "traits.rs"
     let contents = fs::read_to_string("traits.rs")?;
 
     // 2. Identify trait declarations (just the keyword "trait" and name).
     let trait_decl_re = Regex::new(r"(?:pub\s+)?trait\s+([A-Za-z0-9_]+)").unwrap();
 
+    // `extract_traits` marks each file's traits with their module
+    let allowed = |caps: &regex::Captures| {
+        let m = caps.get(0).unwrap();
+        let module = marked_module(&contents, m.start());
+        filter.allows_type(module, &caps[1], m.as_str().starts_with("pub"))
+    };
+
     // Collect all trait names for reference-scanning
-    let mut all_trait_names = HashSet::new();
+    let mut all_trait_names = BTreeSet::new();
     for mat in trait_decl_re.captures_iter(&contents) {
-        all_trait_names.insert(mat[1].to_string());
+        if allowed(&mat) {
+            all_trait_names.insert(mat[1].to_string());
+        }
     }
 
-    // We'll store the final traits here
-    let mut traits_map: HashMap<String, TraitInfo> = HashMap::new();
+    // We'll store the final traits here, keyed by name so they print in name order
+    let mut traits_map: BTreeMap<String, TraitInfo> = BTreeMap::new();
 
     // 3. Find each trait’s braced body and discover references
     let mut search_start = 0;
     while let Some(m) = trait_decl_re.find_at(&contents, search_start) {
-        let caps = trait_decl_re.captures(&contents[m.start()..]).unwrap();
+        let caps = trait_decl_re.captures_at(&contents, m.start()).unwrap();
         let trait_name = caps[1].to_string();
         let trait_name_start = m.start();
 
@@ -52,6 +67,12 @@ fn main() -> io::Result<()> {
             }
         };
 
+        // Traits the filter drops are skipped, body and all
+        if !allowed(&caps) {
+            search_start = end_pos + 1;
+            continue;
+        }
+
         // Collect references (any known trait names appearing in the body)
         let references = find_references(&body_text, &all_trait_names, &trait_name);
 
@@ -70,7 +91,6 @@ fn main() -> io::Result<()> {
 
     // 4. Generate Mermaid output (no methods, just class { } and references)
     let mut mermaid = String::new();
-    mermaid.push_str("```mermaid\n");
     mermaid.push_str("classDiagram\n\n");
 
     // Class definitions without methods
@@ -91,14 +111,18 @@ fn main() -> io::Result<()> {
         mermaid.push('\n');
     }
 
-    mermaid.push_str("\n```");
-
-    let mermaid_clean = mermaid
+    let mut mermaid_clean = mermaid
         .replace("&'a User", "&User")
         .replace("value: &'a User", "value: &User");
+    if let Some(theme) = &theme {
+        mermaid_clean = theme.apply_to_source(&mermaid_clean);
+    }
 
     // 5. Write to architecture_light.md
-    fs::write("architecture_light.md", mermaid_clean)?;
+    fs::write(
+        "architecture_light.md",
+        format!("```mermaid\n{}\n```", mermaid_clean),
+    )?;
 
     println!("Generated architecture_light.md with lighter diagram (no method signatures).");
     Ok(())
@@ -108,12 +132,10 @@ fn main() -> io::Result<()> {
 /// Returns (body, end_index) where `end_index` is the position of the closing brace.
 fn extract_braced_block(contents: &str, brace_start: usize) -> Option<(String, usize)> {
     let mut brace_count = 0;
-    let mut end_pos = brace_start;
     let chars: Vec<_> = contents.char_indices().collect();
     let mut in_brace_region = false;
 
-    for i in 0..chars.len() {
-        let (idx, ch) = chars[i];
+    for &(idx, ch) in &chars {
         if idx < brace_start {
             continue;
         }
@@ -129,7 +151,7 @@ fn extract_braced_block(contents: &str, brace_start: usize) -> Option<(String, u
             } else if ch == '}' {
                 brace_count -= 1;
                 if brace_count == 0 {
-                    end_pos = idx;
+                    let end_pos = idx;
                     let body = &contents[brace_start + 1..end_pos];
                     return Some((body.to_string(), end_pos));
                 }
@@ -143,7 +165,7 @@ fn extract_braced_block(contents: &str, brace_start: usize) -> Option<(String, u
 /// Skips references to itself.
 fn find_references(
     body_text: &str,
-    all_names: &HashSet<String>,
+    all_names: &BTreeSet<String>,
     self_name: &str,
 ) -> BTreeSet<String> {
     let mut refs = BTreeSet::new();
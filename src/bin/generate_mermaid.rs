@@ -1,7 +1,9 @@
+use cargo_invoke::filter::Filter;
+use cargo_invoke::model::marked_module;
+use cargo_invoke::theme::Theme;
 use regex::Regex;
-use std::collections::{BTreeSet, HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs;
-use std::io;
 
 /// Holds information about each method in a trait.
 #[derive(Debug)]
@@ -19,7 +21,11 @@ struct TraitInfo {
     references: BTreeSet<String>,
 }
 
-fn main() -> io::Result<()> {
+fn main() -> anyhow::Result<()> {
+    let mut args: Vec<String> = std::env::args().collect();
+    let filter = Filter::from_args(&mut args)?;
+    let theme = Theme::from_args(&mut args)?;
+
     // 1. Read all contents of "traits.rs"
     let contents = fs::read_to_string("traits.rs")?;
 
@@ -31,16 +37,24 @@ fn main() -> io::Result<()> {
     //       trait TraitName
     let trait_decl_re = Regex::new(r"(?:pub\s+)?trait\s+([A-Za-z0-9_]+)").unwrap();
 
+    // `extract_traits` marks each file's traits with their module
+    let allowed = |caps: &regex::Captures| {
+        let m = caps.get(0).unwrap();
+        let module = marked_module(&contents, m.start());
+        filter.allows_type(module, &caps[1], m.as_str().starts_with("pub"))
+    };
+
     // Step A: Collect all trait names for reference-scanning
     // (We'll do a quick pass just to find all names, ignoring braces)
-    let mut all_trait_names = HashSet::new();
+    let mut all_trait_names = BTreeSet::new();
     for mat in trait_decl_re.captures_iter(&contents) {
-        let trait_name = mat[1].to_string();
-        all_trait_names.insert(trait_name);
+        if allowed(&mat) {
+            all_trait_names.insert(mat[1].to_string());
+        }
     }
 
-    // We'll store the final traits here
-    let mut traits_map = HashMap::new();
+    // We'll store the final traits here, keyed by name so they print in name order
+    let mut traits_map = BTreeMap::new();
 
     // 3. Actually parse each trait’s body by matching braces manually.
     //
@@ -52,7 +66,7 @@ fn main() -> io::Result<()> {
     while let Some(m) = trait_decl_re.find_at(&contents, search_start) {
         // m.start() is the start of "trait" or "pub trait"
         // Capture the name
-        let caps = trait_decl_re.captures(&contents[m.start()..]).unwrap();
+        let caps = trait_decl_re.captures_at(&contents, m.start()).unwrap();
         let trait_name = caps[1].to_string();
 
         // Absolute start index of the name in the full file
@@ -80,6 +94,12 @@ fn main() -> io::Result<()> {
             }
         };
 
+        // Traits the filter drops are skipped, body and all
+        if !allowed(&caps) {
+            search_start = end_pos + 1;
+            continue;
+        }
+
         // Now we have the full text inside { ... } as `body_text`.
         // We'll store a TraitInfo, parse out methods, references, etc.
         let methods = extract_methods(&body_text);
@@ -98,7 +118,6 @@ fn main() -> io::Result<()> {
 
     // 4. Generate Mermaid output
     let mut mermaid = String::new();
-    mermaid.push_str("```mermaid\n");
     mermaid.push_str("classDiagram\n\n");
 
     // Print each trait as a class with methods
@@ -129,8 +148,6 @@ fn main() -> io::Result<()> {
         mermaid.push('\n');
     }
 
-    mermaid.push_str("\n```");
-
     let mermaid_clean = mermaid
         .replace("&'a User", "&User")
         .replace("value: &'a User", "value: &User");
@@ -144,8 +161,13 @@ fn main() -> io::Result<()> {
     let mermaid_no_spaces = re_gt.replace_all(&mermaid_no_spaces, ">");
     let mermaid_no_spaces = re_amp.replace_all(&mermaid_no_spaces, "&");
 
+    let mermaid = match &theme {
+        Some(theme) => theme.apply_to_source(&mermaid_no_spaces),
+        None => mermaid_no_spaces.into_owned(),
+    };
+
     // 3. Write the cleaned string to architecture.md
-    fs::write("architecture.md", mermaid_no_spaces.as_ref())?;
+    fs::write("architecture.md", format!("```mermaid\n{}\n```", mermaid))?;
 
     // 6. Print success
     println!("Generated architecture.md. Below is a snippet showing the captured methods:\n");
@@ -160,7 +182,7 @@ fn main() -> io::Result<()> {
                 println!("   fn {}({}) -> {};", m.name, m.params, m.return_type);
             }
         }
-        println!("");
+        println!();
     }
 
     Ok(())
@@ -171,13 +193,11 @@ fn main() -> io::Result<()> {
 /// position of the closing '}' in `contents`.
 fn extract_braced_block(contents: &str, brace_start: usize) -> Option<(String, usize)> {
     let mut brace_count = 0;
-    let mut end_pos = brace_start;
     let chars: Vec<_> = contents.char_indices().collect();
 
     let mut in_brace_region = false;
 
-    for i in 0..chars.len() {
-        let (idx, ch) = chars[i];
+    for &(idx, ch) in &chars {
         if idx < brace_start {
             continue;
         }
@@ -194,7 +214,7 @@ fn extract_braced_block(contents: &str, brace_start: usize) -> Option<(String, u
             } else if ch == '}' {
                 brace_count -= 1;
                 if brace_count == 0 {
-                    end_pos = idx;
+                    let end_pos = idx;
                     // Extract everything between brace_start+1 and end_pos-1
                     let body = &contents[brace_start + 1..end_pos];
                     return Some((body.to_string(), end_pos));
@@ -245,7 +265,7 @@ fn extract_methods(body_text: &str) -> Vec<MethodInfo> {
 /// Skip references to itself.
 fn find_references(
     body_text: &str,
-    all_names: &HashSet<String>,
+    all_names: &BTreeSet<String>,
     self_name: &str,
 ) -> BTreeSet<String> {
     let mut refs = BTreeSet::new();
@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::process::Command;
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -19,16 +19,16 @@ struct Span {
 fn analyze_rust_errors(
     file_path: &str,
     filter_level: Option<&str>,
-) -> Result<HashMap<String, u32>, String> {
+) -> Result<BTreeMap<String, u32>, String> {
     // Run rustc with JSON output
     let output = Command::new("rustc")
-        .args(&["--error-format=json", file_path])
+        .args(["--error-format=json", file_path])
         .output()
         .map_err(|e| format!("Failed to execute rustc: {}", e))?;
 
     // Parse JSON output
     let stderr = String::from_utf8_lossy(&output.stderr);
-    let mut error_counts: HashMap<String, u32> = HashMap::new();
+    let mut error_counts: BTreeMap<String, u32> = BTreeMap::new();
 
     for line in stderr.lines() {
         if let Ok(message) = serde_json::from_str::<CompilerMessage>(line) {
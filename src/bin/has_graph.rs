@@ -0,0 +1,119 @@
+use cargo_invoke::filter::Filter;
+use cargo_invoke::theme::Theme;
+use cargo_invoke::types::TypeAnalyzer;
+use regex::Regex;
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::Command;
+
+fn main() -> io::Result<()> {
+    let mut args: Vec<String> = std::env::args().collect();
+    let filter = Filter::from_args(&mut args)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+    let theme = Theme::from_args(&mut args)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+    let generate_png = args.contains(&"--png".to_string());
+
+    // We only read from `structs.rs`
+    let structs_path = PathBuf::from("architecture/structs.rs");
+    let state_diagram_path = PathBuf::from("diagrams/state_diagram.mermaid");
+
+    // If `architecture/structs.rs` exists, read it
+    let structs_content = if structs_path.exists() {
+        fs::read_to_string(&structs_path)?
+    } else {
+        String::new()
+    };
+
+    // `architecture` writes a `// module:` marker above each struct, so the
+    // filter sees the real module path
+    let allowed = |cap: &regex::Captures| {
+        let module = cap.get(1).map_or("", |m| m.as_str());
+        filter.allows_type(module, &cap[3], cap.get(2).is_some())
+    };
+
+    // We'll find "struct SomeName {" lines
+    let struct_name_regex = Regex::new(
+        r"(?m)^(?://\s*module:\s*(\S+)\s*\n)?(?:\s*#\[[^\]]*\])*\s*(pub\s+)?struct\s+(\w+)",
+    )
+    .unwrap();
+    let known_classes: HashSet<String> = struct_name_regex
+        .captures_iter(&structs_content)
+        .filter(|cap| allowed(cap))
+        .map(|cap| cap[3].to_string())
+        .collect();
+
+    // Parse each struct block to find fields referencing known classes
+    let struct_block_regex = Regex::new(
+        r"(?s)(?://\s*module:\s*(\S+)\s*\n)?(?:#\[[^\]]+\]\s*)*(pub\s+)?struct\s+(\w+)\s*\{([^}]*)\}",
+    )
+    .unwrap();
+    let analyzer = TypeAnalyzer::new();
+
+    let mut relationships: Vec<(String, String, String)> = Vec::new();
+
+    // For each struct block the filter keeps, parse fields
+    for block_cap in struct_block_regex.captures_iter(&structs_content) {
+        if !allowed(&block_cap) {
+            continue;
+        }
+        let struct_name = block_cap[3].trim();
+        let item: syn::ItemStruct = match syn::parse_str(&block_cap[0]) {
+            Ok(item) => item,
+            Err(e) => {
+                eprintln!("Skipping struct {}: {}", struct_name, e);
+                continue;
+            }
+        };
+
+        for field in &item.fields {
+            // Every type reachable through generics, tuples, trait objects, ...
+            for type_ref in analyzer.analyze(&field.ty) {
+                let t = type_ref.name;
+                if known_classes.contains(&t) && filter.allows_reference(&t) {
+                    relationships.push((struct_name.to_string(), "has".to_string(), t));
+                }
+            }
+        }
+    }
+
+    // Write out `state_diagram.mermaid`
+    let mut file = fs::File::create(&state_diagram_path)?;
+    writeln!(file, "graph LR")?;
+    writeln!(file)?;
+
+    for (a, label, b) in &relationships {
+        writeln!(file, "    {} --> |{}| {}", a, label, b)?;
+    }
+    drop(file);
+    if let Some(theme) = &theme {
+        theme
+            .apply_to_file(&state_diagram_path)
+            .map_err(|e| io::Error::other(e.to_string()))?;
+    }
+
+    println!("Generated diagram at {}", state_diagram_path.display());
+    println!("Known classes used for matching: {:?}", known_classes);
+
+    // Optionally generate PNG if `--png` is passed
+    if generate_png {
+        println!("Generating PNG...");
+        let status = Command::new("cargo")
+            .args([
+                "run",
+                "--bin",
+                "generate_mermaid_png",
+                "--",
+                state_diagram_path.to_str().unwrap(),
+            ])
+            .status()?;
+
+        if !status.success() {
+            return Err(io::Error::other("Failed to generate PNG"));
+        }
+    }
+
+    Ok(())
+}
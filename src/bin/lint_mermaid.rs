@@ -1,134 +1,58 @@
+use cargo_invoke::lint;
+use cargo_invoke::mermaid::{Diagram, Line, Statement};
 use std::error::Error;
 use std::fs;
+use std::io;
 use std::path::Path;
 
-#[derive(Debug)]
-struct MermaidFormatter {
-    formatted: String,
-    indent_level: usize,
-    in_subgraph: bool,
-    class_assignments: Vec<String>,
+/// Formats a diagram of any kind by printing its parsed form: one statement
+/// per line, `indent` spaces per nesting level, and runs of blank lines
+/// collapsed to one. Formatting formatted output changes nothing.
+pub fn format_mermaid(input: &str, indent: usize) -> String {
+    let mut diagram = Diagram::parse(input);
+    collapse_blank_lines(&mut diagram.preamble);
+    collapse_blank_lines(&mut diagram.body);
+    diagram.render(indent)
 }
 
-impl MermaidFormatter {
-    fn new() -> Self {
-        MermaidFormatter {
-            formatted: String::new(),
-            indent_level: 0,
-            in_subgraph: false,
-            class_assignments: Vec::new(),
-        }
+/// Drops blank lines at the start or end of a body and after another blank.
+fn collapse_blank_lines(lines: &mut Vec<Line>) {
+    let mut previous_blank = true;
+    lines.retain(|line| {
+        let blank = line.statement == Statement::Blank;
+        let keep = !(blank && previous_blank);
+        previous_blank = blank;
+        keep
+    });
+    if lines
+        .last()
+        .is_some_and(|line| line.statement == Statement::Blank)
+    {
+        lines.pop();
     }
-
-    fn add_line(&mut self, line: &str) {
-        if !line.is_empty() {
-            self.formatted.push_str(&"    ".repeat(self.indent_level));
-            self.formatted.push_str(line);
-            self.formatted.push('\n');
-        }
-    }
-
-    fn format_mermaid(&mut self, input: &str) {
-        let lines: Vec<&str> = input.lines().collect();
-        let mut i = 0;
-
-        while i < lines.len() {
-            let trimmed = lines[i].trim();
-            if trimmed.is_empty() {
-                i += 1;
-                continue;
-            }
-
-            // Handle initialization blocks
-            if trimmed.starts_with("%%{") {
-                self.add_line(trimmed);
-                i += 1;
-                continue;
-            }
-
-            // Handle comments
-            if trimmed.starts_with("%%") {
-                self.add_line(trimmed);
-                i += 1;
-                continue;
-            }
-
-            // Handle class assignments (:::)
-            if trimmed.contains(":::") {
-                self.class_assignments.push(trimmed.to_string());
-                i += 1;
-                continue;
-            }
-
-            // Handle direction statements
-            if trimmed.starts_with("direction ") {
-                self.add_line(trimmed);
-                i += 1;
-                continue;
-            }
-
-            match trimmed {
-                s if s.starts_with("subgraph") => {
-                    self.add_line(s);
-                    self.indent_level += 1;
-                    self.in_subgraph = true;
-                }
-                "end" => {
-                    self.indent_level = self.indent_level.saturating_sub(1);
-                    self.add_line("end");
-                    if self.in_subgraph {
-                        self.in_subgraph = false;
-                    }
-                }
-                // Root level definitions go at base indentation
-                s if s.starts_with("class ")
-                    || s.starts_with("classDef ")
-                    || s.starts_with("style ") =>
-                {
-                    self.add_line(s);
-                }
-                // Diagram type declarations
-                s if s.starts_with("graph ")
-                    || s.starts_with("sequenceDiagram")
-                    || s.starts_with("classDiagram")
-                    || s.starts_with("stateDiagram")
-                    || s.starts_with("gantt")
-                    || s.starts_with("pie")
-                    || s.starts_with("flowchart")
-                    || s.starts_with("erDiagram") =>
-                {
-                    self.add_line(s);
-                }
-                s => {
-                    self.add_line(s);
-                }
-            }
-            i += 1;
-        }
-
-        // Add sorted class assignments at the end
-        if !self.class_assignments.is_empty() {
-            self.formatted.push('\n');
-            self.class_assignments.sort();
-            let assignments = std::mem::take(&mut self.class_assignments);
-            for assignment in assignments {
-                self.add_line(&assignment);
-            }
+    for line in lines {
+        match &mut line.statement {
+            Statement::Subgraph(subgraph) => collapse_blank_lines(&mut subgraph.body),
+            Statement::Block(block) => collapse_blank_lines(&mut block.body),
+            _ => {}
         }
     }
 }
 
-pub fn format_mermaid(input: &str) -> String {
-    let mut formatter = MermaidFormatter::new();
-    formatter.format_mermaid(input);
-    formatter.formatted
-}
+const USAGE: &str =
+    "[--format] [--inplace|-i] [--check] [--indent <N>] <input_file> | --lint [--fix] <input_file>";
 
 fn main() -> Result<(), Box<dyn Error>> {
     let args: Vec<String> = std::env::args().collect();
 
     // Parse command line arguments
     let mut in_place = false;
+    let mut format = false;
+    let mut lint = false;
+    let mut apply_fixes = false;
+    let mut check = false;
+    let mut indent = 4;
+    let mut indent_set = false;
     let mut input_file = None;
     let mut i = 1;
 
@@ -138,11 +62,39 @@ fn main() -> Result<(), Box<dyn Error>> {
                 in_place = true;
                 i += 1;
             }
+            "--format" => {
+                format = true;
+                i += 1;
+            }
+            "--lint" => {
+                lint = true;
+                i += 1;
+            }
+            "--fix" => {
+                lint = true;
+                apply_fixes = true;
+                i += 1;
+            }
+            "--check" => {
+                check = true;
+                i += 1;
+            }
+            "--indent" => {
+                indent_set = true;
+                indent = match args.get(i + 1).map(|n| n.parse()) {
+                    Some(Ok(n)) => n,
+                    _ => {
+                        eprintln!("--indent expects a number of spaces");
+                        std::process::exit(1);
+                    }
+                };
+                i += 2;
+            }
             arg => {
                 if input_file.is_none() {
                     input_file = Some(arg.to_string());
                 } else {
-                    eprintln!("Usage: {} [--inplace|-i] <input_file>", args[0]);
+                    eprintln!("Usage: {} {}", args[0], USAGE);
                     std::process::exit(1);
                 }
                 i += 1;
@@ -150,10 +102,20 @@ fn main() -> Result<(), Box<dyn Error>> {
         }
     }
 
+    // Formatting is the default; --lint and --fix switch to linting, which
+    // takes none of the formatter's options.
+    if lint && (format || in_place || check || indent_set) {
+        eprintln!(
+            "--format, --inplace, --check and --indent only apply when formatting, not with --lint or --fix"
+        );
+        eprintln!("Usage: {} {}", args[0], USAGE);
+        std::process::exit(1);
+    }
+
     let input_file = match input_file {
         Some(file) => file,
         None => {
-            eprintln!("Usage: {} [--inplace|-i] <input_file>", args[0]);
+            eprintln!("Usage: {} {}", args[0], USAGE);
             std::process::exit(1);
         }
     };
@@ -161,7 +123,59 @@ fn main() -> Result<(), Box<dyn Error>> {
     let input_path = Path::new(&input_file);
     let input_content = fs::read_to_string(input_path)?;
 
-    let formatted = format_mermaid(&input_content);
+    if check {
+        // For CI: fail without writing anything when the file is not formatted
+        if format_mermaid(&input_content, indent) != input_content {
+            eprintln!("{} is not formatted", input_path.display());
+            std::process::exit(1);
+        }
+        println!("{} is formatted", input_path.display());
+        return Ok(());
+    }
+    if !lint {
+        write_formatted(input_path, &input_content, indent, in_place)?;
+        return Ok(());
+    }
+
+    let mut diagram = Diagram::parse(&input_content);
+    let mut source = input_content;
+    if apply_fixes {
+        let fixed = lint::fix(&mut diagram);
+        source = diagram.to_string();
+        fs::write(input_path, &source)?;
+        println!("Fixed {} problem(s) in {}", fixed, input_path.display());
+        // Report what is left against the rewritten file
+        diagram = Diagram::parse(&source);
+    }
+
+    let diagnostics = lint::lint(&diagram, &source);
+    for diagnostic in &diagnostics {
+        println!("{}:{}", input_path.display(), diagnostic);
+    }
+    if !diagnostics.is_empty() {
+        let fixable = diagnostics.iter().filter(|d| d.fixable).count();
+        eprintln!(
+            "{} problem(s) found{}",
+            diagnostics.len(),
+            if fixable > 0 {
+                format!(", {} fixable with --fix", fixable)
+            } else {
+                String::new()
+            }
+        );
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+fn write_formatted(
+    input_path: &Path,
+    input_content: &str,
+    indent: usize,
+    in_place: bool,
+) -> io::Result<()> {
+    let formatted = format_mermaid(input_content, indent);
 
     if in_place {
         // Write back to the same file
@@ -195,35 +209,77 @@ mod tests {
     #[test]
     fn test_basic_formatting() {
         let input = "graph TD\nA-->B\n   C --> D\n";
-        let expected = "graph TD\n    A-->B\n    C --> D\n";
-        assert_eq!(format_mermaid(input), expected);
+        let expected = "graph TD\n    A --> B\n    C --> D\n";
+        assert_eq!(format_mermaid(input, 4), expected);
     }
 
     #[test]
     fn test_subgraph_formatting() {
         let input = "graph TD\nsubgraph One\nA-->B\nend\n";
-        let expected = "graph TD\nsubgraph One\n    A-->B\nend\n";
-        assert_eq!(format_mermaid(input), expected);
+        let expected = "graph TD\n    subgraph One\n        A --> B\n    end\n";
+        assert_eq!(format_mermaid(input, 4), expected);
     }
 
     #[test]
     fn test_nested_subgraphs() {
         let input = "graph TD\nsubgraph One\nsubgraph Two\nA-->B\nend\nend\n";
-        let expected = "graph TD\nsubgraph One\n    subgraph Two\n        A-->B\n    end\nend\n";
-        assert_eq!(format_mermaid(input), expected);
+        let expected = "graph TD\n    subgraph One\n        subgraph Two\n            A --> B\n        end\n    end\n";
+        assert_eq!(format_mermaid(input, 4), expected);
     }
 
     #[test]
     fn test_direction_statements() {
         let input = "graph TD\nsubgraph One\ndirection LR\nA-->B\nend\n";
-        let expected = "graph TD\nsubgraph One\n    direction LR\n    A-->B\nend\n";
-        assert_eq!(format_mermaid(input), expected);
+        let expected =
+            "graph TD\n    subgraph One\n        direction LR\n        A --> B\n    end\n";
+        assert_eq!(format_mermaid(input, 4), expected);
+    }
+
+    #[test]
+    fn test_frontmatter_is_kept() {
+        let input = "---\ntitle: Login\n---\nsequenceDiagram\nAlice->>Bob: hi\n";
+        let expected = "---\ntitle: Login\n---\nsequenceDiagram\n    Alice->>Bob: hi\n";
+        assert_eq!(format_mermaid(input, 4), expected);
     }
 
     #[test]
     fn test_class_assignments() {
         let input = "graph TD\nA-->B:::classB\nC:::classC-->D\n";
-        let expected = "graph TD\n    A-->B\n    C-->D\n\nA-->B:::classB\nC:::classC-->D\n";
-        assert_eq!(format_mermaid(input), expected);
+        let expected = "graph TD\n    A --> B:::classB\n    C:::classC --> D\n";
+        assert_eq!(format_mermaid(input, 4), expected);
+    }
+
+    #[test]
+    fn test_indent_width_and_blank_lines() {
+        let input = "graph TD\n\n\nsubgraph One\n\nA-->B\n\nend\n\n";
+        let expected = "graph TD\n  subgraph One\n    A --> B\n  end\n";
+        assert_eq!(format_mermaid(input, 2), expected);
+    }
+
+    #[test]
+    fn test_all_diagram_kinds() {
+        let cases = [
+            (
+                "classDiagram\nnamespace app {\nclass Server~T~ {\n+start() bool\n  }\n}\nServer \"1\" *-- \"0..1\" Config : config\n",
+                "classDiagram\n    namespace app {\n        class Server~T~ {\n            +start() bool\n        }\n    }\n    Server \"1\" *-- \"0..1\" Config : config\n",
+            ),
+            (
+                "stateDiagram-v2\n[*] --> Open\nstate Open {\n[*] --> Idle\nIdle --> Busy : work\n}\nOpen --> [*]\n",
+                "stateDiagram-v2\n    [*] --> Open\n    state Open {\n        [*] --> Idle\n        Idle --> Busy : work\n    }\n    Open --> [*]\n",
+            ),
+            (
+                "sequenceDiagram\nactor Caller\nloop every tick\nCaller->>Server: poll()\nalt ready\nServer-->>Caller: Ok\nelse busy\nServer-->>Caller: Err\nend\nend\n",
+                "sequenceDiagram\n    actor Caller\n    loop every tick\n        Caller->>Server: poll()\n        alt ready\n            Server-->>Caller: Ok\n        else busy\n            Server-->>Caller: Err\n        end\n    end\n",
+            ),
+            (
+                "erDiagram\nOrder {\nUuid id PK\nUuid customer_id FK \"optional\"\n}\nOrder }o--|| Customer : \"customer_id\"\n",
+                "erDiagram\n    Order {\n        Uuid id PK\n        Uuid customer_id FK \"optional\"\n    }\n    Order }o--|| Customer : \"customer_id\"\n",
+            ),
+        ];
+        for (input, expected) in cases {
+            let formatted = format_mermaid(input, 4);
+            assert_eq!(formatted, expected);
+            assert_eq!(format_mermaid(&formatted, 4), formatted, "not idempotent");
+        }
     }
 }
@@ -1,18 +1,33 @@
-use std::collections::HashMap;
+use cargo_invoke::mermaid::{Diagram, DiagramKind, Edge, Node, Statement, Subgraph};
+use cargo_invoke::theme::Theme;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fs::{self, File};
 use std::io::{self, Write};
 use std::path::Path;
 
-#[derive(Default)]
-struct MermaidClasses {
-    definitions: HashMap<String, String>, // classDef name -> props
-    assignments: Vec<(Vec<String>, String)>, // (nodes, class_name)
+/// One input file, named after its file stem.
+struct Input {
+    name: String,
+    diagram: Diagram,
+}
+
+/// The merged diagram and what had to change to build it.
+struct Merge {
+    diagram: Diagram,
+    reports: Vec<String>,
 }
 
 fn main() -> io::Result<()> {
-    let args: Vec<String> = std::env::args().collect();
+    let mut args: Vec<String> = std::env::args().collect();
+    let theme = match Theme::from_args(&mut args) {
+        Ok(theme) => theme,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
+        }
+    };
     if args.len() != 2 {
-        eprintln!("Usage: {} <directory>", args[0]);
+        eprintln!("Usage: {} <directory> [--theme <NAME>]", args[0]);
         std::process::exit(1);
     }
 
@@ -22,23 +37,36 @@ fn main() -> io::Result<()> {
         std::process::exit(1);
     }
 
-    // Start with flowchart TB and a blank line
-    let mut merged_content = String::from("flowchart LR\n\n");
-    let mut classes = MermaidClasses::default();
+    // Read all .mermaid files, sorted so the output does not depend on the
+    // order the file system lists them in
+    let mut paths: Vec<_> = fs::read_dir(dir_path)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().and_then(|s| s.to_str()) == Some("mermaid"))
+        .collect();
+    paths.sort();
 
-    // Read and process all .mermaid files
-    for entry in fs::read_dir(dir_path)? {
-        let entry = entry?;
-        let path = entry.path();
+    let mut inputs = Vec::new();
+    for path in paths {
+        let content = fs::read_to_string(&path)?;
+        inputs.push(Input {
+            name: sanitize_id(&path.file_stem().unwrap().to_string_lossy()),
+            diagram: Diagram::parse(&content),
+        });
+    }
 
-        if path.extension().and_then(|s| s.to_str()) == Some("mermaid") {
-            let content = fs::read_to_string(&path)?;
-            process_mermaid_file(&content, &mut merged_content, &mut classes);
+    let mut merge = match merge(inputs) {
+        Ok(merge) => merge,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
         }
+    };
+    for report in &merge.reports {
+        eprintln!("warning: {}", report);
+    }
+    if let Some(theme) = &theme {
+        theme.apply(&mut merge.diagram);
     }
-
-    // Append collected class definitions and assignments
-    append_classes(&mut merged_content, &classes);
 
     // Create output file in the parent directory
     let parent_dir = dir_path.parent().unwrap_or_else(|| Path::new("."));
@@ -47,7 +75,7 @@ fn main() -> io::Result<()> {
         dir_path.file_name().unwrap().to_string_lossy()
     ));
     let mut output_file = File::create(&output_filename)?;
-    output_file.write_all(merged_content.as_bytes())?;
+    output_file.write_all(merge.diagram.to_string().as_bytes())?;
 
     println!(
         "Successfully merged Mermaid files into {}",
@@ -56,49 +84,477 @@ fn main() -> io::Result<()> {
     Ok(())
 }
 
-fn process_mermaid_file(content: &str, merged_content: &mut String, classes: &mut MermaidClasses) {
-    for line in content.lines() {
-        let trimmed = line.trim();
+/// Merges flowcharts, each wrapped in a subgraph named after its file.
+///
+/// * Nodes declared the same way in several files are declared once, outside
+///   the subgraphs, and the edges touching them move out too.
+/// * A node id given different labels in different files is prefixed with
+///   each file's name, e.g. `api_Config` and `db_Config`.
+/// * A classDef redefined with different styles is renamed `name_file` in the
+///   later file. Identical classDefs are kept once.
+/// * `linkStyle` indices are renumbered to where each file's edges end up.
+fn merge(mut inputs: Vec<Input>) -> Result<Merge, String> {
+    if let Some(other) = inputs.iter().find(|i| !i.diagram.kind.is_flowchart()) {
+        return Err(format!(
+            "{} is a {}; only flowcharts can be merged",
+            other.name,
+            other.diagram.kind.keyword()
+        ));
+    }
 
-        // Skip ALL lines starting with "flowchart"
-        if trimmed.starts_with("flowchart") {
-            continue;
+    let mut reports = Vec::new();
+    let direction = inputs
+        .first()
+        .and_then(|i| i.diagram.direction.clone())
+        .unwrap_or_else(|| "TB".to_string());
+    let mut merged = Diagram::new(DiagramKind::Flowchart, Some(&direction));
+    if let Some(first) = inputs.first() {
+        merged.preamble = first.diagram.preamble.clone();
+    }
+
+    namespace_conflicting_ids(&mut inputs, &mut reports);
+    let class_defs = merge_class_defs(&mut inputs, &mut reports);
+
+    // Nodes mentioned by more than one file are shared between them
+    let mut files_of: BTreeMap<String, BTreeSet<usize>> = BTreeMap::new();
+    for (i, input) in inputs.iter().enumerate() {
+        for id in input.diagram.nodes().into_keys() {
+            files_of.entry(id).or_default().insert(i);
+        }
+    }
+    let shared: BTreeSet<String> = files_of
+        .into_iter()
+        .filter(|(_, files)| files.len() > 1)
+        .map(|(id, _)| id)
+        .collect();
+
+    let mut shared_nodes: BTreeMap<String, Node> = BTreeMap::new();
+    let mut shared_edges: Vec<Edge> = Vec::new();
+    let mut assignments = Vec::new();
+    let mut link_styles: Vec<FileLinks> = Vec::new();
+    for input in &mut inputs {
+        for (id, node) in input.diagram.nodes() {
+            if shared.contains(&id) {
+                let entry = shared_nodes
+                    .entry(id)
+                    .or_insert_with(|| Node::new(&node.id));
+                if entry.label.is_none() {
+                    entry.shape = node.shape;
+                    entry.label = node.label;
+                }
+                for class in node.classes {
+                    if !entry.classes.contains(&class) {
+                        entry.classes.push(class);
+                    }
+                }
+            }
         }
 
-        if trimmed.starts_with("classDef ") {
-            // Handle class definition
-            let parts: Vec<&str> = trimmed["classDef ".len()..].splitn(2, ' ').collect();
-            if parts.len() == 2 {
-                classes
-                    .definitions
-                    .insert(parts[0].to_string(), parts[1].to_string());
-            }
-        } else if trimmed.starts_with("class ") {
-            // Handle class assignment
-            let parts: Vec<&str> = trimmed["class ".len()..].splitn(2, ' ').collect();
-            if parts.len() == 2 {
-                let nodes: Vec<String> =
-                    parts[0].split(',').map(|s| s.trim().to_string()).collect();
-                classes.assignments.push((nodes, parts[1].to_string()));
-            }
-        } else if !trimmed.is_empty() {
-            // Add non-empty, non-class-related lines to merged content
-            merged_content.push_str(line);
-            merged_content.push('\n');
+        let is_moved: Vec<bool> = input
+            .diagram
+            .edges()
+            .map(|e| shared.contains(&e.from.id) || shared.contains(&e.to.id))
+            .collect();
+        let mut styles = Vec::new();
+        let mut moved = Vec::new();
+        input.diagram.retain(|statement| match statement {
+            Statement::Node(node) => !shared.contains(&node.id),
+            Statement::Edge(edge)
+                if shared.contains(&edge.from.id) || shared.contains(&edge.to.id) =>
+            {
+                moved.push(edge.clone());
+                false
+            }
+            Statement::ClassDef { .. } | Statement::Blank => false,
+            Statement::ClassAssign { nodes, class } => {
+                assignments.push((nodes.clone(), class.clone()));
+                false
+            }
+            Statement::LinkStyle {
+                links,
+                styles: style,
+            } => {
+                styles.push((links.clone(), style.clone()));
+                false
+            }
+            _ => true,
+        });
+        // Shared endpoints are declared once at the top; the file's own
+        // endpoints stay declared inside its subgraph
+        let mut declared = input.diagram.nodes();
+        let mut moved_to = Vec::new();
+        for mut edge in moved {
+            for node in [&mut edge.from, &mut edge.to] {
+                let undeclared = !declared.contains_key(&node.id);
+                if !shared.contains(&node.id) && (undeclared || node.label.is_some()) {
+                    declared.insert(node.id.clone(), node.clone());
+                    input
+                        .diagram
+                        .body
+                        .push(Statement::Node(node.clone()).into());
+                }
+                node.shape = None;
+                node.label = None;
+                node.classes.clear();
+            }
+            match shared_edges.iter().position(|e| *e == edge) {
+                Some(at) => moved_to.push(at),
+                None => {
+                    moved_to.push(shared_edges.len());
+                    shared_edges.push(edge);
+                }
+            }
         }
+
+        let mut moved_to = moved_to.into_iter();
+        let mut own = 0;
+        let places = is_moved
+            .into_iter()
+            .map(|moved| {
+                if moved {
+                    Place::Shared(moved_to.next().unwrap())
+                } else {
+                    own += 1;
+                    Place::Own(own - 1)
+                }
+            })
+            .collect();
+        link_styles.push(FileLinks {
+            places,
+            own,
+            styles,
+        });
+    }
+    let link_styles = renumber_link_styles(&inputs, link_styles, &mut reports);
+
+    for node in shared_nodes.into_values() {
+        merged.body.push(Statement::Node(node).into());
+    }
+    for input in inputs {
+        let mut body = Vec::new();
+        if let Some(own) = input.diagram.direction.filter(|d| *d != direction) {
+            body.push(Statement::Direction(own).into());
+        }
+        body.extend(input.diagram.body);
+        merged.body.push(
+            Statement::Subgraph(Subgraph {
+                id: input.name,
+                title: None,
+                body,
+            })
+            .into(),
+        );
+    }
+    for edge in shared_edges {
+        merged.body.push(Statement::Edge(edge).into());
+    }
+
+    if !class_defs.is_empty() || !assignments.is_empty() || !link_styles.is_empty() {
+        merged.body.push(Statement::Blank.into());
+    }
+    for (name, styles) in class_defs {
+        merged.body.push(
+            Statement::ClassDef {
+                names: vec![name],
+                styles,
+            }
+            .into(),
+        );
     }
+    for (nodes, class) in assignments {
+        merged
+            .body
+            .push(Statement::ClassAssign { nodes, class }.into());
+    }
+    for statement in link_styles {
+        merged.body.push(statement.into());
+    }
+
+    Ok(Merge {
+        diagram: merged,
+        reports,
+    })
+}
+
+/// Where one of a file's edges ends up in the merged diagram.
+enum Place {
+    /// The n-th edge left in the file's subgraph.
+    Own(usize),
+    /// The n-th edge after the subgraphs, between shared nodes.
+    Shared(usize),
 }
 
-fn append_classes(merged_content: &mut String, classes: &MermaidClasses) {
-    merged_content.push('\n');
+/// A file's edges and the `linkStyle`s numbering them.
+struct FileLinks {
+    places: Vec<Place>,
+    /// How many edges stay in the file's subgraph.
+    own: usize,
+    /// `(links, styles)` of each `linkStyle`, in order.
+    styles: Vec<(String, String)>,
+}
+
+/// Renumbers each file's `linkStyle` indices to the positions its edges take
+/// in the merged diagram, where the subgraphs' edges come first, in file
+/// order, followed by the shared ones. Indices without an edge are dropped,
+/// as are `linkStyle default` lines conflicting with an earlier file's.
+fn renumber_link_styles(
+    inputs: &[Input],
+    files: Vec<FileLinks>,
+    reports: &mut Vec<String>,
+) -> Vec<Statement> {
+    let shared_base: usize = files.iter().map(|f| f.own).sum();
+    let mut base = 0;
+    let mut default: Option<String> = None;
+    let mut statements = Vec::new();
+    for (input, file) in inputs.iter().zip(files) {
+        for (links, styles) in file.styles {
+            if links == "default" {
+                match &default {
+                    None => {
+                        default = Some(styles.clone());
+                        statements.push(Statement::LinkStyle { links, styles });
+                    }
+                    Some(existing) if *existing != styles => reports.push(format!(
+                        "linkStyle default in {} (`{}`) conflicts with `{}`; dropped",
+                        input.name, styles, existing
+                    )),
+                    Some(_) => {}
+                }
+                continue;
+            }
+
+            let mut renumbered = Vec::new();
+            for link in links.split(',') {
+                match link
+                    .trim()
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|i| file.places.get(i))
+                {
+                    Some(Place::Own(i)) => renumbered.push(base + i),
+                    Some(Place::Shared(i)) => renumbered.push(shared_base + i),
+                    None => reports.push(format!(
+                        "linkStyle {} in {} refers to no edge; dropped",
+                        link.trim(),
+                        input.name
+                    )),
+                }
+            }
+            if !renumbered.is_empty() {
+                let links: Vec<String> = renumbered.iter().map(usize::to_string).collect();
+                statements.push(Statement::LinkStyle {
+                    links: links.join(","),
+                    styles,
+                });
+            }
+        }
+        base += file.own;
+    }
+    statements
+}
+
+/// Prefixes node ids that carry different labels in different files.
+fn namespace_conflicting_ids(inputs: &mut [Input], reports: &mut Vec<String>) {
+    let mut labels: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    for input in inputs.iter() {
+        for (id, node) in input.diagram.nodes() {
+            if let Some(label) = node.label {
+                labels.entry(id).or_default().insert(label);
+            }
+        }
+    }
+
+    for (id, variants) in labels.into_iter().filter(|(_, v)| v.len() > 1) {
+        let files: Vec<&str> = inputs
+            .iter()
+            .filter(|i| i.diagram.nodes().contains_key(&id))
+            .map(|i| i.name.as_str())
+            .collect();
+        reports.push(format!(
+            "node `{}` has {} different labels across {}; prefixed with the file name",
+            id,
+            variants.len(),
+            files.join(", ")
+        ));
+        for input in inputs.iter_mut() {
+            if input.diagram.nodes().contains_key(&id) {
+                let renamed = format!("{}_{}", input.name, id);
+                rename_id(&mut input.diagram, &id, &renamed);
+            }
+        }
+    }
+}
+
+/// Replaces node id `from` with `to` wherever the diagram refers to it.
+fn rename_id(diagram: &mut Diagram, from: &str, to: &str) {
+    let rename = |id: &mut String| {
+        if id == from {
+            *id = to.to_string();
+        }
+    };
+    diagram.for_each_node_mut(|node| rename(&mut node.id));
+    diagram.for_each_statement_mut(|statement| match statement {
+        Statement::Subgraph(subgraph) => rename(&mut subgraph.id),
+        Statement::ClassAssign { nodes, .. } => nodes.iter_mut().for_each(rename),
+        Statement::Style { node, .. } | Statement::Click { node, .. } => rename(node),
+        _ => {}
+    });
+}
+
+/// Collects classDefs across files, renaming any that clash with an earlier
+/// definition of the same name.
+fn merge_class_defs(inputs: &mut [Input], reports: &mut Vec<String>) -> Vec<(String, String)> {
+    let mut defs: Vec<(String, String)> = Vec::new();
+    for input in inputs.iter_mut() {
+        let mut renames: HashMap<String, String> = HashMap::new();
+        for (name, styles) in input.diagram.class_defs() {
+            match defs.iter().find(|(n, _)| n == name) {
+                Some((_, existing)) if existing == styles => {}
+                Some((_, existing)) => {
+                    let renamed = format!("{}_{}", name, input.name);
+                    reports.push(format!(
+                        "classDef `{}` in {} (`{}`) conflicts with `{}`; renamed to `{}`",
+                        name, input.name, styles, existing, renamed
+                    ));
+                    renames.insert(name.to_string(), renamed.clone());
+                    defs.push((renamed, styles.to_string()));
+                }
+                None => defs.push((name.to_string(), styles.to_string())),
+            }
+        }
+
+        if renames.is_empty() {
+            continue;
+        }
+        let rename = |class: &mut String| {
+            if let Some(renamed) = renames.get(class.as_str()) {
+                *class = renamed.clone();
+            }
+        };
+        input
+            .diagram
+            .for_each_node_mut(|node| node.classes.iter_mut().for_each(rename));
+        input.diagram.for_each_statement_mut(|statement| {
+            if let Statement::ClassAssign { class, .. } = statement {
+                rename(class);
+            }
+        });
+    }
+    defs
+}
+
+/// Turns a file name into something usable as a subgraph and id prefix.
+fn sanitize_id(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(name: &str, source: &str) -> Input {
+        Input {
+            name: name.to_string(),
+            diagram: Diagram::parse(source),
+        }
+    }
+
+    #[test]
+    fn test_merge_shares_and_namespaces_nodes() {
+        let merge = merge(vec![
+            input(
+                "api",
+                "flowchart LR\nHandler[Handler] --> Db[(Database)]\nHandler --> Cfg[Api config]:::hot\nclassDef hot fill:#f00\n",
+            ),
+            input(
+                "store",
+                "flowchart TB\nPool --> Db[(Database)]\nPool --> Cfg[Store config]\nclass Pool hot\nclassDef hot fill:#0f0\n",
+            ),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            merge.diagram.to_string(),
+            "flowchart LR\n\
+             \x20   Db[(Database)]\n\
+             \x20   subgraph api\n\
+             \x20       Handler --> api_Cfg[Api config]:::hot\n\
+             \x20       Handler[Handler]\n\
+             \x20   end\n\
+             \x20   subgraph store\n\
+             \x20       direction TB\n\
+             \x20       Pool --> store_Cfg[Store config]\n\
+             \x20   end\n\
+             \x20   Handler --> Db\n\
+             \x20   Pool --> Db\n\
+             \n\
+             \x20   classDef hot fill:#f00\n\
+             \x20   classDef hot_store fill:#0f0\n\
+             \x20   class Pool hot_store\n"
+        );
+        assert_eq!(merge.reports.len(), 2);
+    }
+
+    #[test]
+    fn test_merge_renumbers_link_styles() {
+        let merge = merge(vec![
+            input(
+                "a",
+                "flowchart LR\nA --> B\nB --> Shared\nlinkStyle 1 stroke:red\nlinkStyle default stroke:grey\n",
+            ),
+            input(
+                "b",
+                "flowchart LR\nC --> D\nE --> F\nShared --> E\nlinkStyle 1 stroke:blue\nlinkStyle 0,2 stroke:green\nlinkStyle 7 stroke:pink\n",
+            ),
+        ])
+        .unwrap();
 
-    // Add class definitions
-    for (name, props) in &classes.definitions {
-        merged_content.push_str(&format!("classDef {} {}\n", name, props));
+        let edges: Vec<String> = merge
+            .diagram
+            .edges()
+            .map(|e| format!("{}-{}", e.from.id, e.to.id))
+            .collect();
+        assert_eq!(edges, ["A-B", "C-D", "E-F", "B-Shared", "Shared-E"]);
+        let styles: Vec<(&str, &str)> = merge
+            .diagram
+            .body
+            .iter()
+            .filter_map(|line| match &line.statement {
+                Statement::LinkStyle { links, styles } => Some((links.as_str(), styles.as_str())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            styles,
+            [
+                ("3", "stroke:red"),
+                ("default", "stroke:grey"),
+                ("2", "stroke:blue"),
+                ("1,4", "stroke:green"),
+            ]
+        );
+        assert_eq!(
+            merge.reports,
+            ["linkStyle 7 in b refers to no edge; dropped"]
+        );
     }
 
-    // Add class assignments
-    for (nodes, class_name) in &classes.assignments {
-        merged_content.push_str(&format!("class {} {}\n", nodes.join(","), class_name));
+    #[test]
+    fn test_merge_refuses_other_kinds() {
+        let err = merge(vec![
+            input("a", "flowchart TB\nA --> B\n"),
+            input("b", "classDiagram\nA --> B\n"),
+        ])
+        .err()
+        .unwrap();
+        assert_eq!(err, "b is a classDiagram; only flowcharts can be merged");
     }
 }
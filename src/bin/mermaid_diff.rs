@@ -0,0 +1,458 @@
+use cargo_invoke::mermaid::{Diagram, DiagramKind, Edge, Node, Statement};
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fs;
+
+/// Highlight classes for `--highlight`, named so they do not clash with the
+/// diagram's own classDefs.
+const ADDED: (&str, &str) = ("diff_added", "fill:#e6ffed,stroke:#2da44e,stroke-width:2px");
+const REMOVED: (&str, &str) = (
+    "diff_removed",
+    "fill:#ffebe9,stroke:#cf222e,stroke-width:2px,stroke-dasharray:5 5",
+);
+const CHANGED: (&str, &str) = (
+    "diff_changed",
+    "fill:#fff8c5,stroke:#bf8700,stroke-width:2px",
+);
+const ADDED_LINK: &str = "stroke:#2da44e,stroke-width:2px";
+const REMOVED_LINK: &str = "stroke:#cf222e,stroke-width:2px,stroke-dasharray:5 5";
+
+/// What changed between two versions of a diagram, ignoring statement order
+/// and formatting.
+#[derive(Debug, Default)]
+struct DiagramDiff {
+    added_nodes: Vec<Node>,
+    removed_nodes: Vec<Node>,
+    /// Nodes whose shape or label changed: `(old, new)`.
+    changed_nodes: Vec<(Node, Node)>,
+    added_edges: Vec<Edge>,
+    removed_edges: Vec<Edge>,
+    /// Class members, entity attributes and state descriptions: `(owner, text)`.
+    added_members: Vec<(String, String)>,
+    removed_members: Vec<(String, String)>,
+    /// `classDef` names and styles; a changed style is removed and added.
+    added_class_defs: Vec<(String, String)>,
+    removed_class_defs: Vec<(String, String)>,
+    /// `(node, class)` assignments.
+    added_classes: Vec<(String, String)>,
+    removed_classes: Vec<(String, String)>,
+}
+
+impl DiagramDiff {
+    fn is_empty(&self) -> bool {
+        self.added_nodes.is_empty()
+            && self.removed_nodes.is_empty()
+            && self.changed_nodes.is_empty()
+            && self.added_edges.is_empty()
+            && self.removed_edges.is_empty()
+            && self.added_members.is_empty()
+            && self.removed_members.is_empty()
+            && self.added_class_defs.is_empty()
+            && self.removed_class_defs.is_empty()
+            && self.added_classes.is_empty()
+            && self.removed_classes.is_empty()
+    }
+
+    /// One line per change: `+` added, `-` removed, `~` changed.
+    fn report(&self, kind: &DiagramKind) -> Vec<String> {
+        let node = |node: &Node| statement_text(kind, Statement::Node(node.clone()));
+        let edge = |edge: &Edge| statement_text(kind, Statement::Edge(edge.clone()));
+        let mut lines = Vec::new();
+        lines.extend(
+            self.removed_nodes
+                .iter()
+                .map(|n| format!("- node {}", node(n))),
+        );
+        lines.extend(
+            self.added_nodes
+                .iter()
+                .map(|n| format!("+ node {}", node(n))),
+        );
+        lines.extend(
+            self.changed_nodes
+                .iter()
+                .map(|(old, new)| format!("~ node {} -> {}", node(old), node(new))),
+        );
+        lines.extend(
+            self.removed_edges
+                .iter()
+                .map(|e| format!("- edge {}", edge(e))),
+        );
+        lines.extend(
+            self.added_edges
+                .iter()
+                .map(|e| format!("+ edge {}", edge(e))),
+        );
+        lines.extend(
+            self.removed_members
+                .iter()
+                .map(|(owner, text)| format!("- member {} : {}", owner, text)),
+        );
+        lines.extend(
+            self.added_members
+                .iter()
+                .map(|(owner, text)| format!("+ member {} : {}", owner, text)),
+        );
+        lines.extend(
+            self.removed_class_defs
+                .iter()
+                .map(|(name, styles)| format!("- classDef {} {}", name, styles)),
+        );
+        lines.extend(
+            self.added_class_defs
+                .iter()
+                .map(|(name, styles)| format!("+ classDef {} {}", name, styles)),
+        );
+        lines.extend(
+            self.removed_classes
+                .iter()
+                .map(|(node, class)| format!("- class {} {}", node, class)),
+        );
+        lines.extend(
+            self.added_classes
+                .iter()
+                .map(|(node, class)| format!("+ class {} {}", node, class)),
+        );
+        lines
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = std::env::args().collect();
+
+    let mut highlight = None;
+    let mut files = Vec::new();
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--highlight" => {
+                highlight = Some(args.get(i + 1).ok_or("--highlight expects a file")?.clone());
+                i += 2;
+            }
+            file => {
+                files.push(file.to_string());
+                i += 1;
+            }
+        }
+    }
+    let [old_file, new_file] = files.as_slice() else {
+        eprintln!(
+            "Usage: {} [--highlight <OUTPUT>] <OLD.mermaid> <NEW.mermaid>",
+            args[0]
+        );
+        std::process::exit(2);
+    };
+
+    let old = Diagram::parse(&fs::read_to_string(old_file)?);
+    let new = Diagram::parse(&fs::read_to_string(new_file)?);
+    let diff = diff_diagrams(&old, &new)?;
+
+    for line in diff.report(&new.kind) {
+        println!("{}", line);
+    }
+    if let Some(output) = highlight {
+        fs::write(&output, highlighted(&new, &diff)?.to_string())?;
+        eprintln!("Wrote highlighted diagram to {}", output);
+    }
+
+    // Like diff(1): 0 when the diagrams match, 1 when they differ
+    if !diff.is_empty() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Compares the nodes, edges, members, classDefs and class assignments of two
+/// diagrams of the same kind.
+fn diff_diagrams(old: &Diagram, new: &Diagram) -> Result<DiagramDiff, String> {
+    let same_kind = old.kind == new.kind || (old.kind.is_flowchart() && new.kind.is_flowchart());
+    if !same_kind {
+        return Err(format!(
+            "cannot compare a {} with a {}",
+            old.kind.keyword(),
+            new.kind.keyword()
+        ));
+    }
+
+    let mut diff = DiagramDiff::default();
+
+    let (old_nodes, new_nodes) = (old.nodes(), new.nodes());
+    for (id, node) in &old_nodes {
+        match new_nodes.get(id) {
+            None => diff.removed_nodes.push(bare(node)),
+            Some(other) if (node.shape, &node.label) != (other.shape, &other.label) => {
+                diff.changed_nodes.push((bare(node), bare(other)))
+            }
+            Some(_) => {}
+        }
+    }
+    for (id, node) in &new_nodes {
+        if !old_nodes.contains_key(id) {
+            diff.added_nodes.push(bare(node));
+        }
+    }
+
+    let (old_edges, new_edges) = (edges(old), edges(new));
+    diff.removed_edges = difference(&old_edges, &new_edges);
+    diff.added_edges = difference(&new_edges, &old_edges);
+
+    let (old_members, new_members) = (members(old), members(new));
+    diff.removed_members = difference(&old_members, &new_members);
+    diff.added_members = difference(&new_members, &old_members);
+
+    let owned = |pairs: Vec<(&str, &str)>| -> BTreeMap<String, (String, String)> {
+        pairs
+            .into_iter()
+            .map(|(a, b)| (format!("{} {}", a, b), (a.to_string(), b.to_string())))
+            .collect()
+    };
+    let (old_defs, new_defs) = (owned(old.class_defs()), owned(new.class_defs()));
+    diff.removed_class_defs = difference(&old_defs, &new_defs);
+    diff.added_class_defs = difference(&new_defs, &old_defs);
+
+    let (old_classes, new_classes) = (
+        owned(old.class_assignments()),
+        owned(new.class_assignments()),
+    );
+    diff.removed_classes = difference(&old_classes, &new_classes);
+    diff.added_classes = difference(&new_classes, &old_classes);
+
+    Ok(diff)
+}
+
+/// The new diagram with removed nodes and edges added back, and every change
+/// marked: classDefs on nodes, and `linkStyle` on flowchart edges.
+fn highlighted(new: &Diagram, diff: &DiagramDiff) -> Result<Diagram, String> {
+    if !new.kind.is_flowchart() && new.kind != DiagramKind::Class {
+        return Err(format!(
+            "--highlight supports flowcharts and classDiagrams, not {}",
+            new.kind.keyword()
+        ));
+    }
+
+    let mut merged = new.clone();
+    let edge_count = merged.edges().count();
+    merged.body.push(Statement::Blank.into());
+    for node in &diff.removed_nodes {
+        merged.body.push(Statement::Node(node.clone()).into());
+    }
+    for edge in &diff.removed_edges {
+        merged.body.push(Statement::Edge(edge.clone()).into());
+    }
+
+    merged.body.push(Statement::Blank.into());
+    let ids = |nodes: Vec<&Node>| nodes.into_iter().map(|n| n.id.clone()).collect();
+    let marks: [(_, Vec<String>); 3] = [
+        (ADDED, ids(diff.added_nodes.iter().collect())),
+        (REMOVED, ids(diff.removed_nodes.iter().collect())),
+        (
+            CHANGED,
+            ids(diff.changed_nodes.iter().map(|(_, n)| n).collect()),
+        ),
+    ];
+    for ((name, styles), nodes) in marks {
+        if nodes.is_empty() {
+            continue;
+        }
+        merged.body.push(
+            Statement::ClassDef {
+                names: vec![name.to_string()],
+                styles: styles.to_string(),
+            }
+            .into(),
+        );
+        merged.body.push(
+            Statement::ClassAssign {
+                nodes,
+                class: name.to_string(),
+            }
+            .into(),
+        );
+    }
+
+    // Mermaid numbers links in the order they are drawn, so the new
+    // diagram's edges come first and the removed ones follow
+    if new.kind.is_flowchart() {
+        let added: BTreeSet<String> = diff
+            .added_edges
+            .iter()
+            .map(|e| statement_text(&new.kind, Statement::Edge(e.clone())))
+            .collect();
+        let added_links: Vec<String> = merged
+            .edges()
+            .take(edge_count)
+            .enumerate()
+            .filter(|(_, e)| {
+                added.contains(&statement_text(&new.kind, Statement::Edge(bare_edge(e))))
+            })
+            .map(|(i, _)| i.to_string())
+            .collect();
+        let removed_links: Vec<String> = (edge_count..edge_count + diff.removed_edges.len())
+            .map(|i| i.to_string())
+            .collect();
+        for (links, styles) in [(added_links, ADDED_LINK), (removed_links, REMOVED_LINK)] {
+            if !links.is_empty() {
+                merged.body.push(
+                    Statement::LinkStyle {
+                        links: links.join(","),
+                        styles: styles.to_string(),
+                    }
+                    .into(),
+                );
+            }
+        }
+    }
+    Ok(merged)
+}
+
+/// Edges keyed by how they print with bare endpoints, so a node's label
+/// changing does not change its edges.
+fn edges(diagram: &Diagram) -> BTreeMap<String, Edge> {
+    diagram
+        .edges()
+        .map(|edge| {
+            let edge = bare_edge(edge);
+            (
+                statement_text(&diagram.kind, Statement::Edge(edge.clone())),
+                edge,
+            )
+        })
+        .collect()
+}
+
+fn members(diagram: &Diagram) -> BTreeMap<String, (String, String)> {
+    diagram
+        .all_lines()
+        .into_iter()
+        .filter_map(|line| match &line.statement {
+            Statement::Member { owner, text } => Some((
+                format!("{} : {}", owner, text),
+                (owner.clone(), text.clone()),
+            )),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Values whose keys are in `a` but not in `b`, in key order.
+fn difference<T: Clone>(a: &BTreeMap<String, T>, b: &BTreeMap<String, T>) -> Vec<T> {
+    a.iter()
+        .filter(|(key, _)| !b.contains_key(*key))
+        .map(|(_, value)| value.clone())
+        .collect()
+}
+
+/// The node without its `:::` classes, which are compared separately.
+fn bare(node: &Node) -> Node {
+    Node {
+        classes: Vec::new(),
+        ..node.clone()
+    }
+}
+
+fn bare_edge(edge: &Edge) -> Edge {
+    Edge {
+        from: Node::new(&edge.from.id),
+        to: Node::new(&edge.to.id),
+        ..edge.clone()
+    }
+}
+
+/// How a single statement prints in a diagram of this kind.
+fn statement_text(kind: &DiagramKind, statement: Statement) -> String {
+    let mut diagram = Diagram::new(kind.clone(), None);
+    diagram.body.push(statement.into());
+    diagram
+        .render(0)
+        .lines()
+        .nth(1)
+        .unwrap_or_default()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OLD: &str =
+        "flowchart LR\nA[Start] --> B\nB --> C\nclassDef hot fill:#f00\nclass A hot\n";
+    const NEW: &str =
+        "flowchart LR\nB --> D\nA[Begin] --> B\nclassDef hot fill:#0f0\nclass A hot\n";
+
+    #[test]
+    fn test_diff_ignores_order_and_reports_changes() {
+        let old = Diagram::parse(OLD);
+        let new = Diagram::parse(NEW);
+        let diff = diff_diagrams(&old, &new).unwrap();
+        assert_eq!(
+            diff.report(&new.kind),
+            [
+                "- node C",
+                "+ node D",
+                "~ node A[Start] -> A[Begin]",
+                "- edge B --> C",
+                "+ edge B --> D",
+                "- classDef hot fill:#f00",
+                "+ classDef hot fill:#0f0",
+            ]
+        );
+
+        let reordered = Diagram::parse(
+            "flowchart LR\nclass A hot\nB --> C\nA[Start] --> B\nclassDef hot fill:#f00\n",
+        );
+        assert!(diff_diagrams(&old, &reordered).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_highlighted_marks_additions_and_removals() {
+        let new = Diagram::parse(NEW);
+        let diff = diff_diagrams(&Diagram::parse(OLD), &new).unwrap();
+        assert_eq!(
+            highlighted(&new, &diff).unwrap().to_string(),
+            "flowchart LR\n\
+             \x20   B --> D\n\
+             \x20   A[Begin] --> B\n\
+             \x20   classDef hot fill:#0f0\n\
+             \x20   class A hot\n\
+             \n\
+             \x20   C\n\
+             \x20   B --> C\n\
+             \n\
+             \x20   classDef diff_added fill:#e6ffed,stroke:#2da44e,stroke-width:2px\n\
+             \x20   class D diff_added\n\
+             \x20   classDef diff_removed fill:#ffebe9,stroke:#cf222e,stroke-width:2px,stroke-dasharray:5 5\n\
+             \x20   class C diff_removed\n\
+             \x20   classDef diff_changed fill:#fff8c5,stroke:#bf8700,stroke-width:2px\n\
+             \x20   class A diff_changed\n\
+             \x20   linkStyle 0 stroke:#2da44e,stroke-width:2px\n\
+             \x20   linkStyle 2 stroke:#cf222e,stroke-width:2px,stroke-dasharray:5 5\n"
+        );
+    }
+
+    #[test]
+    fn test_diff_class_members() {
+        let old = Diagram::parse("classDiagram\nclass Server {\n+start() bool\n+port u16\n}\n");
+        let new = Diagram::parse(
+            "classDiagram\nclass Server {\n+port u16\n+stop()\n}\nServer --> Pool\n",
+        );
+        assert_eq!(
+            diff_diagrams(&old, &new).unwrap().report(&new.kind),
+            [
+                "+ node Pool",
+                "+ edge Server --> Pool",
+                "- member Server : +start() bool",
+                "+ member Server : +stop()",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_refuses_different_kinds() {
+        let err = diff_diagrams(
+            &Diagram::parse("flowchart TB\nA --> B\n"),
+            &Diagram::parse("erDiagram\nA ||--o{ B : has\n"),
+        )
+        .unwrap_err();
+        assert_eq!(err, "cannot compare a flowchart with a erDiagram");
+    }
+}
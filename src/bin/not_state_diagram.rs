@@ -1,189 +1,699 @@
+use anyhow::{anyhow, Context, Result};
+use cargo_invoke::filter::{is_std_type, Filter};
+use cargo_invoke::focus::{Focus, FOCUS_STYLE};
+use cargo_invoke::model::{is_cfg_test, module_path_for_file, tokens_to_string};
+use cargo_invoke::schema::{
+    belongs_to, er_diagram, foreign_key_cardinality, orm_attributes, primary_keys, to_snake_case,
+    ColumnAttributes, Entity, EntityAttribute, Relationship, Schema,
+};
+use cargo_invoke::theme::Theme;
+use cargo_invoke::types::{TypeAnalyzer, TypeRef, Wrapper};
 use regex::Regex;
-use std::collections::HashSet;
-use std::fs;
-use std::io::{self, Write};
-use std::path::PathBuf;
-use std::process::Command;
-
-/// Simplifies a single type by removing references and wrappers like `Option`, `Arc`, `Box`, etc.
-fn unwrap_type(mut ty: &str) -> String {
-    ty = ty.trim();
-    while ty.starts_with('&') {
-        ty = ty[1..].trim_start();
-    }
-
-    let wrappers = [
-        "Option", "Result", "Box", "Arc", "Rc", "Mutex", "HashSet", "Vec", "HashMap",
-    ];
-    let mut result = ty.to_string();
-
-    'outer: loop {
-        for w in &wrappers {
-            let prefix = format!("{}<", w);
-            if result.starts_with(&prefix) {
-                if let Some(start) = result.find('<') {
-                    let mut depth = 0;
-                    let mut end = start + 1;
-                    for (i, c) in result[start + 1..].chars().enumerate() {
-                        if c == '<' {
-                            depth += 1;
-                        } else if c == '>' {
-                            if depth == 0 {
-                                end = start + 1 + i;
-                                break;
-                            } else {
-                                depth -= 1;
-                            }
-                        }
-                    }
-                    let inside = &result[start + 1..end];
-                    result = unwrap_type(inside);
-                    continue 'outer;
-                }
-            }
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+use syn::{parse_file, Attribute, Field, Item, Visibility};
+use walkdir::WalkDir; // Import walkdir for recursive directory traversal
+
+/// A parsed input file and the modules its items were declared in.
+struct Source {
+    module: String,
+    /// One entry per item of `syntax`.
+    item_modules: Vec<String>,
+    syntax: syn::File,
+}
+
+fn main() -> Result<()> {
+    // Step 1: Parse command-line arguments
+    let mut args: Vec<String> = env::args().collect();
+    let focus = Focus::from_args(&mut args)?;
+    let filter = Filter::from_args(&mut args)?;
+    let theme = Theme::from_args(&mut args)?;
+    let orm = match args.iter().position(|a| a == "--orm") {
+        Some(i) => {
+            args.remove(i);
+            true
         }
-        break;
+        None => false,
+    };
+    // ORM mode accepts several files, e.g. schema.rs and models.rs
+    if args.len() != 2 && !(orm && args.len() > 2) {
+        return Err(anyhow!(
+            "Usage: {} <structs_file.rs> [--orm [<more_files.rs>...]] [--focus <Type>] [--depth <N>] \
+             [--direction in|out|both] [--include <GLOB>] [--exclude <GLOB>] [--pub-only] \
+             [--no-tests] [--no-std-types] [--theme <NAME>]",
+            args.first().unwrap_or(&"script".to_string())
+        ));
+    }
+    let filename = &args[1];
+
+    // Steps 2-4: Find, read and parse each file
+    let mut files = Vec::new();
+    for filename in &args[1..] {
+        let filepath = find_file_in_current_dir(filename)
+            .with_context(|| format!("Failed to find file: {}", filename))?;
+        let content = fs::read_to_string(&filepath)
+            .with_context(|| format!("Failed to read file: {:?}", filepath))?;
+        let syntax = parse_file(&content)
+            .with_context(|| format!("Failed to parse Rust file: {:?}", filepath))?;
+        let module = file_module(&filepath);
+        let item_modules = item_modules(&module, &content, &syntax);
+        files.push(Source {
+            module,
+            item_modules,
+            syntax,
+        });
     }
 
-    if let Some(colpos) = result.rfind("::") {
-        result = result[colpos + 2..].to_string();
+    // Steps 5-6: Extract entities and the relationships between them
+    let (mut entities, mut relationships) = if orm {
+        let schema = Schema::from_files(files.iter().map(|f| (f.module.as_str(), &f.syntax)));
+        schema_entities(schema, &filter)
+    } else {
+        let Source {
+            item_modules,
+            syntax,
+            ..
+        } = &files[0];
+        let analyzer = TypeAnalyzer::new().with_aliases_from(&syntax.items);
+        let entities = extract_entities(syntax, item_modules, &analyzer, &filter);
+        let relationships = extract_struct_relationships(syntax, &analyzer, &entities, &filter);
+        (entities, relationships)
+    };
+    let entity_names: Vec<String> = entities.iter().map(|e| e.name.clone()).collect();
+
+    // Step 7: Keep only the neighbourhood of the focus type, if requested
+    if let Some(focus) = &focus {
+        let kept = focus.neighbourhood(
+            entity_names.iter().map(String::as_str),
+            relationships
+                .iter()
+                .map(|r| (r.source.as_str(), r.target.as_str())),
+        )?;
+        relationships.retain(|r| kept.contains(&r.source) && kept.contains(&r.target));
+        entities.retain(|e| kept.contains(&e.name));
     }
-    result.trim().to_string()
+
+    // Step 8: Prepare output file path
+    let output_file = prepare_output_file(filename, focus.as_ref())?;
+
+    // Step 9: Generate Mermaid ER diagram and write to file
+    generate_mermaid_er_diagram(&entities, &relationships, focus.as_ref(), &output_file)?;
+    if let Some(theme) = &theme {
+        theme.apply_to_file(&output_file)?;
+    }
+
+    println!("ER diagram successfully written to {:?}", output_file);
+
+    Ok(())
 }
 
-/// Recursively parses a generic type for all "top-level" arguments.
-fn extract_all_inner_types(ty: &str) -> Vec<String> {
-    let mut results = Vec::new();
+/// The module a file under `src/` declares; any other file is read as a crate root.
+fn file_module(filepath: &Path) -> String {
+    env::current_dir()
+        .map(|dir| dir.join("src"))
+        .ok()
+        .filter(|src| filepath.starts_with(src))
+        .map_or_else(
+            || "crate".to_string(),
+            |src| module_path_for_file(&src, filepath),
+        )
+}
 
-    if let Some(start) = ty.find('<') {
-        let mut depth = 0;
-        let mut end = start + 1;
-        let chars: Vec<char> = ty.chars().collect();
-        for (i, c) in chars[start + 1..].iter().enumerate() {
-            if *c == '<' {
-                depth += 1;
-            } else if *c == '>' {
-                if depth == 0 {
-                    end = start + 1 + i;
-                    break;
-                } else {
-                    depth -= 1;
-                }
+/// The module each top-level item was declared in. `architecture` output marks
+/// every struct with `// module: crate::x`; unmarked items belong to `module`.
+fn item_modules(module: &str, content: &str, syntax: &syn::File) -> Vec<String> {
+    static MARKER: OnceLock<Regex> = OnceLock::new();
+    let mut markers = regex(
+        &MARKER,
+        r"(?m)^//\s*module:\s*(\S+)\s*\n(?:\s*#\[[^\]]*\])*\s*(?:pub(?:\([^)]*\))?\s+)?(?:struct|enum)\s+(\w+)",
+    )
+    .captures_iter(content)
+    .map(|cap| (cap[1].to_string(), cap[2].to_string()))
+    .peekable();
+    syntax
+        .items
+        .iter()
+        .map(|item| {
+            let ident = match item {
+                Item::Struct(s) => &s.ident,
+                Item::Enum(e) => &e.ident,
+                _ => return module.to_string(),
+            };
+            // Markers come in item order
+            match markers.next_if(|(_, name)| ident == name) {
+                Some((marked, _)) => marked,
+                None => module.to_string(),
             }
-        }
+        })
+        .collect()
+}
 
-        let inside = &ty[start + 1..end];
-        let mut comma_depth = 0;
-        let mut last_pos = 0;
-        for (i, c) in inside.chars().enumerate() {
-            match c {
-                '<' => comma_depth += 1,
-                '>' => comma_depth -= 1,
-                ',' if comma_depth == 0 => {
-                    let segment = inside[last_pos..i].trim();
-                    results.push(segment.to_string());
-                    last_pos = i + 1;
-                }
-                _ => {}
+/// Searches the current directory and all subdirectories for the first file matching the given filename.
+fn find_file_in_current_dir(filename: &str) -> Result<PathBuf> {
+    let current_dir = env::current_dir()?;
+    println!(
+        "Searching for '{}' in directory: {:?} and its subdirectories...",
+        filename, current_dir
+    );
+
+    for entry in WalkDir::new(&current_dir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+    {
+        if let Some(name) = entry.file_name().to_str() {
+            println!("Checking file: {:?}", entry.path());
+            if name == filename {
+                println!("Found file: {:?}", entry.path());
+                return Ok(entry.path().to_path_buf());
             }
         }
-        let segment = inside[last_pos..].trim();
-        if !segment.is_empty() {
-            results.push(segment.to_string());
-        }
     }
+    Err(anyhow!(
+        "File '{}' not found in the current directory or its subdirectories.",
+        filename
+    ))
+}
 
-    if results.is_empty() {
-        return vec![unwrap_type(ty)];
-    } else {
-        let mut final_list = Vec::new();
-        for sub in results {
-            let deeper = extract_all_inner_types(&sub);
-            final_list.extend(deeper);
-        }
-        return final_list;
-    }
+/// The tables the filter keeps as entities, with one relationship per foreign key.
+fn schema_entities(mut schema: Schema, filter: &Filter) -> (Vec<Entity>, Vec<Relationship>) {
+    schema
+        .tables
+        .retain(|_, t| filter.allows_type(&t.module, &t.name, true));
+    schema.entities()
 }
 
-fn main() -> io::Result<()> {
-    let args: Vec<String> = std::env::args().collect();
-    let generate_png = args.contains(&"--png".to_string());
+/// Extracts the structs and enums the filter keeps, with their attributes.
+/// `modules` holds the declaring module of each item in `syntax`.
+fn extract_entities(
+    syntax: &syn::File,
+    modules: &[String],
+    analyzer: &TypeAnalyzer,
+    filter: &Filter,
+) -> Vec<Entity> {
+    let names: Vec<String> = syntax
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            Item::Struct(s) => Some(s.ident.to_string()),
+            Item::Enum(e) => Some(e.ident.to_string()),
+            _ => None,
+        })
+        .collect();
 
-    // We only read from `structs.rs`
-    let structs_path = PathBuf::from("architecture/structs.rs");
-    let state_diagram_path = PathBuf::from("diagrams/state_diagram.mermaid");
+    syntax
+        .items
+        .iter()
+        .zip(modules)
+        .filter_map(|(item, module)| {
+            let (ident, vis, attrs) = match item {
+                Item::Struct(s) => (&s.ident, &s.vis, &s.attrs),
+                Item::Enum(e) => (&e.ident, &e.vis, &e.attrs),
+                _ => return None,
+            };
+            if filter.no_tests && is_cfg_test(attrs) {
+                return None;
+            }
+            let name = ident.to_string();
+            let is_public = matches!(vis, Visibility::Public(_));
+            if !filter.allows_type(module, &name, is_public) {
+                return None;
+            }
 
-    let mut known_classes = HashSet::new();
+            let attributes = match item {
+                Item::Struct(s) => {
+                    let table_keys = primary_keys(&s.attrs);
+                    s.fields
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, f)| !filter.pub_only || matches!(f.vis, Visibility::Public(_)))
+                        .filter_map(|(i, f)| {
+                            entity_attribute(&name, i, f, analyzer, &names, &table_keys)
+                        })
+                        .collect()
+                }
+                // Variants are listed so enums still read as a set of values
+                Item::Enum(e) => e
+                    .variants
+                    .iter()
+                    .map(|v| EntityAttribute {
+                        ty: "variant".to_string(),
+                        name: v.ident.to_string(),
+                        keys: Vec::new(),
+                        comment: None,
+                        references: None,
+                    })
+                    .collect(),
+                _ => Vec::new(),
+            };
+            Some(Entity { name, attributes })
+        })
+        .collect()
+}
 
-    // We'll find "struct SomeName {" lines
-    let struct_name_regex =
-        Regex::new(r"(?m)^(?:#\[[^\]]+\])*\s*(?:pub\s+)?struct\s+(\w+)").unwrap();
+/// A field's ORM attributes plus `#[serde(..)]`, whose `skip` and `rename`
+/// count for plain structs too.
+fn field_attributes(attrs: &[Attribute]) -> String {
+    attrs
+        .iter()
+        .filter(|a| a.path().is_ident("serde"))
+        .map(|a| tokens_to_string(&a.meta))
+        .chain([orm_attributes(attrs)])
+        .collect::<Vec<_>>()
+        .join(" ")
+}
 
-    // If `architecture/structs.rs` exists, read it
-    let mut structs_content = String::new();
-    if structs_path.exists() {
-        structs_content = fs::read_to_string(&structs_path)?;
-        for cap in struct_name_regex.captures_iter(&structs_content) {
-            known_classes.insert(cap[1].to_string());
-        }
+fn regex(cell: &'static OnceLock<Regex>, pattern: &str) -> &'static Regex {
+    cell.get_or_init(|| Regex::new(pattern).unwrap())
+}
+
+/// Builds the attribute line for a field, or `None` when serde/sqlx skip it.
+///
+/// Keys are inferred from names and attributes: `id`, `<entity>_id` and
+/// fields marked `primary_key` are PK; other `*_id` fields and newtypes like
+/// `CustomerId` are FK; fields marked `unique` are UK.
+fn entity_attribute(
+    entity: &str,
+    index: usize,
+    field: &Field,
+    analyzer: &TypeAnalyzer,
+    entity_names: &[String],
+    table_keys: &[String],
+) -> Option<EntityAttribute> {
+    let column = ColumnAttributes::parse(&field_attributes(&field.attrs));
+    if column.skip {
+        return None;
     }
+    // Tuple struct fields are named by position
+    let field_name = match &field.ident {
+        Some(ident) => ident.to_string(),
+        None => format!("_{}", index),
+    };
+    let name = column.rename.unwrap_or_else(|| field_name.clone());
 
-    // Parse each struct block to find fields referencing known classes
-    let struct_block_regex =
-        Regex::new(r"(?s)(?:#\[[^\]]+\]\s*)*(?:pub\s+)?struct\s+(\w+)\s*\{([^}]*)\}").unwrap();
-    let field_regex = Regex::new(r"(?m)^\s*(?:pub\s+)?(\w+)\s*:\s*([^,]+)").unwrap();
+    let refs = analyzer.analyze(&field.ty);
+    let main = main_type(&refs);
 
-    let mut relationships: Vec<(String, String, String)> = Vec::new();
+    let mut keys = Vec::new();
+    let own_key = format!("{}_id", to_snake_case(entity));
+    let is_pk = field_name == "id"
+        || field_name == own_key
+        || table_keys.contains(&field_name)
+        || column.primary_key;
+    if is_pk {
+        keys.push("PK");
+    }
 
-    // For each struct block in `structs_content`, parse fields
-    for block_cap in struct_block_regex.captures_iter(&structs_content) {
-        let struct_name = block_cap[1].trim();
-        let struct_body = block_cap[2].trim();
+    // customer_id -> Customer, CustomerId -> Customer
+    let references = if is_pk {
+        None
+    } else if let Some(prefix) = field_name.strip_suffix("_id") {
+        Some(to_pascal_case(prefix))
+    } else {
+        main.and_then(|r| r.name.strip_suffix("Id").filter(|p| !p.is_empty()))
+            .map(str::to_string)
+    };
+    if references.is_some() {
+        keys.push("FK");
+    }
+    if column.unique {
+        keys.push("UK");
+    }
 
-        for field_cap in field_regex.captures_iter(struct_body) {
-            let field_type_raw = field_cap[2].trim();
-            let types_found = extract_all_inner_types(field_type_raw);
+    let ty = match main {
+        Some(r) if r.is_collection() => format!("{}[]", r.name),
+        Some(r) => r.name.clone(),
+        None => "unit".to_string(),
+    };
+    let comment = main
+        .filter(|r| r.is_optional())
+        .map(|_| "optional".to_string());
+
+    Some(EntityAttribute {
+        ty,
+        name,
+        keys,
+        comment,
+        references: references.filter(|r| entity_names.contains(r)),
+    })
+}
+
+/// The type a field is about: a map's value, otherwise the first named type.
+fn main_type(refs: &[TypeRef]) -> Option<&TypeRef> {
+    match refs.first()?.wrappers.first() {
+        Some(Wrapper::Map(_)) => refs.last(),
+        _ => refs.first(),
+    }
+}
 
-            for t in types_found {
-                if known_classes.contains(&t) {
-                    relationships.push((struct_name.to_string(), "has".to_string(), t));
+fn to_pascal_case(name: &str) -> String {
+    name.split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect::<String>(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+/// Extracts relationships between entities: one per entity a field holds,
+/// one per foreign key, and one per enum variant payload.
+fn extract_struct_relationships(
+    syntax: &syn::File,
+    analyzer: &TypeAnalyzer,
+    entities: &[Entity],
+    filter: &Filter,
+) -> Vec<Relationship> {
+    let entity_names: Vec<String> = entities.iter().map(|e| e.name.clone()).collect();
+    let mut relationships = Vec::new();
+
+    for item in &syntax.items {
+        match item {
+            Item::Struct(s) => {
+                let source = s.ident.to_string();
+                let Some(entity) = entities.iter().find(|e| e.name == source) else {
+                    continue;
+                };
+                for field in &s.fields {
+                    if filter.pub_only && !matches!(field.vis, Visibility::Public(_)) {
+                        continue;
+                    }
+                    for (target, cardinality) in
+                        get_field_relationships(field, analyzer, &entity_names)
+                    {
+                        relationships.push(Relationship {
+                            source: source.clone(),
+                            field: field_name(field),
+                            target,
+                            cardinality: cardinality.to_string(),
+                        });
+                    }
+                }
+
+                // Foreign keys: many rows of this entity point at one parent
+                let mut foreign: Vec<(String, String, bool)> = entity
+                    .attributes
+                    .iter()
+                    .filter_map(|a| {
+                        let optional = a.comment.is_some();
+                        a.references.clone().map(|t| (a.name.clone(), t, optional))
+                    })
+                    .collect();
+                for (parent, column) in belongs_to(&s.attrs) {
+                    if entity_names.contains(&parent) && !foreign.iter().any(|(c, ..)| *c == column)
+                    {
+                        foreign.push((column, parent, false));
+                    }
+                }
+                for (column, target, optional) in foreign {
+                    relationships.push(Relationship {
+                        source: source.clone(),
+                        field: column,
+                        target,
+                        cardinality: foreign_key_cardinality(optional).to_string(),
+                    });
+                }
+            }
+            Item::Enum(e) => {
+                let source = e.ident.to_string();
+                if !entity_names.contains(&source) {
+                    continue;
+                }
+                // A payload only exists while the enum holds its variant
+                for variant in &e.variants {
+                    for field in &variant.fields {
+                        for (target, cardinality) in
+                            get_field_relationships(field, analyzer, &entity_names)
+                        {
+                            let cardinality = match cardinality {
+                                "||--||" => "||--o|",
+                                other => other,
+                            };
+                            relationships.push(Relationship {
+                                source: source.clone(),
+                                field: variant.ident.to_string(),
+                                target,
+                                cardinality: cardinality.to_string(),
+                            });
+                        }
+                    }
                 }
             }
+            _ => {}
         }
     }
 
-    // Write out `state_diagram.mermaid`
-    let mut file = fs::File::create(&state_diagram_path)?;
-    writeln!(file, "graph LR")?;
-    writeln!(file)?;
-
-    for (a, label, b) in &relationships {
-        writeln!(file, "    {} --> |{}| {}", a, label, b)?;
-    }
-
-    println!("Generated diagram at {}", state_diagram_path.display());
-    println!("Known classes used for matching: {:?}", known_classes);
-
-    // Optionally generate PNG if `--png` is passed
-    if generate_png {
-        println!("Generating PNG...");
-        let status = Command::new("cargo")
-            .args(&[
-                "run",
-                "--bin",
-                "generate_mermaid_png",
-                "--",
-                state_diagram_path.to_str().unwrap(),
-            ])
-            .status()?;
-
-        if !status.success() {
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
-                "Failed to generate PNG",
-            ));
+    relationships
+}
+
+fn field_name(field: &Field) -> String {
+    field
+        .ident
+        .as_ref()
+        .map(|ident| ident.to_string())
+        .unwrap_or_else(|| "unnamed_field".to_string())
+}
+
+/// Every entity a field holds, with the cardinality implied by its wrappers:
+/// `o{` behind a collection, `o|` behind an `Option`, `||` when held directly.
+fn get_field_relationships(
+    field: &Field,
+    analyzer: &TypeAnalyzer,
+    entity_names: &[String],
+) -> Vec<(String, &'static str)> {
+    analyzer
+        .analyze(&field.ty)
+        .into_iter()
+        // Standard library types never become entities
+        .filter(|r| !is_std_type(&r.name) && entity_names.contains(&r.name))
+        .map(|r| {
+            let cardinality = if r.is_collection() {
+                "||--o{"
+            } else if r.is_optional() {
+                "||--o|"
+            } else {
+                "||--||"
+            };
+            (r.name, cardinality)
+        })
+        .collect()
+}
+
+/// Prepares the output file path in the diagrams/ directory based on the input filename.
+fn prepare_output_file(input_filename: &str, focus: Option<&Focus>) -> Result<PathBuf> {
+    let input_path = Path::new(input_filename);
+    let base_name = input_path
+        .file_stem()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| anyhow!("Invalid input filename: {}", input_filename))?;
+
+    let mut output_path = PathBuf::from("diagrams");
+    // Create diagrams/ directory if it doesn't exist
+    fs::create_dir_all(&output_path).with_context(|| "Failed to create 'diagrams/' directory.")?;
+
+    let suffix = focus.map(Focus::file_suffix).unwrap_or_default();
+    output_path.push(format!("{}{}.mermaid", base_name, suffix));
+
+    Ok(output_path)
+}
+
+/// Generates the Mermaid ER diagram and writes it to the specified file.
+/// Excludes the ```mermaid ``` pre/postfix.
+fn generate_mermaid_er_diagram(
+    entities: &[Entity],
+    relationships: &[Relationship],
+    focus: Option<&Focus>,
+    output_file: &PathBuf,
+) -> Result<()> {
+    fs::write(
+        output_file,
+        focused_er_diagram(entities, relationships, focus),
+    )
+    .with_context(|| format!("Failed to write output file: {:?}", output_file))
+}
+
+/// The ER diagram with the entities the focus names marked by the focus class.
+fn focused_er_diagram(
+    entities: &[Entity],
+    relationships: &[Relationship],
+    focus: Option<&Focus>,
+) -> String {
+    let mut diagram = er_diagram(entities, relationships);
+    if let Some(focus) = focus {
+        diagram.push_str(&format!("    classDef focus {}\n", FOCUS_STYLE));
+        for entity in entities.iter().filter(|e| focus.matches(&e.name)) {
+            diagram.push_str(&format!("    class {} focus\n", entity.name));
         }
     }
+    diagram
+}
 
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cargo_invoke::focus::Direction;
+
+    /// `(name, keys, references)` for each attribute of `source`'s struct.
+    fn attributes(source: &str) -> Vec<(String, String, Option<String>)> {
+        let item: syn::ItemStruct = syn::parse_str(source).unwrap();
+        let entities = ["Order".to_string(), "Customer".to_string()];
+        let table_keys = primary_keys(&item.attrs);
+        item.fields
+            .iter()
+            .enumerate()
+            .filter_map(|(i, field)| {
+                entity_attribute(
+                    &item.ident.to_string(),
+                    i,
+                    field,
+                    &TypeAnalyzer::new(),
+                    &entities,
+                    &table_keys,
+                )
+            })
+            .map(|a| (a.name, a.keys.join(","), a.references))
+            .collect()
+    }
+
+    fn attribute(
+        name: &str,
+        keys: &str,
+        references: Option<&str>,
+    ) -> (String, String, Option<String>) {
+        (
+            name.to_string(),
+            keys.to_string(),
+            references.map(str::to_string),
+        )
+    }
+
+    #[test]
+    fn test_keys_inferred_from_names() {
+        assert_eq!(
+            attributes(
+                "struct Order {
+                    id: Uuid,
+                    customer_id: u64,
+                    billed_to: CustomerId,
+                    shipment_id: Option<u64>,
+                    total: u64,
+                }"
+            ),
+            [
+                attribute("id", "PK", None),
+                attribute("customer_id", "FK", Some("Customer")),
+                attribute("billed_to", "FK", Some("Customer")),
+                // Still a foreign key, to an entity outside the diagram
+                attribute("shipment_id", "FK", None),
+                attribute("total", "", None),
+            ]
+        );
+        assert_eq!(
+            attributes("struct Customer { customer_id: u64 }"),
+            [attribute("customer_id", "PK", None)]
+        );
+    }
+
+    #[test]
+    fn test_keys_inferred_from_attributes() {
+        assert_eq!(
+            attributes(
+                r#"struct Customer {
+                    #[sea_orm(primary_key)]
+                    email: String,
+                    #[sea_orm(unique)]
+                    #[serde(rename = "handle")]
+                    login: String,
+                    #[sqlx(rename = "order_id")]
+                    last_order: i64,
+                    #[serde(skip)]
+                    cache: Vec<u8>,
+                }"#
+            ),
+            [
+                attribute("email", "PK", None),
+                attribute("handle", "UK", None),
+                attribute("order_id", "", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diesel_struct_attributes() {
+        let source = "#[diesel(primary_key(order_id, line_no))]
+             #[diesel(belongs_to(Order))]
+             #[diesel(belongs_to(Customer, foreign_key = buyer))]
+             struct OrderLine { order_id: i64, line_no: i32, buyer: i64 }";
+        let item: syn::ItemStruct = syn::parse_str(source).unwrap();
+        assert_eq!(primary_keys(&item.attrs), ["order_id", "line_no"]);
+        assert_eq!(
+            belongs_to(&item.attrs),
+            [
+                ("Order".to_string(), "order_id".to_string()),
+                ("Customer".to_string(), "buyer".to_string()),
+            ]
+        );
+        assert_eq!(
+            attributes(source),
+            [
+                attribute("order_id", "PK", None),
+                attribute("line_no", "PK", None),
+                // belongs_to only adds the relationship, not a key on the column
+                attribute("buyer", "", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_orm_tables_render_through_er_diagram() {
+        let file: syn::File = syn::parse_str(
+            r#"
+            diesel::table! { users (id) { id -> Int4, } }
+            diesel::table! { posts (id) { id -> Int4, user_id -> Nullable<Int4>, } }
+            mod audit {
+                diesel::table! { events (id) { id -> Int4, user_id -> Int4, } }
+            }
+            "#,
+        )
+        .unwrap();
+        let schema = Schema::from_files([("crate::db", &file)]);
+        let filter = Filter {
+            exclude: vec!["crate::db::audit::*".to_string()],
+            ..Filter::default()
+        };
+        let (entities, relationships) = schema_entities(schema, &filter);
+        assert_eq!(
+            er_diagram(&entities, &relationships),
+            "erDiagram\n\
+             \x20   posts {\n\
+             \x20       Int4 id PK\n\
+             \x20       Int4 user_id FK \"nullable\"\n\
+             \x20   }\n\
+             \x20   users {\n\
+             \x20       Int4 id PK\n\
+             \x20   }\n\
+             \x20   posts }o--o| users : \"user_id\"\n"
+        );
+
+        let focus = Focus {
+            target: "db::users".to_string(),
+            depth: 1,
+            direction: Direction::Both,
+        };
+        assert!(focused_er_diagram(&entities, &relationships, Some(&focus))
+            .ends_with("    classDef focus fill:#ffe082,stroke:#e65100,stroke-width:3px\n"));
+        let focus = Focus {
+            target: "users".to_string(),
+            ..focus
+        };
+        assert!(focused_er_diagram(&entities, &relationships, Some(&focus))
+            .ends_with("    class users focus\n"));
+    }
 }
@@ -1,16 +1,20 @@
+use cargo_invoke::color::{Deficiency, Rgb, AAA_TEXT, AA_GRAPHICS, AA_TEXT, MIN_DELTA_E};
+use cargo_invoke::mermaid::{Diagram, Statement};
+use cargo_invoke::theme::Theme;
 use rand::prelude::IndexedRandom;
 use rand::Rng;
 use rand::SeedableRng;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use serde_json;
 use std::collections::hash_map::DefaultHasher;
-use std::collections::HashMap;
 use std::collections::HashSet;
+use std::collections::{BTreeMap, BTreeSet};
 use std::env;
+use std::fmt;
 use std::fs;
 use std::hash::{Hash, Hasher};
 use std::path::Path;
+use std::sync::OnceLock;
 
 const TEMPORAL_TERMS: &[&str] = &[
     // Time periods
@@ -2271,10 +2275,11 @@ const NEUTRAL_TERMS: &[&str] = &[
 // [All the constant word arrays (LUMINOUS_TERMS, DEEP_TERMS, etc.) go here]
 // (I've omitted them for brevity since we already have them)
 
+/// Sorted maps, so the saved database only changes when a color does.
 #[derive(Serialize, Deserialize, Default)]
 struct ColorMaps {
-    hex_to_name: HashMap<String, String>,
-    name_to_hex: HashMap<String, String>,
+    hex_to_name: BTreeMap<String, String>,
+    name_to_hex: BTreeMap<String, String>,
 }
 
 impl ColorMaps {
@@ -2289,15 +2294,50 @@ impl ColorMaps {
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        eprintln!("Usage: {} <input_mermaid_file>", args[0]);
+    let mut args: Vec<String> = env::args().collect();
+    let theme = Theme::from_args(&mut args)?;
+    let apply = take_flag(&mut args, "--apply");
+    let audit = take_flag(&mut args, "--audit");
+    if args.len() != 2 || (apply && audit) {
+        eprintln!(
+            "Usage: {} [--apply | --audit] [--theme <NAME>] <input_mermaid_file>",
+            args[0]
+        );
+        eprintln!("  --apply  Rewrite the diagram in place with a classDef per palette color");
+        eprintln!(
+            "  --audit  Check contrast (WCAG AA/AAA) and color-blind safety of the colors used"
+        );
+        eprintln!(
+            "  --theme  Theme variables for the swatch chart; the swatches keep their colors"
+        );
         std::process::exit(1);
     }
 
     let input_path = &args[1];
     let content = fs::read_to_string(input_path)?;
 
+    if apply {
+        return apply_to_file(input_path, &content);
+    }
+    if audit {
+        let (checked, issues) = audit_colors(&Diagram::parse(&content));
+        for issue in &issues {
+            println!("{}", issue);
+        }
+        let failures = issues.iter().filter(|i| i.is_failure()).count();
+        println!(
+            "Checked {} color pairs in {}: {} problem(s), {} below AAA only",
+            checked,
+            input_path,
+            failures,
+            issues.len() - failures
+        );
+        if failures > 0 {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     let colors = extract_colors(&content);
     if colors.is_empty() {
         println!("No (non-white) colors found in the file.");
@@ -2315,7 +2355,12 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     save_color_db(db_file_path, &color_db)?;
 
-    let palette_mermaid = generate_mermaid(color_and_name);
+    let mut palette_mermaid = generate_mermaid(color_and_name);
+    // Only the theme's variables: its classDefs and link styles would restyle
+    // the swatches. Mermaid merges the directive with the chart's layout one.
+    if let Some(theme) = &theme {
+        palette_mermaid = format!("%%{{{}}}%%\n{}", theme.directive(), palette_mermaid);
+    }
 
     let output_path = Path::new(input_path).with_extension("palette.mermaid");
     fs::write(&output_path, palette_mermaid)?;
@@ -2330,6 +2375,371 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+/// Replaces the fill colors in the diagram at `path` with palette classes and
+/// saves it, naming new colors in the color database.
+fn apply_to_file(path: &str, content: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let db_file_path = "colors.json";
+    let mut color_db = load_color_db(db_file_path)?;
+
+    let mut diagram = Diagram::parse(content);
+    let applied = apply_palette(&mut diagram, |hex| {
+        get_or_create_artistic_name(hex, &mut color_db)
+    });
+    save_color_db(db_file_path, &color_db)?;
+
+    for (hex, dropped) in &applied.conflicts {
+        eprintln!(
+            "Warning: {} is also styled as `{}`; the palette class keeps the first style",
+            hex, dropped
+        );
+    }
+    if applied.classes.is_empty() {
+        println!("No fill colors found in {}.", path);
+        return Ok(());
+    }
+
+    fs::write(path, diagram.to_string())?;
+    println!(
+        "Replaced {} styles and classDefs with {} palette classes in {}",
+        applied.replaced,
+        applied.classes.len(),
+        path
+    );
+    for (class, hex) in &applied.classes {
+        println!("    {} {}", class, hex);
+    }
+    println!("Color names persisted to: {}", db_file_path);
+    Ok(())
+}
+
+/// What [`apply_palette`] changed.
+#[derive(Debug, Default, PartialEq)]
+struct Applied {
+    /// Palette class names and their fills, in the order the fills first appear.
+    classes: Vec<(String, String)>,
+    /// How many `style` statements and classDefs were folded into palette classes.
+    replaced: usize,
+    /// Fills styled more than one way, with each style that lost to the first.
+    conflicts: Vec<(String, String)>,
+}
+
+/// Rewrites every `style` and classDef with a fill so the fill is a classDef
+/// named by `name_of`: `style X ...` becomes `class X Name` in place, classes
+/// defined with the same fill merge into one and references to them are
+/// renamed. The palette classDefs are appended at the end. `classDef default`
+/// is left alone.
+fn apply_palette(diagram: &mut Diagram, mut name_of: impl FnMut(&str) -> String) -> Applied {
+    let mut applied = Applied::default();
+    // Normalized styles by fill, first one wins
+    let mut styles: Vec<(String, String)> = Vec::new();
+    let mut renamed: BTreeMap<String, String> = BTreeMap::new();
+    let mut record = |hex: String, style: &str, applied: &mut Applied| {
+        let style = normalize_hexes(style);
+        match styles.iter().find(|(h, _)| *h == hex) {
+            Some((_, first)) if *first != style => applied.conflicts.push((hex, style)),
+            Some(_) => {}
+            None => styles.push((hex, style)),
+        }
+        applied.replaced += 1;
+    };
+    for line in diagram.all_lines() {
+        match &line.statement {
+            Statement::ClassDef { names, styles } if !names.iter().any(|n| n == "default") => {
+                if let Some(hex) = fill_hex(styles) {
+                    for name in names {
+                        renamed.insert(name.clone(), hex.clone());
+                    }
+                    record(hex, styles, &mut applied);
+                }
+            }
+            Statement::Style { styles, .. } => {
+                if let Some(hex) = fill_hex(styles) {
+                    record(hex, styles, &mut applied);
+                }
+            }
+            _ => {}
+        }
+    }
+    if styles.is_empty() {
+        return applied;
+    }
+
+    let palette: BTreeMap<String, String> = styles
+        .iter()
+        .map(|(hex, _)| (hex.clone(), sanitize_class_name(&name_of(hex))))
+        .collect();
+    let class_for = |old: &str| renamed.get(old).map(|hex| palette[hex].clone());
+
+    diagram.retain(|statement| match statement {
+        Statement::ClassDef { names, styles } => {
+            names.iter().any(|n| n == "default") || fill_hex(styles).is_none()
+        }
+        _ => true,
+    });
+    diagram.for_each_statement_mut(|statement| match statement {
+        Statement::Style { node, styles } => {
+            if let Some(hex) = fill_hex(styles) {
+                *statement = Statement::ClassAssign {
+                    nodes: vec![node.clone()],
+                    class: palette[&hex].clone(),
+                };
+            }
+        }
+        Statement::ClassAssign { class, .. } => {
+            if let Some(new) = class_for(class) {
+                *class = new;
+            }
+        }
+        _ => {}
+    });
+    diagram.for_each_node_mut(|node| {
+        let mut classes: Vec<String> = Vec::new();
+        for class in node.classes.drain(..) {
+            let class = class_for(&class).unwrap_or(class);
+            if !classes.contains(&class) {
+                classes.push(class);
+            }
+        }
+        node.classes = classes;
+    });
+
+    if !matches!(
+        diagram.body.last().map(|l| &l.statement),
+        None | Some(Statement::Blank)
+    ) {
+        diagram.body.push(Statement::Blank.into());
+    }
+    for (hex, style) in styles {
+        let name = palette[&hex].clone();
+        diagram.body.push(
+            Statement::ClassDef {
+                names: vec![name.clone()],
+                styles: style,
+            }
+            .into(),
+        );
+        applied.classes.push((name, hex));
+    }
+    applied
+}
+
+static FILL: OnceLock<Regex> = OnceLock::new();
+static HEX: OnceLock<Regex> = OnceLock::new();
+
+fn regex(cell: &'static OnceLock<Regex>, pattern: &str) -> &'static Regex {
+    cell.get_or_init(|| Regex::new(pattern).unwrap())
+}
+
+fn fill_regex() -> &'static Regex {
+    regex(&FILL, r"fill:\s*(#(?:[0-9A-Fa-f]{6}|[0-9A-Fa-f]{3}))\b")
+}
+
+/// A `#rrggbb` or `#rgb` color as `#RRGGBB`.
+fn six_digit_hex(hex: &str) -> String {
+    Rgb::parse(hex).map_or_else(|| hex.to_uppercase(), |rgb| rgb.to_string())
+}
+
+/// The `fill:` color in a style as `#RRGGBB`; `#rgb` is expanded.
+fn fill_hex(styles: &str) -> Option<String> {
+    fill_regex()
+        .captures(styles)
+        .map(|cap| six_digit_hex(&cap[1]))
+}
+
+/// Writes every `#rrggbb` or `#rgb` in a style as `#RRGGBB`, so equal colors compare equal.
+fn normalize_hexes(styles: &str) -> String {
+    regex(&HEX, r"#(?:[0-9A-Fa-f]{6}|[0-9A-Fa-f]{3})\b")
+        .replace_all(styles.trim(), |cap: &regex::Captures| {
+            six_digit_hex(&cap[0])
+        })
+        .into_owned()
+}
+
+/// Removes `flag` from `args`, returning whether it was there.
+fn take_flag(args: &mut Vec<String>, flag: &str) -> bool {
+    match args.iter().position(|a| a == flag) {
+        Some(i) => {
+            args.remove(i);
+            true
+        }
+        None => false,
+    }
+}
+
+/// A color problem found by `--audit`.
+#[derive(Debug, PartialEq)]
+enum Issue {
+    /// Text (`color`) or a border (`stroke`) with too little contrast against
+    /// its fill for `level`, and the nearest color that has enough.
+    Contrast {
+        source: String,
+        property: &'static str,
+        color: Rgb,
+        fill: Rgb,
+        level: &'static str,
+        required: f64,
+        suggestion: Option<Rgb>,
+    },
+    /// Two fills that are easy to tell apart, except with `deficiency`.
+    LookAlike {
+        first: String,
+        second: String,
+        fills: (Rgb, Rgb),
+        deficiency: Deficiency,
+        delta_e: f64,
+    },
+}
+
+impl Issue {
+    /// Everything but text that passes AA and misses AAA.
+    fn is_failure(&self) -> bool {
+        !matches!(self, Issue::Contrast { level: "AAA", .. })
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Issue::Contrast {
+                source,
+                property,
+                color,
+                fill,
+                level,
+                required,
+                suggestion,
+            } => {
+                let graphics = if *property == "stroke" {
+                    " for graphics"
+                } else {
+                    ""
+                };
+                write!(
+                    f,
+                    "{}: {} {} on fill {} is {:.2}:1, below {}{} ({}:1); ",
+                    source,
+                    property,
+                    color,
+                    fill,
+                    color.contrast(*fill),
+                    level,
+                    graphics,
+                    required
+                )?;
+                match suggestion {
+                    Some(better) => write!(
+                        f,
+                        "nearest passing {} is {} ({:.2}:1)",
+                        property,
+                        better,
+                        better.contrast(*fill)
+                    ),
+                    None => write!(f, "no {} passes on this fill", property),
+                }
+            }
+            Issue::LookAlike {
+                first,
+                second,
+                fills,
+                deficiency,
+                delta_e,
+            } => write!(
+                f,
+                "{} and {}: fills {} and {} look alike with {} (delta E {:.1})",
+                first, second, fills.0, fills.1, deficiency, delta_e
+            ),
+        }
+    }
+}
+
+/// Checks every classDef and `style` that sets a fill: `color` must reach
+/// WCAG AA and ideally AAA contrast with it and `stroke` the AA level for
+/// graphics, and fills that differ must stay apart under each simulated
+/// color-vision deficiency. Returns how many pairs were checked, with the
+/// problems found.
+fn audit_colors(diagram: &Diagram) -> (usize, Vec<Issue>) {
+    let mut issues = Vec::new();
+    let mut checked = 0;
+    let mut fills: Vec<(String, Rgb)> = Vec::new();
+
+    for line in diagram.all_lines() {
+        let (source, styles) = match &line.statement {
+            Statement::ClassDef { names, styles } => {
+                (format!("classDef {}", names.join(",")), styles)
+            }
+            Statement::Style { node, styles } => (format!("style {}", node), styles),
+            _ => continue,
+        };
+        let colors = style_colors(styles);
+        let Some(&fill) = colors.get("fill") else {
+            continue;
+        };
+        if !fills.iter().any(|(_, f)| *f == fill) {
+            fills.push((source.clone(), fill));
+        }
+
+        for (property, levels) in [
+            ("color", &[("AA", AA_TEXT), ("AAA", AAA_TEXT)][..]),
+            ("stroke", &[("AA", AA_GRAPHICS)][..]),
+        ] {
+            let Some(&color) = colors.get(property) else {
+                continue;
+            };
+            checked += 1;
+            let ratio = color.contrast(fill);
+            if let Some(&(level, required)) = levels.iter().find(|(_, r)| ratio < *r) {
+                issues.push(Issue::Contrast {
+                    source: source.clone(),
+                    property,
+                    color,
+                    fill,
+                    level,
+                    required,
+                    suggestion: color.nearest_with_contrast(fill, required),
+                });
+            }
+        }
+    }
+
+    for (i, (first, a)) in fills.iter().enumerate() {
+        for (second, b) in &fills[i + 1..] {
+            checked += 1;
+            if a.delta_e(*b) < MIN_DELTA_E {
+                continue;
+            }
+            for deficiency in Deficiency::ALL {
+                let delta_e = a.simulate(deficiency).delta_e(b.simulate(deficiency));
+                if delta_e < MIN_DELTA_E {
+                    issues.push(Issue::LookAlike {
+                        first: first.clone(),
+                        second: second.clone(),
+                        fills: (*a, *b),
+                        deficiency,
+                        delta_e,
+                    });
+                }
+            }
+        }
+    }
+
+    (checked, issues)
+}
+
+/// The `fill`, `color` and `stroke` colors written as hex in a style.
+fn style_colors(styles: &str) -> BTreeMap<&str, Rgb> {
+    styles
+        .split(',')
+        .filter_map(|property| property.split_once(':'))
+        .filter_map(|(key, value)| {
+            let key = key.trim();
+            ["fill", "color", "stroke"]
+                .into_iter()
+                .find(|k| *k == key)
+                .zip(Rgb::parse(value))
+        })
+        .collect()
+}
+
 fn get_or_create_artistic_name(hex: &str, db: &mut ColorMaps) -> String {
     if let Some(existing) = db.hex_to_name.get(hex) {
         return existing.clone();
@@ -2354,9 +2764,9 @@ fn generate_artistic_name(hex: &str, db: &ColorMaps) -> String {
     let is_dark = value < 0.25;
     let is_vivid = saturation > 0.8;
     let is_muted = saturation < 0.3;
-    let is_warm = (hue >= 0.0 && hue <= 60.0) || (hue >= 320.0 && hue <= 360.0);
-    let is_cool = hue >= 180.0 && hue <= 300.0;
-    let is_natural = (hue >= 60.0 && hue <= 150.0) || saturation < 0.4;
+    let is_warm = (0.0..=60.0).contains(&hue) || (320.0..=360.0).contains(&hue);
+    let is_cool = (180.0..=300.0).contains(&hue);
+    let is_natural = (60.0..=150.0).contains(&hue) || saturation < 0.4;
 
     let mut word_pools: Vec<(&[&str], f64)> = Vec::new();
 
@@ -2458,7 +2868,7 @@ fn generate_artistic_name(hex: &str, db: &ColorMaps) -> String {
 
         for (pool, weight) in &word_pools {
             let effective_weight = weight * (1.0 - (attempts as f64 / max_attempts as f64));
-            if rng.gen::<f64>() < effective_weight {
+            if rng.random::<f64>() < effective_weight {
                 if let Some(word) = pool.choose(&mut rng) {
                     let category = *pool as *const [&str];
                     if !used_categories.contains(&category) && !selected_words.contains(word) {
@@ -2527,16 +2937,16 @@ fn save_color_db(path: &str, db: &ColorMaps) -> Result<(), Box<dyn std::error::E
 }
 
 fn extract_colors(content: &str) -> Vec<String> {
-    let re = Regex::new(r"fill:\s*([#][0-9A-Fa-f]{6})").unwrap();
-    let mut unique = HashSet::new();
+    let mut unique = BTreeSet::new();
 
-    for cap in re.captures_iter(content) {
-        unique.insert(cap[1].to_uppercase());
+    for cap in fill_regex().captures_iter(content) {
+        unique.insert(six_digit_hex(&cap[1]));
     }
 
     let mut colors: Vec<_> = unique.into_iter().collect();
     colors.retain(|c| c != "#FFFFFF");
 
+    // Colors of equal luminance keep their hex order
     colors.sort_by(|a, b| {
         approximate_luminance(a)
             .partial_cmp(&approximate_luminance(b))
@@ -2593,8 +3003,8 @@ fn rgb_to_hsv(r: u8, g: u8, b: u8) -> (f64, f64, f64) {
     (h, s, v)
 }
 
-/// Adjusts hue to wrap around for hues >= 345.0 degrees.
-/// This ensures that hues near 360° appear next to hues near 0°, creating a seamless rainbow.
+// /// Adjusts hue to wrap around for hues >= 345.0 degrees.
+// /// This ensures that hues near 360° appear next to hues near 0°, creating a seamless rainbow.
 // fn adjust_hue(h: f64) -> f64 {
 //     if h >= 345.0 {
 //         h - 360.0
@@ -2615,7 +3025,7 @@ fn sanitize_class_name(name: &str) -> String {
         }
     }
     // If the first character is a digit, prepend an underscore
-    if sanitized.chars().next().map_or(false, |c| c.is_digit(10)) {
+    if sanitized.chars().next().is_some_and(|c| c.is_ascii_digit()) {
         sanitized = format!("_{}", sanitized);
     }
     sanitized
@@ -2661,7 +3071,7 @@ fn generate_mermaid(color_and_name: Vec<(String, String)>) -> String {
         ));
     }
 
-    out.push_str("\n");
+    out.push('\n');
 
     for (i, (hex, name)) in color_and_name.iter().enumerate() {
         let node_id = format!("color{}", i);
@@ -2684,3 +3094,142 @@ fn generate_mermaid(color_and_name: Vec<(String, String)>) -> String {
 
     out
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(hex: &str) -> String {
+        match hex {
+            "#FF0000" => "Ember Glow".to_string(),
+            _ => "Verdant".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_apply_palette() {
+        let mut diagram = Diagram::parse(
+            "flowchart LR\n\
+             A:::hot --> B\n\
+             C --> D\n\
+             style B fill:#ff0000,stroke:#333\n\
+             style C fill:#00ff00\n\
+             classDef hot fill:#FF0000,stroke:#333\n\
+             classDef warm fill:#ff0000\n\
+             classDef default fill:#eeeeee\n\
+             classDef faint stroke-dasharray:3 3\n\
+             class D warm\n",
+        );
+        let applied = apply_palette(&mut diagram, names);
+        assert_eq!(
+            applied,
+            Applied {
+                classes: vec![
+                    ("Ember_Glow".to_string(), "#FF0000".to_string()),
+                    ("Verdant".to_string(), "#00FF00".to_string()),
+                ],
+                replaced: 4,
+                conflicts: vec![("#FF0000".to_string(), "fill:#FF0000".to_string())],
+            }
+        );
+        let expected = "flowchart LR\n\
+             \x20   A:::Ember_Glow --> B\n\
+             \x20   C --> D\n\
+             \x20   class B Ember_Glow\n\
+             \x20   class C Verdant\n\
+             \x20   classDef default fill:#eeeeee\n\
+             \x20   classDef faint stroke-dasharray:3 3\n\
+             \x20   class D Ember_Glow\n\
+             \n\
+             \x20   classDef Ember_Glow fill:#FF0000,stroke:#333333\n\
+             \x20   classDef Verdant fill:#00FF00\n";
+        assert_eq!(diagram.to_string(), expected);
+
+        // Applying again only re-emits the palette classes
+        let again = apply_palette(&mut diagram, names);
+        assert_eq!(again.replaced, 2);
+        assert!(again.conflicts.is_empty());
+        assert_eq!(diagram.to_string(), expected);
+    }
+
+    #[test]
+    fn test_audit_colors() {
+        let diagram = Diagram::parse(
+            "flowchart LR\n\
+             classDef ok fill:#ffffff,color:#000000,stroke:#000000\n\
+             classDef hot fill:#FFA500,color:#fff,stroke:#ffcc80\n\
+             classDef grey fill:#FFFFFF,color:#767676\n\
+             classDef faint stroke-dasharray:3 3\n\
+             style A fill:#cc6666\n\
+             style B fill:#669966\n",
+        );
+        let (checked, issues) = audit_colors(&diagram);
+        // Five color/stroke pairs and six pairs of the four distinct fills
+        assert_eq!(checked, 11);
+
+        let orange = Rgb(0xFF, 0xA5, 0x00);
+        assert_eq!(
+            issues[0],
+            Issue::Contrast {
+                source: "classDef hot".to_string(),
+                property: "color",
+                color: Rgb::WHITE,
+                fill: orange,
+                level: "AA",
+                required: AA_TEXT,
+                suggestion: Rgb::WHITE.nearest_with_contrast(orange, AA_TEXT),
+            }
+        );
+        assert!(matches!(
+            &issues[1],
+            Issue::Contrast {
+                property: "stroke",
+                level: "AA",
+                suggestion: Some(_),
+                ..
+            }
+        ));
+        assert!(matches!(&issues[2], Issue::Contrast { level: "AAA", .. }));
+        assert!(!issues[2].is_failure());
+        assert!(issues.iter().any(|i| matches!(
+            i,
+            Issue::LookAlike { first, second, deficiency: Deficiency::Deuteranopia, .. }
+                if first == "style A" && second == "style B"
+        )));
+        assert!(issues[0]
+            .to_string()
+            .starts_with("classDef hot: color #FFFFFF on fill #FFA500 is 1.97:1, below AA (4.5:1); nearest passing color is #"));
+    }
+
+    #[test]
+    fn test_apply_palette_expands_short_hexes() {
+        let mut diagram = Diagram::parse(
+            "flowchart LR\n    X --> Y\n    style X fill:#f00\n    classDef hot fill:#F00,stroke:#333\n",
+        );
+        let applied = apply_palette(&mut diagram, names);
+        assert_eq!(
+            applied.classes,
+            vec![("Ember_Glow".to_string(), "#FF0000".to_string())]
+        );
+        assert_eq!(
+            applied.conflicts,
+            vec![(
+                "#FF0000".to_string(),
+                "fill:#FF0000,stroke:#333333".to_string()
+            )]
+        );
+        assert!(diagram.to_string().contains("class X Ember_Glow"));
+        assert_eq!(
+            extract_colors("style X fill:#f00\nstyle Y fill:#00FF00\n"),
+            vec!["#FF0000", "#00FF00"]
+        );
+    }
+
+    #[test]
+    fn test_apply_palette_without_fills() {
+        let source = "classDiagram\n    class A\n    classDef faint stroke:#999999\n";
+        let mut diagram = Diagram::parse(source);
+        assert_eq!(apply_palette(&mut diagram, names), Applied::default());
+        assert_eq!(diagram.to_string(), source);
+    }
+}
@@ -16,8 +16,9 @@ fn main() {
 
         if !status.success() {
             eprintln!(
-                "Command '{}' failed with exit code: {}",
-                format!("{} {}", command, args.join(" ")),
+                "Command '{} {}' failed with exit code: {}",
+                command,
+                args.join(" "),
                 status.code().unwrap_or(-1)
             );
             exit(status.code().unwrap_or(1));
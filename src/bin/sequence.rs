@@ -0,0 +1,53 @@
+use anyhow::{anyhow, Context, Result};
+use cargo_invoke::filter::Filter;
+use cargo_invoke::model::CrateModel;
+use cargo_invoke::sequence::sequence_diagram;
+use cargo_invoke::theme::Theme;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// How many levels of calls are followed when `--depth` is not given.
+const DEFAULT_DEPTH: usize = 3;
+
+fn main() -> Result<()> {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let filter = Filter::from_args(&mut args)?;
+    let theme = Theme::from_args(&mut args)?;
+
+    let mut depth = DEFAULT_DEPTH;
+    if let Some(i) = args.iter().position(|a| a == "--depth") {
+        let value = args
+            .get(i + 1)
+            .ok_or_else(|| anyhow!("--depth requires a number"))?;
+        depth = value
+            .parse()
+            .with_context(|| format!("Invalid --depth '{}'", value))?;
+        args.drain(i..i + 2);
+    }
+
+    let [entry] = args.as_slice() else {
+        return Err(anyhow!(
+            "Usage: sequence <Type::method> [--depth <N>] [--include <GLOB>] [--exclude <GLOB>] \
+             [--pub-only] [--no-tests] [--theme <NAME>]"
+        ));
+    };
+
+    // Calls into types the filter drops are left out
+    let mut model = CrateModel::load(Path::new("src"))?;
+    filter.apply(&mut model);
+    let diagram = sequence_diagram(&model, entry, depth)?;
+
+    fs::create_dir_all("diagrams").context("Failed to create 'diagrams/' directory.")?;
+    let output_path = PathBuf::from(format!(
+        "diagrams/sequence_{}.mermaid",
+        entry.replace("::", "_")
+    ));
+    fs::write(&output_path, diagram)
+        .with_context(|| format!("Failed to write {}", output_path.display()))?;
+    if let Some(theme) = &theme {
+        theme.apply_to_file(&output_path)?;
+    }
+    println!("Generated sequence diagram at {}", output_path.display());
+
+    Ok(())
+}
@@ -0,0 +1,422 @@
+use cargo_invoke::common::open_in_browser;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Component, Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// Directories the server exposes; nothing outside them is ever read.
+const SERVED_DIRS: &[&str] = &["diagrams", "visuals"];
+
+const MERMAID_JS: &str = "https://cdn.jsdelivr.net/npm/mermaid@11/dist/mermaid.esm.min.mjs";
+
+/// Live-reload client: every page subscribes to `/events` and re-renders when
+/// the file it shows is rewritten. The index reloads on any change.
+const RELOAD_SCRIPT: &str = r#"<script>
+const source = new EventSource('/events');
+source.onmessage = (event) => {
+  const watched = document.body.dataset.file;
+  if (!watched || watched === event.data) {
+    if (window.rerender) { window.rerender(); } else { location.reload(); }
+  }
+};
+</script>"#;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = std::env::args().collect();
+    let mut port: u16 = 7878;
+    let mut open = false;
+
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--port" => {
+                i += 1;
+                port = args.get(i).ok_or("--port requires a value")?.parse()?;
+            }
+            "--open" => open = true,
+            other => {
+                eprintln!("Usage: {} [--port <PORT>] [--open]", args[0]);
+                return Err(format!("Unknown argument: {}", other).into());
+            }
+        }
+        i += 1;
+    }
+
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    let url = format!("http://127.0.0.1:{}/", port);
+    println!("Serving diagrams/ and visuals/ at {} (Ctrl-C to stop)", url);
+
+    if open {
+        if let Err(e) = open_in_browser(&url) {
+            eprintln!("Warning: {}", e);
+        }
+    }
+
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                thread::spawn(move || {
+                    if let Err(e) = handle_connection(stream) {
+                        // Clients closing an event stream show up here; not worth more than a note.
+                        if e.kind() != io::ErrorKind::BrokenPipe {
+                            eprintln!("Connection error: {}", e);
+                        }
+                    }
+                });
+            }
+            Err(e) => eprintln!("Failed to accept connection: {}", e),
+        }
+    }
+
+    Ok(())
+}
+
+fn handle_connection(mut stream: TcpStream) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+
+    // Drain the headers; none of them change the response.
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header == "\r\n" || header == "\n" {
+            break;
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let target = parts.next().unwrap_or("/");
+    let path = percent_decode(target.split('?').next().unwrap_or("/"));
+
+    if method != "GET" {
+        return respond(
+            &mut stream,
+            "405 Method Not Allowed",
+            "text/plain",
+            b"GET only",
+        );
+    }
+
+    match path.as_str() {
+        "/" => respond(
+            &mut stream,
+            "200 OK",
+            "text/html; charset=utf-8",
+            render_index().as_bytes(),
+        ),
+        "/events" => stream_events(stream),
+        p if p.starts_with("/view/") => match safe_path(&p["/view/".len()..]) {
+            Some(file) if file.is_file() => respond(
+                &mut stream,
+                "200 OK",
+                "text/html; charset=utf-8",
+                render_view(&file).as_bytes(),
+            ),
+            _ => not_found(&mut stream),
+        },
+        p if p.starts_with("/raw/") => match safe_path(&p["/raw/".len()..]) {
+            Some(file) if file.is_file() => {
+                let body = fs::read(&file)?;
+                respond(&mut stream, "200 OK", content_type(&file), &body)
+            }
+            _ => not_found(&mut stream),
+        },
+        _ => not_found(&mut stream),
+    }
+}
+
+fn respond(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &[u8],
+) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )?;
+    stream.write_all(body)?;
+    stream.flush()
+}
+
+fn not_found(stream: &mut TcpStream) -> io::Result<()> {
+    respond(stream, "404 Not Found", "text/plain", b"Not found")
+}
+
+/// Server-sent events: polls the served directories and sends the relative
+/// path of every file that changes. Ends when the client goes away.
+fn stream_events(mut stream: TcpStream) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-store\r\nConnection: keep-alive\r\n\r\n"
+    )?;
+    stream.flush()?;
+
+    let mut state = snapshot();
+    let mut idle = 0;
+    loop {
+        thread::sleep(Duration::from_millis(500));
+        let current = snapshot();
+        let mut changed: Vec<&PathBuf> = current
+            .iter()
+            .filter(|(path, modified)| state.get(*path) != Some(modified))
+            .map(|(path, _)| path)
+            .collect();
+        changed.sort();
+
+        for path in &changed {
+            write!(stream, "data: {}\n\n", path.display())?;
+        }
+        // A comment line every ~15s keeps proxies from closing the stream and
+        // lets us notice disconnected clients.
+        idle += 1;
+        if changed.is_empty() && idle >= 30 {
+            write!(stream, ": keep-alive\n\n")?;
+            idle = 0;
+        }
+        stream.flush()?;
+        state = current;
+    }
+}
+
+fn snapshot() -> BTreeMap<PathBuf, SystemTime> {
+    let mut files = BTreeMap::new();
+    for dir in SERVED_DIRS {
+        for path in list_files(Path::new(dir)) {
+            if let Ok(modified) = fs::metadata(&path).and_then(|m| m.modified()) {
+                files.insert(path, modified);
+            }
+        }
+    }
+    files
+}
+
+fn list_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .map(|e| e.path())
+                .filter(|p| p.is_file())
+                .collect()
+        })
+        .unwrap_or_default();
+    files.sort();
+    files
+}
+
+/// Resolves a request path to a file inside one of [`SERVED_DIRS`], rejecting
+/// anything that would escape them.
+fn safe_path(relative: &str) -> Option<PathBuf> {
+    let path = PathBuf::from(relative);
+    if path
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_)))
+    {
+        return None;
+    }
+    let top = path.components().next()?.as_os_str().to_str()?;
+    SERVED_DIRS.contains(&top).then_some(path)
+}
+
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("html") => "text/html; charset=utf-8",
+        Some("json") => "application/json",
+        _ => "text/plain; charset=utf-8",
+    }
+}
+
+fn percent_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 3 <= bytes.len() {
+            let hex = &bytes[i + 1..i + 3];
+            if hex.iter().all(u8::is_ascii_hexdigit) {
+                let digit = |b: u8| (b as char).to_digit(16).unwrap() as u8;
+                out.push(digit(hex[0]) * 16 + digit(hex[1]));
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Encodes a served path for use in a URL. The result only contains
+/// unreserved characters, `/` and `%`, so it is also safe inside HTML
+/// attributes and JavaScript strings.
+fn percent_encode(path: &Path) -> String {
+    let mut out = String::new();
+    for byte in path.to_string_lossy().bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                out.push(byte as char)
+            }
+            _ => out.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    out
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn render_index() -> String {
+    let mut body = String::new();
+    for dir in SERVED_DIRS {
+        let files: Vec<PathBuf> = list_files(Path::new(dir))
+            .into_iter()
+            .filter(|p| {
+                matches!(
+                    p.extension().and_then(|e| e.to_str()),
+                    Some("mermaid") | Some("mmd") | Some("svg") | Some("png")
+                )
+            })
+            .collect();
+        body.push_str(&format!("<h2>{}/</h2>\n<ul>\n", dir));
+        if files.is_empty() {
+            body.push_str("<li><em>empty</em></li>\n");
+        }
+        for file in files {
+            body.push_str(&format!(
+                "<li><a href=\"/view/{}\">{}</a></li>\n",
+                percent_encode(&file),
+                html_escape(&file.display().to_string())
+            ));
+        }
+        body.push_str("</ul>\n");
+    }
+
+    page("Diagrams", "", &body)
+}
+
+fn render_view(file: &Path) -> String {
+    let display = html_escape(&file.display().to_string());
+    let raw = format!("/raw/{}", percent_encode(file));
+    let is_mermaid = matches!(
+        file.extension().and_then(|e| e.to_str()),
+        Some("mermaid") | Some("mmd")
+    );
+
+    let body = if is_mermaid {
+        format!(
+            r#"<p><a href="/">&larr; all diagrams</a> · {0}</p>
+<div id="diagram" data-src="{2}"></div>
+<pre id="error" style="color:#b00"></pre>
+<script type="module">
+import mermaid from '{1}';
+mermaid.initialize({{ startOnLoad: false }});
+let counter = 0;
+window.rerender = async () => {{
+  const text = await (await fetch(document.getElementById('diagram').dataset.src, {{ cache: 'no-store' }})).text();
+  try {{
+    const {{ svg }} = await mermaid.render('diagram-' + (counter++), text);
+    document.getElementById('diagram').innerHTML = svg;
+    document.getElementById('error').textContent = '';
+  }} catch (e) {{
+    document.getElementById('error').textContent = String(e);
+  }}
+}};
+window.rerender();
+</script>"#,
+            display, MERMAID_JS, raw
+        )
+    } else {
+        format!(
+            r#"<p><a href="/">&larr; all diagrams</a> · {0}</p>
+<img id="image" src="{1}" alt="{0}">
+<script>
+window.rerender = () => {{
+  const image = document.getElementById('image');
+  image.src = image.getAttribute('src').split('?')[0] + '?t=' + Date.now();
+}};
+</script>"#,
+            display, raw
+        )
+    };
+
+    page(&display, &display, &body)
+}
+
+fn page(title: &str, file: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body data-file=\"{}\">\n{}\n{}\n</body>\n</html>\n",
+        title, file, body, RELOAD_SCRIPT
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(
+            percent_decode("diagrams/a%20b.mermaid"),
+            "diagrams/a b.mermaid"
+        );
+        assert_eq!(percent_decode("%41"), "A");
+        assert_eq!(percent_decode("caf%C3%A9"), "café");
+        // Truncated or invalid escapes are kept as they are
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%4"), "%4");
+        assert_eq!(percent_decode("%zz%+1"), "%zz%+1");
+        // A multi-byte character right after `%` must not panic
+        assert_eq!(percent_decode("%é"), "%é");
+        assert_eq!(percent_decode("%aé"), "%aé");
+    }
+
+    #[test]
+    fn test_percent_encode_round_trips() {
+        let path = Path::new("diagrams/it's <a> #1?.mermaid");
+        let encoded = percent_encode(path);
+        assert!(!encoded.contains(['\'', '"', '<', '#', '?', ' ']));
+        assert_eq!(percent_decode(&encoded), path.to_str().unwrap());
+    }
+
+    #[test]
+    fn test_safe_path() {
+        assert_eq!(
+            safe_path("diagrams/class_diagram.mermaid"),
+            Some(PathBuf::from("diagrams/class_diagram.mermaid"))
+        );
+        assert_eq!(
+            safe_path("visuals/sub/a.svg"),
+            Some(PathBuf::from("visuals/sub/a.svg"))
+        );
+        assert_eq!(safe_path("diagrams/../Cargo.toml"), None);
+        assert_eq!(safe_path("../diagrams/a.mermaid"), None);
+        assert_eq!(safe_path("/etc/passwd"), None);
+        assert_eq!(safe_path("./diagrams/a.mermaid"), None);
+        assert_eq!(safe_path("src/main.rs"), None);
+        assert_eq!(safe_path(""), None);
+    }
+
+    #[test]
+    fn test_render_view_keeps_the_file_name_out_of_scripts() {
+        let html = render_view(Path::new("diagrams/x');alert(1);('.mermaid"));
+        for script in html.split("<script").skip(1) {
+            assert!(!script.split("</script>").next().unwrap().contains("alert"));
+        }
+        assert!(html.contains("data-src=\"/raw/diagrams/x%27%29%3Balert%281%29%3B%28%27.mermaid\""));
+    }
+}
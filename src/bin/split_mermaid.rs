@@ -1,5 +1,6 @@
-use regex::Regex;
-use std::collections::HashSet;
+use cargo_invoke::mermaid::{Block, BlockKind, Diagram, DiagramKind, Edge, Line, Node, Statement};
+use cargo_invoke::theme::Theme;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::fs::{self, File};
 use std::io::{self, Write};
 use std::path::Path;
@@ -16,281 +17,734 @@ struct ClassAssignment {
     class_name: String,
 }
 
-struct Subgraph {
+/// How a diagram is cut into pieces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Strategy {
+    /// One file per top-level flowchart subgraph.
+    Subgraph,
+    /// One file per weakly-connected component of a flowchart.
+    Component,
+    /// Flowchart files of at most this many nodes, keeping components
+    /// together where they fit.
+    Size(usize),
+    /// One file per classDiagram `namespace`, plus `global` for the rest.
+    Namespace,
+}
+
+/// One piece of the split: the statements it keeps and the nodes it owns.
+struct Part {
     name: String,
-    content: String,
-    class_assignments: Vec<ClassAssignment>,
-    used_classes: HashSet<String>,
-    nodes: HashSet<String>,
+    body: Vec<Line>,
+    nodes: BTreeSet<String>,
 }
 
+/// Marks nodes that live in another file; their `click` leads there.
+const STUB_STYLE: &str = "stroke-dasharray:5 5";
+
+const USAGE: &str = "[--by subgraph|component|size|namespace] [--max-nodes <N>] [--theme <NAME>] <input_file.mermaid>";
+
 fn main() -> io::Result<()> {
-    let args: Vec<String> = std::env::args().collect();
-    if args.len() != 2 {
-        eprintln!("Usage: {} <input_file.mermaid>", args[0]);
-        std::process::exit(1);
+    let mut args: Vec<String> = std::env::args().collect();
+    let theme = match Theme::from_args(&mut args) {
+        Ok(theme) => theme,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
+        }
+    };
+
+    let mut by = None;
+    let mut max_nodes = None;
+    let mut input_file = None;
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--by" => {
+                by = args.get(i + 1).cloned();
+                i += 2;
+            }
+            "--max-nodes" => {
+                max_nodes = match args.get(i + 1).map(|n| n.parse::<usize>()) {
+                    Some(Ok(n)) if n > 0 => Some(n),
+                    _ => {
+                        eprintln!("--max-nodes expects a positive number");
+                        std::process::exit(1);
+                    }
+                };
+                i += 2;
+            }
+            arg if input_file.is_none() && !arg.starts_with('-') => {
+                input_file = Some(arg.to_string());
+                i += 1;
+            }
+            _ => {
+                eprintln!("Usage: {} {}", args[0], USAGE);
+                std::process::exit(1);
+            }
+        }
     }
 
-    let input_path = Path::new(&args[1]);
+    let Some(input_file) = input_file else {
+        eprintln!("Usage: {} {}", args[0], USAGE);
+        std::process::exit(1);
+    };
+    let input_path = Path::new(&input_file);
     if !input_path.exists() {
         eprintln!("Input file does not exist");
         std::process::exit(1);
     }
 
+    let content = fs::read_to_string(input_path)?;
+    let diagram = Diagram::parse(&content);
+    let strategy = match (by.as_deref(), max_nodes) {
+        (None, None) if diagram.kind == DiagramKind::Class => Strategy::Namespace,
+        (None | Some("subgraph"), None) => Strategy::Subgraph,
+        (Some("component"), None) => Strategy::Component,
+        (None | Some("size"), max) => Strategy::Size(max.unwrap_or(50)),
+        (Some("namespace"), None) => Strategy::Namespace,
+        (Some(other), _) => {
+            eprintln!(
+                "--by expects subgraph, component, size or namespace{}",
+                if max_nodes.is_some() && other != "size" {
+                    "; --max-nodes only applies to size"
+                } else {
+                    ""
+                }
+            );
+            std::process::exit(1);
+        }
+    };
+
+    let (parts, warnings) = match split(&diagram, strategy) {
+        Ok(split) => split,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
+        }
+    };
+    for warning in &warnings {
+        eprintln!("warning: {}", warning);
+    }
+
     let output_dir = input_path.with_extension("");
     fs::create_dir_all(&output_dir)?;
+    let mut files = build_files(&diagram, &parts);
+    for (name, output) in &mut files {
+        if let Some(theme) = &theme {
+            theme.apply(output);
+        }
+        let output_path = output_dir.join(format!("{}.mermaid", name));
+        let mut file = File::create(output_path)?;
+        file.write_all(output.to_string().as_bytes())?;
+    }
 
-    let content = fs::read_to_string(input_path)?;
-    process_mermaid_file(&content, &output_dir)?;
-
+    println!(
+        "Split {} into {} files in {}",
+        input_path.display(),
+        files.len() - 1,
+        output_dir.display()
+    );
     Ok(())
 }
 
-fn process_mermaid_file(content: &str, output_dir: &Path) -> io::Result<()> {
-    // Extract all class definitions first
-    let class_defs = extract_all_class_definitions(content);
-
-    // Extract all class assignments (both global and inline)
-    let class_assignments = extract_all_class_assignments(content);
+/// Cuts the diagram into parts with unique, file-safe names, and reports
+/// what had to be left out.
+fn split(diagram: &Diagram, strategy: Strategy) -> Result<(Vec<Part>, Vec<String>), String> {
+    let is_class = diagram.kind == DiagramKind::Class;
+    if is_class != (strategy == Strategy::Namespace) {
+        return Err(format!(
+            "a {} cannot be split by {}; classDiagrams split by namespace, flowcharts by subgraph, component or size",
+            diagram.kind.keyword(),
+            match strategy {
+                Strategy::Subgraph => "subgraph",
+                Strategy::Component => "component",
+                Strategy::Size(_) => "size",
+                Strategy::Namespace => "namespace",
+            }
+        ));
+    }
 
-    // Extract subgraphs with recursive nesting
-    let subgraphs = extract_subgraphs(content);
+    let mut warnings = Vec::new();
+    let edge_count = diagram.edges().count();
+    for (links, _) in link_styles(diagram) {
+        for link in links.split(',').map(str::trim).filter(|l| *l != "default") {
+            if !link.parse::<usize>().is_ok_and(|i| i < edge_count) {
+                warnings.push(format!("linkStyle {} refers to no edge; dropped", link));
+            }
+        }
+    }
+    let mut parts = match strategy {
+        Strategy::Subgraph => subgraph_parts(diagram, &mut warnings),
+        Strategy::Component => {
+            let (connected, isolated): (Vec<_>, Vec<_>) =
+                components(diagram).into_iter().partition(|c| c.len() > 1);
+            let mut parts: Vec<Part> = connected
+                .into_iter()
+                .enumerate()
+                .map(|(i, ids)| flat_part(diagram, format!("component_{}", i + 1), ids))
+                .collect();
+            // Unconnected nodes would each get a file of their own otherwise
+            let isolated: Vec<String> = isolated.into_iter().flatten().collect();
+            if !isolated.is_empty() {
+                parts.push(flat_part(diagram, "isolated".to_string(), isolated));
+            }
+            parts
+        }
+        Strategy::Size(max) => {
+            let mut chunks: Vec<Vec<String>> = Vec::new();
+            for component in components(diagram) {
+                for id in component.iter() {
+                    let fits = chunks.last().is_some_and(|chunk| {
+                        // A component that fits in a file is not spread over two
+                        let needed = if id == &component[0] && component.len() <= max {
+                            component.len()
+                        } else {
+                            1
+                        };
+                        chunk.len() + needed <= max
+                    });
+                    if !fits {
+                        chunks.push(Vec::new());
+                    }
+                    chunks.last_mut().unwrap().push(id.clone());
+                }
+            }
+            chunks
+                .into_iter()
+                .enumerate()
+                .map(|(i, ids)| flat_part(diagram, format!("part_{}", i + 1), ids))
+                .collect()
+        }
+        Strategy::Namespace => namespace_parts(diagram),
+    };
+
+    // File names must be unique, and `index` is taken by the overview
+    let mut taken: HashSet<String> = HashSet::from(["index".to_string()]);
+    for part in &mut parts {
+        let base = sanitize_filename(&part.name);
+        let mut name = base.clone();
+        let mut n = 1;
+        while !taken.insert(name.clone()) {
+            n += 1;
+            name = format!("{}_{}", base, n);
+        }
+        if name != part.name {
+            warnings.push(format!("`{}` is written to {}.mermaid", part.name, name));
+        }
+        part.name = name;
+    }
+    Ok((parts, warnings))
+}
 
-    // Process each subgraph
-    for subgraph in subgraphs {
-        // Skip empty or wrapper subgraphs
-        if subgraph.name.contains(".") || subgraph.content.trim().is_empty() {
+/// One part per top-level subgraph, nested subgraphs included in their parent.
+fn subgraph_parts(diagram: &Diagram, warnings: &mut Vec<String>) -> Vec<Part> {
+    let mut parts = Vec::new();
+    for line in &diagram.body {
+        let Statement::Subgraph(subgraph) = &line.statement else {
+            continue;
+        };
+        if subgraph.body.is_empty() {
+            warnings.push(format!("skipping empty subgraph `{}`", subgraph.id));
             continue;
         }
+        parts.push(Part {
+            name: subgraph.id.clone(),
+            body: vec![Statement::Subgraph(subgraph.clone()).into()],
+            nodes: body_nodes(&diagram.kind, &subgraph.body),
+        });
+    }
+    for id in node_order(diagram) {
+        if !parts.iter().any(|p| p.nodes.contains(&id)) {
+            warnings.push(format!("node `{}` is outside every subgraph; left out", id));
+        }
+    }
+    parts
+}
 
-        let mut output = String::new();
-        output.push_str("flowchart TB\n\n");
+/// A part holding the given nodes and the edges between them, without the
+/// subgraphs they were in.
+fn flat_part(diagram: &Diagram, name: String, ids: Vec<String>) -> Part {
+    let nodes: BTreeSet<String> = ids.iter().cloned().collect();
+    let declared = diagram.nodes();
+    let mut body: Vec<Line> = ids
+        .iter()
+        .map(|id| Statement::Node(declared[id].clone()).into())
+        .collect();
+    for edge in diagram.edges() {
+        if nodes.contains(&edge.from.id) && nodes.contains(&edge.to.id) {
+            body.push(Statement::Edge(bare_edge(edge)).into());
+        }
+    }
+    Part { name, body, nodes }
+}
 
-        // Clean and add subgraph content
-        let cleaned_content = clean_content(&subgraph.content);
-        output.push_str(&cleaned_content);
-        output.push('\n');
+/// Weakly-connected components, in the order their first node appears, each
+/// listed breadth first from that node.
+fn components(diagram: &Diagram) -> Vec<Vec<String>> {
+    let order = node_order(diagram);
+    let mut neighbours: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for edge in diagram.edges() {
+        neighbours
+            .entry(&edge.from.id)
+            .or_default()
+            .push(&edge.to.id);
+        neighbours
+            .entry(&edge.to.id)
+            .or_default()
+            .push(&edge.from.id);
+    }
 
-        // Track which classes are actually used
-        let mut actually_used_classes = HashSet::new();
+    let mut seen: HashSet<&str> = HashSet::new();
+    let mut out = Vec::new();
+    for start in &order {
+        if !seen.insert(start) {
+            continue;
+        }
+        let mut component = vec![start.clone()];
+        let mut i = 0;
+        while i < component.len() {
+            for next in neighbours.get(component[i].as_str()).into_iter().flatten() {
+                if seen.insert(next) {
+                    component.push(next.to_string());
+                }
+            }
+            i += 1;
+        }
+        out.push(component);
+    }
+    out
+}
 
-        // Process class assignments and collect used classes
-        let mut relevant_assignments = Vec::new();
-        for assignment in &class_assignments {
-            // Filter elements to only those present in this subgraph
-            let relevant_elements: Vec<String> = assignment
-                .elements
-                .iter()
-                .filter(|elem| subgraph.nodes.contains(*elem))
-                .cloned()
-                .collect();
+/// Node ids in the order they first appear, subgraph ids excluded.
+fn node_order(diagram: &Diagram) -> Vec<String> {
+    let subgraphs: HashSet<&str> = diagram
+        .all_lines()
+        .into_iter()
+        .filter_map(|line| match &line.statement {
+            Statement::Subgraph(subgraph) => Some(subgraph.id.as_str()),
+            _ => None,
+        })
+        .collect();
+    let mut seen = HashSet::new();
+    let mut order = Vec::new();
+    for line in diagram.all_lines() {
+        let ids = match &line.statement {
+            Statement::Node(node) => vec![&node.id],
+            Statement::Edge(edge) => vec![&edge.from.id, &edge.to.id],
+            _ => continue,
+        };
+        for id in ids {
+            if !subgraphs.contains(id.as_str()) && seen.insert(id) {
+                order.push(id.clone());
+            }
+        }
+    }
+    order
+}
 
-            if !relevant_elements.is_empty() {
-                relevant_assignments.push(ClassAssignment {
-                    elements: relevant_elements,
-                    class_name: assignment.class_name.clone(),
+/// One part per classDiagram namespace. Classes outside any namespace go to
+/// `global`, as do edges between them; an edge between two parts is cut.
+fn namespace_parts(diagram: &Diagram) -> Vec<Part> {
+    let mut parts: Vec<Part> = Vec::new();
+    let mut global = Part {
+        name: "global".to_string(),
+        body: Vec::new(),
+        nodes: BTreeSet::new(),
+    };
+    let mut edges = Vec::new();
+    for line in &diagram.body {
+        match &line.statement {
+            Statement::Block(
+                block @ Block {
+                    kind: BlockKind::Namespace,
+                    ..
+                },
+            ) => {
+                parts.push(Part {
+                    name: block.name.clone(),
+                    body: vec![line.clone()],
+                    nodes: body_nodes(&diagram.kind, &block.body),
                 });
-                actually_used_classes.insert(assignment.class_name.clone());
             }
+            Statement::Block(Block { name: id, .. })
+            | Statement::Node(Node { id, .. })
+            | Statement::Member { owner: id, .. } => {
+                global.nodes.insert(class_id(id));
+                global.body.push(line.clone());
+            }
+            Statement::Edge(_) => edges.push(line),
+            Statement::Comment(_) => global.body.push(line.clone()),
+            _ => {}
         }
+    }
 
-        // Sort and write class definitions for actually used classes
-        let mut used_class_defs: Vec<&ClassDefinition> = class_defs
-            .iter()
-            .filter(|def| actually_used_classes.contains(&def.name))
-            .collect();
-        used_class_defs.sort_by(|a, b| a.name.cmp(&b.name));
-
-        for def in used_class_defs {
-            output.push_str(&format!("classDef {} {}\n", def.name, def.properties));
+    // Classes only named by edges live with the class they are related to
+    for line in edges {
+        let Statement::Edge(edge) = &line.statement else {
+            continue;
+        };
+        let owner = |id: &str| {
+            let id = class_id(id);
+            parts
+                .iter()
+                .position(|p| p.nodes.contains(&id))
+                .or_else(|| global.nodes.contains(&id).then_some(parts.len()))
+        };
+        let (from, to) = (owner(&edge.from.id), owner(&edge.to.id));
+        let home = from.or(to).unwrap_or(parts.len());
+        let part = parts.get_mut(home).unwrap_or(&mut global);
+        for (id, owned) in [(&edge.from.id, from), (&edge.to.id, to)] {
+            if owned.is_none() {
+                part.nodes.insert(class_id(id));
+            }
         }
-
-        // Sort and write filtered class assignments
-        relevant_assignments.sort_by(|a, b| {
-            a.elements
-                .join(",")
-                .cmp(&b.elements.join(","))
-                .then(a.class_name.cmp(&b.class_name))
-        });
-
-        for assignment in relevant_assignments {
-            output.push_str(&format!(
-                "class {} {}\n",
-                assignment.elements.join(","),
-                assignment.class_name
-            ));
+        if from.unwrap_or(home) == to.unwrap_or(home) {
+            part.body.push(line.clone());
         }
-
-        // Write to file
-        let filename = format!("{}.mermaid", sanitize_filename(&subgraph.name));
-        let output_path = output_dir.join(filename);
-        let mut file = File::create(output_path)?;
-        file.write_all(output.as_bytes())?;
     }
 
-    Ok(())
+    if !global.body.is_empty() || !global.nodes.is_empty() {
+        parts.push(global);
+    }
+    parts
 }
 
-fn extract_all_class_definitions(content: &str) -> Vec<ClassDefinition> {
-    let mut defs = Vec::new();
-    let class_def_re = Regex::new(r"classDef\s+(\w+)\s+([^;\n]+)").unwrap();
+/// Builds each part's file and the `index` overview linking them.
+///
+/// An edge between two parts is written in both, and the node at its far end
+/// is a stub that `click`s through to the file that owns it. A `style` goes
+/// with the part owning its node or subgraph, and `linkStyle` indices are
+/// renumbered to each file's edges.
+fn build_files(source: &Diagram, parts: &[Part]) -> Vec<(String, Diagram)> {
+    let is_class = source.kind == DiagramKind::Class;
+    let owner = |id: &str| {
+        let id = class_id(id);
+        parts.iter().position(|p| p.nodes.contains(&id))
+    };
+    let declared = source.nodes();
+    let class_defs = extract_all_class_definitions(source);
+    let class_assignments = extract_all_class_assignments(source);
+    let source_edges: Vec<Edge> = source.edges().map(bare_edge).collect();
+    let link_styles = link_styles(source);
+    let node_styles: Vec<(&String, &String)> = source
+        .all_lines()
+        .into_iter()
+        .filter_map(|line| match &line.statement {
+            Statement::Style { node, styles } => Some((node, styles)),
+            _ => None,
+        })
+        .collect();
+
+    let mut files = Vec::new();
+    let mut cuts: BTreeMap<(usize, usize), usize> = BTreeMap::new();
+    for (index, part) in parts.iter().enumerate() {
+        let inside = |id: &str| owner(id) == Some(index);
+        let mut output = Diagram::new(source.kind.clone(), source.direction.as_deref());
+        output.preamble = source.preamble.clone();
+        output.body = part.body.clone();
+
+        let kept: Vec<Edge> = output.edges().cloned().collect();
+        for edge in source.edges() {
+            if inside(&edge.from.id) != inside(&edge.to.id) && !kept.contains(edge) {
+                let cut = if is_class {
+                    edge.clone()
+                } else {
+                    bare_edge(edge)
+                };
+                output.body.push(Statement::Edge(cut).into());
+            }
+        }
 
-    for line in content.lines() {
-        if line.trim().starts_with("classDef ") {
-            if let Some(caps) = class_def_re.captures(line) {
-                defs.push(ClassDefinition {
-                    name: caps[1].to_string(),
-                    properties: caps[2].to_string(),
-                });
+        // Nodes owned by another part link to its file
+        let mut stubs: BTreeMap<String, usize> = BTreeMap::new();
+        for edge in output.edges() {
+            if let (Some(from), Some(to)) = (owner(&edge.from.id), owner(&edge.to.id)) {
+                if from == index && to != index {
+                    *cuts.entry((from, to)).or_default() += 1;
+                }
+                for (id, other) in [(&edge.from.id, from), (&edge.to.id, to)] {
+                    if other != index {
+                        stubs.insert(id.clone(), other);
+                    }
+                }
             }
         }
+        for (id, other) in &stubs {
+            if !is_class {
+                let mut stub = declared.get(id).cloned().unwrap_or_else(|| Node::new(id));
+                stub.classes = vec!["stub".to_string()];
+                output.body.push(Statement::Node(stub).into());
+            }
+            output.body.push(
+                Statement::Click {
+                    node: class_id(id),
+                    action: format!("href \"{}.mermaid\"", parts[*other].name),
+                }
+                .into(),
+            );
+        }
+
+        let nodes: HashSet<&str> = part.nodes.iter().map(String::as_str).collect();
+        add_classes(&mut output, &nodes, &class_defs, &class_assignments);
+        add_styles(&mut output, part, &node_styles);
+        renumber_link_styles(&mut output, &source_edges, &link_styles);
+        files.push((part.name.clone(), output));
     }
 
-    defs
+    files.push(("index".to_string(), index(parts, &cuts)));
+    files
 }
 
-fn extract_all_class_assignments(content: &str) -> Vec<ClassAssignment> {
-    let mut assignments = Vec::new();
-    let class_assignment_re = Regex::new(r"^class\s+([\w,]+)\s+(\w+)").unwrap();
-    let inline_style_re = Regex::new(r"(\w+):::(\w+)").unwrap();
-
-    for line in content.lines() {
-        let trimmed = line.trim();
-
-        // Extract explicit class assignments
-        if trimmed.starts_with("class ") {
-            if let Some(caps) = class_assignment_re.captures(trimmed) {
-                let elements = caps[1].split(',').map(|s| s.trim().to_string()).collect();
-                assignments.push(ClassAssignment {
-                    elements,
-                    class_name: caps[2].to_string(),
-                });
+/// A flowchart with one node per part, edges counting the edges cut between
+/// them, and a `click` opening each part.
+fn index(parts: &[Part], cuts: &BTreeMap<(usize, usize), usize>) -> Diagram {
+    let mut index = Diagram::new(DiagramKind::Flowchart, Some("LR"));
+    let id = |i: usize| format!("P{}", i + 1);
+    for (i, part) in parts.iter().enumerate() {
+        let mut node = Node::new(id(i));
+        node.shape = Some(cargo_invoke::mermaid::Shape::Rect);
+        let count = part.nodes.len();
+        let plural = if count == 1 { "" } else { "s" };
+        node.label = Some(format!("\"{} ({} node{})\"", part.name, count, plural));
+        index.body.push(Statement::Node(node).into());
+    }
+    for (&(from, to), &count) in cuts {
+        let mut edge = Edge::new(id(from), "-->", id(to));
+        edge.label = Some(count.to_string());
+        index.body.push(Statement::Edge(edge).into());
+    }
+    for (i, part) in parts.iter().enumerate() {
+        index.body.push(
+            Statement::Click {
+                node: id(i),
+                action: format!("href \"{}.mermaid\"", part.name),
             }
-        }
+            .into(),
+        );
+    }
+    index
+}
 
-        // Extract inline styles
-        for caps in inline_style_re.captures_iter(trimmed) {
-            assignments.push(ClassAssignment {
-                elements: vec![caps[1].to_string()],
-                class_name: caps[2].to_string(),
+/// Appends the classDefs and class assignments used by the part's nodes.
+fn add_classes(
+    output: &mut Diagram,
+    nodes: &HashSet<&str>,
+    class_defs: &[ClassDefinition],
+    class_assignments: &[ClassAssignment],
+) {
+    // Track which classes are actually used, starting with inline `:::` ones
+    let mut actually_used_classes: HashSet<String> = output
+        .class_assignments()
+        .into_iter()
+        .map(|(_, class)| class.to_string())
+        .collect();
+
+    // Process class assignments and collect used classes
+    let mut relevant_assignments = Vec::new();
+    for assignment in class_assignments {
+        // Filter elements to only those present in this part
+        let relevant_elements: Vec<String> = assignment
+            .elements
+            .iter()
+            .filter(|elem| nodes.contains(elem.as_str()))
+            .cloned()
+            .collect();
+
+        if !relevant_elements.is_empty() {
+            relevant_assignments.push(ClassAssignment {
+                elements: relevant_elements,
+                class_name: assignment.class_name.clone(),
             });
+            actually_used_classes.insert(assignment.class_name.clone());
         }
     }
 
-    assignments
-}
-
-fn extract_subgraphs(content: &str) -> Vec<Subgraph> {
-    let mut subgraphs = Vec::new();
-    let mut lines = content.lines().peekable();
+    // Sort and write class definitions for actually used classes
+    let mut used_class_defs: Vec<ClassDefinition> = class_defs
+        .iter()
+        .filter(|def| actually_used_classes.contains(&def.name))
+        .cloned()
+        .collect();
+    if actually_used_classes.contains("stub") && !class_defs.iter().any(|d| d.name == "stub") {
+        used_class_defs.push(ClassDefinition {
+            name: "stub".to_string(),
+            properties: STUB_STYLE.to_string(),
+        });
+    }
+    used_class_defs.sort_by(|a, b| a.name.cmp(&b.name));
 
-    while let Some(line) = lines.next() {
-        if line.trim().starts_with("subgraph ") {
-            if let Some(subgraph) = extract_single_subgraph(line, &mut lines) {
-                subgraphs.push(subgraph);
+    if !used_class_defs.is_empty() || !relevant_assignments.is_empty() {
+        output.body.push(Statement::Blank.into());
+    }
+    for def in used_class_defs {
+        output.body.push(
+            Statement::ClassDef {
+                names: vec![def.name],
+                styles: def.properties,
             }
-        }
+            .into(),
+        );
     }
 
-    subgraphs
+    // Sort and write filtered class assignments
+    relevant_assignments.sort_by(|a, b| {
+        a.elements
+            .join(",")
+            .cmp(&b.elements.join(","))
+            .then(a.class_name.cmp(&b.class_name))
+    });
+
+    for assignment in relevant_assignments {
+        output.body.push(
+            Statement::ClassAssign {
+                nodes: assignment.elements,
+                class: assignment.class_name,
+            }
+            .into(),
+        );
+    }
 }
 
-fn extract_single_subgraph<'a, I>(
-    first_line: &str,
-    lines: &mut std::iter::Peekable<I>,
-) -> Option<Subgraph>
-where
-    I: Iterator<Item = &'a str>,
-{
-    let mut content = String::new();
-    let mut all_nodes = HashSet::new();
-    let mut depth = 1;
-    let name_re = Regex::new(r#"subgraph\s+(?:"([^"]+)"|([^\s\[]+))"#).unwrap();
-    let node_re = Regex::new(r"\[([^\]]+)\]").unwrap();
-
-    // Extract current subgraph name
-    let name = name_re
-        .captures(first_line)
-        .and_then(|caps| {
-            caps.get(1)
-                .or_else(|| caps.get(2))
-                .map(|m| m.as_str().to_string())
+/// Appends the `style` lines of the nodes and subgraphs the part owns that
+/// its body does not already hold.
+fn add_styles(output: &mut Diagram, part: &Part, node_styles: &[(&String, &String)]) {
+    let part_body = Diagram {
+        body: part.body.clone(),
+        ..Diagram::new(output.kind.clone(), None)
+    };
+    let subgraphs: HashSet<&str> = part_body
+        .all_lines()
+        .into_iter()
+        .filter_map(|line| match &line.statement {
+            Statement::Subgraph(subgraph) => Some(subgraph.id.as_str()),
+            _ => None,
         })
-        .unwrap_or_else(|| "unnamed".to_string());
-
-    // Add the main subgraph name to nodes
-    all_nodes.insert(name.clone());
-    content.push_str(first_line);
-    content.push('\n');
-
-    // Process content until matching end
-    while let Some(line) = lines.next() {
-        let trimmed = line.trim();
-
-        if trimmed.starts_with("subgraph ") {
-            depth += 1;
-            // Extract and add nested subgraph name
-            if let Some(caps) = name_re.captures(trimmed) {
-                if let Some(nested_name) = caps.get(1).or_else(|| caps.get(2)) {
-                    all_nodes.insert(nested_name.as_str().to_string());
-                }
-            }
-        } else if trimmed == "end" {
-            depth -= 1;
-            if depth == 0 {
-                content.push_str(line);
-                content.push('\n');
-                break;
-            }
+        .collect();
+    let present: Vec<Statement> = output
+        .all_lines()
+        .into_iter()
+        .map(|line| line.statement.clone())
+        .collect();
+    for (node, styles) in node_styles {
+        let style = Statement::Style {
+            node: node.to_string(),
+            styles: styles.to_string(),
+        };
+        let owned = part.nodes.contains(&class_id(node)) || subgraphs.contains(node.as_str());
+        if owned && !present.contains(&style) {
+            output.body.push(style.into());
         }
+    }
+}
 
-        // Extract nodes from this line
-        for cap in node_re.captures_iter(trimmed) {
-            all_nodes.insert(cap[1].to_string());
+/// Replaces the `linkStyle` lines copied with the part by ones numbered after
+/// the file's own edges; a style whose edges all stayed elsewhere is dropped.
+fn renumber_link_styles(
+    output: &mut Diagram,
+    source_edges: &[Edge],
+    link_styles: &[(String, String)],
+) {
+    output.retain(|statement| !matches!(statement, Statement::LinkStyle { .. }));
+    let mut used = vec![false; source_edges.len()];
+    let mut position: BTreeMap<usize, usize> = BTreeMap::new();
+    for (j, edge) in output.edges().map(bare_edge).enumerate() {
+        if let Some(i) = (0..source_edges.len()).find(|&i| !used[i] && source_edges[i] == edge) {
+            used[i] = true;
+            position.insert(i, j);
         }
-
-        content.push_str(line);
-        content.push('\n');
     }
-
-    let used_classes = extract_used_classes(&content);
-    Some(Subgraph {
-        name,
-        content,
-        class_assignments: Vec::new(),
-        used_classes,
-        nodes: all_nodes,
-    })
+    for (links, styles) in link_styles {
+        let links = if links == "default" {
+            links.clone()
+        } else {
+            let renumbered: Vec<String> = links
+                .split(',')
+                .filter_map(|link| link.trim().parse::<usize>().ok())
+                .filter_map(|i| position.get(&i))
+                .map(usize::to_string)
+                .collect();
+            if renumbered.is_empty() {
+                continue;
+            }
+            renumbered.join(",")
+        };
+        output.body.push(
+            Statement::LinkStyle {
+                links,
+                styles: styles.clone(),
+            }
+            .into(),
+        );
+    }
 }
 
-fn extract_used_classes(content: &str) -> HashSet<String> {
-    let mut classes = HashSet::new();
-    let inline_style_re = Regex::new(r":::(\w+)").unwrap();
-    let class_assignment_re = Regex::new(r"^class\s+[\w,]+\s+(\w+)").unwrap();
+/// Every `linkStyle` as `(links, styles)`, in source order.
+fn link_styles(diagram: &Diagram) -> Vec<(String, String)> {
+    diagram
+        .all_lines()
+        .into_iter()
+        .filter_map(|line| match &line.statement {
+            Statement::LinkStyle { links, styles } => Some((links.clone(), styles.clone())),
+            _ => None,
+        })
+        .collect()
+}
 
-    for line in content.lines() {
-        let trimmed = line.trim();
+fn extract_all_class_definitions(diagram: &Diagram) -> Vec<ClassDefinition> {
+    diagram
+        .class_defs()
+        .into_iter()
+        .map(|(name, properties)| ClassDefinition {
+            name: name.to_string(),
+            properties: properties.to_string(),
+        })
+        .collect()
+}
 
-        // Extract inline styles
-        for cap in inline_style_re.captures_iter(trimmed) {
-            classes.insert(cap[1].to_string());
-        }
+fn extract_all_class_assignments(diagram: &Diagram) -> Vec<ClassAssignment> {
+    let mut assignments = Vec::new();
 
-        // Extract class names from assignments
-        if let Some(caps) = class_assignment_re.captures(trimmed) {
-            classes.insert(caps[1].to_string());
+    for line in diagram.all_lines() {
+        // Explicit class assignments keep their grouping
+        if let Statement::ClassAssign { nodes, class } = &line.statement {
+            assignments.push(ClassAssignment {
+                elements: nodes.clone(),
+                class_name: class.clone(),
+            });
         }
     }
 
-    classes
+    // Inline `:::` styles stay on their nodes inside the part body
+    assignments
+}
+
+/// Ids of the nodes declared or connected in a block body; subgraph ids and
+/// class generics are not nodes.
+fn body_nodes(kind: &DiagramKind, body: &[Line]) -> BTreeSet<String> {
+    let inner = Diagram {
+        body: body.to_vec(),
+        ..Diagram::new(kind.clone(), None)
+    };
+    inner.nodes().into_keys().map(|id| class_id(&id)).collect()
+}
+
+/// The edge with its endpoints reduced to ids, for nodes declared elsewhere.
+fn bare_edge(edge: &Edge) -> Edge {
+    Edge {
+        from: Node::new(&edge.from.id),
+        to: Node::new(&edge.to.id),
+        ..edge.clone()
+    }
 }
 
-fn clean_content(content: &str) -> String {
-    content
-        .lines()
-        .map(|line| line.replace("&nbsp;", " "))
-        .collect::<Vec<_>>()
-        .join("\n")
+/// A classDiagram class name without generics or label:
+/// `Server~T~["net::Server"]` is `Server`.
+fn class_id(name: &str) -> String {
+    name.split(['~', '['])
+        .next()
+        .unwrap_or(name)
+        .trim()
+        .to_string()
 }
 
 fn sanitize_filename(name: &str) -> String {
@@ -304,3 +758,140 @@ fn sanitize_filename(name: &str) -> String {
         })
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split_files(source: &str, strategy: Strategy) -> Vec<(String, String)> {
+        let diagram = Diagram::parse(source);
+        let (parts, _) = split(&diagram, strategy).unwrap();
+        build_files(&diagram, &parts)
+            .into_iter()
+            .map(|(name, diagram)| (name, diagram.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_split_subgraphs_links_cut_edges() {
+        let files = split_files(
+            "flowchart LR\nsubgraph a.b\nA[Alpha]:::hot --> B\nend\nsubgraph c\nC\nend\nB --> C\nclassDef hot fill:#f00\n",
+            Strategy::Subgraph,
+        );
+        let names: Vec<&str> = files.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, ["a_b", "c", "index"]);
+        assert_eq!(
+            files[0].1,
+            "flowchart LR\n\
+             \x20   subgraph a.b\n\
+             \x20       A[Alpha]:::hot --> B\n\
+             \x20   end\n\
+             \x20   B --> C\n\
+             \x20   C:::stub\n\
+             \x20   click C href \"c.mermaid\"\n\
+             \n\
+             \x20   classDef hot fill:#f00\n\
+             \x20   classDef stub stroke-dasharray:5 5\n"
+        );
+        assert_eq!(
+            files[2].1,
+            "flowchart LR\n\
+             \x20   P1[\"a_b (2 nodes)\"]\n\
+             \x20   P2[\"c (1 node)\"]\n\
+             \x20   P1 -->|1| P2\n\
+             \x20   click P1 href \"a_b.mermaid\"\n\
+             \x20   click P2 href \"c.mermaid\"\n"
+        );
+    }
+
+    #[test]
+    fn test_split_carries_styles_and_link_styles() {
+        let source = "flowchart LR\nsubgraph a\nA --> B\nend\nsubgraph c\nC --> D\nend\nB --> C\nE\n\
+                      style A fill:#f00\nstyle c fill:#eee\nlinkStyle 1,2 stroke:red\nlinkStyle 7 stroke:blue\n";
+        let diagram = Diagram::parse(source);
+        let (parts, warnings) = split(&diagram, Strategy::Subgraph).unwrap();
+        assert_eq!(
+            warnings,
+            [
+                "linkStyle 7 refers to no edge; dropped",
+                "node `E` is outside every subgraph; left out",
+            ]
+        );
+        let files: Vec<String> = build_files(&diagram, &parts)
+            .into_iter()
+            .map(|(_, diagram)| diagram.to_string())
+            .collect();
+        assert_eq!(
+            files[0],
+            "flowchart LR\n\
+             \x20   subgraph a\n\
+             \x20       A --> B\n\
+             \x20   end\n\
+             \x20   B --> C\n\
+             \x20   C:::stub\n\
+             \x20   click C href \"c.mermaid\"\n\
+             \n\
+             \x20   classDef stub stroke-dasharray:5 5\n\
+             \x20   style A fill:#f00\n\
+             \x20   linkStyle 1 stroke:red\n"
+        );
+        assert!(files[1].contains("    style c fill:#eee\n"), "{}", files[1]);
+        assert!(
+            files[1].contains("    linkStyle 0,1 stroke:red\n"),
+            "{}",
+            files[1]
+        );
+    }
+
+    #[test]
+    fn test_split_by_component_and_size() {
+        let source = "flowchart TB\nA --> B\nC --> D\nD --> E\nF\n";
+        let components: Vec<String> = split_files(source, Strategy::Component)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(
+            components,
+            ["component_1", "component_2", "isolated", "index"]
+        );
+
+        let diagram = Diagram::parse(source);
+        let (parts, _) = split(&diagram, Strategy::Size(2)).unwrap();
+        let chunks: Vec<Vec<&str>> = parts
+            .iter()
+            .map(|p| p.nodes.iter().map(String::as_str).collect())
+            .collect();
+        assert_eq!(chunks, [vec!["A", "B"], vec!["C", "D"], vec!["E", "F"]]);
+        let files = build_files(&diagram, &parts);
+        assert!(files[1].1.to_string().contains("D --> E\n"));
+        assert!(files[1]
+            .1
+            .to_string()
+            .contains("click E href \"part_3.mermaid\"\n"));
+    }
+
+    #[test]
+    fn test_split_class_diagram_by_namespace() {
+        let files = split_files(
+            "classDiagram\nnamespace net {\nclass Server\n}\nnamespace db {\nclass Pool\n}\nServer --> Pool\nclass Config\n",
+            Strategy::Namespace,
+        );
+        let names: Vec<&str> = files.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, ["net", "db", "global", "index"]);
+        assert_eq!(
+            files[0].1,
+            "classDiagram\n\
+             \x20   namespace net {\n\
+             \x20       class Server\n\
+             \x20   }\n\
+             \x20   Server --> Pool\n\
+             \x20   click Pool href \"db.mermaid\"\n"
+        );
+    }
+
+    #[test]
+    fn test_split_refuses_mismatched_strategy() {
+        let diagram = Diagram::parse("classDiagram\nA --> B\n");
+        assert!(split(&diagram, Strategy::Component).is_err());
+    }
+}
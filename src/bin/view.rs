@@ -1,14 +1,11 @@
+use cargo_invoke::common::open_in_browser;
+use cargo_invoke::config::Config;
+use cargo_invoke::theme::Theme;
 use std::error::Error;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
-#[derive(Debug, PartialEq)]
-enum DiagramType {
-    Default,
-    Class,
-}
-
 /// Checks if mmdc is installed on the system
 fn check_mmdc_installed() -> bool {
     Command::new("mmdc").arg("--version").output().is_ok()
@@ -33,8 +30,9 @@ fn install_mmdc() -> Result<(), Box<dyn Error>> {
 fn convert_to_format(
     input_path: &Path,
     input_file: &String,
-    diagram_type: DiagramType,
+    theme: Option<&Theme>,
     output_format: &str,
+    open: bool,
 ) -> Result<(), Box<dyn Error>> {
     if !input_path.exists() {
         return Err(format!("Input file {:?} does not exist", input_path).into());
@@ -44,15 +42,10 @@ fn convert_to_format(
         return Err("Input file must be a .mermaid file".into());
     }
 
+    // Without a theme the diagram keeps any directive it was generated with
     let mut content = fs::read_to_string(input_path)?;
-
-    match diagram_type {
-        DiagramType::Class => {
-            content.push_str("\n%%{init: {'theme': 'base', 'themeVariables': { 'primaryColor': '#fff4dd', 'fontSize': '16px' }}}%%\n");
-        }
-        DiagramType::Default => {
-            content.push_str("\n%%{init: {'theme': 'default' }}%%\n");
-        }
+    if let Some(theme) = theme {
+        content = theme.apply_to_source(&content);
     }
 
     let temp_path = input_path.with_extension("temp.mmd");
@@ -71,10 +64,11 @@ fn convert_to_format(
         output_path.to_str().unwrap(),
     ]);
 
-    let config_path = if diagram_type == DiagramType::Default {
-        PathBuf::from("assets/default.config.json")
-    } else {
+    // The themed config is the one `-C` used before it became `--theme light`
+    let config_path = if theme.is_some() {
         PathBuf::from("assets/mermaid.config.json")
+    } else {
+        PathBuf::from("assets/default.config.json")
     };
 
     if config_path.exists() {
@@ -100,25 +94,27 @@ fn convert_to_format(
         input_path, output_path
     );
 
-    #[cfg(target_os = "macos")]
-    Command::new("open")
-        .arg("-a")
-        .arg("Google Chrome")
-        .arg(&output_path)
-        .spawn()?;
+    if open {
+        if let Err(e) = open_in_browser(&output_path) {
+            eprintln!("Warning: {}", e);
+        }
+    }
 
     Ok(())
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let args: Vec<String> = std::env::args().collect();
-    if args.len() < 2 || args.len() > 4 {
+    let mut args: Vec<String> = std::env::args().collect();
+    let mut theme = Theme::from_args(&mut args)?;
+    if args.len() < 2 || args.len() > 5 {
         eprintln!(
-            "Usage: {} <input_file_without_extension> [-C] [--png]",
+            "Usage: {} <input_file_without_extension> [--theme <NAME>] [-C] [--png] [--no-open]",
             args[0]
         );
-        eprintln!("  -C      Generate class diagram with specific styling");
-        eprintln!("  --png   Output as PNG instead of SVG");
+        eprintln!("  --theme    Render with a built-in or configured theme");
+        eprintln!("  -C         Shorthand for --theme light");
+        eprintln!("  --png      Output as PNG instead of SVG");
+        eprintln!("  --no-open  Do not open the result in a browser");
         std::process::exit(1);
     }
 
@@ -127,11 +123,9 @@ fn main() -> Result<(), Box<dyn Error>> {
         install_mmdc()?;
     }
 
-    let diagram_type = if args.contains(&"-C".to_string()) {
-        DiagramType::Class
-    } else {
-        DiagramType::Default
-    };
+    if args.contains(&"-C".to_string()) && theme.is_none() {
+        theme = Some(Theme::named("light", &Config::load()?.themes)?);
+    }
 
     let output_format = if args.contains(&"--png".to_string()) {
         "png"
@@ -139,17 +133,21 @@ fn main() -> Result<(), Box<dyn Error>> {
         "svg"
     };
 
-    let input_file = if args[1] == "-C" || args[1] == "--png" {
-        &args[2]
-    } else {
-        &args[1]
+    let open = !args.contains(&"--no-open".to_string());
+
+    let input_file = match args[1..].iter().find(|a| !a.starts_with('-')) {
+        Some(file) => file,
+        None => {
+            eprintln!("Error: missing input file");
+            std::process::exit(1);
+        }
     };
 
     let mut input_path = PathBuf::from("diagrams");
     input_path.push(input_file);
     input_path.set_extension("mermaid");
 
-    match convert_to_format(&input_path, input_file, diagram_type, output_format) {
+    match convert_to_format(&input_path, input_file, theme.as_ref(), output_format, open) {
         Ok(_) => Ok(()),
         Err(e) => {
             eprintln!("Error: {}", e);
@@ -1,14 +1,11 @@
+use cargo_invoke::common::open_in_browser;
+use cargo_invoke::config::Config;
+use cargo_invoke::theme::Theme;
 use std::error::Error;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
-#[derive(Debug, PartialEq)]
-enum DiagramType {
-    Default,
-    Class,
-}
-
 /// Checks if mmdc is installed on the system
 fn check_mmdc_installed() -> bool {
     Command::new("mmdc").arg("--version").output().is_ok()
@@ -33,7 +30,7 @@ fn install_mmdc() -> Result<(), Box<dyn Error>> {
 fn convert_to_svg(
     input_path: &Path,
     input_file: &String,
-    diagram_type: DiagramType,
+    theme: Option<&Theme>,
 ) -> Result<(), Box<dyn Error>> {
     if !input_path.exists() {
         return Err(format!("Input file {:?} does not exist", input_path).into());
@@ -43,16 +40,10 @@ fn convert_to_svg(
         return Err("Input file must be a .mermaid file".into());
     }
 
+    // Without a theme the diagram keeps any directive it was generated with
     let mut content = fs::read_to_string(input_path)?;
-
-    // Add specific styling based on diagram type
-    match diagram_type {
-        DiagramType::Class => {
-            content.push_str("\n%%{init: {'theme': 'base', 'themeVariables': { 'primaryColor': '#fff4dd', 'fontSize': '16px' }}}%%\n");
-        }
-        DiagramType::Default => {
-            content.push_str("\n%%{init: {'theme': 'default' }}%%\n");
-        }
+    if let Some(theme) = theme {
+        content = theme.apply_to_source(&content);
     }
 
     let temp_path = input_path.with_extension("temp.mmd");
@@ -71,11 +62,11 @@ fn convert_to_svg(
         output_path.to_str().unwrap(),
     ]);
 
-    // Add configuration file argument only if it exists
-    let config_path = if diagram_type == DiagramType::Default {
-        PathBuf::from("assets/default.config.json")
-    } else {
+    // The themed config is the one `-C` used before it became `--theme light`
+    let config_path = if theme.is_some() {
         PathBuf::from("assets/mermaid.config.json")
+    } else {
+        PathBuf::from("assets/default.config.json")
     };
 
     if config_path.exists() {
@@ -101,22 +92,24 @@ fn convert_to_svg(
         input_path, output_path
     );
 
-    #[cfg(target_os = "macos")]
-    Command::new("open")
-        .arg("-a")
-        .arg("Google Chrome")
-        .arg(&output_path)
-        .spawn()?;
+    if let Err(e) = open_in_browser(&output_path) {
+        eprintln!("Warning: {}", e);
+    }
 
     Ok(())
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
     // Get input file from command line arguments
-    let args: Vec<String> = std::env::args().collect();
+    let mut args: Vec<String> = std::env::args().collect();
+    let mut theme = Theme::from_args(&mut args)?;
     if args.len() < 2 || args.len() > 3 {
-        eprintln!("Usage: {} <input_file_without_extension> [-C]", args[0]);
-        eprintln!("  -C    Generate class diagram with specific styling");
+        eprintln!(
+            "Usage: {} <input_file_without_extension> [--theme <NAME>] [-C]",
+            args[0]
+        );
+        eprintln!("  --theme  Render with a built-in or configured theme");
+        eprintln!("  -C       Shorthand for --theme light");
         std::process::exit(1);
     }
 
@@ -126,12 +119,9 @@ fn main() -> Result<(), Box<dyn Error>> {
         install_mmdc()?;
     }
 
-    // Determine if this is a class diagram
-    let diagram_type = if args.contains(&"-C".to_string()) {
-        DiagramType::Class
-    } else {
-        DiagramType::Default
-    };
+    if args.contains(&"-C".to_string()) && theme.is_none() {
+        theme = Some(Theme::named("light", &Config::load()?.themes)?);
+    }
 
     // Get the input file name (ignoring the -C flag if present)
     let input_file = if args[1] == "-C" { &args[2] } else { &args[1] };
@@ -142,7 +132,7 @@ fn main() -> Result<(), Box<dyn Error>> {
     input_path.set_extension("mermaid");
 
     // Convert the file
-    match convert_to_svg(&input_path, input_file, diagram_type) {
+    match convert_to_svg(&input_path, input_file, theme.as_ref()) {
         Ok(_) => Ok(()),
         Err(e) => {
             eprintln!("Error: {}", e);
@@ -0,0 +1,383 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+use std::thread;
+use std::time::{Duration, SystemTime};
+use walkdir::WalkDir;
+
+/// One generator invocation within a pipeline. A step is re-run when any
+/// changed path starts with one of its `inputs`; the paths in `outputs` then
+/// count as changed for the steps after it.
+struct Step {
+    args: &'static [&'static str],
+    inputs: &'static [&'static str],
+    outputs: &'static [&'static str],
+}
+
+struct Pipeline {
+    name: &'static str,
+    steps: &'static [Step],
+}
+
+const ARCHITECTURE: Step = Step {
+    args: &["architecture"],
+    inputs: &["src"],
+    outputs: &["architecture"],
+};
+
+const PIPELINES: &[Pipeline] = &[
+    Pipeline {
+        name: "class_diagram",
+        steps: &[
+            Step {
+                args: &["class_diagram"],
+                inputs: &["src/modules"],
+                outputs: &["diagrams/class_diagram.mermaid"],
+            },
+            Step {
+                args: &["view", "class_diagram", "--no-open"],
+                inputs: &["diagrams/class_diagram.mermaid"],
+                outputs: &["visuals/class_diagram.svg"],
+            },
+        ],
+    },
+    Pipeline {
+        name: "connections",
+        steps: &[
+            ARCHITECTURE,
+            Step {
+                args: &["connections"],
+                inputs: &["architecture"],
+                outputs: &["diagrams/connections.mermaid"],
+            },
+            Step {
+                args: &["view", "connections", "--no-open"],
+                inputs: &["diagrams/connections.mermaid"],
+                outputs: &["visuals/connections.svg"],
+            },
+        ],
+    },
+    Pipeline {
+        name: "docs",
+        steps: &[Step {
+            args: &["docs"],
+            inputs: &["src"],
+            outputs: &["docs"],
+        }],
+    },
+];
+
+struct Options {
+    pipelines: Vec<&'static Pipeline>,
+    watch_dir: PathBuf,
+    interval: Duration,
+    debounce: Duration,
+    html: bool,
+}
+
+fn usage(program: &str) -> String {
+    let names: Vec<&str> = PIPELINES.iter().map(|p| p.name).collect();
+    format!(
+        "Usage: {} [PIPELINE|all] [--dir <DIR>] [--interval <MS>] [--debounce <MS>] [--html]\n  Pipelines: {}",
+        program,
+        names.join(", ")
+    )
+}
+
+fn parse_args(args: &[String]) -> Result<Options, Box<dyn Error>> {
+    let mut options = Options {
+        pipelines: Vec::new(),
+        watch_dir: PathBuf::from("src"),
+        interval: Duration::from_millis(500),
+        debounce: Duration::from_millis(300),
+        html: false,
+    };
+
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--dir" => {
+                i += 1;
+                options.watch_dir = PathBuf::from(args.get(i).ok_or("--dir requires a value")?);
+            }
+            "--interval" => {
+                i += 1;
+                let ms: u64 = args.get(i).ok_or("--interval requires a value")?.parse()?;
+                options.interval = Duration::from_millis(ms);
+            }
+            "--debounce" => {
+                i += 1;
+                let ms: u64 = args.get(i).ok_or("--debounce requires a value")?.parse()?;
+                options.debounce = Duration::from_millis(ms);
+            }
+            "--html" => options.html = true,
+            "all" => options.pipelines.extend(PIPELINES.iter()),
+            name => match PIPELINES.iter().find(|p| p.name == name) {
+                Some(pipeline) => options.pipelines.push(pipeline),
+                None => {
+                    return Err(format!("Unknown pipeline '{}'\n{}", name, usage(&args[0])).into())
+                }
+            },
+        }
+        i += 1;
+    }
+
+    if options.pipelines.is_empty() {
+        options.pipelines.push(&PIPELINES[0]);
+    }
+    Ok(options)
+}
+
+/// Modification times of every file below `dir`.
+fn snapshot(dir: &Path) -> BTreeMap<PathBuf, SystemTime> {
+    WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| {
+            let modified = e.metadata().ok()?.modified().ok()?;
+            Some((e.into_path(), modified))
+        })
+        .collect()
+}
+
+/// Paths that were added, removed or modified between two snapshots.
+fn changed_paths(
+    before: &BTreeMap<PathBuf, SystemTime>,
+    after: &BTreeMap<PathBuf, SystemTime>,
+) -> BTreeSet<PathBuf> {
+    let mut changed = BTreeSet::new();
+    for (path, modified) in after {
+        if before.get(path) != Some(modified) {
+            changed.insert(path.clone());
+        }
+    }
+    for path in before.keys() {
+        if !after.contains_key(path) {
+            changed.insert(path.clone());
+        }
+    }
+    changed
+}
+
+/// `path` made absolute against `root`, with `.` and `..` resolved lexically
+/// so removed files, which can't be canonicalized, compare the same way.
+fn absolute(root: &Path, path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in root.join(path).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+/// Whether any changed path lies under one of the step's inputs. Inputs are
+/// relative to `root`, the directory the generators run in; changed paths may
+/// be relative to it or absolute, wherever `--dir` points.
+fn is_affected(step: &Step, changed: &BTreeSet<PathBuf>, root: &Path) -> bool {
+    changed.iter().any(|path| {
+        let path = absolute(root, path);
+        step.inputs
+            .iter()
+            .any(|input| path.starts_with(absolute(root, Path::new(input))))
+    })
+}
+
+/// Runs the steps of a pipeline touched by `changed`, feeding each step's
+/// outputs forward so downstream renderers follow their generators.
+fn run_pipeline(
+    pipeline: &Pipeline,
+    changed: &BTreeSet<PathBuf>,
+    root: &Path,
+) -> Result<bool, Box<dyn Error>> {
+    let mut changed = changed.clone();
+    let mut ran = false;
+
+    for step in pipeline.steps {
+        if !is_affected(step, &changed, root) {
+            continue;
+        }
+        println!("[{}] cargo invoke {}", pipeline.name, step.args.join(" "));
+        let status = Command::new("cargo")
+            .arg("invoke")
+            .args(step.args)
+            .status()?;
+        if !status.success() {
+            eprintln!(
+                "[{}] '{}' failed with exit code {:?}; skipping the rest of the pipeline",
+                pipeline.name,
+                step.args.join(" "),
+                status.code()
+            );
+            return Ok(ran);
+        }
+        ran = true;
+        changed.extend(step.outputs.iter().map(PathBuf::from));
+    }
+
+    Ok(ran)
+}
+
+/// Writes `visuals/index.html` showing every rendered SVG on one page.
+fn write_html_report() -> Result<(), Box<dyn Error>> {
+    let visuals = Path::new("visuals");
+    let mut svgs: Vec<String> = match fs::read_dir(visuals) {
+        Ok(entries) => entries
+            .filter_map(Result::ok)
+            .map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|ext| ext == "svg"))
+            .filter_map(|p| p.file_name().map(|n| n.to_string_lossy().into_owned()))
+            .collect(),
+        Err(_) => return Ok(()),
+    };
+    svgs.sort();
+
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Diagrams</title>\n</head>\n<body>\n",
+    );
+    for svg in &svgs {
+        html.push_str(&format!(
+            "<section>\n<h2>{0}</h2>\n<img src=\"{0}\" alt=\"{0}\">\n</section>\n",
+            svg
+        ));
+    }
+    html.push_str("</body>\n</html>\n");
+
+    fs::write(visuals.join("index.html"), html)?;
+    println!("Updated {}", visuals.join("index.html").display());
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.iter().any(|a| a == "--help" || a == "-h") {
+        println!("{}", usage(&args[0]));
+        return Ok(());
+    }
+    let options = parse_args(&args)?;
+
+    if !options.watch_dir.exists() {
+        return Err(format!("Directory not found: {}", options.watch_dir.display()).into());
+    }
+
+    // Generators run in the current directory, so step inputs are relative to it.
+    let root = std::env::current_dir()?.canonicalize()?;
+
+    let names: Vec<&str> = options.pipelines.iter().map(|p| p.name).collect();
+    println!(
+        "Watching {} for pipelines: {} (Ctrl-C to stop)",
+        options.watch_dir.display(),
+        names.join(", ")
+    );
+
+    // Initial build so the outputs exist before the first edit.
+    let mut state = snapshot(&options.watch_dir);
+    let everything: BTreeSet<PathBuf> = state.keys().cloned().collect();
+    for pipeline in &options.pipelines {
+        run_pipeline(pipeline, &everything, &root)?;
+    }
+    if options.html {
+        write_html_report()?;
+    }
+
+    loop {
+        thread::sleep(options.interval);
+        let current = snapshot(&options.watch_dir);
+        if current == state {
+            continue;
+        }
+
+        // Debounce: wait until the tree stops changing before rebuilding.
+        let mut settled = current;
+        loop {
+            thread::sleep(options.debounce);
+            let next = snapshot(&options.watch_dir);
+            if next == settled {
+                break;
+            }
+            settled = next;
+        }
+
+        let changed = changed_paths(&state, &settled);
+        state = settled;
+        for path in &changed {
+            println!("Changed: {}", path.display());
+        }
+
+        let mut any_ran = false;
+        for pipeline in &options.pipelines {
+            any_ran |= run_pipeline(pipeline, &changed, &root)?;
+        }
+        if any_ran && options.html {
+            write_html_report()?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(list: &[&str]) -> BTreeSet<PathBuf> {
+        list.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn test_changed_paths() {
+        let t0 = SystemTime::UNIX_EPOCH;
+        let t1 = t0 + Duration::from_secs(1);
+        let before: BTreeMap<PathBuf, SystemTime> = [
+            ("src/kept.rs", t0),
+            ("src/edited.rs", t0),
+            ("src/removed.rs", t0),
+        ]
+        .into_iter()
+        .map(|(p, t)| (PathBuf::from(p), t))
+        .collect();
+        let after: BTreeMap<PathBuf, SystemTime> = [
+            ("src/kept.rs", t0),
+            ("src/edited.rs", t1),
+            ("src/added.rs", t1),
+        ]
+        .into_iter()
+        .map(|(p, t)| (PathBuf::from(p), t))
+        .collect();
+
+        assert_eq!(
+            changed_paths(&before, &after),
+            paths(&["src/added.rs", "src/edited.rs", "src/removed.rs"])
+        );
+        assert!(changed_paths(&after, &after).is_empty());
+    }
+
+    #[test]
+    fn test_is_affected_resolves_paths_against_the_root() {
+        let root = Path::new("/work/shop");
+        let step = &PIPELINES[0].steps[0];
+        assert_eq!(step.inputs, ["src/modules"]);
+
+        for hit in [
+            "src/modules/orders.rs",
+            "./src/modules/orders.rs",
+            "/work/shop/src/modules/orders.rs",
+            "src/../src/modules/orders.rs",
+        ] {
+            assert!(is_affected(step, &paths(&[hit]), root), "{}", hit);
+        }
+        for miss in [
+            "src/lib.rs",
+            "src/modules_old/orders.rs",
+            "/elsewhere/src/modules/orders.rs",
+            "../src/modules/orders.rs",
+        ] {
+            assert!(!is_affected(step, &paths(&[miss]), root), "{}", miss);
+        }
+    }
+}
@@ -0,0 +1,244 @@
+//! sRGB colors and the accessibility checks run on them: WCAG 2.x contrast
+//! ratios, color-vision deficiency simulation and CIELAB color differences.
+//!
+//! Deficiencies are simulated with the full-severity matrices from Machado,
+//! Oliveira and Fernandes (2009), applied in linear RGB.
+
+use std::fmt;
+
+/// WCAG AA minimum contrast for normal text.
+pub const AA_TEXT: f64 = 4.5;
+/// WCAG AAA minimum contrast for normal text.
+pub const AAA_TEXT: f64 = 7.0;
+/// WCAG AA minimum contrast for graphical objects such as node borders.
+pub const AA_GRAPHICS: f64 = 3.0;
+/// The CIE76 difference below which two fills are hard to tell apart at a glance.
+pub const MIN_DELTA_E: f64 = 10.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl Rgb {
+    pub const BLACK: Rgb = Rgb(0, 0, 0);
+    pub const WHITE: Rgb = Rgb(255, 255, 255);
+
+    /// Parses `#rrggbb` or `#rgb`.
+    pub fn parse(hex: &str) -> Option<Rgb> {
+        let digits = hex.trim().strip_prefix('#')?;
+        if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        let channel = |s: &str| u8::from_str_radix(s, 16).ok();
+        match digits.len() {
+            6 => Some(Rgb(
+                channel(&digits[0..2])?,
+                channel(&digits[2..4])?,
+                channel(&digits[4..6])?,
+            )),
+            3 => {
+                let short = |i: usize| channel(&digits[i..i + 1]).map(|c| c * 17);
+                Some(Rgb(short(0)?, short(1)?, short(2)?))
+            }
+            _ => None,
+        }
+    }
+
+    /// WCAG relative luminance, from 0 for black to 1 for white.
+    pub fn luminance(self) -> f64 {
+        let [r, g, b] = self.linear();
+        0.2126 * r + 0.7152 * g + 0.0722 * b
+    }
+
+    /// The WCAG contrast ratio with `other`, from 1 to 21.
+    pub fn contrast(self, other: Rgb) -> f64 {
+        let (a, b) = (self.luminance(), other.luminance());
+        (a.max(b) + 0.05) / (a.min(b) + 0.05)
+    }
+
+    /// How the color looks with `deficiency`.
+    pub fn simulate(self, deficiency: Deficiency) -> Rgb {
+        let m = deficiency.matrix();
+        let c = self.linear();
+        let row = |r: [f64; 3]| r[0] * c[0] + r[1] * c[1] + r[2] * c[2];
+        Rgb::from_linear([row(m[0]), row(m[1]), row(m[2])])
+    }
+
+    /// The CIE76 difference with `other`: about 2.3 is just noticeable.
+    pub fn delta_e(self, other: Rgb) -> f64 {
+        let (a, b) = (self.lab(), other.lab());
+        ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+    }
+
+    /// The color closest to this one, mixed towards black or white, that has
+    /// at least `ratio` contrast with `background`. `None` when neither black
+    /// nor white has enough.
+    pub fn nearest_with_contrast(self, background: Rgb, ratio: f64) -> Option<Rgb> {
+        if self.contrast(background) >= ratio {
+            return Some(self);
+        }
+        [Rgb::BLACK, Rgb::WHITE]
+            .into_iter()
+            .filter_map(|target| {
+                (1..=100)
+                    .map(|step| self.mix(target, step as f64 / 100.0))
+                    .find(|c| c.contrast(background) >= ratio)
+            })
+            .min_by(|a, b| self.delta_e(*a).total_cmp(&self.delta_e(*b)))
+    }
+
+    /// `amount` of the way from this color to `other`, in sRGB.
+    fn mix(self, other: Rgb, amount: f64) -> Rgb {
+        let channel = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * amount).round() as u8;
+        Rgb(
+            channel(self.0, other.0),
+            channel(self.1, other.1),
+            channel(self.2, other.2),
+        )
+    }
+
+    fn linear(self) -> [f64; 3] {
+        let channel = |c: u8| {
+            let c = c as f64 / 255.0;
+            if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        [channel(self.0), channel(self.1), channel(self.2)]
+    }
+
+    fn from_linear(c: [f64; 3]) -> Rgb {
+        let channel = |c: f64| {
+            let c = c.clamp(0.0, 1.0);
+            let c = if c <= 0.0031308 {
+                c * 12.92
+            } else {
+                1.055 * c.powf(1.0 / 2.4) - 0.055
+            };
+            (c * 255.0).round() as u8
+        };
+        Rgb(channel(c[0]), channel(c[1]), channel(c[2]))
+    }
+
+    /// CIELAB under the D65 white point.
+    fn lab(self) -> [f64; 3] {
+        let [r, g, b] = self.linear();
+        let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+        let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+        let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+        let f = |t: f64| {
+            if t > 0.008856 {
+                t.cbrt()
+            } else {
+                7.787 * t + 16.0 / 116.0
+            }
+        };
+        let (fx, fy, fz) = (f(x), f(y), f(z));
+        [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+    }
+}
+
+impl fmt::Display for Rgb {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{:02X}{:02X}{:02X}", self.0, self.1, self.2)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Deficiency {
+    /// No working long-wavelength (red) cones.
+    Protanopia,
+    /// No working medium-wavelength (green) cones.
+    Deuteranopia,
+    /// No working short-wavelength (blue) cones.
+    Tritanopia,
+}
+
+impl Deficiency {
+    pub const ALL: [Deficiency; 3] = [
+        Deficiency::Protanopia,
+        Deficiency::Deuteranopia,
+        Deficiency::Tritanopia,
+    ];
+
+    fn matrix(self) -> [[f64; 3]; 3] {
+        match self {
+            Deficiency::Protanopia => [
+                [0.152286, 1.052583, -0.204868],
+                [0.114503, 0.786281, 0.099216],
+                [-0.003882, -0.048116, 1.051998],
+            ],
+            Deficiency::Deuteranopia => [
+                [0.367322, 0.860646, -0.227968],
+                [0.280085, 0.672501, 0.047413],
+                [-0.011820, 0.042940, 0.968881],
+            ],
+            Deficiency::Tritanopia => [
+                [1.255528, -0.076749, -0.178779],
+                [-0.078411, 0.930809, 0.147602],
+                [0.004733, 0.691367, 0.303900],
+            ],
+        }
+    }
+}
+
+impl fmt::Display for Deficiency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Deficiency::Protanopia => "protanopia",
+            Deficiency::Deuteranopia => "deuteranopia",
+            Deficiency::Tritanopia => "tritanopia",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_display() {
+        assert_eq!(Rgb::parse("#ffa500"), Some(Rgb(255, 165, 0)));
+        assert_eq!(Rgb::parse("#F0a"), Some(Rgb(255, 0, 170)));
+        assert_eq!(Rgb::parse("red"), None);
+        assert_eq!(Rgb::parse("#12345"), None);
+        assert_eq!(Rgb(255, 165, 0).to_string(), "#FFA500");
+    }
+
+    #[test]
+    fn test_contrast_matches_wcag() {
+        assert!((Rgb::BLACK.contrast(Rgb::WHITE) - 21.0).abs() < 1e-9);
+        assert!((Rgb::WHITE.contrast(Rgb::WHITE) - 1.0).abs() < 1e-9);
+        // #767676 is the lightest grey passing AA on white
+        let grey = Rgb(0x76, 0x76, 0x76);
+        assert!(grey.contrast(Rgb::WHITE) >= AA_TEXT);
+        assert!(Rgb(0x77, 0x77, 0x77).contrast(Rgb::WHITE) < AA_TEXT);
+    }
+
+    #[test]
+    fn test_red_and_green_look_alike_without_green_cones() {
+        let (red, green) = (Rgb(0xCC, 0x66, 0x66), Rgb(0x66, 0x99, 0x66));
+        assert!(red.delta_e(green) > MIN_DELTA_E);
+        let (r, g) = (
+            red.simulate(Deficiency::Deuteranopia),
+            green.simulate(Deficiency::Deuteranopia),
+        );
+        assert!(r.delta_e(g) < MIN_DELTA_E);
+        assert_eq!(Rgb::WHITE.simulate(Deficiency::Protanopia), Rgb::WHITE);
+    }
+
+    #[test]
+    fn test_nearest_with_contrast() {
+        let orange = Rgb(255, 165, 0);
+        let text = Rgb::WHITE.nearest_with_contrast(orange, AA_TEXT).unwrap();
+        assert!(text.contrast(orange) >= AA_TEXT);
+        assert_eq!(text.0, text.1);
+        assert_eq!(
+            Rgb::BLACK.nearest_with_contrast(orange, AA_TEXT),
+            Some(Rgb::BLACK)
+        );
+        let grey = Rgb(0x80, 0x80, 0x80);
+        assert_eq!(grey.nearest_with_contrast(grey, 21.0), None);
+    }
+}
@@ -1 +1,26 @@
+//! Small helpers shared by the command binaries.
 
+use crate::Result;
+use anyhow::anyhow;
+use std::ffi::OsStr;
+use std::process::Command;
+
+/// Opens a file or URL with the platform's default handler: `open` on macOS,
+/// `xdg-open` on Linux and the BSDs, `start` on Windows.
+pub fn open_in_browser<S: AsRef<OsStr>>(target: S) -> Result<()> {
+    let target = target.as_ref();
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(target_os = "windows") {
+        let mut c = Command::new("cmd");
+        c.args(["/C", "start", ""]);
+        c
+    } else {
+        Command::new("xdg-open")
+    };
+    command
+        .arg(target)
+        .spawn()
+        .map_err(|e| anyhow!("Failed to open {:?}: {}", target, e))?;
+    Ok(())
+}
@@ -1 +1,53 @@
-pub struct Config;
+//! Project settings read from `invoke.toml` in the working directory.
+//!
+//! ```toml
+//! theme = "dark"
+//!
+//! [filter]
+//! exclude = ["crate::generated::*", "*Builder"]
+//! pub-only = true
+//! no-tests = true
+//!
+//! [themes.dark]
+//! extends = "dark"
+//! variables = { fontFamily = "Inter" }
+//! ```
+
+use crate::filter::Filter;
+use crate::theme::Theme;
+use crate::Result;
+use anyhow::Context;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+pub const CONFIG_FILE: &str = "invoke.toml";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Defaults for the filters every generator accepts on the command line.
+    pub filter: Filter,
+    /// Theme for every generated diagram, unless `--theme` names another.
+    pub theme: Option<String>,
+    /// Themes added to, or replacing, the built-in ones. See [`crate::theme`].
+    pub themes: BTreeMap<String, Theme>,
+}
+
+impl Config {
+    /// Loads [`CONFIG_FILE`] from the working directory, or the defaults when
+    /// there is none.
+    pub fn load() -> Result<Config> {
+        Config::load_from(Path::new(CONFIG_FILE))
+    }
+
+    pub fn load_from(path: &Path) -> Result<Config> {
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        toml::from_str(&content).with_context(|| format!("Invalid config in {}", path.display()))
+    }
+}
@@ -0,0 +1,349 @@
+//! Include/exclude filters shared by the diagram generators.
+//!
+//! Patterns are globs (`*` matches any run of characters, `?` a single one)
+//! tested against a type's name, its module path (`crate::net::server`, with or
+//! without the leading `crate::`) and the two joined (`crate::net::server::Server`).
+
+use crate::config::Config;
+use crate::model::{is_cfg_test, CrateModel, Vis};
+use crate::Result;
+use anyhow::anyhow;
+use serde::Deserialize;
+use syn::{ImplItem, Item, Visibility};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Filter {
+    /// When non-empty, only items matching one of these patterns are kept.
+    pub include: Vec<String>,
+    /// Items matching any of these patterns are dropped.
+    pub exclude: Vec<String>,
+    /// Keep only `pub` types, fields and methods.
+    pub pub_only: bool,
+    /// Skip modules declared under `#[cfg(test)]`.
+    pub no_tests: bool,
+    /// Drop relationships to standard library types such as `Option` or `Vec`.
+    pub no_std_types: bool,
+}
+
+/// Standard library and primitive types left out by `--no-std-types`.
+const STD_TYPES: &[&str] = &[
+    "Option",
+    "Result",
+    "Box",
+    "Rc",
+    "Arc",
+    "Weak",
+    "Cell",
+    "RefCell",
+    "Mutex",
+    "RwLock",
+    "Vec",
+    "VecDeque",
+    "LinkedList",
+    "HashMap",
+    "HashSet",
+    "BTreeMap",
+    "BTreeSet",
+    "BinaryHeap",
+    "String",
+    "str",
+    "Cow",
+    "PathBuf",
+    "Path",
+    "OsString",
+    "OsStr",
+    "Duration",
+    "Instant",
+    "SystemTime",
+    "PhantomData",
+    "Pin",
+    "bool",
+    "char",
+    "u8",
+    "u16",
+    "u32",
+    "u64",
+    "u128",
+    "usize",
+    "i8",
+    "i16",
+    "i32",
+    "i64",
+    "i128",
+    "isize",
+    "f32",
+    "f64",
+];
+
+impl Filter {
+    /// Starts from the `[filter]` table of `invoke.toml` and applies
+    /// `--include <GLOB>`, `--exclude <GLOB>`, `--pub-only`, `--no-tests` and
+    /// `--no-std-types` on top, removing them from `args`. Include and exclude
+    /// may be repeated or given comma-separated.
+    pub fn from_args(args: &mut Vec<String>) -> Result<Filter> {
+        let mut filter = Config::load()?.filter;
+
+        let mut i = 0;
+        while i < args.len() {
+            let taken = match args[i].as_str() {
+                "--include" | "--exclude" => {
+                    let value = args
+                        .get(i + 1)
+                        .ok_or_else(|| anyhow!("{} requires a pattern", args[i]))?;
+                    let patterns = value
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|p| !p.is_empty())
+                        .map(String::from);
+                    if args[i] == "--include" {
+                        filter.include.extend(patterns);
+                    } else {
+                        filter.exclude.extend(patterns);
+                    }
+                    2
+                }
+                "--pub-only" => {
+                    filter.pub_only = true;
+                    1
+                }
+                "--no-tests" => {
+                    filter.no_tests = true;
+                    1
+                }
+                "--no-std-types" => {
+                    filter.no_std_types = true;
+                    1
+                }
+                _ => {
+                    i += 1;
+                    continue;
+                }
+            };
+            args.drain(i..i + taken);
+        }
+
+        Ok(filter)
+    }
+
+    /// Whether a module survives `--exclude` and `--no-tests`. Includes are
+    /// checked per type, so a module is never dropped for not matching one.
+    pub fn allows_module(&self, module: &str, is_test: bool) -> bool {
+        if self.no_tests && is_test {
+            return false;
+        }
+        !self.exclude.iter().any(|p| matches_path(p, module))
+    }
+
+    /// Whether a type declared in `module` (empty when unknown) is kept.
+    pub fn allows_type(&self, module: &str, name: &str, is_public: bool) -> bool {
+        if self.pub_only && !is_public {
+            return false;
+        }
+        let candidates = type_candidates(module, name);
+        let matches = |pattern: &String| candidates.iter().any(|c| glob_match(pattern, c));
+        if self.exclude.iter().any(matches) {
+            return false;
+        }
+        self.include.is_empty() || self.include.iter().any(matches)
+    }
+
+    /// Whether a field or method with the given visibility is kept.
+    pub fn allows_member(&self, vis: Vis) -> bool {
+        !self.pub_only || vis == Vis::Public
+    }
+
+    /// Whether an edge to the type named `name` is kept.
+    pub fn allows_reference(&self, name: &str) -> bool {
+        !(self.no_std_types && is_std_type(name))
+    }
+
+    /// Drops the modules, types, fields and methods the filter rejects.
+    pub fn apply(&self, model: &mut CrateModel) {
+        model
+            .modules
+            .retain(|m| self.allows_module(&m.path, m.is_test));
+        for module in &mut model.modules {
+            let path = module.path.clone();
+            module
+                .types
+                .retain(|t| self.allows_type(&path, &t.name, t.vis == Vis::Public));
+            module.functions.retain(|f| self.allows_member(f.vis));
+            for ty in &mut module.types {
+                ty.fields.retain(|f| self.allows_member(f.vis));
+                ty.methods.retain(|m| self.allows_member(m.vis));
+            }
+        }
+        if !self.include.is_empty() {
+            // Keep explicitly included modules even when none of their types matched.
+            model.modules.retain(|m| {
+                !m.types.is_empty() || self.include.iter().any(|p| matches_path(p, &m.path))
+            });
+        }
+    }
+
+    /// Drops the inline modules, structs, enums and inherent methods the filter
+    /// rejects from `items` declared in `module`, for the generators that work
+    /// on syntax rather than a [`CrateModel`].
+    pub fn retain_items(&self, module: &str, items: &mut Vec<Item>) {
+        let is_public = |vis: &Visibility| matches!(vis, Visibility::Public(_));
+        items.retain_mut(|item| match item {
+            Item::Mod(m) => {
+                let path = format!("{}::{}", module, m.ident);
+                if !self.allows_module(&path, is_cfg_test(&m.attrs)) {
+                    return false;
+                }
+                if let Some((_, inner)) = &mut m.content {
+                    self.retain_items(&path, inner);
+                }
+                true
+            }
+            Item::Struct(s) => self.allows_type(module, &s.ident.to_string(), is_public(&s.vis)),
+            Item::Enum(e) => self.allows_type(module, &e.ident.to_string(), is_public(&e.vis)),
+            Item::Impl(imp) if imp.trait_.is_none() => {
+                imp.items.retain(|i| match i {
+                    ImplItem::Fn(f) => self.allows_member(Vis::from_syn(&f.vis)),
+                    _ => true,
+                });
+                true
+            }
+            _ => true,
+        });
+    }
+}
+
+/// True for primitives and the common `std` containers and smart pointers.
+/// Paths such as `std::sync::Arc` are matched on their last segment.
+pub fn is_std_type(name: &str) -> bool {
+    let base = name.split('<').next().unwrap_or(name).trim();
+    let last = base.rsplit("::").next().unwrap_or(base);
+    STD_TYPES.contains(&last)
+}
+
+fn matches_path(pattern: &str, module: &str) -> bool {
+    glob_match(pattern, module)
+        || module
+            .strip_prefix("crate::")
+            .is_some_and(|relative| glob_match(pattern, relative))
+}
+
+fn type_candidates(module: &str, name: &str) -> Vec<String> {
+    let mut candidates = vec![name.to_string()];
+    if !module.is_empty() {
+        candidates.push(module.to_string());
+        candidates.push(format!("{}::{}", module, name));
+        if let Some(relative) = module.strip_prefix("crate::") {
+            candidates.push(relative.to_string());
+            candidates.push(format!("{}::{}", relative, name));
+        }
+    }
+    candidates
+}
+
+/// Matches `text` against a glob where `*` is any (possibly empty) run of
+/// characters and `?` exactly one character.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` and the text index it is currently absorbing up to.
+    let mut star: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("crate::net::*", "crate::net::server"));
+        assert!(glob_match("*Builder", "RequestBuilder"));
+        assert!(glob_match("S?rver", "Server"));
+        assert!(!glob_match("crate::net::*", "crate::db"));
+        assert!(!glob_match("*Builder", "Builders"));
+    }
+
+    #[test]
+    fn test_apply_filters_model() {
+        let source = r#"
+            pub struct Server { pub port: u16, secret: String }
+            struct Internal;
+            pub struct RequestBuilder;
+            #[cfg(test)]
+            mod tests {
+                pub struct Fixture;
+            }
+        "#;
+        let mut model = CrateModel::from_source(Path::new("lib.rs"), source).unwrap();
+        let filter = Filter {
+            exclude: vec!["*Builder".to_string()],
+            pub_only: true,
+            no_tests: true,
+            ..Filter::default()
+        };
+        filter.apply(&mut model);
+
+        let names: Vec<&str> = model.types().map(|(_, t)| t.name.as_str()).collect();
+        assert_eq!(names, ["Server"]);
+        let (_, server) = model.find_type("Server", "crate").unwrap();
+        assert_eq!(server.fields.len(), 1);
+    }
+
+    #[test]
+    fn test_retain_items() {
+        let mut file: syn::File = syn::parse_str(
+            r#"
+            pub enum State { Idle, Busy }
+            enum Hidden { A }
+            impl State {
+                pub fn start(&mut self) { *self = State::Busy; }
+                fn reset(&mut self) { *self = State::Idle; }
+            }
+            pub mod net {
+                pub struct Conn;
+            }
+            "#,
+        )
+        .unwrap();
+        let filter = Filter {
+            exclude: vec!["crate::net::*".to_string()],
+            pub_only: true,
+            ..Filter::default()
+        };
+        filter.retain_items("crate", &mut file.items);
+
+        let kept: Vec<String> = file
+            .items
+            .iter()
+            .map(|item| match item {
+                Item::Enum(e) => e.ident.to_string(),
+                Item::Mod(m) => m.ident.to_string(),
+                Item::Impl(imp) => format!("impl with {} fn", imp.items.len()),
+                _ => String::new(),
+            })
+            .collect();
+        assert_eq!(kept, ["State", "impl with 1 fn", "net"]);
+        let Item::Mod(net) = &file.items[2] else {
+            unreachable!()
+        };
+        assert!(net.content.as_ref().unwrap().1.is_empty());
+    }
+}
@@ -0,0 +1,199 @@
+//! Neighbourhood extraction for generated diagrams: keep only the nodes within
+//! a number of relationship hops of a focus type.
+
+use crate::Result;
+use anyhow::{anyhow, bail};
+use std::collections::{BTreeSet, HashMap, VecDeque};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Follow edges pointing at the focus (who uses it).
+    In,
+    /// Follow edges leaving the focus (what it uses).
+    Out,
+    Both,
+}
+
+#[derive(Debug, Clone)]
+pub struct Focus {
+    pub target: String,
+    pub depth: usize,
+    pub direction: Direction,
+}
+
+/// Style used to mark the focus node in every diagram kind.
+pub const FOCUS_STYLE: &str = "fill:#ffe082,stroke:#e65100,stroke-width:3px";
+
+impl Focus {
+    /// Removes `--focus <Type>`, `--depth <N>` and `--direction in|out|both`
+    /// from `args`. Returns `None` when no `--focus` was given; `--depth` or
+    /// `--direction` on their own are an error.
+    pub fn from_args(args: &mut Vec<String>) -> Result<Option<Focus>> {
+        let mut target = None;
+        let mut depth = 1;
+        let mut direction = Direction::Both;
+        let mut modifier = None;
+
+        let mut i = 0;
+        while i < args.len() {
+            let value = |name: &str| {
+                args.get(i + 1)
+                    .cloned()
+                    .ok_or_else(|| anyhow!("{} requires a value", name))
+            };
+            match args[i].as_str() {
+                "--focus" => target = Some(value("--focus")?),
+                "--depth" => {
+                    modifier = Some("--depth");
+                    depth = value("--depth")?
+                        .parse()
+                        .map_err(|_| anyhow!("--depth expects a number"))?
+                }
+                "--direction" => {
+                    modifier = Some("--direction");
+                    direction = match value("--direction")?.as_str() {
+                        "in" => Direction::In,
+                        "out" => Direction::Out,
+                        "both" => Direction::Both,
+                        other => bail!("--direction expects in, out or both, got '{}'", other),
+                    }
+                }
+                _ => {
+                    i += 1;
+                    continue;
+                }
+            }
+            args.drain(i..i + 2);
+        }
+        if let (None, Some(name)) = (&target, modifier) {
+            bail!("{} requires --focus", name);
+        }
+
+        Ok(target.map(|target| Focus {
+            target,
+            depth,
+            direction,
+        }))
+    }
+
+    /// True when `node` names the focus type, either exactly or as the last
+    /// segment of a path-qualified name.
+    pub fn matches(&self, node: &str) -> bool {
+        node == self.target
+            || node
+                .rsplit("::")
+                .next()
+                .is_some_and(|last| last == self.target)
+    }
+
+    /// Breadth-first walk from the focus over `edges` (`from -> to`), returning
+    /// every node reachable within `depth` hops in the configured direction.
+    /// `nodes` lists types without edges so an isolated focus still resolves.
+    pub fn neighbourhood<'a, N, I>(&self, nodes: N, edges: I) -> Result<BTreeSet<String>>
+    where
+        N: IntoIterator<Item = &'a str>,
+        I: IntoIterator<Item = (&'a str, &'a str)>,
+    {
+        let mut outgoing: HashMap<&str, Vec<&str>> = HashMap::new();
+        let mut incoming: HashMap<&str, Vec<&str>> = HashMap::new();
+        let mut all: BTreeSet<&str> = nodes.into_iter().collect();
+        for (from, to) in edges {
+            outgoing.entry(from).or_default().push(to);
+            incoming.entry(to).or_default().push(from);
+            all.insert(from);
+            all.insert(to);
+        }
+        let start: Vec<&str> = all.into_iter().filter(|n| self.matches(n)).collect();
+        if start.is_empty() {
+            bail!("Focus type '{}' not found in the diagram", self.target);
+        }
+
+        let mut kept: BTreeSet<String> = BTreeSet::new();
+        let mut queue: VecDeque<(&str, usize)> = VecDeque::new();
+        for node in start {
+            kept.insert(node.to_string());
+            queue.push_back((node, 0));
+        }
+
+        while let Some((node, distance)) = queue.pop_front() {
+            if distance == self.depth {
+                continue;
+            }
+            let mut next: Vec<&str> = Vec::new();
+            if self.direction != Direction::In {
+                next.extend(outgoing.get(node).into_iter().flatten());
+            }
+            if self.direction != Direction::Out {
+                next.extend(incoming.get(node).into_iter().flatten());
+            }
+            for neighbour in next {
+                if kept.insert(neighbour.to_string()) {
+                    queue.push_back((neighbour, distance + 1));
+                }
+            }
+        }
+
+        Ok(kept)
+    }
+
+    /// A filename-safe suffix for focused outputs, e.g. `_Server`.
+    pub fn file_suffix(&self) -> String {
+        let name: String = self
+            .target
+            .chars()
+            .map(|c| if c.is_alphanumeric() { c } else { '_' })
+            .collect();
+        format!("_{}", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn focus(direction: Direction, depth: usize) -> Focus {
+        Focus {
+            target: "B".to_string(),
+            depth,
+            direction,
+        }
+    }
+
+    #[test]
+    fn test_neighbourhood_directions() {
+        let edges = [("A", "B"), ("B", "C"), ("C", "D"), ("E", "A")];
+        let both = focus(Direction::Both, 1).neighbourhood([], edges).unwrap();
+        assert_eq!(both.into_iter().collect::<Vec<_>>(), ["A", "B", "C"]);
+
+        let out = focus(Direction::Out, 2).neighbourhood([], edges).unwrap();
+        assert_eq!(out.into_iter().collect::<Vec<_>>(), ["B", "C", "D"]);
+
+        let inward = focus(Direction::In, 2).neighbourhood([], edges).unwrap();
+        assert_eq!(inward.into_iter().collect::<Vec<_>>(), ["A", "B", "E"]);
+    }
+
+    #[test]
+    fn test_from_args_strips_flags() {
+        let mut args: Vec<String> = ["file.rs", "--focus", "Server", "--depth", "2", "--png"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let parsed = Focus::from_args(&mut args).unwrap().unwrap();
+        assert_eq!(parsed.target, "Server");
+        assert_eq!(parsed.depth, 2);
+        assert_eq!(args, ["file.rs", "--png"]);
+    }
+
+    #[test]
+    fn test_from_args_rejects_modifiers_without_focus() {
+        let mut args: Vec<String> = vec!["--direction".to_string(), "in".to_string()];
+        let err = Focus::from_args(&mut args).unwrap_err();
+        assert_eq!(err.to_string(), "--direction requires --focus");
+
+        let mut args: Vec<String> = vec!["--depth".to_string(), "2".to_string()];
+        assert!(Focus::from_args(&mut args).is_err());
+
+        let mut args: Vec<String> = vec!["file.rs".to_string()];
+        assert!(Focus::from_args(&mut args).unwrap().is_none());
+    }
+}
@@ -1,5 +1,20 @@
 use anyhow::Error;
 
+pub mod color;
+pub mod common;
+pub mod config;
+pub mod filter;
+pub mod focus;
+pub mod lint;
+pub mod mermaid;
+pub mod model;
+pub mod schema;
+pub mod sequence;
+pub mod states;
+pub mod theme;
+pub mod topology;
+pub mod types;
+
 pub type Result<T> = std::result::Result<T, Error>;
 
 pub fn initialize() -> Result<()> {
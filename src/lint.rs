@@ -0,0 +1,480 @@
+//! Lint rules for Mermaid diagrams, run over the [`Diagram`] tree.
+//!
+//! | Code | Rule                 | Fix                                     |
+//! |------|----------------------|-----------------------------------------|
+//! | M001 | `undefined-class`    | -                                       |
+//! | M002 | `unused-classdef`    | removes the classDef                    |
+//! | M003 | `unbalanced-block`   | drops stray closers, closes open blocks |
+//! | M004 | `conflicting-label`  | -                                       |
+//! | M005 | `undeclared-node`    | declares the class                      |
+//! | M006 | `reserved-id`        | renames `end` to `End`                  |
+//! | M007 | `invalid-directive`  | -                                       |
+//! | M008 | `misplaced-directive`| moves it above the diagram type         |
+
+use crate::mermaid::{BlockKind, Diagram, DiagramKind, Line, Node, Statement};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub code: &'static str,
+    pub rule: &'static str,
+    /// 1-based position in the source.
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+    /// Whether [`fix`] resolves it.
+    pub fixable: bool,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: {} {}: {}",
+            self.line, self.column, self.code, self.rule, self.message
+        )?;
+        if self.fixable {
+            f.write_str(" (fixable)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Ids Mermaid cannot use for flowchart nodes.
+const RESERVED_IDS: [&str; 1] = ["end"];
+
+/// The replacement [`fix`] uses for a reserved id.
+fn unreserved(id: &str) -> String {
+    let mut chars = id.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+struct Linter<'a> {
+    source: Vec<&'a str>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Linter<'_> {
+    fn report(
+        &mut self,
+        code: &'static str,
+        rule: &'static str,
+        line: usize,
+        needle: &str,
+        message: String,
+        fixable: bool,
+    ) {
+        let column = line
+            .checked_sub(1)
+            .and_then(|i| self.source.get(i))
+            .and_then(|text| text.find(needle))
+            .map_or(1, |i| i + 1);
+        self.diagnostics.push(Diagnostic {
+            code,
+            rule,
+            line,
+            column,
+            message,
+            fixable,
+        });
+    }
+}
+
+/// Checks `diagram`, parsed from `source`, against every rule.
+pub fn lint(diagram: &Diagram, source: &str) -> Vec<Diagnostic> {
+    let mut linter = Linter {
+        source: source.lines().collect(),
+        diagnostics: Vec::new(),
+    };
+    let lines = diagram.all_lines();
+
+    // M001/M002: classDefs against the classes nodes reference
+    let defined: BTreeSet<&str> = diagram.class_defs().into_iter().map(|(n, _)| n).collect();
+    let mut used = BTreeSet::new();
+    for line in &lines {
+        for class in referenced_classes(&line.statement) {
+            used.insert(class);
+            if !defined.contains(class) {
+                linter.report(
+                    "M001",
+                    "undefined-class",
+                    line.number,
+                    class,
+                    format!("class `{}` has no classDef", class),
+                    false,
+                );
+            }
+        }
+    }
+    for line in &lines {
+        if let Statement::ClassDef { names, .. } = &line.statement {
+            // `default` styles every node without being referenced
+            for name in names
+                .iter()
+                .filter(|n| *n != "default" && !used.contains(n.as_str()))
+            {
+                linter.report(
+                    "M002",
+                    "unused-classdef",
+                    line.number,
+                    name,
+                    format!("classDef `{}` is never used", name),
+                    true,
+                );
+            }
+        }
+    }
+
+    // M003: stray closers and blocks left open
+    for line in &lines {
+        if let Statement::Raw(text) = &line.statement {
+            if text == "end" || text == "}" {
+                linter.report(
+                    "M003",
+                    "unbalanced-block",
+                    line.number,
+                    text,
+                    format!("`{}` does not close any block", text),
+                    true,
+                );
+            }
+        }
+    }
+    for &number in &diagram.unclosed {
+        linter.report(
+            "M003",
+            "unbalanced-block",
+            number,
+            "",
+            "block is never closed".to_string(),
+            true,
+        );
+    }
+
+    // M004: one id drawn with different labels
+    let mut labels: BTreeMap<&str, &str> = BTreeMap::new();
+    for line in &lines {
+        for node in nodes_of(&line.statement) {
+            let Some(label) = &node.label else {
+                continue;
+            };
+            match labels.get(node.id.as_str()) {
+                Some(first) if first != label => linter.report(
+                    "M004",
+                    "conflicting-label",
+                    line.number,
+                    &node.id,
+                    format!(
+                        "node `{}` is labelled `{}` here but `{}` earlier",
+                        node.id, label, first
+                    ),
+                    false,
+                ),
+                Some(_) => {}
+                None => {
+                    labels.insert(&node.id, label);
+                }
+            }
+        }
+    }
+
+    // M005: classDiagram edges must point at declared classes
+    if diagram.kind == DiagramKind::Class {
+        let declared = declared_classes(diagram);
+        let mut reported = BTreeSet::new();
+        for line in &lines {
+            if let Statement::Edge(edge) = &line.statement {
+                for id in [&edge.from.id, &edge.to.id] {
+                    if !declared.contains(id.as_str()) && reported.insert(id.as_str()) {
+                        linter.report(
+                            "M005",
+                            "undeclared-node",
+                            line.number,
+                            id,
+                            format!("edge references `{}`, which is never declared", id),
+                            true,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    // M006: reserved words as flowchart ids
+    if diagram.kind.is_flowchart() {
+        for line in &lines {
+            for node in nodes_of(&line.statement) {
+                if RESERVED_IDS.contains(&node.id.as_str()) {
+                    linter.report(
+                        "M006",
+                        "reserved-id",
+                        line.number,
+                        &node.id,
+                        format!(
+                            "`{}` is a reserved word and breaks the diagram; use `{}`",
+                            node.id,
+                            unreserved(&node.id)
+                        ),
+                        true,
+                    );
+                }
+            }
+        }
+    }
+
+    // M007/M008: init directives must parse and come first
+    for line in &diagram.preamble {
+        check_directive(&mut linter, line);
+    }
+    for line in diagram.all_lines().into_iter().skip(diagram.preamble.len()) {
+        if let Statement::Directive(text) = &line.statement {
+            check_directive(&mut linter, line);
+            if text.trim_start().starts_with("init") {
+                linter.report(
+                    "M008",
+                    "misplaced-directive",
+                    line.number,
+                    "%%{",
+                    "init directive after the diagram type is ignored".to_string(),
+                    true,
+                );
+            }
+        }
+    }
+
+    linter
+        .diagnostics
+        .sort_by(|a, b| (a.line, a.column, a.code).cmp(&(b.line, b.column, b.code)));
+    linter.diagnostics
+}
+
+fn check_directive(linter: &mut Linter, line: &Line) {
+    let Statement::Directive(text) = &line.statement else {
+        return;
+    };
+    let Some(json) = text.trim().strip_prefix("init") else {
+        return;
+    };
+    let json = json.trim_start().trim_start_matches(':').trim();
+    if let Err(err) = parse_init(json) {
+        linter.report(
+            "M007",
+            "invalid-directive",
+            line.number,
+            "%%{",
+            format!("init directive is not valid JSON: {}", err),
+            false,
+        );
+    }
+}
+
+/// Parses an init directive's JSON, allowing the single quotes Mermaid accepts.
+pub fn parse_init(json: &str) -> serde_json::Result<serde_json::Value> {
+    serde_json::from_str(json)
+        .or_else(|err| serde_json::from_str(&json.replace('\'', "\"")).map_err(|_| err))
+}
+
+fn referenced_classes(statement: &Statement) -> Vec<&str> {
+    match statement {
+        Statement::ClassAssign { class, .. } => vec![class.as_str()],
+        _ => nodes_of(statement)
+            .into_iter()
+            .flat_map(|node| node.classes.iter().map(String::as_str))
+            .collect(),
+    }
+}
+
+fn nodes_of(statement: &Statement) -> Vec<&Node> {
+    match statement {
+        Statement::Node(node) => vec![node],
+        Statement::Edge(edge) => vec![&edge.from, &edge.to],
+        _ => Vec::new(),
+    }
+}
+
+/// Classes declared with `class X`, a class block, or an `X : member` line.
+fn declared_classes(diagram: &Diagram) -> BTreeSet<&str> {
+    let mut declared = BTreeSet::new();
+    for line in diagram.all_lines() {
+        match &line.statement {
+            Statement::Node(node) => {
+                declared.insert(node.id.as_str());
+            }
+            Statement::Block(block) if block.kind == BlockKind::Class => {
+                declared.insert(class_id(&block.name));
+            }
+            Statement::Member { owner, .. } => {
+                declared.insert(owner.as_str());
+            }
+            _ => {}
+        }
+    }
+    declared
+}
+
+/// `Server~T~` is declared as `Server`.
+fn class_id(name: &str) -> &str {
+    name.split('~').next().unwrap_or(name).trim()
+}
+
+/// Applies every autofix and returns how many diagnostics it resolved.
+pub fn fix(diagram: &mut Diagram) -> usize {
+    let before = lint(diagram, "").iter().filter(|d| d.fixable).count();
+
+    // M002: drop unused classDef names
+    let used: BTreeSet<String> = diagram
+        .all_lines()
+        .into_iter()
+        .flat_map(|line| referenced_classes(&line.statement))
+        .map(str::to_string)
+        .collect();
+    diagram.for_each_statement_mut(|statement| {
+        if let Statement::ClassDef { names, .. } = statement {
+            names.retain(|n| n == "default" || used.contains(n));
+        }
+    });
+
+    // M003: stray closers go; open blocks are closed when printed.
+    // M008: init directives move above the diagram type.
+    let mut moved = Vec::new();
+    diagram.retain(|statement| match statement {
+        Statement::ClassDef { names, .. } => !names.is_empty(),
+        Statement::Raw(text) => text != "end" && text != "}",
+        _ => true,
+    });
+    diagram.body.retain(|line| match &line.statement {
+        Statement::Directive(text) if text.trim_start().starts_with("init") => {
+            moved.push(line.clone());
+            false
+        }
+        _ => true,
+    });
+    diagram.preamble.extend(moved);
+    diagram.unclosed.clear();
+
+    // M006: rename reserved ids
+    if diagram.kind.is_flowchart() {
+        diagram.for_each_node_mut(|node| {
+            if RESERVED_IDS.contains(&node.id.as_str()) {
+                node.id = unreserved(&node.id);
+            }
+        });
+        diagram.for_each_statement_mut(|statement| match statement {
+            Statement::ClassAssign { nodes, .. } => {
+                for node in nodes
+                    .iter_mut()
+                    .filter(|n| RESERVED_IDS.contains(&n.as_str()))
+                {
+                    *node = unreserved(node);
+                }
+            }
+            Statement::Style { node, .. } | Statement::Click { node, .. }
+                if RESERVED_IDS.contains(&node.as_str()) =>
+            {
+                *node = unreserved(node)
+            }
+            _ => {}
+        });
+    }
+
+    // M005: declare classes that only appear in edges
+    if diagram.kind == DiagramKind::Class {
+        let declared: BTreeSet<String> = declared_classes(diagram)
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+        let missing: BTreeSet<String> = diagram
+            .edges()
+            .flat_map(|e| [e.from.id.clone(), e.to.id.clone()])
+            .filter(|id| !declared.contains(id))
+            .collect();
+        let declarations = missing.into_iter().map(|id| {
+            let mut node = Node::new(id);
+            node.keyword = Some("class".to_string());
+            Line::from(Statement::Node(node))
+        });
+        diagram.body.splice(0..0, declarations);
+    }
+
+    before - lint(diagram, "").iter().filter(|d| d.fixable).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codes(source: &str) -> Vec<(usize, usize, &'static str)> {
+        lint(&Diagram::parse(source), source)
+            .into_iter()
+            .map(|d| (d.line, d.column, d.code))
+            .collect()
+    }
+
+    #[test]
+    fn test_flowchart_rules() {
+        let source = "flowchart TB\n\
+            A[One]:::hot --> end\n\
+            A[Two] --> B\n\
+            %%{init: {'theme': 'dark'}}%%\n\
+            %%{init: {theme: dark}}%%\n\
+            classDef cold fill:#00f\n\
+            end\n";
+        assert_eq!(
+            codes(source),
+            [
+                (2, 10, "M001"),
+                (2, 18, "M006"),
+                (3, 1, "M004"),
+                (4, 1, "M008"),
+                (5, 1, "M007"),
+                (5, 1, "M008"),
+                (6, 10, "M002"),
+                (7, 1, "M003"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unclosed_and_class_rules() {
+        assert_eq!(
+            codes("flowchart TB\nsubgraph One\nA --> B\n"),
+            [(2, 1, "M003")]
+        );
+        assert_eq!(
+            codes("classDiagram\nclass A\nA --> B\nB --> C\nC : +run()\n"),
+            [(3, 7, "M005")]
+        );
+    }
+
+    #[test]
+    fn test_fix() {
+        let source = "flowchart TB\n\
+            A:::hot --> end\n\
+            %%{init: {\"theme\": \"dark\"}}%%\n\
+            classDef hot,cold fill:#f00\n\
+            class end hot\n\
+            end\n";
+        let mut diagram = Diagram::parse(source);
+        assert_eq!(fix(&mut diagram), 4);
+        assert!(lint(&diagram, "").is_empty());
+        assert_eq!(
+            diagram.to_string(),
+            "%%{init: {\"theme\": \"dark\"}}%%\n\
+             flowchart TB\n\
+             \x20   A:::hot --> End\n\
+             \x20   classDef hot fill:#f00\n\
+             \x20   class End hot\n"
+        );
+
+        let mut class = Diagram::parse("classDiagram\nA --> B\n");
+        assert_eq!(fix(&mut class), 2);
+        assert_eq!(
+            class.to_string(),
+            "classDiagram\n    class A\n    class B\n    A --> B\n"
+        );
+    }
+}
@@ -46,7 +46,7 @@ fn get_available_commands(manifest: &CommandsManifest) -> Vec<(String, CommandIn
             eprintln!("Warning: No script found for command '{}'.", cmd_name);
         }
     }
-    commands.sort_by(|a, b| a.0.to_lowercase().cmp(&b.0.to_lowercase()));
+    commands.sort_by_key(|a| a.0.to_lowercase());
     commands
 }
 
@@ -125,13 +125,15 @@ fn main() -> Result<(), Box<dyn Error>> {
         }
         _ => {
             let commands = get_available_commands(&manifest);
-            if let Some((_, cmd_info)) = commands.iter().find(|(cmd, _)| cmd == command) {
+            // Binaries are snake_case; `mermaid-diff` runs `mermaid_diff`
+            let binary = command.replace('-', "_");
+            if let Some((_, cmd_info)) = commands.iter().find(|(cmd, _)| *cmd == binary) {
                 if args.len() > 1 && (args[1] == "--help" || args[1] == "-h") {
-                    show_command_help(command, cmd_info);
+                    show_command_help(&binary, cmd_info);
                     return Ok(());
                 }
 
-                let status = Command::new(command).args(&args[1..]).status()?;
+                let status = Command::new(&binary).args(&args[1..]).status()?;
 
                 if !status.success() {
                     return Err(format!(
@@ -0,0 +1,1607 @@
+//! A parser and printer for Mermaid diagrams.
+//!
+//! [`Diagram::parse`] turns source text into a typed tree of [`Statement`]s:
+//! nodes with shapes and labels, edges with arrows and labels, subgraphs and
+//! other blocks, `classDef`/`class`/`:::` styling, `style`, `linkStyle`,
+//! `click`, `%%{init}%%` directives and comments. Each statement keeps the
+//! line it came from. Lines the parser does not understand are kept as
+//! [`Statement::Raw`], so nothing is lost.
+//!
+//! Printing ([`Diagram::render`] or `Display`) is canonical: one statement per
+//! line, blocks indented, flowchart edge labels written `A -->|text| B`, and
+//! chains like `A --> B --> C` or `A & B --> C` written as one edge per line.
+//! Parsing printed output gives back the same tree.
+
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiagramKind {
+    Flowchart,
+    Graph,
+    Class,
+    State,
+    StateV2,
+    Sequence,
+    Er,
+    /// Any other diagram type (`gantt`, `pie`, ...); its body is kept raw.
+    Other(String),
+}
+
+impl DiagramKind {
+    fn from_keyword(keyword: &str) -> Option<Self> {
+        Some(match keyword {
+            "flowchart" => DiagramKind::Flowchart,
+            "graph" => DiagramKind::Graph,
+            "classDiagram" | "classDiagram-v2" => DiagramKind::Class,
+            "stateDiagram" => DiagramKind::State,
+            "stateDiagram-v2" => DiagramKind::StateV2,
+            "sequenceDiagram" => DiagramKind::Sequence,
+            "erDiagram" => DiagramKind::Er,
+            "gantt" | "pie" | "journey" | "gitGraph" | "mindmap" | "timeline" | "quadrantChart"
+            | "requirementDiagram" | "C4Context" | "sankey-beta" | "xychart-beta"
+            | "block-beta" => DiagramKind::Other(keyword.to_string()),
+            _ => return None,
+        })
+    }
+
+    pub fn keyword(&self) -> &str {
+        match self {
+            DiagramKind::Flowchart => "flowchart",
+            DiagramKind::Graph => "graph",
+            DiagramKind::Class => "classDiagram",
+            DiagramKind::State => "stateDiagram",
+            DiagramKind::StateV2 => "stateDiagram-v2",
+            DiagramKind::Sequence => "sequenceDiagram",
+            DiagramKind::Er => "erDiagram",
+            DiagramKind::Other(keyword) => keyword,
+        }
+    }
+
+    pub fn is_flowchart(&self) -> bool {
+        matches!(self, DiagramKind::Flowchart | DiagramKind::Graph)
+    }
+
+    pub fn is_state(&self) -> bool {
+        matches!(self, DiagramKind::State | DiagramKind::StateV2)
+    }
+}
+
+/// Flowchart node shapes, named after the Mermaid documentation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shape {
+    Rect,
+    Round,
+    Stadium,
+    Subroutine,
+    Cylinder,
+    Circle,
+    DoubleCircle,
+    Asymmetric,
+    Rhombus,
+    Hexagon,
+    Parallelogram,
+    ParallelogramAlt,
+    Trapezoid,
+    TrapezoidAlt,
+}
+
+impl Shape {
+    /// Longest delimiters first, so `[(` is tried before `[`.
+    const ALL: [Shape; 14] = [
+        Shape::DoubleCircle,
+        Shape::Stadium,
+        Shape::Subroutine,
+        Shape::Cylinder,
+        Shape::Circle,
+        Shape::Hexagon,
+        Shape::Parallelogram,
+        Shape::ParallelogramAlt,
+        Shape::Trapezoid,
+        Shape::TrapezoidAlt,
+        Shape::Rect,
+        Shape::Round,
+        Shape::Rhombus,
+        Shape::Asymmetric,
+    ];
+
+    pub fn delimiters(self) -> (&'static str, &'static str) {
+        match self {
+            Shape::Rect => ("[", "]"),
+            Shape::Round => ("(", ")"),
+            Shape::Stadium => ("([", "])"),
+            Shape::Subroutine => ("[[", "]]"),
+            Shape::Cylinder => ("[(", ")]"),
+            Shape::Circle => ("((", "))"),
+            Shape::DoubleCircle => ("(((", ")))"),
+            Shape::Asymmetric => (">", "]"),
+            Shape::Rhombus => ("{", "}"),
+            Shape::Hexagon => ("{{", "}}"),
+            Shape::Parallelogram => ("[/", "/]"),
+            Shape::ParallelogramAlt => ("[\\", "\\]"),
+            Shape::Trapezoid => ("[/", "\\]"),
+            Shape::TrapezoidAlt => ("[\\", "/]"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    /// `participant`, `actor`, `class` or `state` for declarations using one.
+    pub keyword: Option<String>,
+    pub id: String,
+    pub shape: Option<Shape>,
+    /// The label as written, including any quotes.
+    pub label: Option<String>,
+    /// Classes attached with `:::`.
+    pub classes: Vec<String>,
+}
+
+impl Node {
+    pub fn new(id: impl Into<String>) -> Self {
+        Node {
+            keyword: None,
+            id: id.into(),
+            shape: None,
+            label: None,
+            classes: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edge {
+    pub from: Node,
+    pub to: Node,
+    /// The arrow without its label, e.g. `-->`, `-.->`, `*--` or `||--o{`.
+    pub arrow: String,
+    pub label: Option<String>,
+    /// classDiagram multiplicities, e.g. the `"1"` in `A "1" *-- "0..1" B`.
+    pub from_cardinality: Option<String>,
+    pub to_cardinality: Option<String>,
+}
+
+impl Edge {
+    pub fn new(from: impl Into<String>, arrow: impl Into<String>, to: impl Into<String>) -> Self {
+        Edge {
+            from: Node::new(from),
+            to: Node::new(to),
+            arrow: arrow.into(),
+            label: None,
+            from_cardinality: None,
+            to_cardinality: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Subgraph {
+    pub id: String,
+    /// The `[title]` after the id, as written.
+    pub title: Option<String>,
+    pub body: Vec<Line>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlockKind {
+    /// classDiagram `class Name { ... }`
+    Class,
+    /// classDiagram `namespace Name { ... }`
+    Namespace,
+    /// stateDiagram composite `state Name { ... }`
+    State,
+    /// erDiagram `Entity { ... }`
+    Entity,
+    /// sequenceDiagram `loop`, `alt`, `opt`, `par`, `critical`, `break`, `rect`
+    /// or `box`, closed by `end`.
+    Sequence(String),
+}
+
+/// A braced or `end`-terminated block other than a flowchart subgraph.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub kind: BlockKind,
+    /// The class, namespace, state or entity name, or a sequence block's
+    /// condition text.
+    pub name: String,
+    pub body: Vec<Line>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Statement {
+    Blank,
+    /// The text after `%%`.
+    Comment(String),
+    /// The text between `%%{` and `}%%`, e.g. `init: {"theme": "dark"}`.
+    Directive(String),
+    Direction(String),
+    Node(Node),
+    Edge(Edge),
+    Subgraph(Subgraph),
+    Block(Block),
+    /// A class member, entity attribute or state description: the body lines
+    /// of `class`/entity blocks, and `Owner : text` lines elsewhere.
+    Member {
+        owner: String,
+        text: String,
+    },
+    /// A sequence block divider: `else ...`, `and ...` or `option ...`.
+    Section(String),
+    ClassDef {
+        names: Vec<String>,
+        styles: String,
+    },
+    /// `class A,B name`, or `cssClass "A,B" name` in a classDiagram.
+    ClassAssign {
+        nodes: Vec<String>,
+        class: String,
+    },
+    Style {
+        node: String,
+        styles: String,
+    },
+    LinkStyle {
+        links: String,
+        styles: String,
+    },
+    Click {
+        node: String,
+        action: String,
+    },
+    /// A line kept verbatim.
+    Raw(String),
+}
+
+/// A statement and the 1-based source line it started on (0 when built in code).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    pub number: usize,
+    pub statement: Statement,
+}
+
+impl From<Statement> for Line {
+    fn from(statement: Statement) -> Self {
+        Line {
+            number: 0,
+            statement,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagram {
+    /// The YAML between the `---` lines that open the file, kept verbatim.
+    pub frontmatter: Option<String>,
+    /// Comments and directives before the diagram type line.
+    pub preamble: Vec<Line>,
+    pub kind: DiagramKind,
+    /// The direction on the type line, e.g. the `TB` of `flowchart TB`.
+    pub direction: Option<String>,
+    pub body: Vec<Line>,
+    /// Lines opening a block that has no closing `end` or `}`. Printing
+    /// closes them.
+    pub unclosed: Vec<usize>,
+}
+
+impl Diagram {
+    pub fn new(kind: DiagramKind, direction: Option<&str>) -> Self {
+        Diagram {
+            frontmatter: None,
+            preamble: Vec::new(),
+            kind,
+            direction: direction.map(str::to_string),
+            body: Vec::new(),
+            unclosed: Vec::new(),
+        }
+    }
+
+    /// Parses Mermaid source. The type line may follow YAML frontmatter,
+    /// comments and directives. Text without a type line parses as a
+    /// `flowchart` so fragments can still be handled.
+    pub fn parse(source: &str) -> Self {
+        let mut lines = source
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line))
+            .peekable();
+
+        // Frontmatter must open the file and be closed by another `---`
+        let mut frontmatter = None;
+        if lines.peek().is_some_and(|(_, line)| line.trim() == "---") {
+            let mut yaml = Vec::new();
+            for (_, line) in lines.clone().skip(1) {
+                if line.trim() == "---" {
+                    frontmatter = Some(yaml.join("\n"));
+                    break;
+                }
+                yaml.push(line);
+            }
+            if frontmatter.is_some() {
+                for _ in 0..yaml.len() + 2 {
+                    lines.next();
+                }
+            }
+        }
+
+        let mut preamble = Vec::new();
+        let mut header = None;
+        while let Some(&(number, line)) = lines.peek() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with("%%") {
+                lines.next();
+                if let Some(statement) = comment_or_directive(trimmed, &mut lines) {
+                    preamble.push(Line { number, statement });
+                }
+                continue;
+            }
+            let mut words = trimmed.trim_end_matches(';').split_whitespace();
+            if let Some(kind) = words.next().and_then(DiagramKind::from_keyword) {
+                lines.next();
+                header = Some((kind, words.next().map(str::to_string)));
+            }
+            break;
+        }
+
+        let (kind, direction) = header.unwrap_or((DiagramKind::Flowchart, None));
+        let mut parser = Parser {
+            kind: &kind,
+            lines,
+            current: 0,
+            unclosed: Vec::new(),
+        };
+        let body = parser.body(None);
+        let unclosed = parser.unclosed;
+        Diagram {
+            frontmatter,
+            preamble,
+            kind,
+            direction,
+            body,
+            unclosed,
+        }
+    }
+
+    /// Prints the diagram with `indent` spaces per nesting level.
+    pub fn render(&self, indent: usize) -> String {
+        let mut printer = Printer {
+            kind: &self.kind,
+            indent,
+            out: String::new(),
+        };
+        if let Some(frontmatter) = &self.frontmatter {
+            printer.out.push_str("---\n");
+            if !frontmatter.is_empty() {
+                printer.out.push_str(frontmatter);
+                printer.out.push('\n');
+            }
+            printer.out.push_str("---\n");
+        }
+        printer.lines(&self.preamble, 0, None);
+        printer.out.push_str(self.kind.keyword());
+        if let Some(direction) = &self.direction {
+            printer.out.push(' ');
+            printer.out.push_str(direction);
+        }
+        printer.out.push('\n');
+        printer.lines(&self.body, 1, None);
+        printer.out
+    }
+
+    /// Every line in the diagram, depth first, including those inside blocks.
+    pub fn all_lines(&self) -> Vec<&Line> {
+        fn walk<'a>(lines: &'a [Line], out: &mut Vec<&'a Line>) {
+            for line in lines {
+                out.push(line);
+                match &line.statement {
+                    Statement::Subgraph(s) => walk(&s.body, out),
+                    Statement::Block(b) => walk(&b.body, out),
+                    _ => {}
+                }
+            }
+        }
+        let mut out = Vec::new();
+        walk(&self.preamble, &mut out);
+        walk(&self.body, &mut out);
+        out
+    }
+
+    /// Every edge in the diagram, in source order.
+    pub fn edges(&self) -> impl Iterator<Item = &Edge> {
+        self.all_lines()
+            .into_iter()
+            .filter_map(|line| match &line.statement {
+                Statement::Edge(edge) => Some(edge),
+                _ => None,
+            })
+    }
+
+    /// Every node by id, from declarations and edge endpoints. A node seen
+    /// more than once keeps the first shape and label it was given.
+    pub fn nodes(&self) -> BTreeMap<String, Node> {
+        let mut nodes: BTreeMap<String, Node> = BTreeMap::new();
+        let mut add = |node: &Node| {
+            let entry = nodes.entry(node.id.clone()).or_insert_with(|| Node {
+                classes: Vec::new(),
+                ..node.clone()
+            });
+            if entry.label.is_none() && node.label.is_some() {
+                entry.shape = node.shape;
+                entry.label = node.label.clone();
+            }
+            for class in &node.classes {
+                if !entry.classes.contains(class) {
+                    entry.classes.push(class.clone());
+                }
+            }
+        };
+        for line in self.all_lines() {
+            match &line.statement {
+                Statement::Node(node) => add(node),
+                Statement::Edge(edge) => {
+                    add(&edge.from);
+                    add(&edge.to);
+                }
+                Statement::Block(block)
+                    if matches!(block.kind, BlockKind::Class | BlockKind::Entity) =>
+                {
+                    add(&Node::new(&block.name))
+                }
+                _ => {}
+            }
+        }
+        nodes
+    }
+
+    /// `classDef` names and their styles, in source order.
+    pub fn class_defs(&self) -> Vec<(&str, &str)> {
+        self.all_lines()
+            .into_iter()
+            .filter_map(|line| match &line.statement {
+                Statement::ClassDef { names, styles } => Some((names, styles)),
+                _ => None,
+            })
+            .flat_map(|(names, styles)| names.iter().map(move |n| (n.as_str(), styles.as_str())))
+            .collect()
+    }
+
+    /// `(node, class)` pairs from `class` statements and `:::` shorthand.
+    pub fn class_assignments(&self) -> Vec<(&str, &str)> {
+        let mut out = Vec::new();
+        for line in self.all_lines() {
+            match &line.statement {
+                Statement::ClassAssign { nodes, class } => {
+                    out.extend(nodes.iter().map(|n| (n.as_str(), class.as_str())))
+                }
+                Statement::Node(node) => {
+                    out.extend(node.classes.iter().map(|c| (node.id.as_str(), c.as_str())))
+                }
+                Statement::Edge(edge) => {
+                    for node in [&edge.from, &edge.to] {
+                        out.extend(node.classes.iter().map(|c| (node.id.as_str(), c.as_str())));
+                    }
+                }
+                _ => {}
+            }
+        }
+        out
+    }
+
+    /// Removes every statement, at any depth, for which `keep` returns false.
+    pub fn retain(&mut self, mut keep: impl FnMut(&Statement) -> bool) {
+        fn walk(lines: &mut Vec<Line>, keep: &mut impl FnMut(&Statement) -> bool) {
+            lines.retain(|line| keep(&line.statement));
+            for line in lines {
+                match &mut line.statement {
+                    Statement::Subgraph(s) => walk(&mut s.body, keep),
+                    Statement::Block(b) => walk(&mut b.body, keep),
+                    _ => {}
+                }
+            }
+        }
+        walk(&mut self.preamble, &mut keep);
+        walk(&mut self.body, &mut keep);
+    }
+
+    /// Calls `f` on every statement, at any depth.
+    pub fn for_each_statement_mut(&mut self, mut f: impl FnMut(&mut Statement)) {
+        fn walk(lines: &mut [Line], f: &mut impl FnMut(&mut Statement)) {
+            for line in lines {
+                f(&mut line.statement);
+                match &mut line.statement {
+                    Statement::Subgraph(s) => walk(&mut s.body, f),
+                    Statement::Block(b) => walk(&mut b.body, f),
+                    _ => {}
+                }
+            }
+        }
+        walk(&mut self.preamble, &mut f);
+        walk(&mut self.body, &mut f);
+    }
+
+    /// Calls `f` on every node, including edge endpoints, at any depth.
+    pub fn for_each_node_mut(&mut self, mut f: impl FnMut(&mut Node)) {
+        self.for_each_statement_mut(|statement| match statement {
+            Statement::Node(node) => f(node),
+            Statement::Edge(edge) => {
+                f(&mut edge.from);
+                f(&mut edge.to);
+            }
+            _ => {}
+        });
+    }
+}
+
+impl Subgraph {
+    /// Ids of the nodes, edge endpoints and nested subgraphs inside this one.
+    pub fn node_ids(&self) -> Vec<&str> {
+        fn walk<'a>(lines: &'a [Line], out: &mut Vec<&'a str>) {
+            for line in lines {
+                match &line.statement {
+                    Statement::Node(node) => out.push(&node.id),
+                    Statement::Edge(edge) => {
+                        out.push(&edge.from.id);
+                        out.push(&edge.to.id);
+                    }
+                    Statement::Subgraph(s) => {
+                        out.push(&s.id);
+                        walk(&s.body, out);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        let mut out = vec![self.id.as_str()];
+        walk(&self.body, &mut out);
+        out.sort_unstable();
+        out.dedup();
+        out
+    }
+}
+
+impl fmt::Display for Diagram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.render(4))
+    }
+}
+
+/// Writes a Rust type the way classDiagram members and class names accept it:
+/// generics use `~` instead of `<`/`>`, and since Mermaid can't parse a comma
+/// inside them, generic lists with several arguments are dropped.
+/// `Option<Vec<Job>>` becomes `Option~Vec~Job~~`, `HashMap<u64, Job>` becomes
+/// `HashMap` and `Option<HashMap<u64, Job>>` becomes `Option~HashMap~`.
+pub fn class_member_type(ty: &str) -> String {
+    let mut out = String::new();
+    let mut rest = ty;
+    while let Some(open) = rest.find('<') {
+        out.push_str(&rest[..open]);
+        // The matching `>`, skipping the arrow in `fn(A) -> B`
+        let mut depth = 0;
+        let mut close = None;
+        let mut prev = ' ';
+        for (i, c) in rest[open..].char_indices() {
+            match c {
+                '<' => depth += 1,
+                '>' if prev != '-' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        close = Some(open + i);
+                        break;
+                    }
+                }
+                _ => {}
+            }
+            prev = c;
+        }
+        let Some(close) = close else {
+            out.push_str(&rest[open..].replace('<', "~"));
+            return out;
+        };
+        let inner = class_member_type(&rest[open + 1..close]);
+        if !inner.contains(',') {
+            out.push('~');
+            out.push_str(&inner);
+            out.push('~');
+        }
+        rest = &rest[close + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// A classDiagram class declaration without its body: `class Id~T~`, followed
+/// by `["label"]` when the id needs one. A class with several generic
+/// parameters can't list them between `~`, so they go in the label instead:
+/// `class Cache["Cache<K, V>"]`.
+pub fn class_header(id: &str, name: &str, generics: &[String], label: Option<&str>) -> String {
+    match generics {
+        [] | [_] => {
+            let generics = generics
+                .first()
+                .map(|g| format!("~{}~", g))
+                .unwrap_or_default();
+            match label {
+                Some(label) => format!("class {}{}[\"{}\"]", id, generics, label),
+                None => format!("class {}{}", id, generics),
+            }
+        }
+        _ => format!(
+            "class {}[\"{}<{}>\"]",
+            id,
+            label.unwrap_or(name),
+            generics.join(", ")
+        ),
+    }
+}
+
+/// Reads a `%%` comment, or a `%%{ ... }%%` directive spanning one or more lines.
+fn comment_or_directive<'a>(
+    trimmed: &str,
+    lines: &mut impl Iterator<Item = (usize, &'a str)>,
+) -> Option<Statement> {
+    if trimmed.is_empty() {
+        return Some(Statement::Blank);
+    }
+    if let Some(rest) = trimmed.strip_prefix("%%{") {
+        let mut text = rest.to_string();
+        while !text.trim_end().ends_with("}%%") {
+            match lines.next() {
+                Some((_, line)) => {
+                    text.push('\n');
+                    text.push_str(line.trim());
+                }
+                None => break,
+            }
+        }
+        let text = text.trim_end().trim_end_matches("}%%").trim();
+        return Some(Statement::Directive(text.to_string()));
+    }
+    trimmed
+        .strip_prefix("%%")
+        .map(|rest| Statement::Comment(rest.trim().to_string()))
+}
+
+fn regex(cell: &'static OnceLock<Regex>, pattern: &str) -> &'static Regex {
+    cell.get_or_init(|| Regex::new(pattern).unwrap())
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Closer {
+    End,
+    Brace,
+}
+
+struct Parser<'k, I: Iterator> {
+    kind: &'k DiagramKind,
+    lines: std::iter::Peekable<I>,
+    /// The line being parsed, which opens any block started from it.
+    current: usize,
+    unclosed: Vec<usize>,
+}
+
+impl<'k, 'a, I: Iterator<Item = (usize, &'a str)>> Parser<'k, I> {
+    /// Parses lines until `closer` (consumed) or the end of input.
+    fn body(&mut self, closer: Option<Closer>) -> Vec<Line> {
+        let opened_at = self.current;
+        let mut out = Vec::new();
+        while let Some((number, line)) = self.lines.next() {
+            self.current = number;
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with("%%") {
+                out.extend(
+                    comment_or_directive(trimmed, &mut self.lines)
+                        .map(|statement| Line { number, statement }),
+                );
+                continue;
+            }
+            let is_close = match closer {
+                Some(Closer::End) => trimmed == "end",
+                Some(Closer::Brace) => trimmed == "}",
+                None => false,
+            };
+            if is_close {
+                return out;
+            }
+            // A closer with nothing to close is kept as written
+            if closer.is_none() && (trimmed == "end" || trimmed == "}") {
+                out.push(Line {
+                    number,
+                    statement: Statement::Raw(trimmed.to_string()),
+                });
+                continue;
+            }
+            for statement in self.statement(trimmed) {
+                out.push(Line { number, statement });
+            }
+        }
+        if closer.is_some() {
+            self.unclosed.push(opened_at);
+        }
+        out
+    }
+
+    /// The body of a classDiagram class or erDiagram entity: one member per line.
+    fn members(&mut self, owner: &str) -> Vec<Line> {
+        let mut out = Vec::new();
+        for (number, line) in self.lines.by_ref() {
+            let trimmed = line.trim();
+            if trimmed == "}" {
+                return out;
+            }
+            let statement = if trimmed.is_empty() || trimmed.starts_with("%%") {
+                match comment_or_directive(trimmed, &mut std::iter::empty()) {
+                    Some(statement) => statement,
+                    None => continue,
+                }
+            } else {
+                Statement::Member {
+                    owner: owner.to_string(),
+                    text: trimmed.to_string(),
+                }
+            };
+            out.push(Line { number, statement });
+        }
+        self.unclosed.push(self.current);
+        out
+    }
+
+    fn statement(&mut self, line: &str) -> Vec<Statement> {
+        if let Some(statement) = self.common(line) {
+            return vec![statement];
+        }
+        match self.kind {
+            DiagramKind::Flowchart | DiagramKind::Graph => self.flowchart(line),
+            DiagramKind::Class => vec![self.class(line)],
+            DiagramKind::State | DiagramKind::StateV2 => vec![self.state(line)],
+            DiagramKind::Sequence => vec![self.sequence(line)],
+            DiagramKind::Er => vec![self.er(line)],
+            DiagramKind::Other(_) => vec![Statement::Raw(line.to_string())],
+        }
+    }
+
+    /// Statements shared by the diagram kinds that support them.
+    fn common(&self, line: &str) -> Option<Statement> {
+        let line = line.trim_end_matches(';').trim_end();
+        let (word, rest) = line.split_once(char::is_whitespace)?;
+        let rest = rest.trim();
+        Some(match word {
+            "direction" => Statement::Direction(rest.to_string()),
+            "classDef" => {
+                let (names, styles) = rest.split_once(char::is_whitespace)?;
+                Statement::ClassDef {
+                    names: split_list(names),
+                    styles: styles.trim().to_string(),
+                }
+            }
+            "class" if !matches!(self.kind, DiagramKind::Class) => {
+                let (nodes, class) = rest.rsplit_once(char::is_whitespace)?;
+                Statement::ClassAssign {
+                    nodes: split_list(nodes),
+                    class: class.to_string(),
+                }
+            }
+            "cssClass" => {
+                let (nodes, class) = rest.rsplit_once(char::is_whitespace)?;
+                Statement::ClassAssign {
+                    nodes: split_list(nodes.trim_matches('"')),
+                    class: class.to_string(),
+                }
+            }
+            "style" => {
+                let (node, styles) = rest.split_once(char::is_whitespace)?;
+                Statement::Style {
+                    node: node.to_string(),
+                    styles: styles.trim().to_string(),
+                }
+            }
+            "linkStyle" => {
+                let (links, styles) = rest.split_once(char::is_whitespace)?;
+                Statement::LinkStyle {
+                    links: links.to_string(),
+                    styles: styles.trim().to_string(),
+                }
+            }
+            "click" => {
+                let (node, action) = rest.split_once(char::is_whitespace)?;
+                Statement::Click {
+                    node: node.to_string(),
+                    action: action.trim().to_string(),
+                }
+            }
+            _ => return None,
+        })
+    }
+
+    fn flowchart(&mut self, line: &str) -> Vec<Statement> {
+        if let Some(rest) = line.strip_prefix("subgraph") {
+            if rest.is_empty() || rest.starts_with(char::is_whitespace) {
+                let (id, title) = subgraph_header(rest.trim());
+                let body = self.body(Some(Closer::End));
+                return vec![Statement::Subgraph(Subgraph { id, title, body })];
+            }
+        }
+        split_statements(line)
+            .into_iter()
+            .flat_map(|part| {
+                parse_flow_statement(part).unwrap_or_else(|| vec![Statement::Raw(part.to_string())])
+            })
+            .collect()
+    }
+
+    fn class(&mut self, line: &str) -> Statement {
+        static EDGE: OnceLock<Regex> = OnceLock::new();
+        let edge = regex(
+            &EDGE,
+            r#"^(\S+?)\s*(?:"([^"]*)"\s*)?(<\|--|<\|\.\.|\*--|o--|<--|<\.\.|--\|>|\.\.\|>|--\*|--o|-->|\.\.>|--|\.\.)\s*(?:"([^"]*)"\s*)?([^\s:]+)\s*(?::\s*(.+))?$"#,
+        );
+
+        if line == "}" {
+            return Statement::Raw(line.to_string());
+        }
+        if let Some(header) = line.strip_suffix('{').map(str::trim_end) {
+            if let Some(name) = header.strip_prefix("namespace ") {
+                let body = self.body(Some(Closer::Brace));
+                return Statement::Block(Block {
+                    kind: BlockKind::Namespace,
+                    name: name.trim().to_string(),
+                    body,
+                });
+            }
+            if let Some(name) = header.strip_prefix("class ") {
+                let name = name.trim().to_string();
+                let body = self.members(&name);
+                return Statement::Block(Block {
+                    kind: BlockKind::Class,
+                    name,
+                    body,
+                });
+            }
+        }
+        if let Some(rest) = line.strip_prefix("class ") {
+            if let Some(mut node) = parse_node(rest.trim()) {
+                node.keyword = Some("class".to_string());
+                return Statement::Node(node);
+            }
+        }
+        if let Some(caps) = edge.captures(line) {
+            return Statement::Edge(Edge {
+                from: Node::new(&caps[1]),
+                to: Node::new(&caps[5]),
+                arrow: caps[3].to_string(),
+                label: caps.get(6).map(|m| m.as_str().trim().to_string()),
+                from_cardinality: caps.get(2).map(|m| m.as_str().to_string()),
+                to_cardinality: caps.get(4).map(|m| m.as_str().to_string()),
+            });
+        }
+        member_or_raw(line)
+    }
+
+    fn state(&mut self, line: &str) -> Statement {
+        static EDGE: OnceLock<Regex> = OnceLock::new();
+        static ALIAS: OnceLock<Regex> = OnceLock::new();
+        let edge = regex(&EDGE, r"^(\S+)\s*-->\s*([^\s:]+)\s*(?::\s*(.+))?$");
+        let alias = regex(&ALIAS, r#"^state\s+("[^"]*")\s+as\s+(\S+)$"#);
+
+        if let Some(header) = line.strip_suffix('{').map(str::trim_end) {
+            if let Some(name) = header.strip_prefix("state ") {
+                let body = self.body(Some(Closer::Brace));
+                return Statement::Block(Block {
+                    kind: BlockKind::State,
+                    name: name.trim().to_string(),
+                    body,
+                });
+            }
+        }
+        if let Some(caps) = alias.captures(line) {
+            let mut node = Node::new(&caps[2]);
+            node.keyword = Some("state".to_string());
+            node.label = Some(caps[1].to_string());
+            return Statement::Node(node);
+        }
+        if let Some(id) = line.strip_prefix("state ") {
+            if !id.trim().contains(char::is_whitespace) {
+                let mut node = Node::new(id.trim());
+                node.keyword = Some("state".to_string());
+                return Statement::Node(node);
+            }
+        }
+        if let Some(caps) = edge.captures(line) {
+            let mut e = Edge::new(&caps[1], "-->", &caps[2]);
+            e.label = caps.get(3).map(|m| m.as_str().trim().to_string());
+            return Statement::Edge(e);
+        }
+        member_or_raw(line)
+    }
+
+    fn sequence(&mut self, line: &str) -> Statement {
+        const BLOCKS: [&str; 8] = [
+            "loop", "alt", "opt", "par", "critical", "break", "rect", "box",
+        ];
+        static MESSAGE: OnceLock<Regex> = OnceLock::new();
+        static PARTICIPANT: OnceLock<Regex> = OnceLock::new();
+        let message = regex(
+            &MESSAGE,
+            r"^([^\s:]+?)\s*(<<-->>|<<->>|-->>|->>|--x|-x|--\)|-\)|-->|->)([+-]?)\s*([^\s:]+)\s*:\s*(.*)$",
+        );
+        let participant = regex(
+            &PARTICIPANT,
+            r"^(participant|actor)\s+(\S+)(?:\s+as\s+(.+))?$",
+        );
+
+        let (word, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        if BLOCKS.contains(&word) {
+            let body = self.body(Some(Closer::End));
+            return Statement::Block(Block {
+                kind: BlockKind::Sequence(word.to_string()),
+                name: rest.trim().to_string(),
+                body,
+            });
+        }
+        if ["else", "and", "option"].contains(&word) {
+            return Statement::Section(line.to_string());
+        }
+        if let Some(caps) = participant.captures(line) {
+            let mut node = Node::new(&caps[2]);
+            node.keyword = Some(caps[1].to_string());
+            node.label = caps.get(3).map(|m| m.as_str().trim().to_string());
+            return Statement::Node(node);
+        }
+        if let Some(caps) = message.captures(line) {
+            let mut e = Edge::new(&caps[1], format!("{}{}", &caps[2], &caps[3]), &caps[4]);
+            e.label = Some(caps[5].trim().to_string());
+            return Statement::Edge(e);
+        }
+        Statement::Raw(line.to_string())
+    }
+
+    fn er(&mut self, line: &str) -> Statement {
+        static EDGE: OnceLock<Regex> = OnceLock::new();
+        let edge = regex(
+            &EDGE,
+            r"^(\S+)\s+([|}o][|o]?(?:--|\.\.)[|o][|{o]?)\s+(\S+)\s*:\s*(.+)$",
+        );
+
+        if let Some(name) = line.strip_suffix('{').map(str::trim_end) {
+            if !name.is_empty() && !name.contains(char::is_whitespace) {
+                let body = self.members(name);
+                return Statement::Block(Block {
+                    kind: BlockKind::Entity,
+                    name: name.to_string(),
+                    body,
+                });
+            }
+        }
+        if let Some(caps) = edge.captures(line) {
+            let mut e = Edge::new(&caps[1], &caps[2], &caps[3]);
+            e.label = Some(caps[4].trim().to_string());
+            return Statement::Edge(e);
+        }
+        if !line.contains(char::is_whitespace) {
+            return Statement::Node(Node::new(line));
+        }
+        Statement::Raw(line.to_string())
+    }
+}
+
+/// `Owner : text` member lines, otherwise the raw line.
+fn member_or_raw(line: &str) -> Statement {
+    match line.split_once(" : ").or_else(|| line.split_once(':')) {
+        Some((owner, text)) if !owner.trim().is_empty() && !owner.trim().contains(' ') => {
+            Statement::Member {
+                owner: owner.trim().to_string(),
+                text: text.trim().to_string(),
+            }
+        }
+        _ => Statement::Raw(line.to_string()),
+    }
+}
+
+fn split_list(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// `id[title]`, `id [title]`, `"Title"` or a bare id.
+fn subgraph_header(rest: &str) -> (String, Option<String>) {
+    if let Some(open) = rest.find('[') {
+        let id = rest[..open].trim_end();
+        if rest.ends_with(']') && !id.is_empty() && !id.contains(char::is_whitespace) {
+            return (
+                id.to_string(),
+                Some(rest[open + 1..rest.len() - 1].to_string()),
+            );
+        }
+    }
+    (rest.trim_matches('"').to_string(), None)
+}
+
+/// Walks `text` outside brackets and quotes, calling `f` with each byte offset.
+/// `f` returns how many bytes to skip, or 0 to step to the next character.
+fn scan_top_level(text: &str, mut f: impl FnMut(usize) -> usize) {
+    let mut depth = 0usize;
+    let mut quoted = false;
+    let mut i = 0;
+    while i < text.len() {
+        let c = text[i..].chars().next().unwrap();
+        if quoted {
+            quoted = c != '"';
+        } else if c == '"' {
+            quoted = true;
+        } else if depth > 0 {
+            match c {
+                '[' | '(' | '{' => depth += 1,
+                ']' | ')' | '}' => depth -= 1,
+                _ => {}
+            }
+        } else {
+            let skip = f(i);
+            if skip > 0 {
+                i += skip;
+                continue;
+            }
+            match c {
+                '[' | '(' | '{' => depth += 1,
+                // `A>label]` opens an asymmetric node right after its id
+                '>' if text[..i].ends_with(|c: char| c.is_alphanumeric() || c == '_') => depth += 1,
+                _ => {}
+            }
+        }
+        i += c.len_utf8();
+    }
+}
+
+/// Splits a flowchart line on top-level `;`.
+fn split_statements(line: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut in_pipes = false;
+    scan_top_level(line, |i| {
+        if line[i..].starts_with('|') {
+            in_pipes = !in_pipes;
+        } else if line[i..].starts_with(';') && !in_pipes && !ends_with_entity(&line[start..i]) {
+            parts.push(&line[start..i]);
+            start = i + 1;
+        }
+        0
+    });
+    parts.push(&line[start..]);
+    parts
+        .into_iter()
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .collect()
+}
+
+/// True when `text` ends in the `&name` of an HTML entity like `&nbsp;`.
+fn ends_with_entity(text: &str) -> bool {
+    static ENTITY: OnceLock<Regex> = OnceLock::new();
+    regex(&ENTITY, r"&#?\w+$").is_match(text)
+}
+
+/// A flowchart link found at the start of some text: its arrow, label and length.
+fn match_link(text: &str) -> Option<(String, Option<String>, usize)> {
+    static TEXT: OnceLock<Regex> = OnceLock::new();
+    static PLAIN: OnceLock<Regex> = OnceLock::new();
+    static PIPE: OnceLock<Regex> = OnceLock::new();
+    let with_text = regex(
+        &TEXT,
+        r"^(<?)(--|==|-\.)\s+([^|>]+?)\s+(-{2,}>|={2,}>|\.-+>|-{3,}|={3,}|\.-+)",
+    );
+    let plain = regex(&PLAIN, r"^<?(?:-{2,}|={2,}|-\.+-|~{3,})([>ox]?)");
+    let pipe = regex(&PIPE, r"^\s*\|([^|]*)\|");
+
+    if let Some(caps) = with_text.captures(text) {
+        let end = &caps[4];
+        let arrow = if end.starts_with('.') {
+            format!("{}-{}", &caps[1], end)
+        } else {
+            format!("{}{}", &caps[1], end)
+        };
+        return Some((arrow, Some(caps[3].to_string()), caps[0].len()));
+    }
+    let caps = plain.captures(text)?;
+    let mut len = caps[0].len();
+    // `--o`/`--x` heads also count right before an id, as in `A--oB`; before
+    // anything else, like `A--o[x]`, the letter is taken to be a node id
+    if matches!(&caps[1], "o" | "x")
+        && !text[len..].is_empty()
+        && !text[len..].starts_with(|c: char| c.is_whitespace() || c.is_alphanumeric() || c == '_')
+        && !pipe.is_match(&text[len..])
+    {
+        len -= 1;
+    }
+    let arrow = text[..len].to_string();
+    match pipe.captures(&text[len..]) {
+        Some(label) => Some((arrow, Some(label[1].to_string()), len + label[0].len())),
+        None => Some((arrow, None, len)),
+    }
+}
+
+/// Parses `A[x] --> B & C -.->|y| D` into its edges, or a lone node.
+fn parse_flow_statement(text: &str) -> Option<Vec<Statement>> {
+    let mut groups: Vec<&str> = Vec::new();
+    let mut links: Vec<(String, Option<String>)> = Vec::new();
+    let mut start = 0;
+    scan_top_level(text, |i| {
+        if i < start {
+            return 0;
+        }
+        match match_link(&text[i..]) {
+            Some((arrow, label, len)) if i > start => {
+                groups.push(&text[start..i]);
+                links.push((arrow, label));
+                start = i + len;
+                len
+            }
+            _ => 0,
+        }
+    });
+    groups.push(&text[start..]);
+
+    let groups: Vec<Vec<Node>> = groups
+        .iter()
+        .map(|group| {
+            let mut nodes = Vec::new();
+            let mut start = 0;
+            scan_top_level(group, |i| {
+                if group[i..].starts_with('&') {
+                    nodes.push(&group[start..i]);
+                    start = i + 1;
+                }
+                0
+            });
+            nodes.push(&group[start..]);
+            nodes.into_iter().map(|n| parse_node(n.trim())).collect()
+        })
+        .collect::<Option<_>>()?;
+
+    if links.is_empty() {
+        return Some(groups.into_iter().flatten().map(Statement::Node).collect());
+    }
+    let mut edges = Vec::new();
+    for (i, (arrow, label)) in links.into_iter().enumerate() {
+        for from in &groups[i] {
+            for to in &groups[i + 1] {
+                edges.push(Statement::Edge(Edge {
+                    from: from.clone(),
+                    to: to.clone(),
+                    arrow: arrow.clone(),
+                    label: label.clone(),
+                    from_cardinality: None,
+                    to_cardinality: None,
+                }));
+            }
+        }
+    }
+    Some(edges)
+}
+
+/// Parses `id`, `id[label]`, `id((label)):::cls` and friends.
+fn parse_node(text: &str) -> Option<Node> {
+    static ID: OnceLock<Regex> = OnceLock::new();
+    let id_re = regex(&ID, r#"^[^\s\[\](){}>"|&;]+"#);
+
+    // Trailing `:::class` markers
+    let mut classes = Vec::new();
+    let mut rest = text;
+    while let Some(pos) = rest.rfind(":::") {
+        let class = &rest[pos + 3..];
+        if class.is_empty()
+            || !class
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+        {
+            break;
+        }
+        classes.insert(0, class.to_string());
+        rest = &rest[..pos];
+    }
+
+    if rest == "[*]" {
+        return Some(Node {
+            classes,
+            ..Node::new(rest)
+        });
+    }
+    let id = id_re.find(rest)?.as_str();
+    let body = &rest[id.len()..];
+    let (shape, label) = if body.is_empty() {
+        (None, None)
+    } else {
+        let shape = Shape::ALL.into_iter().find(|shape| {
+            let (open, close) = shape.delimiters();
+            body.len() >= open.len() + close.len()
+                && body.starts_with(open)
+                && body.ends_with(close)
+        })?;
+        let (open, close) = shape.delimiters();
+        (
+            Some(shape),
+            Some(body[open.len()..body.len() - close.len()].to_string()),
+        )
+    };
+    Some(Node {
+        keyword: None,
+        id: id.to_string(),
+        shape,
+        label,
+        classes,
+    })
+}
+
+struct Printer<'k> {
+    kind: &'k DiagramKind,
+    indent: usize,
+    out: String,
+}
+
+impl Printer<'_> {
+    fn lines(&mut self, lines: &[Line], depth: usize, parent: Option<&BlockKind>) {
+        for line in lines {
+            self.statement(&line.statement, depth, parent);
+        }
+    }
+
+    fn line(&mut self, depth: usize, text: &str) {
+        if !text.is_empty() {
+            self.out.push_str(&" ".repeat(self.indent * depth));
+            self.out.push_str(text);
+        }
+        self.out.push('\n');
+    }
+
+    fn statement(&mut self, statement: &Statement, depth: usize, parent: Option<&BlockKind>) {
+        match statement {
+            Statement::Blank => self.line(0, ""),
+            Statement::Comment(text) => self.line(depth, format!("%% {}", text).trim_end()),
+            Statement::Directive(text) => self.line(depth, &format!("%%{{{}}}%%", text)),
+            Statement::Direction(direction) => {
+                self.line(depth, &format!("direction {}", direction))
+            }
+            Statement::Node(node) => {
+                let text = self.node_declaration(node);
+                self.line(depth, &text)
+            }
+            Statement::Edge(edge) => {
+                let text = self.edge(edge);
+                self.line(depth, &text)
+            }
+            Statement::Subgraph(subgraph) => {
+                let header = match &subgraph.title {
+                    Some(title) => format!("subgraph {}[{}]", subgraph.id, title),
+                    None if subgraph.id.contains(char::is_whitespace) => {
+                        format!("subgraph \"{}\"", subgraph.id)
+                    }
+                    None => format!("subgraph {}", subgraph.id),
+                };
+                self.line(depth, header.trim_end());
+                self.lines(&subgraph.body, depth + 1, None);
+                self.line(depth, "end");
+            }
+            Statement::Block(block) => {
+                let (header, close) = match &block.kind {
+                    BlockKind::Class => (format!("class {} {{", block.name), "}"),
+                    BlockKind::Namespace => (format!("namespace {} {{", block.name), "}"),
+                    BlockKind::State => (format!("state {} {{", block.name), "}"),
+                    BlockKind::Entity => (format!("{} {{", block.name), "}"),
+                    BlockKind::Sequence(keyword) => (
+                        format!("{} {}", keyword, block.name).trim().to_string(),
+                        "end",
+                    ),
+                };
+                self.line(depth, &header);
+                self.lines(&block.body, depth + 1, Some(&block.kind));
+                self.line(depth, close);
+            }
+            Statement::Member { owner, text } => match parent {
+                Some(BlockKind::Class | BlockKind::Entity) => self.line(depth, text),
+                _ => self.line(depth, &format!("{} : {}", owner, text)),
+            },
+            // Dividers sit at the level of the block they split
+            Statement::Section(text) => self.line(depth.saturating_sub(1), text),
+            Statement::ClassDef { names, styles } => {
+                self.line(depth, &format!("classDef {} {}", names.join(","), styles))
+            }
+            Statement::ClassAssign { nodes, class } => {
+                let text = if matches!(self.kind, DiagramKind::Class) {
+                    format!("cssClass \"{}\" {}", nodes.join(","), class)
+                } else {
+                    format!("class {} {}", nodes.join(","), class)
+                };
+                self.line(depth, &text)
+            }
+            Statement::Style { node, styles } => {
+                self.line(depth, &format!("style {} {}", node, styles))
+            }
+            Statement::LinkStyle { links, styles } => {
+                self.line(depth, &format!("linkStyle {} {}", links, styles))
+            }
+            Statement::Click { node, action } => {
+                self.line(depth, &format!("click {} {}", node, action))
+            }
+            Statement::Raw(text) => self.line(depth, text),
+        }
+    }
+
+    fn node_declaration(&self, node: &Node) -> String {
+        match node.keyword.as_deref() {
+            Some(keyword @ ("participant" | "actor")) => match &node.label {
+                Some(label) => format!("{} {} as {}", keyword, node.id, label),
+                None => format!("{} {}", keyword, node.id),
+            },
+            Some("state") => match &node.label {
+                Some(label) => format!("state {} as {}", label, node.id),
+                None => format!("state {}", node.id),
+            },
+            Some(keyword) => format!("{} {}", keyword, node_text(node)),
+            None => node_text(node),
+        }
+    }
+
+    fn edge(&self, edge: &Edge) -> String {
+        match self.kind {
+            DiagramKind::Flowchart | DiagramKind::Graph => {
+                let label = edge
+                    .label
+                    .as_ref()
+                    .map(|l| format!("|{}|", l))
+                    .unwrap_or_default();
+                format!(
+                    "{} {}{} {}",
+                    node_text(&edge.from),
+                    edge.arrow,
+                    label,
+                    node_text(&edge.to)
+                )
+            }
+            DiagramKind::Sequence => format!(
+                "{}{}{}: {}",
+                edge.from.id,
+                edge.arrow,
+                edge.to.id,
+                edge.label.as_deref().unwrap_or_default()
+            )
+            .trim_end()
+            .to_string(),
+            _ => {
+                let mut text = edge.from.id.clone();
+                if let Some(card) = &edge.from_cardinality {
+                    text.push_str(&format!(" \"{}\"", card));
+                }
+                text.push_str(&format!(" {} ", edge.arrow));
+                if let Some(card) = &edge.to_cardinality {
+                    text.push_str(&format!("\"{}\" ", card));
+                }
+                text.push_str(&edge.to.id);
+                if let Some(label) = &edge.label {
+                    text.push_str(&format!(" : {}", label));
+                }
+                text
+            }
+        }
+    }
+}
+
+fn node_text(node: &Node) -> String {
+    let mut text = node.id.clone();
+    if let (Some(shape), Some(label)) = (node.shape, &node.label) {
+        let (open, close) = shape.delimiters();
+        text.push_str(open);
+        text.push_str(label);
+        text.push_str(close);
+    }
+    for class in &node.classes {
+        text.push_str(":::");
+        text.push_str(class);
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(source: &str) -> Diagram {
+        let diagram = Diagram::parse(source);
+        let printed = diagram.to_string();
+        assert_eq!(
+            Diagram::parse(&printed).to_string(),
+            printed,
+            "printing is not stable"
+        );
+        diagram
+    }
+
+    #[test]
+    fn test_flowchart() {
+        let source = "%%{init: {\"theme\": \"dark\"}}%%\n\
+            flowchart TB\n\
+            A[Start] -->|go| B((Mid)):::hot\n\
+            B -- maybe --> C{Choice} & D\n\
+            subgraph sub[\"Sub graph\"]\n\
+            direction LR\n\
+            C -.-> E>flag]\n\
+            end\n\
+            classDef hot fill:#f96,stroke:#333\n\
+            class A,E hot\n\
+            style D fill:#eee\n\
+            click D \"https://example.com\"\n";
+        let diagram = round_trip(source);
+
+        assert_eq!(diagram.kind, DiagramKind::Flowchart);
+        assert_eq!(diagram.direction.as_deref(), Some("TB"));
+        assert_eq!(
+            diagram.preamble[0].statement,
+            Statement::Directive("init: {\"theme\": \"dark\"}".to_string())
+        );
+        let edges: Vec<_> = diagram
+            .edges()
+            .map(|e| {
+                (
+                    e.from.id.as_str(),
+                    e.arrow.as_str(),
+                    e.label.as_deref(),
+                    e.to.id.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            edges,
+            [
+                ("A", "-->", Some("go"), "B"),
+                ("B", "-->", Some("maybe"), "C"),
+                ("B", "-->", Some("maybe"), "D"),
+                ("C", "-.->", None, "E"),
+            ]
+        );
+        let nodes = diagram.nodes();
+        assert_eq!(nodes["B"].shape, Some(Shape::Circle));
+        assert_eq!(nodes["E"].shape, Some(Shape::Asymmetric));
+        assert_eq!(nodes["C"].label.as_deref(), Some("Choice"));
+        assert_eq!(diagram.class_defs(), [("hot", "fill:#f96,stroke:#333")]);
+        assert_eq!(
+            diagram.class_assignments(),
+            [("B", "hot"), ("A", "hot"), ("E", "hot")]
+        );
+        let Statement::Subgraph(sub) = &diagram.body[3].statement else {
+            panic!("expected a subgraph");
+        };
+        assert_eq!(sub.title.as_deref(), Some("\"Sub graph\""));
+        assert_eq!(sub.node_ids(), ["C", "E", "sub"]);
+        assert_eq!(diagram.body[3].number, 5);
+
+        assert_eq!(
+            diagram.to_string(),
+            "%%{init: {\"theme\": \"dark\"}}%%\n\
+             flowchart TB\n\
+             \x20   A[Start] -->|go| B((Mid)):::hot\n\
+             \x20   B -->|maybe| C{Choice}\n\
+             \x20   B -->|maybe| D\n\
+             \x20   subgraph sub[\"Sub graph\"]\n\
+             \x20       direction LR\n\
+             \x20       C -.-> E>flag]\n\
+             \x20   end\n\
+             \x20   classDef hot fill:#f96,stroke:#333\n\
+             \x20   class A,E hot\n\
+             \x20   style D fill:#eee\n\
+             \x20   click D \"https://example.com\"\n"
+        );
+    }
+
+    #[test]
+    fn test_spaced_subgraph_titles_and_circle_heads() {
+        let diagram =
+            round_trip("flowchart LR\nsubgraph one [One]\nA\nend\none --> X\nI--oJ\nK --x L\n");
+        let Statement::Subgraph(sub) = &diagram.body[0].statement else {
+            panic!("expected a subgraph");
+        };
+        assert_eq!(sub.id, "one");
+        assert_eq!(sub.title.as_deref(), Some("One"));
+        let edges: Vec<_> = diagram
+            .edges()
+            .map(|e| (e.from.id.as_str(), e.arrow.as_str(), e.to.id.as_str()))
+            .collect();
+        assert_eq!(
+            edges,
+            [("one", "-->", "X"), ("I", "--o", "J"), ("K", "--x", "L")]
+        );
+        assert_eq!(
+            diagram.to_string(),
+            "flowchart LR\n    subgraph one[One]\n        A\n    end\n    one --> X\n    I --o J\n    K --x L\n"
+        );
+    }
+
+    #[test]
+    fn test_other_kinds() {
+        let class = round_trip(
+            "classDiagram\nclass Server~T~ {\n+start() bool\n}\nServer \"1\" *-- \"0..1\" Config : config\nServer : +name String\n",
+        );
+        assert_eq!(
+            class.to_string(),
+            "classDiagram\n    class Server~T~ {\n        +start() bool\n    }\n    Server \"1\" *-- \"0..1\" Config : config\n    Server : +name String\n"
+        );
+        let edge = class.edges().next().unwrap();
+        assert_eq!(edge.to_cardinality.as_deref(), Some("0..1"));
+
+        let sequence = round_trip(
+            "sequenceDiagram\nactor Caller\nCaller->>Server: start()\nalt ok\nServer-->>Caller: Ok\nelse failed\nServer-->>Caller: Err\nend\n",
+        );
+        assert_eq!(
+            sequence.to_string(),
+            "sequenceDiagram\n    actor Caller\n    Caller->>Server: start()\n    alt ok\n        Server-->>Caller: Ok\n    else failed\n        Server-->>Caller: Err\n    end\n"
+        );
+
+        let er = round_trip(
+            "erDiagram\nOrder {\nUuid id PK\n}\nOrder }o--|| Customer : \"customer_id\"\n",
+        );
+        assert_eq!(er.nodes().keys().collect::<Vec<_>>(), ["Customer", "Order"]);
+
+        let state =
+            round_trip("stateDiagram-v2\n[*] --> Open\nstate Open {\nIdle --> Busy : work\n}\n");
+        assert_eq!(state.edges().count(), 2);
+    }
+
+    #[test]
+    fn test_frontmatter_and_directives_before_the_type_line() {
+        let source = "---\ntitle: Checkout\nconfig:\n  theme: forest\n---\n%%{init: {\"theme\": \"dark\"}}%%\n%% flow\nsequenceDiagram\nAlice->>Bob: pay\n";
+        let diagram = round_trip(source);
+        assert_eq!(diagram.kind, DiagramKind::Sequence);
+        assert_eq!(
+            diagram.frontmatter.as_deref(),
+            Some("title: Checkout\nconfig:\n  theme: forest")
+        );
+        assert_eq!(diagram.preamble.len(), 2);
+        assert_eq!(diagram.body[0].number, 9);
+        assert_eq!(
+            diagram.to_string(),
+            "---\ntitle: Checkout\nconfig:\n  theme: forest\n---\n%%{init: {\"theme\": \"dark\"}}%%\n%% flow\nsequenceDiagram\n    Alice->>Bob: pay\n"
+        );
+
+        // An unclosed `---` is not frontmatter
+        let diagram = Diagram::parse("---\ngraph LR\nA --> B\n");
+        assert_eq!(diagram.frontmatter, None);
+    }
+
+    #[test]
+    fn test_class_header() {
+        let params =
+            |names: &[&str]| -> Vec<String> { names.iter().map(|n| n.to_string()).collect() };
+        assert_eq!(class_header("Job", "Job", &[], None), "class Job");
+        assert_eq!(
+            class_header("net_Slot", "Slot", &params(&["T"]), Some("net::Slot")),
+            "class net_Slot~T~[\"net::Slot\"]"
+        );
+        assert_eq!(
+            class_header("Cache", "Cache", &params(&["K", "V"]), None),
+            "class Cache[\"Cache<K, V>\"]"
+        );
+        let diagram = Diagram::parse(
+            "classDiagram\nclass Cache[\"Cache<K, V>\"] {\n+get() V\n}\nCache --> Entry\n",
+        );
+        assert_eq!(
+            diagram.to_string().lines().nth(1),
+            Some("    class Cache[\"Cache<K, V>\"] {")
+        );
+    }
+
+    #[test]
+    fn test_class_member_type() {
+        assert_eq!(class_member_type("Vec<Job>"), "Vec~Job~");
+        assert_eq!(class_member_type("Option<Vec<Job>>"), "Option~Vec~Job~~");
+        assert_eq!(class_member_type("HashMap<u64, Job>"), "HashMap");
+        assert_eq!(
+            class_member_type("Option<HashMap<u64, Job>>"),
+            "Option~HashMap~"
+        );
+        assert_eq!(class_member_type("Vec<(u8, Job)>"), "Vec");
+        assert_eq!(class_member_type("(u8, Vec<Job>)"), "(u8, Vec~Job~)");
+        assert_eq!(
+            class_member_type("Box<dyn Fn(Job) -> bool>"),
+            "Box~dyn Fn(Job) -> bool~"
+        );
+        assert_eq!(class_member_type("&str"), "&str");
+    }
+}
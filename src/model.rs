@@ -0,0 +1,920 @@
+//! A syn-based model of a crate: its modules and the structs, enums and traits
+//! declared in them, with fields, methods and doc comments attached.
+//!
+//! Modules are kept in [`module_order`] and the types of each module by kind
+//! (structs, enums, traits), then name, so [`CrateModel::types`] lists types
+//! in [`type_order`]. Generators rely on this to produce the same output, byte
+//! for byte, every time they run on the same sources. Fields, variants and
+//! methods stay in declaration order.
+
+use crate::Result;
+use anyhow::Context;
+use quote::ToTokens;
+use regex::Regex;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use syn::{
+    Attribute, Fields, FnArg, GenericParam, Generics, ImplItem, Item, Meta, Signature, TraitItem,
+    Type, Visibility,
+};
+use walkdir::WalkDir;
+
+#[derive(Debug, Default)]
+pub struct CrateModel {
+    pub modules: Vec<Module>,
+}
+
+#[derive(Debug)]
+pub struct Module {
+    /// Full module path, e.g. `crate::net::server`.
+    pub path: String,
+    pub file: PathBuf,
+    pub docs: String,
+    pub types: Vec<TypeDef>,
+    pub functions: Vec<Method>,
+    pub aliases: Vec<TypeAlias>,
+    pub imports: Vec<Import>,
+    /// Declared under `#[cfg(test)]`, directly or through a parent module.
+    pub is_test: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TypeKind {
+    Struct,
+    Enum,
+    Trait,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Vis {
+    Public,
+    Crate,
+    Restricted,
+    Private,
+}
+
+#[derive(Debug)]
+pub struct TypeDef {
+    pub name: String,
+    pub kind: TypeKind,
+    pub vis: Vis,
+    pub docs: String,
+    pub generics: Vec<String>,
+    pub derives: Vec<String>,
+    pub fields: Vec<Field>,
+    pub variants: Vec<Variant>,
+    pub methods: Vec<Method>,
+    /// Traits implemented for this type, as written in the `impl` header.
+    pub traits: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct Field {
+    /// Field name, or its position (`0`, `1`, ...) for tuple fields.
+    pub name: String,
+    pub ty: Type,
+    pub vis: Vis,
+    pub docs: String,
+}
+
+#[derive(Debug)]
+pub struct Variant {
+    pub name: String,
+    pub fields: Vec<Field>,
+    pub docs: String,
+}
+
+/// One name brought into scope by a `use` item.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Import {
+    /// The local name, after any `as` rename; `*` for glob imports.
+    pub name: String,
+    /// The imported path with `self`/`super` resolved, e.g. `crate::net::Config`.
+    /// For globs, the path of the module whose items are imported.
+    pub path: String,
+}
+
+/// `type Name = Target;`
+#[derive(Debug)]
+pub struct TypeAlias {
+    pub name: String,
+    /// Type parameters, e.g. `T` for `type Result<T> = ...`.
+    pub generics: Vec<String>,
+    pub ty: Type,
+}
+
+#[derive(Debug)]
+pub struct Method {
+    pub name: String,
+    pub vis: Vis,
+    pub docs: String,
+    pub sig: Signature,
+    /// `None` for trait methods without a default body.
+    pub body: Option<syn::Block>,
+}
+
+impl TypeKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TypeKind::Struct => "struct",
+            TypeKind::Enum => "enum",
+            TypeKind::Trait => "trait",
+        }
+    }
+}
+
+impl Vis {
+    pub(crate) fn from_syn(vis: &Visibility) -> Self {
+        match vis {
+            Visibility::Public(_) => Vis::Public,
+            Visibility::Restricted(r) if r.path.is_ident("crate") => Vis::Crate,
+            Visibility::Restricted(_) => Vis::Restricted,
+            Visibility::Inherited => Vis::Private,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Vis::Public => "pub",
+            Vis::Crate => "pub(crate)",
+            Vis::Restricted => "pub(in ..)",
+            Vis::Private => "",
+        }
+    }
+
+    /// The UML visibility marker: `+` public, `#` visible within the crate or a
+    /// parent module, `-` private.
+    pub fn uml_marker(&self) -> char {
+        match self {
+            Vis::Public => '+',
+            Vis::Crate | Vis::Restricted => '#',
+            Vis::Private => '-',
+        }
+    }
+}
+
+impl Method {
+    /// True when the method takes no `self` receiver.
+    pub fn is_static(&self) -> bool {
+        !matches!(self.sig.inputs.first(), Some(FnArg::Receiver(_)))
+    }
+
+    /// The signature as it would be written in source, e.g. `fn run(&self) -> Result<()>`.
+    pub fn signature(&self) -> String {
+        tokens_to_string(&self.sig)
+    }
+}
+
+/// An `impl` block whose self type is resolved once every module has been read.
+struct PendingImpl {
+    module: String,
+    self_ty: String,
+    trait_name: Option<String>,
+    methods: Vec<Method>,
+}
+
+impl CrateModel {
+    /// Reads every `.rs` file below `src_dir`, skipping `bin` directories the
+    /// same way `architecture` does.
+    pub fn load(src_dir: &Path) -> Result<Self> {
+        let mut model = CrateModel::default();
+        let mut pending = Vec::new();
+
+        let mut files: Vec<PathBuf> = WalkDir::new(src_dir)
+            .into_iter()
+            .filter_entry(|e| !(e.file_type().is_dir() && e.file_name() == "bin"))
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().extension().is_some_and(|ext| ext == "rs"))
+            .map(|e| e.into_path())
+            .collect();
+        files.sort();
+
+        for file in files {
+            let content = fs::read_to_string(&file)
+                .with_context(|| format!("Failed to read {}", file.display()))?;
+            let syntax = syn::parse_file(&content)
+                .with_context(|| format!("Failed to parse {}", file.display()))?;
+            let path = module_path_for_file(src_dir, &file);
+            let docs = doc_string(&syntax.attrs);
+            model.add_module(path, &file, docs, false, &syntax.items, &mut pending);
+        }
+
+        for imp in pending {
+            model.attach_impl(imp);
+        }
+        model.sort();
+
+        Ok(model)
+    }
+
+    /// Parses a single source string as the crate root. Used for single-file inputs.
+    pub fn from_source(file: &Path, content: &str) -> Result<Self> {
+        let syntax = syn::parse_file(content)
+            .with_context(|| format!("Failed to parse {}", file.display()))?;
+        let mut model = CrateModel::default();
+        let mut pending = Vec::new();
+        let docs = doc_string(&syntax.attrs);
+        model.add_module(
+            "crate".to_string(),
+            file,
+            docs,
+            false,
+            &syntax.items,
+            &mut pending,
+        );
+        for imp in pending {
+            model.attach_impl(imp);
+        }
+        model.sort();
+        Ok(model)
+    }
+
+    /// Puts modules and their types in the documented order.
+    fn sort(&mut self) {
+        self.modules.sort_by(|a, b| module_order(&a.path, &b.path));
+        for module in &mut self.modules {
+            module
+                .types
+                .sort_by(|a, b| a.kind.cmp(&b.kind).then_with(|| a.name.cmp(&b.name)));
+        }
+    }
+
+    fn add_module(
+        &mut self,
+        path: String,
+        file: &Path,
+        docs: String,
+        is_test: bool,
+        items: &[Item],
+        pending: &mut Vec<PendingImpl>,
+    ) {
+        let mut module = Module {
+            path: path.clone(),
+            file: file.to_path_buf(),
+            docs,
+            types: Vec::new(),
+            functions: Vec::new(),
+            aliases: Vec::new(),
+            imports: Vec::new(),
+            is_test,
+        };
+
+        for item in items {
+            match item {
+                Item::Struct(s) => module.types.push(TypeDef {
+                    name: s.ident.to_string(),
+                    kind: TypeKind::Struct,
+                    vis: Vis::from_syn(&s.vis),
+                    docs: doc_string(&s.attrs),
+                    generics: generic_names(&s.generics),
+                    derives: derives(&s.attrs),
+                    fields: fields(&s.fields),
+                    variants: Vec::new(),
+                    methods: Vec::new(),
+                    traits: Vec::new(),
+                }),
+                Item::Enum(e) => module.types.push(TypeDef {
+                    name: e.ident.to_string(),
+                    kind: TypeKind::Enum,
+                    vis: Vis::from_syn(&e.vis),
+                    docs: doc_string(&e.attrs),
+                    generics: generic_names(&e.generics),
+                    derives: derives(&e.attrs),
+                    fields: Vec::new(),
+                    variants: e
+                        .variants
+                        .iter()
+                        .map(|v| Variant {
+                            name: v.ident.to_string(),
+                            fields: fields(&v.fields),
+                            docs: doc_string(&v.attrs),
+                        })
+                        .collect(),
+                    methods: Vec::new(),
+                    traits: Vec::new(),
+                }),
+                Item::Trait(t) => module.types.push(TypeDef {
+                    name: t.ident.to_string(),
+                    kind: TypeKind::Trait,
+                    vis: Vis::from_syn(&t.vis),
+                    docs: doc_string(&t.attrs),
+                    generics: generic_names(&t.generics),
+                    derives: Vec::new(),
+                    fields: Vec::new(),
+                    variants: Vec::new(),
+                    methods: t
+                        .items
+                        .iter()
+                        .filter_map(|ti| match ti {
+                            TraitItem::Fn(f) => Some(Method {
+                                name: f.sig.ident.to_string(),
+                                vis: Vis::Public,
+                                docs: doc_string(&f.attrs),
+                                sig: f.sig.clone(),
+                                body: f.default.clone(),
+                            }),
+                            _ => None,
+                        })
+                        .collect(),
+                    traits: Vec::new(),
+                }),
+                Item::Fn(f) => module.functions.push(Method {
+                    name: f.sig.ident.to_string(),
+                    vis: Vis::from_syn(&f.vis),
+                    docs: doc_string(&f.attrs),
+                    sig: f.sig.clone(),
+                    body: Some((*f.block).clone()),
+                }),
+                Item::Use(u) => collect_imports(&path, &u.tree, Vec::new(), &mut module.imports),
+                Item::Type(alias) => module.aliases.push(TypeAlias {
+                    name: alias.ident.to_string(),
+                    generics: generic_names(&alias.generics),
+                    ty: (*alias.ty).clone(),
+                }),
+                Item::Impl(imp) => pending.push(PendingImpl {
+                    module: path.clone(),
+                    self_ty: tokens_to_string(&imp.self_ty),
+                    trait_name: imp.trait_.as_ref().map(|(_, p, _)| tokens_to_string(p)),
+                    methods: imp
+                        .items
+                        .iter()
+                        .filter_map(|ii| match ii {
+                            ImplItem::Fn(f) => Some(Method {
+                                name: f.sig.ident.to_string(),
+                                // Trait methods are as visible as the trait itself.
+                                vis: if imp.trait_.is_some() {
+                                    Vis::Public
+                                } else {
+                                    Vis::from_syn(&f.vis)
+                                },
+                                docs: doc_string(&f.attrs),
+                                sig: f.sig.clone(),
+                                body: Some(f.block.clone()),
+                            }),
+                            _ => None,
+                        })
+                        .collect(),
+                }),
+                Item::Mod(m) => {
+                    if let Some((_, inner)) = &m.content {
+                        let child = format!("{}::{}", path, m.ident);
+                        self.add_module(
+                            child,
+                            file,
+                            doc_string(&m.attrs),
+                            is_test || is_cfg_test(&m.attrs),
+                            inner,
+                            pending,
+                        );
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // `lib.rs` and `main.rs` both map to `crate`; fold them into one module.
+        match self.modules.iter_mut().find(|m| m.path == module.path) {
+            Some(existing) => {
+                if existing.docs.is_empty() {
+                    existing.docs = module.docs;
+                }
+                existing.types.extend(module.types);
+                existing.functions.extend(module.functions);
+                existing.aliases.extend(module.aliases);
+                existing.imports.extend(module.imports);
+            }
+            None => self.modules.push(module),
+        }
+    }
+
+    /// Attaches an impl block to its self type, resolved from the impl's module.
+    fn attach_impl(&mut self, imp: PendingImpl) {
+        let base = imp.self_ty.split('<').next().unwrap_or_default().trim();
+        if let Some((mi, ti)) = self.resolve_position(&imp.module, base) {
+            let ty = &mut self.modules[mi].types[ti];
+            if let Some(trait_name) = imp.trait_name {
+                ty.traits.push(trait_name);
+            }
+            ty.methods.extend(imp.methods);
+        }
+    }
+
+    /// Resolves a type path as written in `module` to the type it names.
+    ///
+    /// Multi-segment paths are resolved through `crate`/`self`/`super` and the
+    /// module's imports; single names look in the module itself, then its
+    /// imports (including `as` renames and globs). Either falls back to a type
+    /// of that name declared exactly once in the crate.
+    pub fn resolve(&self, module: &str, written: &str) -> Option<(&Module, &TypeDef)> {
+        let (mi, ti) = self.resolve_position(module, written)?;
+        let found = &self.modules[mi];
+        Some((found, &found.types[ti]))
+    }
+
+    fn resolve_position(&self, module: &str, written: &str) -> Option<(usize, usize)> {
+        let written = written.trim_start_matches("::");
+        let imports = self
+            .modules
+            .iter()
+            .find(|m| m.path == module)
+            .map(|m| m.imports.as_slice())
+            .unwrap_or_default();
+
+        let found = match written.split_once("::") {
+            None => self
+                .position(module, written)
+                .or_else(|| {
+                    imports
+                        .iter()
+                        .filter(|i| i.name == written)
+                        .find_map(|i| self.lookup(module, &i.path))
+                })
+                .or_else(|| {
+                    imports
+                        .iter()
+                        .filter(|i| i.name == "*")
+                        .find_map(|i| self.position(&i.path, written))
+                }),
+            Some((first, rest)) => {
+                let expanded = match first {
+                    "crate" | "self" | "super" => absolute_path(module, written),
+                    _ => match imports.iter().find(|i| i.name == first) {
+                        Some(import) => format!("{}::{}", import.path, rest),
+                        None => written.to_string(),
+                    },
+                };
+                self.lookup(module, &expanded)
+            }
+        };
+
+        // As a last resort a name declared exactly once in the crate is taken to
+        // be that type, unless it is spelled or imported as a path into another
+        // crate such as `serde_json::Value`.
+        let first = written.split("::").next().unwrap_or(written);
+        let is_local = |path: &str| {
+            !path.contains("::")
+                || matches!(path.split("::").next(), Some("crate" | "self" | "super"))
+        };
+        let imported_from_elsewhere = imports
+            .iter()
+            .any(|i| i.name == first && !is_local(&i.path));
+        if !is_local(written) || imported_from_elsewhere {
+            return found;
+        }
+        found.or_else(|| {
+            let name = written.rsplit("::").next().unwrap_or(written);
+            let mut all = self.modules.iter().enumerate().flat_map(|(mi, m)| {
+                m.types
+                    .iter()
+                    .enumerate()
+                    .filter(move |(_, t)| t.name == name)
+                    .map(move |(ti, _)| (mi, ti))
+            });
+            match (all.next(), all.next()) {
+                (Some(only), None) => Some(only),
+                _ => None,
+            }
+        })
+    }
+
+    /// Finds `a::b::Name` as an absolute path, then relative to `module`.
+    fn lookup(&self, module: &str, path: &str) -> Option<(usize, usize)> {
+        let (prefix, name) = path.rsplit_once("::")?;
+        self.position(prefix, name)
+            .or_else(|| self.position(&format!("{}::{}", module, prefix), name))
+            .or_else(|| self.position(&format!("crate::{}", prefix), name))
+    }
+
+    fn position(&self, module_path: &str, name: &str) -> Option<(usize, usize)> {
+        let mi = self.modules.iter().position(|m| m.path == module_path)?;
+        let ti = self.modules[mi].types.iter().position(|t| t.name == name)?;
+        Some((mi, ti))
+    }
+
+    /// Iterates over every type together with the module declaring it.
+    pub fn types(&self) -> impl Iterator<Item = (&Module, &TypeDef)> {
+        self.modules
+            .iter()
+            .flat_map(|m| m.types.iter().map(move |t| (m, t)))
+    }
+
+    /// Finds the module declaring a type named `name`, preferring `near` when
+    /// several modules declare the same name.
+    pub fn find_type(&self, name: &str, near: &str) -> Option<(&Module, &TypeDef)> {
+        let mut found = self.types().filter(|(_, t)| t.name == name);
+        let first = found.next()?;
+        if first.0.path == near {
+            return Some(first);
+        }
+        found.find(|(m, _)| m.path == near).or(Some(first))
+    }
+}
+
+/// Flattens a `use` tree into one [`Import`] per name it brings into scope.
+fn collect_imports(module: &str, tree: &syn::UseTree, prefix: Vec<String>, out: &mut Vec<Import>) {
+    let path_of = |segments: &[String]| absolute_path(module, &segments.join("::"));
+    match tree {
+        syn::UseTree::Path(p) => {
+            let mut prefix = prefix;
+            prefix.push(p.ident.to_string());
+            collect_imports(module, &p.tree, prefix, out);
+        }
+        syn::UseTree::Name(n) => {
+            let name = n.ident.to_string();
+            if name == "self" {
+                // `use a::b::{self}` imports the module `b`.
+                if let Some(last) = prefix.last() {
+                    out.push(Import {
+                        name: last.clone(),
+                        path: path_of(&prefix),
+                    });
+                }
+                return;
+            }
+            let mut segments = prefix;
+            segments.push(name.clone());
+            out.push(Import {
+                name,
+                path: path_of(&segments),
+            });
+        }
+        syn::UseTree::Rename(r) => {
+            let mut segments = prefix;
+            segments.push(r.ident.to_string());
+            out.push(Import {
+                name: r.rename.to_string(),
+                path: path_of(&segments),
+            });
+        }
+        syn::UseTree::Glob(_) => out.push(Import {
+            name: "*".to_string(),
+            path: path_of(&prefix),
+        }),
+        syn::UseTree::Group(g) => {
+            for item in &g.items {
+                collect_imports(module, item, prefix.clone(), out);
+            }
+        }
+    }
+}
+
+/// Rewrites a leading `self`/`super` relative to `module`; other paths are
+/// returned unchanged.
+pub(crate) fn absolute_path(module: &str, path: &str) -> String {
+    let mut base: Vec<&str> = module.split("::").collect();
+    let mut rest: Vec<&str> = path.split("::").collect();
+    match rest.first() {
+        Some(&"self") => {
+            rest.remove(0);
+        }
+        Some(&"super") => {
+            while rest.first() == Some(&"super") {
+                rest.remove(0);
+                if base.len() > 1 {
+                    base.pop();
+                }
+            }
+        }
+        _ => return path.to_string(),
+    }
+    base.extend(rest);
+    base.join("::")
+}
+
+/// Compares module paths segment by segment, so a module's children follow it
+/// directly: `crate::a`, `crate::a::b`, `crate::a1`.
+pub fn module_order(a: &str, b: &str) -> Ordering {
+    a.split("::").cmp(b.split("::"))
+}
+
+/// The order generated output lists types in: module path (see
+/// [`module_order`]), then kind (structs, enums, traits), then name.
+pub fn type_order(a: (&Module, &TypeDef), b: (&Module, &TypeDef)) -> Ordering {
+    module_order(&a.0.path, &b.0.path)
+        .then(a.1.kind.cmp(&b.1.kind))
+        .then_with(|| a.1.name.cmp(&b.1.name))
+}
+
+/// Mermaid node id and label for a path-qualified type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeName {
+    pub id: String,
+    /// Set when the bare name is ambiguous, e.g. `net::Config`.
+    pub label: Option<String>,
+}
+
+/// Assigns diagram ids to path-qualified type names (`crate::net::Config`).
+/// Names declared once keep their bare identifier as id; names declared in
+/// several modules get an id built from the whole path and a `net::Config` label.
+pub fn node_names<'a>(paths: impl IntoIterator<Item = &'a str>) -> BTreeMap<String, NodeName> {
+    let paths: BTreeSet<&str> = paths.into_iter().collect();
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for path in &paths {
+        *counts.entry(bare_name(path)).or_default() += 1;
+    }
+    paths
+        .into_iter()
+        .map(|path| {
+            let name = bare_name(path);
+            let node = if counts[name] == 1 {
+                NodeName {
+                    id: name.to_string(),
+                    label: None,
+                }
+            } else {
+                let relative = path.strip_prefix("crate::").unwrap_or(path);
+                NodeName {
+                    id: relative.replace("::", "_"),
+                    label: Some(relative.to_string()),
+                }
+            };
+            (path.to_string(), node)
+        })
+        .collect()
+}
+
+fn bare_name(path: &str) -> &str {
+    path.rsplit("::").next().unwrap_or(path)
+}
+
+/// The module named by the last `// module:` marker before `pos`, as written
+/// by `architecture` and `extract_traits`; empty when there is none.
+pub fn marked_module(content: &str, pos: usize) -> &str {
+    content[..pos]
+        .rmatch_indices("// module:")
+        .find(|(i, _)| *i == 0 || content[..*i].ends_with('\n'))
+        .and_then(|(i, marker)| content[i + marker.len()..].lines().next())
+        .map_or("", str::trim)
+}
+
+/// Maps `src/net/server.rs` to `crate::net::server`, treating `lib.rs`,
+/// `main.rs` and `mod.rs` as the module of their directory.
+pub fn module_path_for_file(src_dir: &Path, file: &Path) -> String {
+    let relative = file.strip_prefix(src_dir).unwrap_or(file);
+    let mut segments = vec!["crate".to_string()];
+    let components: Vec<String> = relative
+        .with_extension("")
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    for (i, segment) in components.iter().enumerate() {
+        let is_last = i + 1 == components.len();
+        if is_last && (segment == "mod" || (i == 0 && (segment == "lib" || segment == "main"))) {
+            continue;
+        }
+        segments.push(segment.clone());
+    }
+    segments.join("::")
+}
+
+fn fields(fields: &Fields) -> Vec<Field> {
+    fields
+        .iter()
+        .enumerate()
+        .map(|(i, f)| Field {
+            name: f
+                .ident
+                .as_ref()
+                .map_or_else(|| i.to_string(), |ident| ident.to_string()),
+            ty: f.ty.clone(),
+            vis: Vis::from_syn(&f.vis),
+            docs: doc_string(&f.attrs),
+        })
+        .collect()
+}
+
+pub(crate) fn generic_names(generics: &Generics) -> Vec<String> {
+    generics
+        .params
+        .iter()
+        .filter_map(|p| match p {
+            GenericParam::Type(t) => Some(t.ident.to_string()),
+            GenericParam::Const(c) => Some(c.ident.to_string()),
+            GenericParam::Lifetime(_) => None,
+        })
+        .collect()
+}
+
+/// Collects the trait names listed in `#[derive(...)]` attributes.
+pub fn derives(attrs: &[Attribute]) -> Vec<String> {
+    let mut names = Vec::new();
+    for attr in attrs.iter().filter(|a| a.path().is_ident("derive")) {
+        let _ = attr.parse_nested_meta(|meta| {
+            if let Some(last) = meta.path.segments.last() {
+                names.push(last.ident.to_string());
+            }
+            Ok(())
+        });
+    }
+    names
+}
+
+/// True for items gated behind `#[cfg(test)]`.
+pub fn is_cfg_test(attrs: &[Attribute]) -> bool {
+    attrs.iter().any(|a| {
+        a.path().is_ident("cfg")
+            && a.parse_args::<syn::Ident>()
+                .is_ok_and(|ident| ident == "test")
+    })
+}
+
+/// Joins `///` and `//!` doc comments into a single string.
+pub fn doc_string(attrs: &[Attribute]) -> String {
+    let lines: Vec<String> = attrs
+        .iter()
+        .filter(|a| a.path().is_ident("doc"))
+        .filter_map(|a| match &a.meta {
+            Meta::NameValue(nv) => match &nv.value {
+                syn::Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Str(s),
+                    ..
+                }) => Some(s.value()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .map(|line| line.strip_prefix(' ').unwrap_or(&line).to_string())
+        .collect();
+    lines.join("\n").trim().to_string()
+}
+
+/// Renders a syntax node back to source text without the spacing quote adds,
+/// e.g. `Vec < Option < Foo > >` becomes `Vec<Option<Foo>>`.
+pub fn tokens_to_string<T: ToTokens>(node: &T) -> String {
+    static TIDY: OnceLock<Vec<(Regex, &str)>> = OnceLock::new();
+    let tidy = TIDY.get_or_init(|| {
+        [
+            (r"\s*::\s*", "::"),
+            (r"\s*<\s*", "<"),
+            (r"([^-])\s+>", "$1>"),
+            (r"\s+,", ","),
+            (r"&\s+", "&"),
+            (r"(\w)\s+\(", "$1("),
+            (r"\(\s+", "("),
+            (r"\s+\)", ")"),
+            (r"\[\s+", "["),
+            (r"\s+\]", "]"),
+            (r"\s+;", ";"),
+            (r"(\w)\s+:\s", "$1: "),
+            (r",\)", ")"),
+        ]
+        .into_iter()
+        .map(|(pattern, replacement)| (Regex::new(pattern).unwrap(), replacement))
+        .collect()
+    });
+    let mut out = node.to_token_stream().to_string();
+    // Two passes so adjacent closers like `> >` collapse fully.
+    for _ in 0..2 {
+        for (regex, replacement) in tidy {
+            out = regex.replace_all(&out, *replacement).into_owned();
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_module_paths() {
+        let src = Path::new("src");
+        assert_eq!(module_path_for_file(src, Path::new("src/lib.rs")), "crate");
+        assert_eq!(
+            module_path_for_file(src, Path::new("src/net/mod.rs")),
+            "crate::net"
+        );
+        assert_eq!(
+            module_path_for_file(src, Path::new("src/net/server.rs")),
+            "crate::net::server"
+        );
+    }
+
+    #[test]
+    fn test_marked_module() {
+        let content = "trait A {}\n// module: crate::net\ntrait B {}\n    // module: nested\n// module: crate::db \ntrait C {}\n";
+        assert_eq!(marked_module(content, content.find("A").unwrap()), "");
+        assert_eq!(
+            marked_module(content, content.find("B").unwrap()),
+            "crate::net"
+        );
+        assert_eq!(
+            marked_module(content, content.find("C").unwrap()),
+            "crate::db"
+        );
+    }
+
+    #[test]
+    fn test_impls_attach_across_modules() {
+        let source = r#"
+            /// A server.
+            pub struct Server { config: Config }
+            pub struct Config;
+            mod handlers {
+                impl super::Server {
+                    /// Starts serving.
+                    pub fn start(&self) -> Option<Vec<Config>> { None }
+                }
+            }
+        "#;
+        let model = CrateModel::from_source(Path::new("lib.rs"), source).unwrap();
+        let (_, server) = model.find_type("Server", "crate").unwrap();
+        assert_eq!(server.docs, "A server.");
+        assert_eq!(server.methods.len(), 1);
+        assert_eq!(
+            server.methods[0].signature(),
+            "fn start(&self) -> Option<Vec<Config>>"
+        );
+    }
+
+    #[test]
+    fn test_resolve_through_imports() {
+        let source = r#"
+            mod a { pub struct Config; }
+            mod b { pub struct Config; }
+            mod json { pub struct Value; }
+            mod app {
+                use crate::a::Config;
+                use serde_json::Value;
+                use super::b::{self, Config as StoreConfig};
+                pub struct App;
+            }
+            mod impls {
+                use super::app::*;
+                impl App { fn run(&self) {} }
+            }
+        "#;
+        let model = CrateModel::from_source(Path::new("lib.rs"), source).unwrap();
+        let path = |written: &str| {
+            model
+                .resolve("crate::app", written)
+                .map(|(m, t)| format!("{}::{}", m.path, t.name))
+        };
+        assert_eq!(path("Config").as_deref(), Some("crate::a::Config"));
+        assert_eq!(path("StoreConfig").as_deref(), Some("crate::b::Config"));
+        assert_eq!(path("b::Config").as_deref(), Some("crate::b::Config"));
+        assert_eq!(path("self::App").as_deref(), Some("crate::app::App"));
+
+        // A name declared once in the crate is found from anywhere in it, but
+        // paths into other crates never fall back to a local type of that name
+        let from_b = |written: &str| {
+            model
+                .resolve("crate::b", written)
+                .map(|(m, t)| format!("{}::{}", m.path, t.name))
+        };
+        assert_eq!(from_b("Value").as_deref(), Some("crate::json::Value"));
+        assert_eq!(
+            from_b("crate::Value").as_deref(),
+            Some("crate::json::Value")
+        );
+        assert_eq!(from_b("serde_json::Value"), None);
+        assert_eq!(from_b("std::fmt::App"), None);
+        assert_eq!(path("Value"), None);
+
+        let (_, app) = model.resolve("crate", "app::App").unwrap();
+        assert_eq!(app.methods.len(), 1);
+
+        let names = node_names(["crate::a::Config", "crate::b::Config", "crate::app::App"]);
+        assert_eq!(names["crate::app::App"].id, "App");
+        assert_eq!(names["crate::a::Config"].id, "a_Config");
+        assert_eq!(
+            names["crate::a::Config"].label.as_deref(),
+            Some("a::Config")
+        );
+    }
+
+    #[test]
+    fn test_types_in_documented_order() {
+        let source = r#"
+            mod a1 { pub struct First; }
+            pub trait Shape {}
+            pub enum Color { Red }
+            pub struct Zebra;
+            pub struct Apple;
+            mod a {
+                mod b { pub struct Inner; }
+                pub enum Kind { One }
+            }
+        "#;
+        let model = CrateModel::from_source(Path::new("lib.rs"), source).unwrap();
+        let order: Vec<String> = model
+            .types()
+            .map(|(m, t)| format!("{}::{}", m.path, t.name))
+            .collect();
+        assert_eq!(
+            order,
+            [
+                "crate::Apple",
+                "crate::Zebra",
+                "crate::Color",
+                "crate::Shape",
+                "crate::a::Kind",
+                "crate::a::b::Inner",
+                "crate::a1::First",
+            ]
+        );
+    }
+}
@@ -0,0 +1,672 @@
+//! Database schemas recovered from ORM definitions, for `erDiagram` output.
+//!
+//! Tables come from diesel `table!` invocations and from structs deriving one
+//! of the row traits in [`ROW_DERIVES`] (diesel `Queryable`, sqlx `FromRow`,
+//! sea-orm `DeriveEntityModel`, ...). Foreign keys come from `joinable!`,
+//! `#[diesel(belongs_to(..))]`, and `<table>_id` columns naming a known table.
+//! A `table!` definition wins over a struct mapping the same table.
+//!
+//! The attribute helpers and [`er_diagram`] are shared with `not_state_diagram`,
+//! which draws plain structs the same way.
+
+use crate::model::{derives, tokens_to_string};
+use crate::types::display_type;
+use proc_macro2::{Delimiter, TokenStream, TokenTree};
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+use syn::{Attribute, Fields, GenericArgument, Item, ItemStruct, PathArguments, Type};
+
+/// Derives marking a struct as a database row.
+pub const ROW_DERIVES: &[&str] = &[
+    "FromRow",
+    "Queryable",
+    "QueryableByName",
+    "Selectable",
+    "Insertable",
+    "Identifiable",
+    "DeriveEntityModel",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Column {
+    pub name: String,
+    /// SQL type for `table!` columns, Rust type for struct fields.
+    pub ty: String,
+    pub nullable: bool,
+    pub primary_key: bool,
+    /// The table this column references.
+    pub references: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Table {
+    pub name: String,
+    /// The module the table was declared in, e.g. `crate::schema`.
+    pub module: String,
+    pub columns: Vec<Column>,
+}
+
+#[derive(Debug, Default)]
+pub struct Schema {
+    /// Tables keyed by SQL name.
+    pub tables: BTreeMap<String, Table>,
+}
+
+/// A table, struct or enum drawn with its attribute block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entity {
+    pub name: String,
+    pub attributes: Vec<EntityAttribute>,
+}
+
+/// One line of an entity block, e.g. `Uuid customer_id FK "optional"`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntityAttribute {
+    pub ty: String,
+    pub name: String,
+    /// `PK`, `FK` and/or `UK`.
+    pub keys: Vec<&'static str>,
+    pub comment: Option<String>,
+    /// The entity a foreign key points at, when known.
+    pub references: Option<String>,
+}
+
+/// A relationship between two entities via a field or column.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Relationship {
+    pub source: String,
+    pub field: String,
+    pub target: String,
+    /// e.g. `||--||` or `}o--o|`.
+    pub cardinality: String,
+}
+
+/// What the ORM attributes on a field say about its column.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ColumnAttributes {
+    /// `#[sqlx(skip)]`, `#[serde(skip)]`, ...
+    pub skip: bool,
+    /// `rename = "..."` or `column_name = "..."`.
+    pub rename: Option<String>,
+    pub primary_key: bool,
+    pub unique: bool,
+}
+
+impl ColumnAttributes {
+    /// Reads a field's attributes as joined by [`orm_attributes`].
+    pub fn parse(orm: &str) -> Self {
+        static SKIP: OnceLock<Regex> = OnceLock::new();
+        static RENAME: OnceLock<Regex> = OnceLock::new();
+        static PRIMARY_KEY: OnceLock<Regex> = OnceLock::new();
+        static UNIQUE: OnceLock<Regex> = OnceLock::new();
+        ColumnAttributes {
+            skip: regex(&SKIP, r"\bskip\b").is_match(orm),
+            rename: regex(&RENAME, r#"(?:rename|column_name)\s*=\s*"?(\w+)"?"#)
+                .captures(orm)
+                .map(|cap| cap[1].to_string()),
+            primary_key: regex(&PRIMARY_KEY, r"\bprimary_key\b").is_match(orm),
+            unique: regex(&UNIQUE, r"\bunique\b").is_match(orm),
+        }
+    }
+}
+
+/// A struct-side table, kept until every struct has been seen so
+/// `belongs_to(Parent)` can be resolved to the parent's table.
+struct RowStruct {
+    rust_name: String,
+    table: Table,
+    belongs_to: Vec<(String, String)>,
+}
+
+impl Schema {
+    /// Collects the tables defined in `files`, including nested inline modules.
+    /// Each file comes with its module path, e.g. `crate::db`.
+    pub fn from_files<'a>(files: impl IntoIterator<Item = (&'a str, &'a syn::File)>) -> Self {
+        let mut schema = Schema::default();
+        let mut joins = Vec::new();
+        let mut rows = Vec::new();
+        for (module, file) in files {
+            schema.collect(module, &file.items, &mut joins, &mut rows);
+        }
+
+        let table_of: BTreeMap<String, String> = rows
+            .iter()
+            .map(|r| (r.rust_name.clone(), r.table.name.clone()))
+            .collect();
+        for row in rows {
+            for (parent, column) in &row.belongs_to {
+                let parent = table_of
+                    .get(parent)
+                    .cloned()
+                    .unwrap_or_else(|| to_table_name(parent));
+                joins.push((row.table.name.clone(), parent, column.clone()));
+            }
+            // table! is the source of truth for the columns it declares
+            schema
+                .tables
+                .entry(row.table.name.clone())
+                .or_insert(row.table);
+        }
+
+        for (child, parent, column) in joins {
+            if let Some(col) = schema
+                .tables
+                .get_mut(&child)
+                .and_then(|t| t.columns.iter_mut().find(|c| c.name == column))
+            {
+                col.references = Some(parent);
+            }
+        }
+        schema.infer_foreign_keys();
+        schema
+    }
+
+    fn collect(
+        &mut self,
+        module: &str,
+        items: &[Item],
+        joins: &mut Vec<(String, String, String)>,
+        rows: &mut Vec<RowStruct>,
+    ) {
+        for item in items {
+            match item {
+                Item::Macro(m) => {
+                    let name = m.mac.path.segments.last().map(|s| s.ident.to_string());
+                    match name.as_deref() {
+                        Some("table") => {
+                            for mut table in parse_tables(m.mac.tokens.clone()) {
+                                table.module = module.to_string();
+                                self.tables.insert(table.name.clone(), table);
+                            }
+                        }
+                        Some("joinable") => joins.extend(parse_joinable(m.mac.tokens.clone())),
+                        _ => {}
+                    }
+                }
+                Item::Struct(s) if is_row_struct(s) => {
+                    let mut row = row_struct(s);
+                    row.table.module = module.to_string();
+                    rows.push(row);
+                }
+                Item::Mod(m) => {
+                    if let Some((_, items)) = &m.content {
+                        let module = format!("{}::{}", module, m.ident);
+                        self.collect(&module, items, joins, rows);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Marks `user_id` as referencing `users` (or `user`) when no explicit
+    /// foreign key was declared for it.
+    fn infer_foreign_keys(&mut self) {
+        let names: Vec<String> = self.tables.keys().cloned().collect();
+        for table in self.tables.values_mut() {
+            for col in &mut table.columns {
+                if col.primary_key || col.references.is_some() {
+                    continue;
+                }
+                let Some(prefix) = col.name.strip_suffix("_id") else {
+                    continue;
+                };
+                col.references = [format!("{}s", prefix), prefix.to_string()]
+                    .into_iter()
+                    .find(|candidate| names.contains(candidate));
+            }
+        }
+    }
+
+    /// The tables as ER entities, with one relationship per foreign key
+    /// between two of them.
+    pub fn entities(&self) -> (Vec<Entity>, Vec<Relationship>) {
+        let entities = self
+            .tables
+            .values()
+            .map(|t| Entity {
+                name: t.name.clone(),
+                attributes: t
+                    .columns
+                    .iter()
+                    .map(|c| {
+                        let mut keys = Vec::new();
+                        if c.primary_key {
+                            keys.push("PK");
+                        }
+                        if c.references.is_some() {
+                            keys.push("FK");
+                        }
+                        EntityAttribute {
+                            ty: c.ty.clone(),
+                            name: c.name.clone(),
+                            keys,
+                            comment: c.nullable.then(|| "nullable".to_string()),
+                            references: c.references.clone(),
+                        }
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        let relationships = self
+            .foreign_keys()
+            .filter(|(_, parent, _)| self.tables.contains_key(*parent))
+            .map(|(child, parent, col)| Relationship {
+                source: child.to_string(),
+                field: col.name.clone(),
+                target: parent.to_string(),
+                cardinality: foreign_key_cardinality(col.nullable).to_string(),
+            })
+            .collect();
+
+        (entities, relationships)
+    }
+
+    /// Every `(child table, parent table, column)` foreign key.
+    pub fn foreign_keys(&self) -> impl Iterator<Item = (&str, &str, &Column)> {
+        self.tables.values().flat_map(|t| {
+            t.columns.iter().filter_map(move |c| {
+                c.references
+                    .as_deref()
+                    .map(|parent| (t.name.as_str(), parent, c))
+            })
+        })
+    }
+}
+
+fn is_row_struct(s: &ItemStruct) -> bool {
+    derives(&s.attrs)
+        .iter()
+        .any(|d| ROW_DERIVES.contains(&d.as_str()))
+}
+
+fn regex(cell: &'static OnceLock<Regex>, pattern: &str) -> &'static Regex {
+    cell.get_or_init(|| Regex::new(pattern).unwrap())
+}
+
+/// The contents of the ORM attributes on an item (`#[diesel(..)]`,
+/// `#[sqlx(..)]`, `#[sea_orm(..)]`, `#[table_name = ..]`, `#[primary_key(..)]`)
+/// as one string.
+pub fn orm_attributes(attrs: &[Attribute]) -> String {
+    attrs
+        .iter()
+        .filter(|a| {
+            ["diesel", "sqlx", "sea_orm", "table_name", "primary_key"]
+                .iter()
+                .any(|name| a.path().is_ident(name))
+        })
+        .map(|a| tokens_to_string(&a.meta))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Columns named by a struct-level `#[diesel(primary_key(a, b))]`.
+pub fn primary_keys(attrs: &[Attribute]) -> Vec<String> {
+    static PRIMARY_KEYS: OnceLock<Regex> = OnceLock::new();
+    regex(&PRIMARY_KEYS, r"primary_key\s*\(([^)]*)\)")
+        .captures(&orm_attributes(attrs))
+        .map(|cap| cap[1].split(',').map(|k| k.trim().to_string()).collect())
+        .unwrap_or_default()
+}
+
+/// `(parent, foreign key column)` for each struct-level
+/// `#[diesel(belongs_to(Parent, foreign_key = col))]`; the column defaults
+/// to `parent_id`.
+pub fn belongs_to(attrs: &[Attribute]) -> Vec<(String, String)> {
+    static BELONGS_TO: OnceLock<Regex> = OnceLock::new();
+    regex(
+        &BELONGS_TO,
+        r"belongs_to\s*\(\s*(\w+)\s*(?:,\s*foreign_key\s*=\s*(\w+))?",
+    )
+    .captures_iter(&orm_attributes(attrs))
+    .map(|cap| {
+        let parent = cap[1].to_string();
+        let column = cap
+            .get(2)
+            .map(|c| c.as_str().to_string())
+            .unwrap_or_else(|| format!("{}_id", to_snake_case(&parent)));
+        (parent, column)
+    })
+    .collect()
+}
+
+/// Many rows point at one parent; `o|` when the key may be null.
+pub fn foreign_key_cardinality(nullable: bool) -> &'static str {
+    if nullable {
+        "}o--o|"
+    } else {
+        "}o--||"
+    }
+}
+
+/// Renders entities and relationships as a Mermaid `erDiagram`. Entities
+/// without attributes are drawn bare.
+pub fn er_diagram(entities: &[Entity], relationships: &[Relationship]) -> String {
+    let mut out = String::from("erDiagram\n");
+    for entity in entities {
+        if entity.attributes.is_empty() {
+            out.push_str(&format!("    {}\n", entity.name));
+            continue;
+        }
+        out.push_str(&format!("    {} {{\n", entity.name));
+        for attr in &entity.attributes {
+            out.push_str(&format!("        {} {}", attr.ty, attr.name));
+            if !attr.keys.is_empty() {
+                out.push_str(&format!(" {}", attr.keys.join(", ")));
+            }
+            if let Some(comment) = &attr.comment {
+                out.push_str(&format!(" \"{}\"", comment));
+            }
+            out.push('\n');
+        }
+        out.push_str("    }\n");
+    }
+    for rel in relationships {
+        out.push_str(&format!(
+            "    {} {} {} : \"{}\"\n",
+            rel.source, rel.cardinality, rel.target, rel.field
+        ));
+    }
+    out
+}
+
+fn row_struct(s: &ItemStruct) -> RowStruct {
+    let rust_name = s.ident.to_string();
+    let orm = orm_attributes(&s.attrs);
+
+    // #[diesel(table_name = posts)], #[table_name = "posts"], #[sea_orm(table_name = "posts")]
+    static TABLE_NAME: OnceLock<Regex> = OnceLock::new();
+    let name = regex(&TABLE_NAME, r#"table_name\s*=\s*"?([\w.:]+)"?"#)
+        .captures(&orm)
+        .map(|cap| cap[1].rsplit("::").next().unwrap_or(&cap[1]).to_string())
+        .unwrap_or_else(|| to_table_name(&rust_name));
+
+    let keys = primary_keys(&s.attrs);
+    let belongs_to = belongs_to(&s.attrs);
+
+    let mut columns = Vec::new();
+    if let Fields::Named(fields) = &s.fields {
+        for field in &fields.named {
+            let column = ColumnAttributes::parse(&orm_attributes(&field.attrs));
+            if column.skip {
+                continue;
+            }
+            let ident = field
+                .ident
+                .as_ref()
+                .map(|i| i.to_string())
+                .unwrap_or_default();
+            let name = column.rename.unwrap_or(ident);
+            let (ty, nullable) = match option_inner(&field.ty) {
+                Some(inner) => (inner, true),
+                None => (&field.ty, false),
+            };
+            let primary_key =
+                keys.contains(&name) || (keys.is_empty() && name == "id") || column.primary_key;
+            columns.push(Column {
+                name,
+                ty: display_type(ty).replace(' ', ""),
+                nullable,
+                primary_key,
+                references: None,
+            });
+        }
+    }
+
+    RowStruct {
+        rust_name,
+        table: Table {
+            name,
+            columns,
+            ..Table::default()
+        },
+        belongs_to,
+    }
+}
+
+fn option_inner(ty: &Type) -> Option<&Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let last = path.path.segments.last()?;
+    if last.ident != "Option" {
+        return None;
+    }
+    match &last.arguments {
+        PathArguments::AngleBracketed(args) => args.args.iter().find_map(|arg| match arg {
+            GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        }),
+        _ => None,
+    }
+}
+
+/// Parses the body of a `table!` invocation, which may hold several tables:
+///
+/// ```text
+/// use diesel::sql_types::*;
+/// #[sql_name = "people"]
+/// users (id) {
+///     id -> Int4,
+///     email -> Nullable<Text>,
+/// }
+/// ```
+fn parse_tables(tokens: TokenStream) -> Vec<Table> {
+    let mut tables = Vec::new();
+    let mut name = String::new();
+    let mut keys: Vec<String> = Vec::new();
+    let mut tokens = tokens.into_iter().peekable();
+
+    while let Some(token) = tokens.next() {
+        match token {
+            TokenTree::Ident(ident) if ident == "use" => {
+                // Skip imports up to their `;`
+                for t in tokens.by_ref() {
+                    if matches!(&t, TokenTree::Punct(p) if p.as_char() == ';') {
+                        break;
+                    }
+                }
+            }
+            TokenTree::Punct(p) if p.as_char() == '#' => {
+                tokens.next(); // the [..] attribute group
+            }
+            // `schema.table` keeps only the table name
+            TokenTree::Ident(ident) => name = ident.to_string(),
+            TokenTree::Group(group) if group.delimiter() == Delimiter::Parenthesis => {
+                keys = group
+                    .stream()
+                    .into_iter()
+                    .filter_map(|t| match t {
+                        TokenTree::Ident(i) => Some(i.to_string()),
+                        _ => None,
+                    })
+                    .collect();
+            }
+            TokenTree::Group(group) if group.delimiter() == Delimiter::Brace => {
+                if keys.is_empty() {
+                    keys.push("id".to_string());
+                }
+                let columns = parse_columns(group.stream(), &keys);
+                tables.push(Table {
+                    name: std::mem::take(&mut name),
+                    columns,
+                    ..Table::default()
+                });
+                keys.clear();
+            }
+            _ => {}
+        }
+    }
+    tables
+}
+
+/// Parses `name -> Type,` column declarations, skipping `#[sql_name]` attributes.
+fn parse_columns(tokens: TokenStream, keys: &[String]) -> Vec<Column> {
+    let mut columns = Vec::new();
+    let mut current: Vec<TokenTree> = Vec::new();
+    let mut tokens = tokens.into_iter().peekable();
+
+    let mut finish = |current: &mut Vec<TokenTree>| {
+        let mut parts = current.drain(..);
+        let Some(TokenTree::Ident(name)) = parts.next() else {
+            return;
+        };
+        // Drop the `->` arrow
+        let ty: String = parts
+            .skip(2)
+            .map(|t| t.to_string())
+            .collect::<Vec<_>>()
+            .join("");
+        let (ty, nullable) = match ty
+            .strip_prefix("Nullable<")
+            .and_then(|t| t.strip_suffix('>'))
+        {
+            Some(inner) => (inner.to_string(), true),
+            None => (ty, false),
+        };
+        let name = name.to_string();
+        columns.push(Column {
+            primary_key: keys.contains(&name),
+            name,
+            ty,
+            nullable,
+            references: None,
+        });
+    };
+
+    while let Some(token) = tokens.next() {
+        match &token {
+            TokenTree::Punct(p) if p.as_char() == '#' => {
+                tokens.next();
+            }
+            TokenTree::Punct(p) if p.as_char() == ',' => finish(&mut current),
+            _ => current.push(token),
+        }
+    }
+    finish(&mut current);
+    columns
+}
+
+/// Parses `joinable!(posts -> users (user_id))` into `(child, parent, column)`.
+fn parse_joinable(tokens: TokenStream) -> Option<(String, String, String)> {
+    let idents: Vec<String> = tokens
+        .into_iter()
+        .flat_map(|t| match t {
+            TokenTree::Group(group) => group.stream().into_iter().collect(),
+            other => vec![other],
+        })
+        .filter_map(|t| match t {
+            TokenTree::Ident(i) => Some(i.to_string()),
+            _ => None,
+        })
+        .collect();
+    match idents.as_slice() {
+        [child, parent, column] => Some((child.clone(), parent.clone(), column.clone())),
+        _ => None,
+    }
+}
+
+/// `OrderLine` to `order_line`.
+pub fn to_snake_case(name: &str) -> String {
+    let mut out = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                out.push('_');
+            }
+            out.extend(c.to_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// The default table for a struct: `OrderLine` maps to `order_lines`.
+fn to_table_name(name: &str) -> String {
+    let snake = to_snake_case(name);
+    if snake.ends_with('s') {
+        snake
+    } else {
+        format!("{}s", snake)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tables_joins_and_row_structs() {
+        let schema_rs: syn::File = syn::parse_str(
+            r#"
+            diesel::table! {
+                users (id) {
+                    id -> Int4,
+                    email -> Nullable<Text>,
+                }
+            }
+            diesel::table! {
+                posts (id) {
+                    id -> Int4,
+                    #[sql_name = "author"]
+                    user_id -> Int4,
+                    title -> Varchar,
+                }
+            }
+            diesel::joinable!(posts -> users (user_id));
+            "#,
+        )
+        .unwrap();
+        let models_rs: syn::File = syn::parse_str(
+            r#"
+            #[derive(Queryable)]
+            #[diesel(table_name = posts)]
+            pub struct Post { pub id: i32, pub ignored: String }
+
+            #[derive(sqlx::FromRow)]
+            pub struct Comment {
+                pub id: i64,
+                pub post_id: i32,
+                #[sqlx(rename = "body")]
+                pub text: Option<String>,
+            }
+            "#,
+        )
+        .unwrap();
+
+        let schema = Schema::from_files([("crate", &schema_rs), ("crate::models", &models_rs)]);
+        assert_eq!(
+            schema.tables.keys().collect::<Vec<_>>(),
+            ["comments", "posts", "users"]
+        );
+        // The table! definition is kept over the Post struct
+        assert_eq!(schema.tables["posts"].columns.len(), 3);
+        assert_eq!(schema.tables["posts"].module, "crate");
+        assert_eq!(schema.tables["comments"].module, "crate::models");
+
+        let (entities, relationships) = schema.entities();
+        assert_eq!(
+            er_diagram(&entities, &relationships),
+            "erDiagram\n\
+             \x20   comments {\n\
+             \x20       i64 id PK\n\
+             \x20       i32 post_id FK\n\
+             \x20       String body \"nullable\"\n\
+             \x20   }\n\
+             \x20   posts {\n\
+             \x20       Int4 id PK\n\
+             \x20       Int4 user_id FK\n\
+             \x20       Varchar title\n\
+             \x20   }\n\
+             \x20   users {\n\
+             \x20       Int4 id PK\n\
+             \x20       Text email \"nullable\"\n\
+             \x20   }\n\
+             \x20   comments }o--|| posts : \"post_id\"\n\
+             \x20   posts }o--|| users : \"user_id\"\n"
+        );
+    }
+}
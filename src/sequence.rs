@@ -0,0 +1,673 @@
+//! Sequence diagrams recovered from a method's call chain.
+//!
+//! Starting from an entry method such as `Server::handle_request`, calls on
+//! types of the crate are followed into their bodies up to a depth limit.
+//! Receivers are typed from `self`, parameters, `let` bindings and fields,
+//! looking through wrappers like `Arc<Mutex<Db>>`. Branches become `alt`/`opt`
+//! blocks, `?` an `opt` block returning the error, and loops `loop` blocks;
+//! blocks without any call in them are left out.
+
+use crate::model::{node_names, tokens_to_string, CrateModel, Method, Module, TypeDef};
+use crate::types::{display_type, TypeAnalyzer};
+use crate::Result;
+use anyhow::anyhow;
+use regex::Regex;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::OnceLock;
+use syn::{Block, Expr, FnArg, Member, Pat, ReturnType, Stmt, Type};
+
+/// The participant standing for whoever calls the entry method.
+pub const CALLER: &str = "Caller";
+
+/// Longest condition or pattern text shown on a block before it is cut.
+const MAX_LABEL: usize = 40;
+
+/// Builds the `sequenceDiagram` for `entry`, written `Type::method` or with a
+/// module path (`net::Server::handle`). Calls are followed `depth` levels deep.
+pub fn sequence_diagram(model: &CrateModel, entry: &str, depth: usize) -> Result<String> {
+    let (type_path, method_name) = entry
+        .rsplit_once("::")
+        .ok_or_else(|| anyhow!("Entry point '{}' must be written Type::method", entry))?;
+    let (module, ty) = model
+        .resolve("crate", type_path)
+        .ok_or_else(|| anyhow!("No type '{}' found", type_path))?;
+    let method = ty
+        .methods
+        .iter()
+        .find(|m| m.name == method_name)
+        .ok_or_else(|| anyhow!("Type '{}' has no method '{}'", ty.name, method_name))?;
+
+    let mut builder = Builder {
+        model,
+        analyzer: TypeAnalyzer::from_model(model),
+        types: model
+            .types()
+            .map(|(m, t)| (format!("{}::{}", m.path, t.name), (m, t)))
+            .collect(),
+        depth,
+        participants: Vec::new(),
+        stack: Vec::new(),
+        lines: Vec::new(),
+    };
+    let path = format!("{}::{}", module.path, ty.name);
+    builder.call(CALLER, &path, method, 0);
+
+    Ok(builder.render())
+}
+
+/// One line of the diagram body; blocks are indented when rendered.
+#[derive(Debug, Clone)]
+enum Line {
+    Message {
+        from: String,
+        to: String,
+        arrow: &'static str,
+        text: String,
+    },
+    Open(String),
+    Else(String),
+    End,
+}
+
+static SELF_TYPE: OnceLock<Regex> = OnceLock::new();
+
+fn regex(cell: &'static OnceLock<Regex>, pattern: &str) -> &'static Regex {
+    cell.get_or_init(|| Regex::new(pattern).unwrap())
+}
+
+/// The method being walked: its type, its caller and the known types of its locals.
+struct Frame<'m> {
+    path: String,
+    module: &'m Module,
+    caller: String,
+    scope: HashMap<String, String>,
+}
+
+struct Builder<'m> {
+    model: &'m CrateModel,
+    analyzer: TypeAnalyzer,
+    types: BTreeMap<String, (&'m Module, &'m TypeDef)>,
+    depth: usize,
+    /// Type paths in order of first appearance.
+    participants: Vec<String>,
+    /// `Type::method` keys being walked, to stop at recursion.
+    stack: Vec<String>,
+    lines: Vec<Line>,
+}
+
+impl<'m> Builder<'m> {
+    /// Emits `from->>to: method(params)`, walks the callee's body when still
+    /// within the depth limit, then emits the reply if it returns something.
+    fn call(&mut self, from: &str, to: &str, method: &'m Method, level: usize) {
+        self.participate(from);
+        self.participate(to);
+
+        let params: Vec<String> = method
+            .sig
+            .inputs
+            .iter()
+            .filter_map(|input| match input {
+                FnArg::Typed(pat_type) => match &*pat_type.pat {
+                    Pat::Ident(ident) => Some(ident.ident.to_string()),
+                    _ => None,
+                },
+                FnArg::Receiver(_) => None,
+            })
+            .collect();
+        self.lines.push(Line::Message {
+            from: from.to_string(),
+            to: to.to_string(),
+            arrow: "->>",
+            text: format!("{}({})", method.name, params.join(", ")),
+        });
+
+        let key = format!("{}::{}", to, method.name);
+        if level < self.depth && !self.stack.contains(&key) {
+            if let (Some(body), Some(&(module, _))) = (&method.body, self.types.get(to)) {
+                let mut frame = Frame {
+                    path: to.to_string(),
+                    module,
+                    caller: from.to_string(),
+                    scope: HashMap::new(),
+                };
+                frame.scope.insert("self".to_string(), to.to_string());
+                for input in &method.sig.inputs {
+                    if let FnArg::Typed(pat_type) = input {
+                        if let (Pat::Ident(ident), Some(ty)) =
+                            (&*pat_type.pat, self.resolve_type(&frame, &pat_type.ty))
+                        {
+                            frame.scope.insert(ident.ident.to_string(), ty);
+                        }
+                    }
+                }
+
+                self.stack.push(key);
+                self.block(&mut frame, body, level + 1);
+                self.stack.pop();
+            }
+        }
+
+        if let ReturnType::Type(_, ret) = &method.sig.output {
+            if from != to {
+                self.lines.push(Line::Message {
+                    from: to.to_string(),
+                    to: from.to_string(),
+                    arrow: "-->>",
+                    text: regex(&SELF_TYPE, r"\bSelf\b")
+                        .replace_all(&display_type(ret), short_name(to))
+                        .into_owned(),
+                });
+            }
+        }
+    }
+
+    fn participate(&mut self, path: &str) {
+        if path != CALLER && !self.participants.iter().any(|p| p == path) {
+            self.participants.push(path.to_string());
+        }
+    }
+
+    fn block(&mut self, frame: &mut Frame<'m>, block: &Block, level: usize) {
+        for stmt in &block.stmts {
+            match stmt {
+                Stmt::Local(local) => {
+                    let Some(init) = &local.init else {
+                        continue;
+                    };
+                    self.expr(frame, &init.expr, level);
+                    let (name, annotated) = match &local.pat {
+                        Pat::Ident(ident) => (ident.ident.to_string(), None),
+                        Pat::Type(typed) => match &*typed.pat {
+                            Pat::Ident(ident) => (ident.ident.to_string(), Some(&*typed.ty)),
+                            _ => continue,
+                        },
+                        _ => continue,
+                    };
+                    let ty = match annotated {
+                        Some(ty) => self.resolve_type(frame, ty),
+                        None => self.expr_type(frame, &init.expr),
+                    };
+                    if let Some(ty) = ty {
+                        frame.scope.insert(name, ty);
+                    }
+                }
+                Stmt::Expr(expr, _) => self.expr(frame, expr, level),
+                Stmt::Item(_) | Stmt::Macro(_) => {}
+            }
+        }
+    }
+
+    fn expr(&mut self, frame: &mut Frame<'m>, expr: &Expr, level: usize) {
+        match expr {
+            Expr::MethodCall(call) => {
+                self.expr(frame, &call.receiver, level);
+                for arg in &call.args {
+                    self.expr(frame, arg, level);
+                }
+                if let Some(target) = self.expr_type(frame, &call.receiver) {
+                    if let Some(method) = self.method(&target, &call.method.to_string()) {
+                        let from = frame.path.clone();
+                        self.call(&from, &target, method, level);
+                    }
+                }
+            }
+            Expr::Call(call) => {
+                for arg in &call.args {
+                    self.expr(frame, arg, level);
+                }
+                if let Some((target, method)) = self.path_call(frame, &call.func) {
+                    let from = frame.path.clone();
+                    self.call(&from, &target, method, level);
+                }
+            }
+            Expr::Try(t) => {
+                let start = self.lines.len();
+                self.expr(frame, &t.expr, level);
+                if self.lines.len() > start {
+                    self.lines.push(Line::Open("opt error".to_string()));
+                    self.lines.push(Line::Message {
+                        from: frame.path.clone(),
+                        to: frame.caller.clone(),
+                        arrow: "-->>",
+                        text: "Err".to_string(),
+                    });
+                    self.lines.push(Line::End);
+                }
+            }
+            Expr::If(i) => {
+                self.expr(frame, &i.cond, level);
+                let cond = label(&i.cond);
+                let mut branches = vec![(
+                    cond.clone(),
+                    self.branch(frame, |b, f| b.block(f, &i.then_branch, level)),
+                )];
+                if let Some((_, else_branch)) = &i.else_branch {
+                    branches.push((
+                        format!("not {}", cond),
+                        self.branch(frame, |b, f| b.expr(f, else_branch, level)),
+                    ));
+                }
+                self.blocks(branches);
+            }
+            Expr::Match(m) => {
+                self.expr(frame, &m.expr, level);
+                let branches = m
+                    .arms
+                    .iter()
+                    .map(|arm| {
+                        (
+                            label(&arm.pat),
+                            self.branch(frame, |b, f| b.expr(f, &arm.body, level)),
+                        )
+                    })
+                    .collect();
+                self.blocks(branches);
+            }
+            Expr::ForLoop(f) => {
+                self.expr(frame, &f.expr, level);
+                let text = format!("for {} in {}", label(&f.pat), label(&f.expr));
+                let body = self.branch(frame, |b, fr| b.block(fr, &f.body, level));
+                self.looped(text, body);
+            }
+            Expr::While(w) => {
+                self.expr(frame, &w.cond, level);
+                let text = format!("while {}", label(&w.cond));
+                let body = self.branch(frame, |b, f| b.block(f, &w.body, level));
+                self.looped(text, body);
+            }
+            Expr::Loop(l) => {
+                let body = self.branch(frame, |b, f| b.block(f, &l.body, level));
+                self.looped("loop".to_string(), body);
+            }
+            Expr::Block(b) => self.block(frame, &b.block, level),
+            Expr::Unsafe(u) => self.block(frame, &u.block, level),
+            Expr::Async(a) => self.block(frame, &a.block, level),
+            Expr::Closure(c) => self.expr(frame, &c.body, level),
+            Expr::Let(l) => self.expr(frame, &l.expr, level),
+            Expr::Assign(a) => self.expr(frame, &a.right, level),
+            Expr::Binary(b) => {
+                self.expr(frame, &b.left, level);
+                self.expr(frame, &b.right, level);
+            }
+            Expr::Return(r) => {
+                if let Some(e) = &r.expr {
+                    self.expr(frame, e, level);
+                }
+            }
+            Expr::Struct(s) => {
+                for field in &s.fields {
+                    self.expr(frame, &field.expr, level);
+                }
+            }
+            Expr::Tuple(t) => t.elems.iter().for_each(|e| self.expr(frame, e, level)),
+            Expr::Array(a) => a.elems.iter().for_each(|e| self.expr(frame, e, level)),
+            Expr::Paren(p) => self.expr(frame, &p.expr, level),
+            Expr::Group(g) => self.expr(frame, &g.expr, level),
+            Expr::Reference(r) => self.expr(frame, &r.expr, level),
+            Expr::Unary(u) => self.expr(frame, &u.expr, level),
+            Expr::Await(a) => self.expr(frame, &a.base, level),
+            Expr::Field(f) => self.expr(frame, &f.base, level),
+            Expr::Cast(c) => self.expr(frame, &c.expr, level),
+            _ => {}
+        }
+    }
+
+    /// Runs `walk` with a copy of the frame and returns the lines it produced.
+    fn branch(
+        &mut self,
+        frame: &Frame<'m>,
+        walk: impl FnOnce(&mut Self, &mut Frame<'m>),
+    ) -> Vec<Line> {
+        let mut inner = Frame {
+            path: frame.path.clone(),
+            module: frame.module,
+            caller: frame.caller.clone(),
+            scope: frame.scope.clone(),
+        };
+        let start = self.lines.len();
+        walk(self, &mut inner);
+        self.lines.split_off(start)
+    }
+
+    /// Emits the branches that made calls: `opt` for one, `alt`/`else` for several.
+    fn blocks(&mut self, branches: Vec<(String, Vec<Line>)>) {
+        let taken: Vec<(String, Vec<Line>)> = branches
+            .into_iter()
+            .filter(|(_, lines)| !lines.is_empty())
+            .collect();
+        for (i, (text, lines)) in taken.iter().enumerate() {
+            match (i, taken.len()) {
+                (0, 1) => self.lines.push(Line::Open(format!("opt {}", text))),
+                (0, _) => self.lines.push(Line::Open(format!("alt {}", text))),
+                _ => self.lines.push(Line::Else(text.clone())),
+            }
+            self.lines.extend(lines.iter().cloned());
+        }
+        if !taken.is_empty() {
+            self.lines.push(Line::End);
+        }
+    }
+
+    fn looped(&mut self, text: String, body: Vec<Line>) {
+        if !body.is_empty() {
+            self.lines.push(Line::Open(format!("loop {}", text)));
+            self.lines.extend(body);
+            self.lines.push(Line::End);
+        }
+    }
+
+    fn method(&self, path: &str, name: &str) -> Option<&'m Method> {
+        let (_, ty) = self.types.get(path)?;
+        ty.methods.iter().find(|m| m.name == name)
+    }
+
+    /// `Type::method` or `Self::method` written as a call's function.
+    fn path_call(&self, frame: &Frame<'m>, func: &Expr) -> Option<(String, &'m Method)> {
+        let (target, name) = self.path_target(frame, func)?;
+        let method = self.method(&target, &name)?;
+        Some((target, method))
+    }
+
+    /// The type and function name of a `Type::function` path.
+    fn path_target(&self, frame: &Frame<'m>, func: &Expr) -> Option<(String, String)> {
+        let Expr::Path(p) = func else {
+            return None;
+        };
+        let segments: Vec<String> = p
+            .path
+            .segments
+            .iter()
+            .map(|s| s.ident.to_string())
+            .collect();
+        let (name, owner) = segments.split_last()?;
+        if owner.is_empty() {
+            return None;
+        }
+        let target = if owner == ["Self"] {
+            frame.path.clone()
+        } else {
+            let (m, t) = self.model.resolve(&frame.module.path, &owner.join("::"))?;
+            format!("{}::{}", m.path, t.name)
+        };
+        Some((target, name.clone()))
+    }
+
+    /// The crate type an expression evaluates to, when it can be told.
+    fn expr_type(&self, frame: &Frame<'m>, expr: &Expr) -> Option<String> {
+        match expr {
+            Expr::Path(p) => {
+                let ident = p.path.get_ident()?;
+                frame.scope.get(&ident.to_string()).cloned()
+            }
+            Expr::Field(f) => {
+                let owner = self.expr_type(frame, &f.base)?;
+                let (module, ty) = *self.types.get(&owner)?;
+                let name = match &f.member {
+                    Member::Named(ident) => ident.to_string(),
+                    Member::Unnamed(index) => index.index.to_string(),
+                };
+                let field = ty.fields.iter().find(|field| field.name == name)?;
+                self.resolve_in(module, &owner, &field.ty)
+            }
+            Expr::MethodCall(call) => {
+                let receiver = self.expr_type(frame, &call.receiver)?;
+                match self.method(&receiver, &call.method.to_string()) {
+                    Some(method) => self.return_type(&receiver, method),
+                    // `lock()`, `unwrap()`, `clone()` and friends keep the type
+                    None => Some(receiver),
+                }
+            }
+            Expr::Call(call) => {
+                let (target, name) = self.path_target(frame, &call.func)?;
+                match self.method(&target, &name) {
+                    Some(method) => self.return_type(&target, method),
+                    // Derived constructors like `default()` and tuple variants
+                    None => Some(target),
+                }
+            }
+            Expr::Struct(s) => {
+                if s.path.is_ident("Self") {
+                    return Some(frame.path.clone());
+                }
+                let (m, t) = self
+                    .model
+                    .resolve(&frame.module.path, &tokens_to_string(&s.path))?;
+                Some(format!("{}::{}", m.path, t.name))
+            }
+            Expr::Try(t) => self.expr_type(frame, &t.expr),
+            Expr::Await(a) => self.expr_type(frame, &a.base),
+            Expr::Paren(p) => self.expr_type(frame, &p.expr),
+            Expr::Group(g) => self.expr_type(frame, &g.expr),
+            Expr::Reference(r) => self.expr_type(frame, &r.expr),
+            Expr::Unary(u) => self.expr_type(frame, &u.expr),
+            _ => None,
+        }
+    }
+
+    fn return_type(&self, owner: &str, method: &Method) -> Option<String> {
+        let ReturnType::Type(_, ret) = &method.sig.output else {
+            return None;
+        };
+        let (module, _) = *self.types.get(owner)?;
+        self.resolve_in(module, owner, ret)
+    }
+
+    fn resolve_type(&self, frame: &Frame<'m>, ty: &Type) -> Option<String> {
+        self.resolve_in(frame.module, &frame.path, ty)
+    }
+
+    /// The first crate type inside `ty`, written in `module`; `Self` is `self_path`.
+    fn resolve_in(&self, module: &Module, self_path: &str, ty: &Type) -> Option<String> {
+        let analyzer = self.analyzer.in_module(&module.path);
+        analyzer.analyze(ty).into_iter().find_map(|r| {
+            if r.path == "Self" {
+                return Some(self_path.to_string());
+            }
+            let (m, t) = self.model.resolve(&module.path, &r.path)?;
+            Some(format!("{}::{}", m.path, t.name))
+        })
+    }
+
+    fn render(&self) -> String {
+        let names = node_names(self.participants.iter().map(String::as_str));
+        let id = |path: &str| {
+            if path == CALLER {
+                CALLER.to_string()
+            } else {
+                names[path].id.clone()
+            }
+        };
+
+        let mut out = String::from("sequenceDiagram\n");
+        out.push_str(&format!("    actor {}\n", CALLER));
+        for path in &self.participants {
+            let node = &names[path];
+            match &node.label {
+                Some(label) => out.push_str(&format!("    participant {} as {}\n", node.id, label)),
+                None => out.push_str(&format!("    participant {}\n", node.id)),
+            }
+        }
+
+        let mut indent = 1;
+        for line in &self.lines {
+            match line {
+                Line::Message {
+                    from,
+                    to,
+                    arrow,
+                    text,
+                } => out.push_str(&format!(
+                    "{}{}{}{}: {}\n",
+                    "    ".repeat(indent),
+                    id(from),
+                    arrow,
+                    id(to),
+                    text
+                )),
+                Line::Open(text) => {
+                    out.push_str(&format!("{}{}\n", "    ".repeat(indent), text));
+                    indent += 1;
+                }
+                Line::Else(text) => {
+                    out.push_str(&format!("{}else {}\n", "    ".repeat(indent - 1), text));
+                }
+                Line::End => {
+                    indent -= 1;
+                    out.push_str(&format!("{}end\n", "    ".repeat(indent)));
+                }
+            }
+        }
+        out
+    }
+}
+
+/// `Server` for `crate::net::Server`.
+fn short_name(path: &str) -> &str {
+    path.rsplit("::").next().unwrap_or(path)
+}
+
+/// Source text for a block label, on one line, without characters Mermaid
+/// treats specially, and cut to [`MAX_LABEL`] characters.
+fn label<T: quote::ToTokens>(node: &T) -> String {
+    let text: String = tokens_to_string(node)
+        .replace(" . ", ".")
+        .chars()
+        .map(|c| match c {
+            ';' | '#' | '\n' => ' ',
+            c => c,
+        })
+        .collect();
+    if text.chars().count() > MAX_LABEL {
+        let cut: String = text.chars().take(MAX_LABEL - 3).collect();
+        format!("{}...", cut.trim_end())
+    } else {
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn test_sequence_follows_calls() {
+        let source = r#"
+            use std::sync::{Arc, Mutex};
+            pub struct Server { db: Arc<Mutex<Db>>, cache: Cache }
+            pub struct Db;
+            pub struct Cache;
+            pub struct Row;
+            impl Server {
+                pub fn handle(&self, ids: Vec<u32>) -> Result<Row, Error> {
+                    if let Some(row) = self.cache.get() {
+                        return Ok(row);
+                    }
+                    for id in ids {
+                        self.db.lock().unwrap().query(id)?;
+                    }
+                    self.log();
+                    Ok(Row)
+                }
+                fn log(&self) {}
+            }
+            impl Db {
+                pub fn query(&self, id: u32) -> Result<Row, Error> { self.connect() }
+                fn connect(&self) -> Result<Row, Error> { todo!() }
+            }
+            impl Cache {
+                pub fn get(&self) -> Option<Row> { None }
+            }
+        "#;
+        let model = CrateModel::from_source(Path::new("lib.rs"), source).unwrap();
+        let diagram = sequence_diagram(&model, "Server::handle", 1).unwrap();
+        assert_eq!(
+            diagram,
+            "sequenceDiagram
+    actor Caller
+    participant Server
+    participant Cache
+    participant Db
+    Caller->>Server: handle(ids)
+    Server->>Cache: get()
+    Cache-->>Server: Option<Row>
+    loop for id in ids
+        Server->>Db: query(id)
+        Db-->>Server: Result<Row, Error>
+        opt error
+            Server-->>Caller: Err
+        end
+    end
+    Server->>Server: log()
+    Server-->>Caller: Result<Row, Error>
+"
+        );
+    }
+
+    #[test]
+    fn test_sequence_dispatches_trait_methods() {
+        let source = r#"
+            pub trait Storage {
+                fn save(&self, order: Order) -> Receipt;
+                fn flush(&self) { }
+            }
+            pub struct Order;
+            pub struct Receipt;
+            pub struct Disk;
+            impl Storage for Disk {
+                fn save(&self, order: Order) -> Receipt { self.write() }
+            }
+            impl Disk {
+                fn write(&self) -> Receipt { todo!() }
+            }
+            pub struct Shop { disk: Disk, backup: Box<dyn Storage> }
+            impl Shop {
+                pub fn checkout(&self, order: Order) {
+                    self.disk.save(order);
+                    self.backup.flush();
+                }
+            }
+        "#;
+        let model = CrateModel::from_source(Path::new("lib.rs"), source).unwrap();
+        let diagram = sequence_diagram(&model, "Shop::checkout", 3).unwrap();
+        assert_eq!(
+            diagram,
+            "sequenceDiagram
+    actor Caller
+    participant Shop
+    participant Disk
+    participant Storage
+    Caller->>Shop: checkout(order)
+    Shop->>Disk: save(order)
+    Disk->>Disk: write()
+    Disk-->>Shop: Receipt
+    Shop->>Storage: flush()
+"
+        );
+    }
+
+    #[test]
+    fn test_sequence_stops_at_recursion_and_substitutes_self() {
+        let source = r#"
+            pub struct Node { next: Option<Box<Node>> }
+            impl Node {
+                pub fn walk(&self) -> Option<Self> { self.visit(); self.walk() }
+                fn visit(&self) { self.walk(); }
+            }
+        "#;
+        let model = CrateModel::from_source(Path::new("lib.rs"), source).unwrap();
+        let diagram = sequence_diagram(&model, "Node::walk", 5).unwrap();
+        assert_eq!(
+            diagram,
+            "sequenceDiagram
+    actor Caller
+    participant Node
+    Caller->>Node: walk()
+    Node->>Node: visit()
+    Node->>Node: walk()
+    Node->>Node: walk()
+    Node-->>Caller: Option<Node>
+"
+        );
+    }
+}
@@ -0,0 +1,709 @@
+//! State machines recovered from source code, for `stateDiagram-v2` output.
+//!
+//! Two shapes are recognised:
+//!
+//! * **Enum state machines**: transitions between the variants of an enum are
+//!   read from `match` arms (`State::A => State::B`), guarded assignments such
+//!   as `if self.state == State::A { self.state = State::B }` and functions
+//!   returning the enum. Matches on a tuple like `(state, event)` label the
+//!   transition with the event variant instead of the function name.
+//! * **Typestate**: for a generic struct `Conn<S>`, methods in `impl Conn<Open>`
+//!   returning `Conn<Closed>` are transitions from `Open` to `Closed`.
+
+use crate::types::display_type;
+use std::collections::BTreeSet;
+use syn::{
+    BinOp, Block, Expr, FnArg, GenericArgument, Item, Pat, PathArguments, ReturnType, Signature,
+    Stmt, Type,
+};
+
+/// The pseudo-state Mermaid uses for entry and exit points.
+pub const TERMINAL: &str = "[*]";
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Transition {
+    /// Source state, or [`TERMINAL`] for a state entered on construction.
+    pub from: String,
+    pub to: String,
+    /// The function, or the event variant, causing the transition.
+    pub label: String,
+}
+
+#[derive(Debug)]
+pub struct StateMachine {
+    /// The enum or typestate struct the states belong to.
+    pub name: String,
+    /// Every known state in declaration (or discovery) order.
+    pub states: Vec<String>,
+    pub transitions: BTreeSet<Transition>,
+}
+
+impl StateMachine {
+    /// Finds the enum or generic struct called `name` among `files` and
+    /// collects its transitions. Returns `None` when no such type exists.
+    pub fn extract(files: &[syn::File], name: &str) -> Option<StateMachine> {
+        let items: Vec<&Item> = files.iter().flat_map(|f| flatten(&f.items)).collect();
+
+        for item in &items {
+            match item {
+                Item::Enum(e) if e.ident == name => {
+                    let states = e.variants.iter().map(|v| v.ident.to_string()).collect();
+                    return Some(enum_machine(&items, name, states));
+                }
+                Item::Struct(s) if s.ident == name && !s.generics.params.is_empty() => {
+                    return Some(typestate_machine(&items, name));
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
+    /// Renders the machine as a Mermaid `stateDiagram-v2`. States reached by
+    /// some transition but never left are drawn as final states.
+    pub fn to_mermaid(&self) -> String {
+        let mut out = String::from("stateDiagram-v2\n");
+
+        let mentioned: BTreeSet<&str> = self
+            .transitions
+            .iter()
+            .flat_map(|t| [t.from.as_str(), t.to.as_str()])
+            .collect();
+        for state in &self.states {
+            if !mentioned.contains(state.as_str()) {
+                out.push_str(&format!("    {}\n", state_id(state)));
+            }
+        }
+
+        for t in &self.transitions {
+            out.push_str(&format!(
+                "    {} --> {} : {}\n",
+                state_id(&t.from),
+                state_id(&t.to),
+                t.label
+            ));
+        }
+
+        for state in &self.states {
+            let entered = self
+                .transitions
+                .iter()
+                .any(|t| &t.to == state && t.from != *state);
+            let left = self
+                .transitions
+                .iter()
+                .any(|t| &t.from == state && t.to != *state);
+            if entered && !left {
+                out.push_str(&format!("    {} --> {}\n", state_id(state), TERMINAL));
+            }
+        }
+
+        out
+    }
+}
+
+/// Mermaid state ids may not contain `<`, `,` or spaces, so `Open<T>` becomes `Open_T_`.
+fn state_id(state: &str) -> String {
+    if state == TERMINAL {
+        return state.to_string();
+    }
+    state
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// Items of the file including those of inline modules.
+fn flatten(items: &[Item]) -> Vec<&Item> {
+    let mut all = Vec::new();
+    for item in items {
+        all.push(item);
+        if let Item::Mod(m) = item {
+            if let Some((_, inner)) = &m.content {
+                all.extend(flatten(inner));
+            }
+        }
+    }
+    all
+}
+
+/// The last segment of a type's path, e.g. `Conn` for `net::Conn<Open>`.
+fn type_name(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Path(p) => p.path.segments.last().map(|s| s.ident.to_string()),
+        _ => None,
+    }
+}
+
+fn has_receiver(sig: &Signature) -> bool {
+    matches!(sig.inputs.first(), Some(FnArg::Receiver(_)))
+}
+
+// ---------------------------------------------------------------------------
+// Enum state machines
+// ---------------------------------------------------------------------------
+
+fn enum_machine(items: &[&Item], name: &str, states: Vec<String>) -> StateMachine {
+    let mut transitions = BTreeSet::new();
+
+    for item in items {
+        match item {
+            Item::Fn(f) => {
+                let mut walker = Walker::new(name, &states, false, &f.sig, &mut transitions);
+                walker.block(&f.block, &Context::default());
+            }
+            Item::Impl(imp) => {
+                let in_impl = type_name(&imp.self_ty).is_some_and(|n| n == name);
+                for impl_item in &imp.items {
+                    if let syn::ImplItem::Fn(f) = impl_item {
+                        let mut walker =
+                            Walker::new(name, &states, in_impl, &f.sig, &mut transitions);
+                        walker.block(&f.block, &Context::default());
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    StateMachine {
+        name: name.to_string(),
+        states,
+        transitions,
+    }
+}
+
+/// What is known about the enum's current state at some point in a function.
+#[derive(Debug, Default, Clone)]
+struct Context {
+    /// The states control can be in here, or `None` when nothing is known.
+    from: Option<BTreeSet<String>>,
+    /// The event variant matched alongside the state, if any.
+    event: Option<String>,
+}
+
+/// What a pattern says about the state it matches.
+enum PatStates {
+    /// Matches exactly these variants.
+    States(BTreeSet<String>),
+    /// `_` or a plain binding: matches anything.
+    CatchAll,
+    /// Not a pattern over the enum.
+    Other,
+}
+
+/// Walks one function body, recording a transition wherever a variant is
+/// constructed.
+struct Walker<'a> {
+    name: &'a str,
+    states: &'a [String],
+    /// Whether `Self::Variant` refers to the enum.
+    in_impl: bool,
+    label: String,
+    /// Functions without a receiver construct rather than transition.
+    constructor: bool,
+    transitions: &'a mut BTreeSet<Transition>,
+}
+
+impl<'a> Walker<'a> {
+    fn new(
+        name: &'a str,
+        states: &'a [String],
+        in_impl: bool,
+        sig: &Signature,
+        transitions: &'a mut BTreeSet<Transition>,
+    ) -> Self {
+        Walker {
+            name,
+            states,
+            in_impl,
+            label: sig.ident.to_string(),
+            constructor: !has_receiver(sig),
+            transitions,
+        }
+    }
+
+    /// The variant named by `State::A`, `path::State::A` or, inside the enum's
+    /// own impl, `Self::A`.
+    fn variant(&self, path: &syn::Path) -> Option<String> {
+        let segments: Vec<String> = path.segments.iter().map(|s| s.ident.to_string()).collect();
+        let [.., owner, variant] = segments.as_slice() else {
+            return None;
+        };
+        let owned = owner == self.name || (owner == "Self" && self.in_impl);
+        (owned && self.states.contains(variant)).then(|| variant.clone())
+    }
+
+    fn record(&mut self, to: String, ctx: &Context) {
+        let label = ctx.event.clone().unwrap_or_else(|| self.label.clone());
+        let sources: Vec<String> = match &ctx.from {
+            Some(from) => from.iter().cloned().collect(),
+            None if self.constructor => vec![TERMINAL.to_string()],
+            // An unguarded transition can happen from any other state
+            None => self.states.iter().filter(|s| **s != to).cloned().collect(),
+        };
+        for from in sources {
+            self.transitions.insert(Transition {
+                from,
+                to: to.clone(),
+                label: label.clone(),
+            });
+        }
+    }
+
+    fn block(&mut self, block: &Block, ctx: &Context) {
+        for stmt in &block.stmts {
+            match stmt {
+                Stmt::Local(local) => {
+                    if let Some(init) = &local.init {
+                        self.expr(&init.expr, ctx);
+                        if let Some((_, diverge)) = &init.diverge {
+                            self.expr(diverge, ctx);
+                        }
+                    }
+                }
+                Stmt::Expr(expr, _) => self.expr(expr, ctx),
+                Stmt::Item(_) | Stmt::Macro(_) => {}
+            }
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr, ctx: &Context) {
+        match expr {
+            Expr::Path(p) => {
+                if let Some(to) = self.variant(&p.path) {
+                    self.record(to, ctx);
+                }
+            }
+            Expr::Call(call) => {
+                match &*call.func {
+                    Expr::Path(p) if self.variant(&p.path).is_some() => {
+                        let to = self.variant(&p.path).unwrap_or_default();
+                        self.record(to, ctx);
+                    }
+                    func => self.expr(func, ctx),
+                }
+                for arg in &call.args {
+                    self.expr(arg, ctx);
+                }
+            }
+            Expr::Struct(s) => {
+                if let Some(to) = self.variant(&s.path) {
+                    self.record(to, ctx);
+                }
+                for field in &s.fields {
+                    self.expr(&field.expr, ctx);
+                }
+            }
+            Expr::Match(m) => self.match_expr(m, ctx),
+            Expr::If(i) => {
+                self.expr(&i.cond, ctx);
+                let then_ctx = match self.cond_states(&i.cond) {
+                    Some(from) => Context {
+                        from: Some(from),
+                        event: ctx.event.clone(),
+                    },
+                    None => ctx.clone(),
+                };
+                self.block(&i.then_branch, &then_ctx);
+                if let Some((_, else_branch)) = &i.else_branch {
+                    self.expr(else_branch, ctx);
+                }
+            }
+            // Comparisons test the state rather than change it
+            Expr::Binary(b) if matches!(b.op, BinOp::Eq(_) | BinOp::Ne(_)) => {}
+            Expr::Binary(b) => {
+                self.expr(&b.left, ctx);
+                self.expr(&b.right, ctx);
+            }
+            Expr::Let(l) => self.expr(&l.expr, ctx),
+            Expr::Assign(a) => self.expr(&a.right, ctx),
+            Expr::Block(b) => self.block(&b.block, ctx),
+            Expr::Unsafe(u) => self.block(&u.block, ctx),
+            Expr::Async(a) => self.block(&a.block, ctx),
+            Expr::TryBlock(t) => self.block(&t.block, ctx),
+            Expr::Loop(l) => self.block(&l.body, ctx),
+            Expr::While(w) => {
+                self.expr(&w.cond, ctx);
+                self.block(&w.body, ctx);
+            }
+            Expr::ForLoop(f) => {
+                self.expr(&f.expr, ctx);
+                self.block(&f.body, ctx);
+            }
+            Expr::Closure(c) => self.expr(&c.body, ctx),
+            Expr::MethodCall(m) => {
+                self.expr(&m.receiver, ctx);
+                for arg in &m.args {
+                    self.expr(arg, ctx);
+                }
+            }
+            Expr::Return(r) => {
+                if let Some(e) = &r.expr {
+                    self.expr(e, ctx);
+                }
+            }
+            Expr::Break(b) => {
+                if let Some(e) = &b.expr {
+                    self.expr(e, ctx);
+                }
+            }
+            Expr::Tuple(t) => t.elems.iter().for_each(|e| self.expr(e, ctx)),
+            Expr::Array(a) => a.elems.iter().for_each(|e| self.expr(e, ctx)),
+            Expr::Paren(p) => self.expr(&p.expr, ctx),
+            Expr::Group(g) => self.expr(&g.expr, ctx),
+            Expr::Reference(r) => self.expr(&r.expr, ctx),
+            Expr::Unary(u) => self.expr(&u.expr, ctx),
+            Expr::Try(t) => self.expr(&t.expr, ctx),
+            Expr::Await(a) => self.expr(&a.base, ctx),
+            Expr::Field(f) => self.expr(&f.base, ctx),
+            Expr::Cast(c) => self.expr(&c.expr, ctx),
+            _ => {}
+        }
+    }
+
+    fn match_expr(&mut self, m: &syn::ExprMatch, ctx: &Context) {
+        self.expr(&m.expr, ctx);
+
+        let arms: Vec<(PatStates, Option<String>)> = m
+            .arms
+            .iter()
+            .map(|arm| self.arm_pattern(&arm.pat))
+            .collect();
+        let about_state = arms.iter().any(|(s, _)| matches!(s, PatStates::States(_)));
+        let covered: BTreeSet<String> = arms
+            .iter()
+            .filter_map(|(s, _)| match s {
+                PatStates::States(states) => Some(states.clone()),
+                _ => None,
+            })
+            .flatten()
+            .collect();
+
+        for (arm, (states, event)) in m.arms.iter().zip(arms) {
+            let from = match states {
+                PatStates::States(states) => Some(states),
+                // A catch-all arm takes every state the other arms did not
+                PatStates::CatchAll if about_state => Some(
+                    self.states
+                        .iter()
+                        .filter(|s| !covered.contains(*s))
+                        .cloned()
+                        .collect(),
+                ),
+                _ => ctx.from.clone(),
+            };
+            let arm_ctx = Context {
+                from,
+                event: event.or_else(|| ctx.event.clone()),
+            };
+            if let Some((_, guard)) = &arm.guard {
+                self.expr(guard, &arm_ctx);
+            }
+            self.expr(&arm.body, &arm_ctx);
+        }
+    }
+
+    /// The states an arm matches and, for `(state, event)` tuples, the event.
+    fn arm_pattern(&self, pat: &Pat) -> (PatStates, Option<String>) {
+        let Pat::Tuple(tuple) = pat else {
+            return (self.pat_states(pat), None);
+        };
+
+        let mut states = PatStates::Other;
+        let mut event = None;
+        for elem in &tuple.elems {
+            match self.pat_states(elem) {
+                found @ PatStates::States(_) => states = found,
+                // A wildcard in the state position matches every state
+                PatStates::CatchAll => {}
+                PatStates::Other => event = event.or_else(|| pat_variant_name(elem)),
+            }
+        }
+        if matches!(states, PatStates::Other) && event.is_some() {
+            states = PatStates::CatchAll;
+        }
+        (states, event)
+    }
+
+    fn pat_states(&self, pat: &Pat) -> PatStates {
+        let path = match pat {
+            Pat::Path(p) => &p.path,
+            Pat::TupleStruct(p) => &p.path,
+            Pat::Struct(p) => &p.path,
+            Pat::Wild(_) => return PatStates::CatchAll,
+            Pat::Ident(i) => {
+                return match &i.subpat {
+                    Some((_, sub)) => self.pat_states(sub),
+                    None => PatStates::CatchAll,
+                }
+            }
+            Pat::Reference(r) => return self.pat_states(&r.pat),
+            Pat::Paren(p) => return self.pat_states(&p.pat),
+            Pat::Or(or) => {
+                let mut all = BTreeSet::new();
+                for case in &or.cases {
+                    match self.pat_states(case) {
+                        PatStates::States(states) => all.extend(states),
+                        other => return other,
+                    }
+                }
+                return PatStates::States(all);
+            }
+            _ => return PatStates::Other,
+        };
+        match self.variant(path) {
+            Some(variant) => PatStates::States(BTreeSet::from([variant])),
+            None => PatStates::Other,
+        }
+    }
+
+    /// States an `if` condition restricts its branch to: `if let State::A = ..`,
+    /// `state == State::A` and `matches!(state, State::A | State::B)`.
+    fn cond_states(&self, cond: &Expr) -> Option<BTreeSet<String>> {
+        match cond {
+            Expr::Let(l) => match self.pat_states(&l.pat) {
+                PatStates::States(states) => Some(states),
+                _ => None,
+            },
+            Expr::Binary(b) if matches!(b.op, BinOp::Eq(_)) => [&b.left, &b.right]
+                .into_iter()
+                .find_map(|side| match &**side {
+                    Expr::Path(p) => self.variant(&p.path),
+                    _ => None,
+                })
+                .map(|v| BTreeSet::from([v])),
+            Expr::Binary(b) if matches!(b.op, BinOp::And(_)) => self
+                .cond_states(&b.left)
+                .or_else(|| self.cond_states(&b.right)),
+            Expr::Paren(p) => self.cond_states(&p.expr),
+            Expr::Macro(m) if m.mac.path.is_ident("matches") => {
+                let pat = m
+                    .mac
+                    .parse_body_with(|input: syn::parse::ParseStream| {
+                        input.parse::<Expr>()?;
+                        input.parse::<syn::Token![,]>()?;
+                        Pat::parse_multi(input)
+                    })
+                    .ok()?;
+                match self.pat_states(&pat) {
+                    PatStates::States(states) => Some(states),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+/// `Start` for a pattern like `Event::Start` or `Event::Data(..)`.
+fn pat_variant_name(pat: &Pat) -> Option<String> {
+    let path = match pat {
+        Pat::Path(p) => &p.path,
+        Pat::TupleStruct(p) => &p.path,
+        Pat::Struct(p) => &p.path,
+        Pat::Reference(r) => return pat_variant_name(&r.pat),
+        _ => return None,
+    };
+    path.segments.last().map(|s| s.ident.to_string())
+}
+
+// ---------------------------------------------------------------------------
+// Typestate
+// ---------------------------------------------------------------------------
+
+fn typestate_machine(items: &[&Item], name: &str) -> StateMachine {
+    let mut states: Vec<String> = Vec::new();
+    let mut transitions = BTreeSet::new();
+    let add_state = |state: &str, states: &mut Vec<String>| {
+        if !states.iter().any(|s| s == state) {
+            states.push(state.to_string());
+        }
+    };
+
+    // First pass: the concrete states impl blocks are written for
+    let impls: Vec<(&syn::ItemImpl, Option<String>)> = items
+        .iter()
+        .filter_map(|item| match item {
+            Item::Impl(imp) if imp.trait_.is_none() => Some(imp),
+            _ => None,
+        })
+        .filter(|imp| type_name(&imp.self_ty).is_some_and(|n| n == name))
+        .map(|imp| (imp, typestate_arg(&imp.self_ty, imp)))
+        .collect();
+    for (_, state) in &impls {
+        if let Some(state) = state {
+            add_state(state, &mut states);
+        }
+    }
+
+    for (imp, from) in &impls {
+        for impl_item in &imp.items {
+            let syn::ImplItem::Fn(f) = impl_item else {
+                continue;
+            };
+            let ReturnType::Type(_, ret) = &f.sig.output else {
+                continue;
+            };
+            let Some(to) = find_typestate(ret, name, imp) else {
+                continue;
+            };
+            add_state(&to, &mut states);
+
+            let sources: Vec<String> = if !has_receiver(&f.sig) {
+                vec![TERMINAL.to_string()]
+            } else if let Some(from) = from {
+                vec![from.clone()]
+            } else {
+                // `impl<S> Conn<S>`: available in every state
+                states.iter().filter(|s| **s != to).cloned().collect()
+            };
+            for from in sources.into_iter().filter(|s| *s != to) {
+                transitions.insert(Transition {
+                    from,
+                    to: to.clone(),
+                    label: f.sig.ident.to_string(),
+                });
+            }
+        }
+    }
+
+    StateMachine {
+        name: name.to_string(),
+        states,
+        transitions,
+    }
+}
+
+/// The concrete state argument of `Conn<Open>`, or `None` when the argument
+/// is one of the impl's own generic parameters.
+fn typestate_arg(ty: &Type, imp: &syn::ItemImpl) -> Option<String> {
+    let Type::Path(p) = ty else {
+        return None;
+    };
+    let PathArguments::AngleBracketed(args) = &p.path.segments.last()?.arguments else {
+        return None;
+    };
+    let arg = args.args.iter().find_map(|a| match a {
+        GenericArgument::Type(t) => Some(t),
+        _ => None,
+    })?;
+    let generic = type_name(arg).is_some_and(|n| imp.generics.type_params().any(|p| p.ident == n));
+    (!generic).then(|| display_type(arg))
+}
+
+/// The state of the first `name<State>` inside `ty`, looking through wrappers
+/// like `Result<Conn<Closed>, Error>`. `Self` is the impl's own state.
+fn find_typestate(ty: &Type, name: &str, imp: &syn::ItemImpl) -> Option<String> {
+    let Type::Path(p) = ty else {
+        return None;
+    };
+    let last = p.path.segments.last()?;
+    if last.ident == name {
+        return typestate_arg(ty, imp);
+    }
+    if last.ident == "Self" {
+        return typestate_arg(&imp.self_ty, imp);
+    }
+    let PathArguments::AngleBracketed(args) = &last.arguments else {
+        return None;
+    };
+    args.args.iter().find_map(|a| match a {
+        GenericArgument::Type(t) => find_typestate(t, name, imp),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn machine(source: &str, name: &str) -> StateMachine {
+        let file = syn::parse_file(source).unwrap();
+        StateMachine::extract(&[file], name).unwrap()
+    }
+
+    fn edges(machine: &StateMachine) -> Vec<String> {
+        machine
+            .transitions
+            .iter()
+            .map(|t| format!("{} -> {} : {}", t.from, t.to, t.label))
+            .collect()
+    }
+
+    #[test]
+    fn test_enum_transitions() {
+        let source = r#"
+            enum State { Idle, Running { pid: u32 }, Done }
+            enum Event { Start, Stop }
+            struct Job { state: State }
+            impl Job {
+                fn new() -> Self { Job { state: State::Idle } }
+                fn finish(&mut self) {
+                    if matches!(self.state, State::Running { .. }) {
+                        self.state = State::Done;
+                    }
+                }
+                fn reset(&mut self) { self.state = State::Idle; }
+            }
+            impl State {
+                fn on(self, event: Event) -> Self {
+                    match (self, event) {
+                        (Self::Idle, Event::Start) => Self::Running { pid: 1 },
+                        (s, _) => s,
+                    }
+                }
+                fn is_done(&self) -> bool { *self == State::Done }
+            }
+        "#;
+        let m = machine(source, "State");
+        assert_eq!(
+            edges(&m),
+            [
+                "Done -> Idle : reset",
+                "Idle -> Running : Start",
+                "Running -> Done : finish",
+                "Running -> Idle : reset",
+                "[*] -> Idle : new",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_typestate_transitions() {
+        let source = r#"
+            struct Open; struct Closed;
+            struct Conn<S> { state: S }
+            impl Conn<Closed> {
+                fn new() -> Self { todo!() }
+                fn open(self) -> Result<Conn<Open>, Error> { todo!() }
+            }
+            impl Conn<Open> {
+                fn close(self) -> Conn<Closed> { todo!() }
+                fn send(&mut self) -> Self { todo!() }
+            }
+        "#;
+        let m = machine(source, "Conn");
+        assert_eq!(
+            edges(&m),
+            [
+                "Closed -> Open : open",
+                "Open -> Closed : close",
+                "[*] -> Closed : new"
+            ]
+        );
+        assert_eq!(m.states, ["Closed", "Open"]);
+    }
+
+    #[test]
+    fn test_to_mermaid_marks_final_states() {
+        let source = r#"
+            enum Door { Open, Closed, Broken }
+            fn kick(d: Door) -> Door { match d { Door::Open => Door::Broken, other => other } }
+        "#;
+        let out = machine(source, "Door").to_mermaid();
+        assert!(out.starts_with("stateDiagram-v2\n"));
+        assert!(out.contains("    Open --> Broken : kick\n"));
+        assert!(out.contains("    Broken --> [*]\n"));
+    }
+}
@@ -0,0 +1,482 @@
+//! Named Mermaid themes.
+//!
+//! A theme sets Mermaid's `themeVariables`, a link style per relationship and
+//! a classDef per kind of type: `struct`, `enum`, `trait` and `external`. Four
+//! are built in: `light`, `dark`, `high-contrast` and `print`. `invoke.toml`
+//! can pick the theme for every generated diagram, add themes or replace the
+//! built-in ones. A theme that `extends` another lists only what it changes:
+//!
+//! ```toml
+//! theme = "brand"
+//!
+//! [themes.brand]
+//! extends = "light"
+//! variables = { primaryColor = "#e3f2fd", fontFamily = "Inter" }
+//! links = { has = "stroke:#1565c0,stroke-width:2px" }
+//! classes = { external = "fill:#eeeeee,stroke:#9e9e9e" }
+//! ```
+//!
+//! Links are styled by their label, with `default` for every link. Mermaid only
+//! supports `linkStyle` in flowcharts, so other diagrams get the variables and
+//! classes only. In a classDiagram the kind of a class comes from its
+//! `<<enum>>`/`<<trait>>` stereotype, and classes that are only referenced are
+//! external; elsewhere nodes keep the kind classes their generator gave them.
+
+use crate::config::Config;
+use crate::mermaid::{BlockKind, Diagram, DiagramKind, Line, Statement};
+use crate::Result;
+use anyhow::{anyhow, bail, Context};
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+pub const BUILT_IN: [&str; 4] = ["light", "dark", "high-contrast", "print"];
+
+/// The kinds of type a theme has a classDef for.
+pub const KINDS: [&str; 4] = ["struct", "enum", "trait", "external"];
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    /// The theme this one starts from.
+    pub extends: Option<String>,
+    /// Mermaid `themeVariables`, e.g. `primaryColor` or `fontSize`.
+    pub variables: BTreeMap<String, String>,
+    /// `linkStyle` styles by link label, or `default` for every link.
+    pub links: BTreeMap<String, String>,
+    /// classDef styles by kind of type.
+    pub classes: BTreeMap<String, String>,
+}
+
+impl Theme {
+    /// Takes `--theme NAME` from `args`, falling back to `theme` in
+    /// `invoke.toml`. `None` when neither names one.
+    pub fn from_args(args: &mut Vec<String>) -> Result<Option<Theme>> {
+        let config = Config::load()?;
+        let mut name = config.theme.clone();
+        if let Some(i) = args.iter().position(|a| a == "--theme") {
+            let value = args
+                .get(i + 1)
+                .cloned()
+                .ok_or_else(|| anyhow!("--theme requires a name"))?;
+            args.drain(i..i + 2);
+            name = Some(value);
+        }
+        name.map(|name| Theme::named(&name, &config.themes))
+            .transpose()
+    }
+
+    /// The theme called `name` from `themes` or the built-in ones, with the
+    /// themes it extends applied underneath.
+    pub fn named(name: &str, themes: &BTreeMap<String, Theme>) -> Result<Theme> {
+        resolve(name, themes, &mut Vec::new())
+    }
+
+    /// The `init` directive, without the surrounding `%%{ }%%`.
+    pub fn directive(&self) -> String {
+        let init = serde_json::json!({
+            "theme": "base",
+            "themeVariables": self.variables,
+        });
+        format!("init: {}", init)
+    }
+
+    /// Puts the theme's directive at the top of `diagram`, replacing any init
+    /// directive, kind classDefs or link styles already there, and appends its
+    /// classDefs, class assignments and link styles. Link styles count as a
+    /// theme's when they are `linkStyle default` or cover exactly the links
+    /// of one label. Applying a theme twice changes nothing.
+    pub fn apply(&self, diagram: &mut Diagram) {
+        let infers_kinds = diagram.kind == DiagramKind::Class;
+        let themed_links: BTreeSet<String> = if diagram.kind.is_flowchart() {
+            label_groups(diagram)
+                .into_values()
+                .map(|links| links.join(","))
+                .chain(["default".to_string()])
+                .collect()
+        } else {
+            BTreeSet::new()
+        };
+        diagram.retain(|statement| match statement {
+            Statement::Directive(text) => !text.trim_start().starts_with("init"),
+            Statement::ClassDef { names, .. } => !names.iter().all(|n| KINDS.contains(&n.as_str())),
+            Statement::LinkStyle { links, .. } => !themed_links.contains(links),
+            Statement::ClassAssign { class, .. } if infers_kinds => {
+                !KINDS.contains(&class.as_str())
+            }
+            _ => true,
+        });
+        diagram
+            .preamble
+            .insert(0, Statement::Directive(self.directive()).into());
+
+        let assigned: BTreeSet<(String, String)> = diagram
+            .class_assignments()
+            .into_iter()
+            .map(|(node, class)| (node.to_string(), class.to_string()))
+            .collect();
+        let mut kinds: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+        if infers_kinds {
+            for (id, kind) in class_kinds(diagram) {
+                if !assigned.contains(&(id.clone(), kind.to_string())) {
+                    kinds.entry(kind).or_default().push(id);
+                }
+            }
+        }
+        let used: BTreeSet<&str> = assigned
+            .iter()
+            .map(|(_, class)| class.as_str())
+            .chain(kinds.keys().copied())
+            .collect();
+
+        let mut added: Vec<Statement> = Vec::new();
+        for kind in KINDS.into_iter().filter(|k| used.contains(k)) {
+            if let Some(styles) = self.classes.get(kind) {
+                added.push(Statement::ClassDef {
+                    names: vec![kind.to_string()],
+                    styles: styles.clone(),
+                });
+            }
+        }
+        for (kind, nodes) in kinds {
+            added.push(Statement::ClassAssign {
+                nodes,
+                class: kind.to_string(),
+            });
+        }
+        if diagram.kind.is_flowchart() {
+            added.extend(self.link_styles(diagram));
+        }
+
+        let existing: Vec<Statement> = diagram
+            .body
+            .iter()
+            .map(|line| line.statement.clone())
+            .collect();
+        added.retain(|statement| !existing.contains(statement));
+        if added.is_empty() {
+            return;
+        }
+        if !matches!(
+            diagram.body.last().map(|l| &l.statement),
+            None | Some(Statement::Blank)
+        ) {
+            diagram.body.push(Statement::Blank.into());
+        }
+        diagram.body.extend(added.into_iter().map(Line::from));
+    }
+
+    /// Applies the theme to the Mermaid file at `path` in place.
+    pub fn apply_to_file(&self, path: &Path) -> Result<()> {
+        let source = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        fs::write(path, self.apply_to_source(&source))
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Applies the theme to Mermaid source, returning the rewritten source.
+    pub fn apply_to_source(&self, source: &str) -> String {
+        let mut diagram = Diagram::parse(source);
+        self.apply(&mut diagram);
+        diagram.to_string()
+    }
+
+    /// `linkStyle default` first, then the links of each styled label by
+    /// index, so labelled styles win.
+    fn link_styles(&self, diagram: &Diagram) -> Vec<Statement> {
+        let mut styles = Vec::new();
+        if let Some(default) = self.links.get("default") {
+            styles.push(Statement::LinkStyle {
+                links: "default".to_string(),
+                styles: default.clone(),
+            });
+        }
+        for (label, links) in label_groups(diagram) {
+            if let Some(style) = self.links.get(&label) {
+                styles.push(Statement::LinkStyle {
+                    links: links.join(","),
+                    styles: style.clone(),
+                });
+            }
+        }
+        styles
+    }
+
+    /// Layers `other` over this theme.
+    fn merge(&mut self, other: Theme) {
+        self.variables.extend(other.variables);
+        self.links.extend(other.links);
+        self.classes.extend(other.classes);
+    }
+}
+
+/// A theme from `themes`, or a built-in one once `themes` has been visited
+/// under that name, so `[themes.dark]` can extend the built-in `dark`.
+fn resolve(name: &str, themes: &BTreeMap<String, Theme>, seen: &mut Vec<String>) -> Result<Theme> {
+    let visited = seen.iter().any(|s| s == name);
+    seen.push(name.to_string());
+    let theme = match themes.get(name).filter(|_| !visited) {
+        Some(theme) => theme.clone(),
+        None => {
+            return match built_in(name) {
+                Some(theme) => Ok(theme),
+                None if visited => bail!("Themes extend each other: {}", seen.join(" -> ")),
+                None => bail!(
+                    "Unknown theme '{}'; the built-in themes are {}",
+                    name,
+                    BUILT_IN.join(", ")
+                ),
+            }
+        }
+    };
+    match &theme.extends {
+        Some(parent) => {
+            let mut base = resolve(parent, themes, seen)?;
+            base.merge(theme);
+            Ok(base)
+        }
+        None => Ok(theme),
+    }
+}
+
+/// The indices of a flowchart's links by label.
+fn label_groups(diagram: &Diagram) -> BTreeMap<String, Vec<String>> {
+    let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (index, edge) in diagram.edges().enumerate() {
+        if let Some(label) = &edge.label {
+            let label = label.trim().trim_matches('"');
+            groups
+                .entry(label.to_string())
+                .or_default()
+                .push(index.to_string());
+        }
+    }
+    groups
+}
+
+/// The kind of every class in a classDiagram, by id.
+fn class_kinds(diagram: &Diagram) -> BTreeMap<String, &'static str> {
+    let mut kinds: BTreeMap<String, &'static str> = diagram
+        .nodes()
+        .into_keys()
+        .map(|id| (class_id(&id), "external"))
+        .collect();
+    for line in diagram.all_lines() {
+        match &line.statement {
+            Statement::Node(node) if node.keyword.as_deref() == Some("class") => {
+                kinds.insert(class_id(&node.id), "struct");
+            }
+            Statement::Block(block) if block.kind == BlockKind::Class => {
+                let stereotype = block.body.iter().find_map(|line| match &line.statement {
+                    Statement::Member { text, .. } => match text.trim() {
+                        "<<enum>>" | "<<enumeration>>" => Some("enum"),
+                        "<<trait>>" | "<<interface>>" => Some("trait"),
+                        _ => None,
+                    },
+                    _ => None,
+                });
+                kinds.insert(class_id(&block.name), stereotype.unwrap_or("struct"));
+            }
+            _ => {}
+        }
+    }
+    kinds
+}
+
+/// A classDiagram class name without generics or label:
+/// `Server~T~["net::Server"]` is `Server`.
+fn class_id(name: &str) -> String {
+    name.split(['~', '['])
+        .next()
+        .unwrap_or(name)
+        .trim()
+        .to_string()
+}
+
+fn built_in(name: &str) -> Option<Theme> {
+    let theme = |variables: &[(&str, &str)], links: &[(&str, &str)], classes: [&str; 4]| Theme {
+        extends: None,
+        variables: pairs(variables),
+        links: pairs(links),
+        classes: KINDS
+            .iter()
+            .zip(classes)
+            .map(|(kind, style)| (kind.to_string(), style.to_string()))
+            .collect(),
+    };
+    Some(match name {
+        "light" => theme(
+            &[
+                ("primaryColor", "#fff4dd"),
+                ("primaryTextColor", "#333333"),
+                ("primaryBorderColor", "#d4a017"),
+                ("lineColor", "#555555"),
+                ("fontSize", "16px"),
+            ],
+            &[
+                ("default", "stroke:#555555"),
+                ("has", "stroke:#d4a017,stroke-width:2px"),
+                ("spawns", "stroke:#43a047,stroke-width:2px"),
+            ],
+            [
+                "fill:#fff4dd,stroke:#d4a017,color:#333333",
+                "fill:#e8f5e9,stroke:#43a047,color:#333333",
+                "fill:#e3f2fd,stroke:#1e88e5,color:#333333",
+                "fill:#f5f5f5,stroke:#9e9e9e,color:#616161,stroke-dasharray:3 3",
+            ],
+        ),
+        "dark" => theme(
+            &[
+                ("background", "#1e1e1e"),
+                ("primaryColor", "#2d333b"),
+                ("primaryTextColor", "#e6edf3"),
+                ("primaryBorderColor", "#768390"),
+                ("lineColor", "#8b949e"),
+                ("fontSize", "16px"),
+            ],
+            &[
+                ("default", "stroke:#8b949e"),
+                ("has", "stroke:#e3b341,stroke-width:2px"),
+                ("spawns", "stroke:#57ab5a,stroke-width:2px"),
+            ],
+            [
+                "fill:#2d333b,stroke:#e3b341,color:#e6edf3",
+                "fill:#1b3a2a,stroke:#57ab5a,color:#e6edf3",
+                "fill:#1c2d41,stroke:#539bf5,color:#e6edf3",
+                "fill:#22272e,stroke:#636e7b,color:#adbac7,stroke-dasharray:3 3",
+            ],
+        ),
+        "high-contrast" => theme(
+            &[
+                ("background", "#ffffff"),
+                ("primaryColor", "#ffffff"),
+                ("primaryTextColor", "#000000"),
+                ("primaryBorderColor", "#000000"),
+                ("lineColor", "#000000"),
+                ("fontSize", "18px"),
+            ],
+            &[("default", "stroke:#000000,stroke-width:3px")],
+            [
+                "fill:#ffffff,stroke:#000000,color:#000000,stroke-width:3px",
+                "fill:#ffff00,stroke:#000000,color:#000000,stroke-width:3px",
+                "fill:#000000,stroke:#000000,color:#ffffff,stroke-width:3px",
+                "fill:#ffffff,stroke:#000000,color:#000000,stroke-width:3px,stroke-dasharray:6 3",
+            ],
+        ),
+        "print" => theme(
+            &[
+                ("background", "#ffffff"),
+                ("primaryColor", "#ffffff"),
+                ("primaryTextColor", "#000000"),
+                ("primaryBorderColor", "#000000"),
+                ("lineColor", "#000000"),
+                ("fontSize", "14px"),
+            ],
+            &[("default", "stroke:#000000")],
+            [
+                "fill:#ffffff,stroke:#000000,color:#000000",
+                "fill:#eeeeee,stroke:#000000,color:#000000",
+                "fill:#ffffff,stroke:#000000,color:#000000,stroke-width:2px",
+                "fill:#ffffff,stroke:#666666,color:#000000,stroke-dasharray:4 2",
+            ],
+        ),
+        _ => return None,
+    })
+}
+
+fn pairs(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+    pairs
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_named_resolves_extends() {
+        let themes: BTreeMap<String, Theme> = toml::from_str(
+            r##"
+            [brand]
+            extends = "dark"
+            variables = { primaryColor = "#123456" }
+
+            [dark]
+            extends = "dark"
+            classes = { external = "fill:#000000" }
+            "##,
+        )
+        .unwrap();
+
+        let brand = Theme::named("brand", &themes).unwrap();
+        assert_eq!(brand.variables["primaryColor"], "#123456");
+        assert_eq!(brand.variables["background"], "#1e1e1e");
+        assert_eq!(brand.classes["external"], "fill:#000000");
+        assert_eq!(brand.classes.len(), 4);
+
+        assert!(Theme::named("neon", &themes).is_err());
+        let cycle: BTreeMap<String, Theme> =
+            toml::from_str("[a]\nextends = \"b\"\n[b]\nextends = \"a\"\n").unwrap();
+        assert!(Theme::named("a", &cycle).is_err());
+    }
+
+    #[test]
+    fn test_apply_class_diagram() {
+        let theme = Theme::named("print", &BTreeMap::new()).unwrap();
+        let mut diagram = Diagram::parse(
+            "classDiagram\n\
+             %%{init: {\"theme\": \"dark\"}}%%\n\
+             class Order {\n+id: u64\n}\n\
+             class Status {\n<<enum>>\n+Open\n}\n\
+             Order *-- Status\n\
+             Order ..> Invoice\n",
+        );
+        theme.apply(&mut diagram);
+        let once = diagram.to_string();
+        assert!(once.starts_with(&format!("%%{{{}}}%%\nclassDiagram\n", theme.directive())));
+        assert_eq!(once.matches("init:").count(), 1);
+        assert!(once.ends_with(
+            "\n\n    classDef struct fill:#ffffff,stroke:#000000,color:#000000\n\
+             \x20   classDef enum fill:#eeeeee,stroke:#000000,color:#000000\n\
+             \x20   classDef external fill:#ffffff,stroke:#666666,color:#000000,stroke-dasharray:4 2\n\
+             \x20   cssClass \"Status\" enum\n\
+             \x20   cssClass \"Invoice\" external\n\
+             \x20   cssClass \"Order\" struct\n"
+        ));
+
+        // Applying again, or another theme, replaces rather than stacks
+        theme.apply(&mut diagram);
+        assert_eq!(diagram.to_string(), once);
+        let dark = Theme::named("dark", &BTreeMap::new()).unwrap();
+        dark.apply(&mut diagram);
+        let redone = diagram.to_string();
+        assert_eq!(redone.matches("classDef enum").count(), 1);
+        assert!(redone.contains("classDef enum fill:#1b3a2a"));
+    }
+
+    #[test]
+    fn test_apply_flowchart_link_styles() {
+        let theme = Theme::named("light", &BTreeMap::new()).unwrap();
+        let mut diagram =
+            Diagram::parse("graph LR\n    A -->|has| B\n    A -->|place| C\n    C -->|has| B\n");
+        theme.apply(&mut diagram);
+        assert_eq!(
+            diagram.to_string(),
+            format!(
+                "%%{{{}}}%%\n\
+                 graph LR\n\
+                 \x20   A -->|has| B\n\
+                 \x20   A -->|place| C\n\
+                 \x20   C -->|has| B\n\
+                 \n\
+                 \x20   linkStyle default stroke:#555555\n\
+                 \x20   linkStyle 0,2 stroke:#d4a017,stroke-width:2px\n",
+                theme.directive()
+            )
+        );
+    }
+}
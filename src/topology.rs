@@ -0,0 +1,599 @@
+//! Async topology: which functions spawn which tasks and threads, what they
+//! await, and the channels connecting them.
+//!
+//! Channels are found where they are created (`let (tx, rx) = mpsc::channel(..)`)
+//! and followed through clones and `subscribe()` to the tasks that send on or
+//! receive from them. Channel ends held in struct fields are identified by
+//! flavour and message type, so `Sender<Command>` in one type and
+//! `Receiver<Command>` in another meet at the same channel node.
+
+use crate::model::{tokens_to_string, CrateModel, Method, TypeDef};
+use crate::types::display_type;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use syn::visit::{self, Visit};
+use syn::{Expr, Pat};
+
+/// Methods that put a message on a channel.
+const SEND_METHODS: &[&str] = &[
+    "send",
+    "try_send",
+    "blocking_send",
+    "send_timeout",
+    "send_replace",
+];
+/// Methods that take one off.
+const RECV_METHODS: &[&str] = &[
+    "recv",
+    "try_recv",
+    "blocking_recv",
+    "recv_timeout",
+    "recv_async",
+    "changed",
+    "borrow_and_update",
+];
+/// Crates and modules whose `channel()` constructors are recognised.
+const CHANNEL_FLAVOURS: &[&str] = &[
+    "mpsc",
+    "broadcast",
+    "oneshot",
+    "watch",
+    "crossbeam_channel",
+    "flume",
+    "async_channel",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NodeKind {
+    Function {
+        is_async: bool,
+    },
+    /// A future handed to `tokio::spawn` and friends.
+    Task,
+    /// A closure handed to `std::thread::spawn` or `spawn_blocking`.
+    Thread,
+    Channel,
+}
+
+#[derive(Debug, Clone)]
+pub struct Node {
+    pub label: String,
+    pub kind: NodeKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Edge {
+    pub from: String,
+    pub to: String,
+    /// `spawns`, `runs`, `await`, `send` or `recv`.
+    pub label: &'static str,
+}
+
+/// Nodes keyed by path-like keys such as `crate::net::Server::run::task1`.
+#[derive(Debug, Default)]
+pub struct Topology {
+    pub nodes: BTreeMap<String, Node>,
+    pub edges: BTreeSet<Edge>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Role {
+    Send,
+    Recv,
+}
+
+/// A function or method with a body, and where it lives.
+struct FnInfo<'m> {
+    key: String,
+    label: String,
+    method: &'m Method,
+    owner: Option<&'m TypeDef>,
+}
+
+impl Topology {
+    pub fn from_model(model: &CrateModel) -> Topology {
+        let mut functions = Vec::new();
+        for module in &model.modules {
+            for f in &module.functions {
+                functions.push(FnInfo {
+                    key: format!("{}::{}", module.path, f.name),
+                    label: f.name.clone(),
+                    method: f,
+                    owner: None,
+                });
+            }
+            for ty in &module.types {
+                for m in &ty.methods {
+                    functions.push(FnInfo {
+                        key: format!("{}::{}::{}", module.path, ty.name, m.name),
+                        label: format!("{}::{}", ty.name, m.name),
+                        method: m,
+                        owner: Some(ty),
+                    });
+                }
+            }
+        }
+
+        let mut by_name: HashMap<String, Vec<String>> = HashMap::new();
+        for f in &functions {
+            by_name
+                .entry(f.method.name.clone())
+                .or_default()
+                .push(f.key.clone());
+        }
+
+        let mut topology = Topology::default();
+        for f in &functions {
+            let is_async = f.method.sig.asyncness.is_some();
+            if is_async {
+                topology.add_function(f);
+            }
+            let Some(body) = &f.method.body else {
+                continue;
+            };
+            let mut walker = Walker {
+                topology: &mut topology,
+                function: f,
+                by_name: &by_name,
+                context: vec![f.key.clone()],
+                ends: HashMap::new(),
+                fields: f.owner.map(field_channels).unwrap_or_default(),
+                spawned: 0,
+            };
+            walker.visit_block(body);
+        }
+
+        // Functions only appear once something connects to them
+        let keys: BTreeMap<&str, &FnInfo> = functions.iter().map(|f| (f.key.as_str(), f)).collect();
+        let endpoints: Vec<String> = topology
+            .edges
+            .iter()
+            .flat_map(|e| [e.from.clone(), e.to.clone()])
+            .collect();
+        for key in endpoints {
+            if let Some(f) = keys.get(key.as_str()) {
+                topology.add_function(f);
+            }
+        }
+
+        topology
+    }
+
+    fn add_function(&mut self, f: &FnInfo) {
+        let is_async = f.method.sig.asyncness.is_some();
+        self.nodes.entry(f.key.clone()).or_insert_with(|| Node {
+            label: format!("{}fn {}", if is_async { "async " } else { "" }, f.label),
+            kind: NodeKind::Function { is_async },
+        });
+    }
+
+    fn connect(&mut self, from: &str, to: &str, label: &'static str) {
+        if from != to {
+            self.edges.insert(Edge {
+                from: from.to_string(),
+                to: to.to_string(),
+                label,
+            });
+        }
+    }
+
+    fn connected(&self, from: &str, to: &str) -> bool {
+        self.edges.iter().any(|e| e.from == from && e.to == to)
+    }
+
+    /// Renders a `flowchart LR`: functions as boxes, tasks as stadiums,
+    /// threads as subroutines and channels as hexagons.
+    pub fn to_mermaid(&self) -> String {
+        let mut out = String::from("flowchart LR\n");
+        for (key, node) in &self.nodes {
+            let label = node.label.replace('"', "'");
+            let shape = match node.kind {
+                NodeKind::Function { .. } => format!("[\"{}\"]", label),
+                NodeKind::Task => format!("([\"{}\"])", label),
+                NodeKind::Thread => format!("[[\"{}\"]]", label),
+                NodeKind::Channel => format!("{{{{\"{}\"}}}}", label),
+            };
+            out.push_str(&format!("    {}{}\n", node_id(key), shape));
+        }
+        out.push('\n');
+        for edge in &self.edges {
+            let arrow = if edge.label == "await" { "-.->" } else { "-->" };
+            out.push_str(&format!(
+                "    {} {}|{}| {}\n",
+                node_id(&edge.from),
+                arrow,
+                edge.label,
+                node_id(&edge.to)
+            ));
+        }
+        out
+    }
+}
+
+/// `net_Server_run_task1` for `crate::net::Server::run::task1`.
+fn node_id(key: &str) -> String {
+    key.strip_prefix("crate::")
+        .unwrap_or(key)
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect::<String>()
+        .split('_')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+/// Channel ends held in the fields of `ty`, keyed by field name. The channel
+/// is identified by flavour and message type, e.g. `mpsc<Command>`.
+fn field_channels(ty: &TypeDef) -> HashMap<String, (String, Role)> {
+    let mut ends = HashMap::new();
+    for field in &ty.fields {
+        let written = tokens_to_string(&field.ty);
+        let shown = display_type(&field.ty);
+        let (end, message) = shown.split_once('<').unwrap_or((&shown, ""));
+        let role = match end {
+            "Sender" | "UnboundedSender" | "SyncSender" => Role::Send,
+            "Receiver" | "UnboundedReceiver" => Role::Recv,
+            _ => continue,
+        };
+        let flavour = CHANNEL_FLAVOURS
+            .iter()
+            .find(|f| written.contains(&format!("{}::", f)))
+            .unwrap_or(&"mpsc");
+        let message = message.strip_suffix('>').unwrap_or(message);
+        ends.insert(
+            field.name.clone(),
+            (format!("channel::{}<{}>", flavour, message), role),
+        );
+    }
+    ends
+}
+
+/// Path segments of a call's function, e.g. `["tokio", "spawn"]`.
+fn call_path(func: &Expr) -> Vec<String> {
+    match func {
+        Expr::Path(p) => p
+            .path
+            .segments
+            .iter()
+            .map(|s| s.ident.to_string())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// The flavour of a channel constructor such as `mpsc::channel` or
+/// `tokio::sync::broadcast::channel`.
+fn channel_flavour(path: &[String]) -> Option<String> {
+    let (last, rest) = path.split_last()?;
+    if !matches!(
+        last.as_str(),
+        "channel" | "unbounded_channel" | "sync_channel" | "unbounded" | "bounded"
+    ) {
+        return None;
+    }
+    match rest
+        .iter()
+        .rev()
+        .find(|s| CHANNEL_FLAVOURS.contains(&s.as_str()))
+    {
+        Some(flavour) => Some(flavour.clone()),
+        None if rest.is_empty() => Some("mpsc".to_string()),
+        None => None,
+    }
+}
+
+/// The name of the first function or method called inside `expr`.
+fn first_call(expr: &Expr) -> Option<String> {
+    struct Finder(Option<String>);
+    impl<'ast> Visit<'ast> for Finder {
+        fn visit_expr_call(&mut self, call: &'ast syn::ExprCall) {
+            if self.0.is_none() {
+                self.0 = call_path(&call.func).pop();
+            }
+            visit::visit_expr_call(self, call);
+        }
+        fn visit_expr_method_call(&mut self, call: &'ast syn::ExprMethodCall) {
+            // The receiver is evaluated first
+            visit::visit_expr(self, &call.receiver);
+            if self.0.is_none() {
+                self.0 = Some(call.method.to_string());
+            }
+        }
+    }
+    let mut finder = Finder(None);
+    finder.visit_expr(expr);
+    finder.0
+}
+
+/// Walks one function body. `context` holds the function and the tasks
+/// spawned around the current expression; the innermost one sends, receives
+/// and awaits.
+struct Walker<'a, 'm> {
+    topology: &'a mut Topology,
+    function: &'a FnInfo<'m>,
+    by_name: &'a HashMap<String, Vec<String>>,
+    context: Vec<String>,
+    /// Local channel ends: variable name to channel key and role.
+    ends: HashMap<String, (String, Role)>,
+    /// Channel ends in `self`'s fields.
+    fields: HashMap<String, (String, Role)>,
+    spawned: usize,
+}
+
+impl Walker<'_, '_> {
+    fn current(&self) -> &str {
+        self.context.last().map(String::as_str).unwrap_or_default()
+    }
+
+    /// The channel end an expression names: a local or a field of `self`.
+    fn end_of(&self, expr: &Expr) -> Option<(String, Role)> {
+        match expr {
+            Expr::Path(p) => {
+                let ident = p.path.get_ident()?.to_string();
+                self.ends.get(&ident).cloned()
+            }
+            Expr::Field(f) => {
+                let Expr::Path(base) = &*f.base else {
+                    return None;
+                };
+                if !base.path.is_ident("self") {
+                    return None;
+                }
+                let syn::Member::Named(name) = &f.member else {
+                    return None;
+                };
+                self.fields.get(&name.to_string()).cloned()
+            }
+            Expr::Reference(r) => self.end_of(&r.expr),
+            Expr::Paren(p) => self.end_of(&p.expr),
+            _ => None,
+        }
+    }
+
+    fn use_end(&mut self, (channel, role): (String, Role)) {
+        self.ensure_channel(&channel);
+        let current = self.current().to_string();
+        match role {
+            Role::Send => self.topology.connect(&current, &channel, "send"),
+            Role::Recv => self.topology.connect(&channel, &current, "recv"),
+        }
+    }
+
+    fn ensure_channel(&mut self, key: &str) {
+        let label = key.strip_prefix("channel::").unwrap_or(key).to_string();
+        self.topology.nodes.entry(key.to_string()).or_insert(Node {
+            label,
+            kind: NodeKind::Channel,
+        });
+    }
+
+    /// Records a spawned task or thread running `future` and walks it in the
+    /// task's context.
+    fn spawn(&mut self, kind: NodeKind, future: &Expr) {
+        self.spawned += 1;
+        let key = format!("{}::task{}", self.function.key, self.spawned);
+        let runs = first_call(future);
+        let prefix = if kind == NodeKind::Thread {
+            "thread"
+        } else {
+            "spawn"
+        };
+        let label = match &runs {
+            Some(name) => format!("{}: {}", prefix, name),
+            None => format!("{} {}", prefix, self.spawned),
+        };
+        self.topology
+            .nodes
+            .insert(key.clone(), Node { label, kind });
+        let current = self.current().to_string();
+        self.topology.connect(&current, &key, "spawns");
+
+        if let Some(targets) = runs.as_ref().and_then(|name| self.by_name.get(name)) {
+            for target in targets.clone() {
+                self.topology.connect(&key, &target, "runs");
+            }
+        }
+
+        self.context.push(key);
+        self.visit_expr(future);
+        self.context.pop();
+    }
+}
+
+impl<'ast> Visit<'ast> for Walker<'_, '_> {
+    fn visit_local(&mut self, local: &'ast syn::Local) {
+        if let Some(init) = &local.init {
+            let names: Vec<String> = match &local.pat {
+                Pat::Tuple(t) => t
+                    .elems
+                    .iter()
+                    .filter_map(|p| match p {
+                        Pat::Ident(i) => Some(i.ident.to_string()),
+                        _ => None,
+                    })
+                    .collect(),
+                Pat::Ident(i) => vec![i.ident.to_string()],
+                Pat::Type(t) => match &*t.pat {
+                    Pat::Ident(i) => vec![i.ident.to_string()],
+                    _ => Vec::new(),
+                },
+                _ => Vec::new(),
+            };
+
+            match &*init.expr {
+                // let (tx, rx) = mpsc::channel(16);
+                Expr::Call(call) if names.len() == 2 => {
+                    if let Some(flavour) = channel_flavour(&call_path(&call.func)) {
+                        let key = format!("{}::{}", self.function.key, names[0]);
+                        self.topology.nodes.insert(
+                            key.clone(),
+                            Node {
+                                label: format!("{} ({}, {})", flavour, names[0], names[1]),
+                                kind: NodeKind::Channel,
+                            },
+                        );
+                        self.ends
+                            .insert(names[0].clone(), (key.clone(), Role::Send));
+                        self.ends.insert(names[1].clone(), (key, Role::Recv));
+                    }
+                }
+                // let tx2 = tx.clone(); let rx2 = tx.subscribe();
+                Expr::MethodCall(call) if names.len() == 1 => {
+                    if let Some((channel, role)) = self.end_of(&call.receiver) {
+                        match call.method.to_string().as_str() {
+                            "clone" => {
+                                self.ends.insert(names[0].clone(), (channel, role));
+                            }
+                            "subscribe" => {
+                                self.ends.insert(names[0].clone(), (channel, Role::Recv));
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        visit::visit_local(self, local);
+    }
+
+    fn visit_expr_call(&mut self, call: &'ast syn::ExprCall) {
+        let path = call_path(&call.func);
+        let spawn = path.last().is_some_and(|last| {
+            matches!(last.as_str(), "spawn" | "spawn_blocking" | "spawn_local")
+        });
+        if let (true, Some(future)) = (spawn, call.args.first()) {
+            let is_thread = path.iter().any(|s| s == "thread")
+                || path.last().is_some_and(|s| s == "spawn_blocking");
+            let kind = if is_thread {
+                NodeKind::Thread
+            } else {
+                NodeKind::Task
+            };
+            self.spawn(kind, future);
+            return;
+        }
+
+        // A channel end handed to a function is used by whoever calls it
+        for arg in &call.args {
+            if let Some(end) = self.end_of(arg) {
+                self.use_end(end);
+            }
+        }
+        visit::visit_expr_call(self, call);
+    }
+
+    fn visit_expr_method_call(&mut self, call: &'ast syn::ExprMethodCall) {
+        let method = call.method.to_string();
+
+        // JoinSet::spawn(async { .. }), thread::Builder::new().spawn(|| ..)
+        if method == "spawn" {
+            match call.args.first() {
+                Some(future @ Expr::Async(_)) => return self.spawn(NodeKind::Task, future),
+                Some(closure @ Expr::Closure(_)) => return self.spawn(NodeKind::Thread, closure),
+                _ => {}
+            }
+        }
+
+        if let Some((channel, role)) = self.end_of(&call.receiver) {
+            let used = match role {
+                Role::Send => SEND_METHODS.contains(&method.as_str()),
+                Role::Recv => RECV_METHODS.contains(&method.as_str()),
+            };
+            if used {
+                self.use_end((channel, role));
+            }
+        }
+        for arg in &call.args {
+            if let Some(end) = self.end_of(arg) {
+                self.use_end(end);
+            }
+        }
+        visit::visit_expr_method_call(self, call);
+    }
+
+    fn visit_expr_await(&mut self, expr: &'ast syn::ExprAwait) {
+        // A oneshot receiver is awaited directly
+        if let Some(end @ (_, Role::Recv)) = self.end_of(&expr.base) {
+            self.use_end(end);
+        }
+
+        let callee = match &*expr.base {
+            Expr::Call(call) => call_path(&call.func).pop(),
+            Expr::MethodCall(call) => Some(call.method.to_string()),
+            _ => None,
+        };
+        if let Some(targets) = callee.and_then(|name| self.by_name.get(&name)) {
+            let current = self.current().to_string();
+            for target in targets.clone() {
+                if !self.topology.connected(&current, &target) {
+                    self.topology.connect(&current, &target, "await");
+                }
+            }
+        }
+        visit::visit_expr_await(self, expr);
+    }
+
+    // Items nested in a body are separate functions
+    fn visit_item(&mut self, _: &'ast syn::Item) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn test_tasks_and_channels() {
+        let source = r#"
+            use tokio::sync::mpsc;
+            pub struct Command;
+            pub struct Service { commands: mpsc::Sender<Command> }
+            pub struct Worker { inbox: mpsc::Receiver<Command> }
+            impl Service {
+                pub async fn submit(&self) { self.commands.send(Command).await; }
+            }
+            impl Worker {
+                pub async fn run(mut self) { while let Some(c) = self.inbox.recv().await {} }
+            }
+            pub async fn start() {
+                let (tx, mut rx) = mpsc::channel(8);
+                let tx2 = tx.clone();
+                tokio::spawn(async move { tx2.send(1).await });
+                tokio::spawn(consume(rx));
+                std::thread::spawn(move || blocking());
+                fetch().await;
+            }
+            async fn consume(rx: mpsc::Receiver<u32>) {}
+            async fn fetch() {}
+            fn blocking() {}
+        "#;
+        let model = CrateModel::from_source(Path::new("lib.rs"), source).unwrap();
+        let topology = Topology::from_model(&model);
+        let edges: Vec<String> = topology
+            .edges
+            .iter()
+            .map(|e| format!("{} -{}-> {}", node_id(&e.from), e.label, node_id(&e.to)))
+            .collect();
+        assert_eq!(
+            edges,
+            [
+                "channel_mpsc_Command -recv-> Worker_run",
+                "Service_submit -send-> channel_mpsc_Command",
+                "start -await-> fetch",
+                "start -spawns-> start_task1",
+                "start -spawns-> start_task2",
+                "start -spawns-> start_task3",
+                "start_task1 -send-> start_tx",
+                "start_task2 -runs-> consume",
+                "start_task3 -runs-> blocking",
+                "start_tx -recv-> start_task2",
+            ]
+        );
+        assert_eq!(topology.nodes["crate::start::task3"].kind, NodeKind::Thread);
+
+        let mermaid = topology.to_mermaid();
+        assert!(mermaid.contains("    start_tx{{\"mpsc (tx, rx)\"}}\n"));
+        assert!(mermaid.contains("    start -.->|await| fetch\n"));
+    }
+}
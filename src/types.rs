@@ -0,0 +1,746 @@
+//! Syn-based analysis of Rust types: finds every named type inside a field,
+//! parameter or return type together with the chain of wrappers around it, so
+//! `Arc<Mutex<Vec<Job>>>` yields `Job` behind `Arc -> Mutex -> Vec`.
+
+use crate::model::{absolute_path, generic_names, tokens_to_string, CrateModel, Import};
+use std::collections::HashMap;
+use std::rc::Rc;
+use syn::visit_mut::{self, VisitMut};
+use syn::{
+    GenericArgument, Item, PathArguments, ReturnType, Signature, Type, TypeParamBound, TypePath,
+};
+
+/// A layer between a field or signature and the type it refers to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Wrapper {
+    /// `&T` or `&mut T`.
+    Ref {
+        mutable: bool,
+    },
+    /// `*const T` or `*mut T`.
+    Ptr,
+    Box,
+    /// `Rc` or `Arc`.
+    Shared(String),
+    /// `rc::Weak` or `sync::Weak`.
+    Weak,
+    /// `Cell`, `RefCell`, `Mutex`, `RwLock` and friends.
+    Lock(String),
+    Option,
+    Result,
+    /// `Vec`, `VecDeque`, `HashSet`, ... and slices and arrays.
+    Collection(String),
+    /// `HashMap` or `BTreeMap`; both keys and values sit behind it.
+    Map(String),
+    /// `dyn Trait`.
+    TraitObject,
+    /// `impl Trait`.
+    ImplTrait,
+    /// Parameters and return type of a `fn(..)` pointer.
+    FnPointer,
+}
+
+/// A named type found inside a larger type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeRef {
+    /// Last path segment, e.g. `Job` for `crate::queue::Job`.
+    pub name: String,
+    /// The path as written, e.g. `queue::Job`.
+    pub path: String,
+    /// Wrappers from the outermost inwards.
+    pub wrappers: Vec<Wrapper>,
+}
+
+impl Wrapper {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Wrapper::Ref { mutable: false } => "&",
+            Wrapper::Ref { mutable: true } => "&mut",
+            Wrapper::Ptr => "*",
+            Wrapper::Box => "Box",
+            Wrapper::Shared(name) | Wrapper::Lock(name) => name,
+            Wrapper::Collection(name) | Wrapper::Map(name) => name,
+            Wrapper::Weak => "Weak",
+            Wrapper::Option => "Option",
+            Wrapper::Result => "Result",
+            Wrapper::TraitObject => "dyn",
+            Wrapper::ImplTrait => "impl",
+            Wrapper::FnPointer => "fn",
+        }
+    }
+
+    /// Recognises the std wrapper named by the last segment of a path.
+    fn from_ident(ident: &str) -> Option<Wrapper> {
+        Some(match ident {
+            "Box" => Wrapper::Box,
+            "Rc" | "Arc" => Wrapper::Shared(ident.to_string()),
+            "Weak" => Wrapper::Weak,
+            "Cell" | "RefCell" | "OnceCell" | "Mutex" | "RwLock" | "OnceLock" => {
+                Wrapper::Lock(ident.to_string())
+            }
+            "Option" => Wrapper::Option,
+            "Result" => Wrapper::Result,
+            "Vec" | "VecDeque" | "LinkedList" | "HashSet" | "BTreeSet" | "BinaryHeap" => {
+                Wrapper::Collection(ident.to_string())
+            }
+            "HashMap" | "BTreeMap" => Wrapper::Map(ident.to_string()),
+            _ => return None,
+        })
+    }
+}
+
+impl TypeRef {
+    /// True when the type is held many times: behind a collection, map, slice or array.
+    pub fn is_collection(&self) -> bool {
+        self.wrappers
+            .iter()
+            .any(|w| matches!(w, Wrapper::Collection(_) | Wrapper::Map(_)))
+    }
+
+    /// True when the type may be absent.
+    pub fn is_optional(&self) -> bool {
+        self.wrappers.contains(&Wrapper::Option)
+    }
+
+    /// True when ownership is shared through `Rc`/`Arc`.
+    pub fn is_shared(&self) -> bool {
+        self.wrappers
+            .iter()
+            .any(|w| matches!(w, Wrapper::Shared(_) | Wrapper::Weak))
+    }
+
+    /// True when the outermost layer is a reference or raw pointer, i.e. the
+    /// type is used but not owned.
+    pub fn is_borrowed(&self) -> bool {
+        matches!(
+            self.wrappers.first(),
+            Some(Wrapper::Ref { .. } | Wrapper::Ptr)
+        )
+    }
+
+    /// The wrapper chain as written, e.g. `Arc<Mutex<Vec<_>>>`.
+    pub fn chain(&self) -> String {
+        let mut out = String::new();
+        let mut closers = 0;
+        for wrapper in &self.wrappers {
+            match wrapper {
+                Wrapper::Ref { .. } | Wrapper::Ptr => out.push_str(wrapper.as_str()),
+                Wrapper::TraitObject | Wrapper::ImplTrait | Wrapper::FnPointer => {
+                    out.push_str(wrapper.as_str());
+                    out.push(' ');
+                }
+                _ => {
+                    out.push_str(wrapper.as_str());
+                    out.push('<');
+                    closers += 1;
+                }
+            }
+        }
+        out.push_str(&self.name);
+        out.push_str(&">".repeat(closers));
+        out
+    }
+}
+
+/// A `type Name<T, ..> = Target;` declaration.
+#[derive(Debug, Clone)]
+struct Alias {
+    params: Vec<String>,
+    ty: Type,
+}
+
+/// What every module declares and imports, shared by the analyzers scoped to
+/// each of them.
+#[derive(Debug, Clone, Default)]
+struct Scopes {
+    /// Aliases by the path of the declaring module, then by name.
+    aliases: HashMap<String, HashMap<String, Alias>>,
+    imports: HashMap<String, Vec<Import>>,
+}
+
+/// Walks types, expanding the type aliases it has been told about.
+///
+/// Names are looked up from one module, `crate` unless set with
+/// [`TypeAnalyzer::in_module`], the way [`CrateModel::resolve`] does: the
+/// module's own aliases, then its imports, then an alias declared exactly once
+/// in the crate.
+#[derive(Debug, Clone)]
+pub struct TypeAnalyzer {
+    scopes: Rc<Scopes>,
+    module: String,
+    /// Generic parameters of the item whose types are analysed.
+    params: Vec<String>,
+}
+
+impl Default for TypeAnalyzer {
+    fn default() -> Self {
+        TypeAnalyzer {
+            scopes: Rc::default(),
+            module: "crate".to_string(),
+            params: Vec::new(),
+        }
+    }
+}
+
+impl TypeAnalyzer {
+    pub fn new() -> Self {
+        TypeAnalyzer::default()
+    }
+
+    /// Registers every `type Name = ...;` among `items`, including inline
+    /// modules, taking `items` to be the crate root.
+    pub fn with_aliases_from(mut self, items: &[Item]) -> Self {
+        self.add_aliases("crate", items);
+        self
+    }
+
+    fn add_aliases(&mut self, module: &str, items: &[Item]) {
+        for item in items {
+            match item {
+                Item::Type(alias) => self.add_alias(
+                    module,
+                    alias.ident.to_string(),
+                    generic_names(&alias.generics),
+                    (*alias.ty).clone(),
+                ),
+                Item::Mod(m) => {
+                    if let Some((_, inner)) = &m.content {
+                        self.add_aliases(&format!("{}::{}", module, m.ident), inner);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Registers every type alias declared in the crate, and every module's
+    /// imports to find them by.
+    pub fn from_model(model: &CrateModel) -> Self {
+        let mut analyzer = TypeAnalyzer::new();
+        for module in &model.modules {
+            for alias in &module.aliases {
+                analyzer.add_alias(
+                    &module.path,
+                    alias.name.clone(),
+                    alias.generics.clone(),
+                    alias.ty.clone(),
+                );
+            }
+            Rc::make_mut(&mut analyzer.scopes)
+                .imports
+                .entry(module.path.clone())
+                .or_default()
+                .extend(module.imports.iter().cloned());
+        }
+        analyzer
+    }
+
+    pub fn add_alias(&mut self, module: &str, name: String, params: Vec<String>, ty: Type) {
+        Rc::make_mut(&mut self.scopes)
+            .aliases
+            .entry(module.to_string())
+            .or_default()
+            .insert(name, Alias { params, ty });
+    }
+
+    /// An analyzer looking names up from the module at `path`.
+    pub fn in_module(&self, path: &str) -> Self {
+        TypeAnalyzer {
+            module: path.to_string(),
+            ..self.clone()
+        }
+    }
+
+    /// An analyzer treating `T::Name` as an associated type of one of
+    /// `params`, the generic parameters of the item being analysed.
+    pub fn with_params(&self, params: &[String]) -> Self {
+        let mut analyzer = self.clone();
+        analyzer.params.extend(params.iter().cloned());
+        analyzer
+    }
+
+    /// Every named type inside `ty`, wrappers excluded, in source order.
+    pub fn analyze(&self, ty: &Type) -> Vec<TypeRef> {
+        let mut refs = Vec::new();
+        let mut expanding = Vec::new();
+        self.walk(ty, &mut Vec::new(), &mut expanding, &mut refs);
+        refs
+    }
+
+    /// Types referenced by a function's parameters and return type.
+    pub fn analyze_signature(&self, sig: &Signature) -> Vec<TypeRef> {
+        let analyzer = self.with_params(&generic_names(&sig.generics));
+        let mut refs = Vec::new();
+        for input in &sig.inputs {
+            if let syn::FnArg::Typed(pat) = input {
+                refs.extend(analyzer.analyze(&pat.ty));
+            }
+        }
+        refs.extend(analyzer.analyze_return(&sig.output));
+        refs
+    }
+
+    pub fn analyze_return(&self, output: &ReturnType) -> Vec<TypeRef> {
+        match output {
+            ReturnType::Default => Vec::new(),
+            ReturnType::Type(_, ty) => self.analyze(ty),
+        }
+    }
+
+    fn walk(
+        &self,
+        ty: &Type,
+        wrappers: &mut Vec<Wrapper>,
+        expanding: &mut Vec<String>,
+        refs: &mut Vec<TypeRef>,
+    ) {
+        match ty {
+            Type::Path(tp) => self.walk_path(tp, wrappers, expanding, refs),
+            Type::Reference(r) => self.nested(
+                Wrapper::Ref {
+                    mutable: r.mutability.is_some(),
+                },
+                &r.elem,
+                wrappers,
+                expanding,
+                refs,
+            ),
+            Type::Ptr(p) => self.nested(Wrapper::Ptr, &p.elem, wrappers, expanding, refs),
+            Type::Slice(s) => self.nested(
+                Wrapper::Collection("[]".to_string()),
+                &s.elem,
+                wrappers,
+                expanding,
+                refs,
+            ),
+            Type::Array(a) => self.nested(
+                Wrapper::Collection("[;]".to_string()),
+                &a.elem,
+                wrappers,
+                expanding,
+                refs,
+            ),
+            Type::Paren(p) => self.walk(&p.elem, wrappers, expanding, refs),
+            Type::Group(g) => self.walk(&g.elem, wrappers, expanding, refs),
+            Type::Tuple(t) => {
+                for elem in &t.elems {
+                    self.walk(elem, wrappers, expanding, refs);
+                }
+            }
+            Type::TraitObject(t) => {
+                wrappers.push(Wrapper::TraitObject);
+                self.walk_bounds(&t.bounds, wrappers, expanding, refs);
+                wrappers.pop();
+            }
+            Type::ImplTrait(t) => {
+                wrappers.push(Wrapper::ImplTrait);
+                self.walk_bounds(&t.bounds, wrappers, expanding, refs);
+                wrappers.pop();
+            }
+            Type::BareFn(f) => {
+                wrappers.push(Wrapper::FnPointer);
+                for input in &f.inputs {
+                    self.walk(&input.ty, wrappers, expanding, refs);
+                }
+                if let ReturnType::Type(_, ret) = &f.output {
+                    self.walk(ret, wrappers, expanding, refs);
+                }
+                wrappers.pop();
+            }
+            _ => {}
+        }
+    }
+
+    fn nested(
+        &self,
+        wrapper: Wrapper,
+        inner: &Type,
+        wrappers: &mut Vec<Wrapper>,
+        expanding: &mut Vec<String>,
+        refs: &mut Vec<TypeRef>,
+    ) {
+        wrappers.push(wrapper);
+        self.walk(inner, wrappers, expanding, refs);
+        wrappers.pop();
+    }
+
+    fn walk_path(
+        &self,
+        tp: &TypePath,
+        wrappers: &mut Vec<Wrapper>,
+        expanding: &mut Vec<String>,
+        refs: &mut Vec<TypeRef>,
+    ) {
+        // `<T as Trait>::Output`: both the self type and the trait are referenced.
+        if let Some(qself) = &tp.qself {
+            self.walk(&qself.ty, wrappers, expanding, refs);
+        }
+
+        let Some(last) = tp.path.segments.last() else {
+            return;
+        };
+        let ident = last.ident.to_string();
+
+        if tp.qself.is_none() {
+            let args: Vec<&Type> = match &last.arguments {
+                PathArguments::AngleBracketed(args) => args
+                    .args
+                    .iter()
+                    .filter_map(|arg| match arg {
+                        GenericArgument::Type(ty) => Some(ty),
+                        _ => None,
+                    })
+                    .collect(),
+                _ => Vec::new(),
+            };
+            // An alias used with another number of arguments is some other type,
+            // e.g. `Result<T, E>` next to `type Result<T> = ...`.
+            if let Some((key, alias)) = self
+                .find_alias(&path_string(tp))
+                .filter(|(_, alias)| alias.params.len() == args.len())
+            {
+                // Guard against `type A = Vec<A>;`-style cycles.
+                if !expanding.contains(&key) {
+                    let bindings: HashMap<&str, &Type> =
+                        alias.params.iter().map(String::as_str).zip(args).collect();
+                    let mut target = alias.ty.clone();
+                    Substitute(&bindings).visit_type_mut(&mut target);
+                    expanding.push(key);
+                    self.walk(&target, wrappers, expanding, refs);
+                    expanding.pop();
+                }
+                return;
+            }
+        }
+
+        // `Self::Item` and `T::Output` name an associated type, not a type of their own.
+        let is_associated = tp.qself.is_none()
+            && tp.path.segments.len() == 2
+            && (tp.path.segments[0].ident == "Self"
+                || self.params.iter().any(|p| tp.path.segments[0].ident == p));
+
+        let wrapper = Wrapper::from_ident(&ident);
+        match &wrapper {
+            Some(w) => wrappers.push(w.clone()),
+            None if !is_associated => refs.push(TypeRef {
+                name: ident.clone(),
+                path: path_string(tp),
+                wrappers: wrappers.clone(),
+            }),
+            None => {}
+        }
+
+        for segment in &tp.path.segments {
+            match &segment.arguments {
+                PathArguments::AngleBracketed(args) => {
+                    for arg in &args.args {
+                        match arg {
+                            GenericArgument::Type(inner) => {
+                                self.walk(inner, wrappers, expanding, refs)
+                            }
+                            // `Iterator<Item = Job>`
+                            GenericArgument::AssocType(assoc) => {
+                                self.walk(&assoc.ty, wrappers, expanding, refs)
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                // `Fn(Job) -> Report`
+                PathArguments::Parenthesized(args) => {
+                    for input in &args.inputs {
+                        self.walk(input, wrappers, expanding, refs);
+                    }
+                    if let ReturnType::Type(_, ret) = &args.output {
+                        self.walk(ret, wrappers, expanding, refs);
+                    }
+                }
+                PathArguments::None => {}
+            }
+        }
+
+        if wrapper.is_some() {
+            wrappers.pop();
+        }
+    }
+
+    fn walk_bounds<'a>(
+        &self,
+        bounds: impl IntoIterator<Item = &'a TypeParamBound>,
+        wrappers: &mut Vec<Wrapper>,
+        expanding: &mut Vec<String>,
+        refs: &mut Vec<TypeRef>,
+    ) {
+        for bound in bounds {
+            if let TypeParamBound::Trait(t) = bound {
+                let tp = TypePath {
+                    qself: None,
+                    path: t.path.clone(),
+                };
+                self.walk_path(&tp, wrappers, expanding, refs);
+            }
+        }
+    }
+
+    /// The alias `written` names from the current module, with the declaring
+    /// module's path and its name as a key.
+    fn find_alias(&self, written: &str) -> Option<(String, &Alias)> {
+        let scopes = &*self.scopes;
+        let get = |module: &str, name: &str| {
+            let alias = scopes.aliases.get(module)?.get(name)?;
+            Some((format!("{}::{}", module, name), alias))
+        };
+        let imports = scopes
+            .imports
+            .get(&self.module)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+
+        let Some((prefix, name)) = written.rsplit_once("::") else {
+            let imported = || {
+                imports
+                    .iter()
+                    .filter(|i| i.name == written)
+                    .find_map(|i| i.path.rsplit_once("::").and_then(|(m, n)| get(m, n)))
+            };
+            let glob = || {
+                imports
+                    .iter()
+                    .filter(|i| i.name == "*")
+                    .find_map(|i| get(&i.path, written))
+            };
+            // A name imported from elsewhere, such as `std::io::Result`, is not
+            // an alias of this crate even when one shares its name.
+            let unique = || {
+                if imports.iter().any(|i| i.name == written) {
+                    return None;
+                }
+                let mut all = scopes
+                    .aliases
+                    .iter()
+                    .filter_map(|(module, aliases)| Some((module, aliases.get(written)?)));
+                match (all.next(), all.next()) {
+                    (Some((module, alias)), None) => {
+                        Some((format!("{}::{}", module, written), alias))
+                    }
+                    _ => None,
+                }
+            };
+            return get(&self.module, written)
+                .or_else(imported)
+                .or_else(glob)
+                .or_else(unique);
+        };
+
+        let (first, rest) = prefix.split_once("::").unwrap_or((prefix, ""));
+        let module = match first {
+            "crate" => prefix.to_string(),
+            "self" | "super" => absolute_path(&self.module, prefix),
+            _ => match imports.iter().find(|i| i.name == first) {
+                Some(import) if rest.is_empty() => import.path.clone(),
+                Some(import) => format!("{}::{}", import.path, rest),
+                None => format!("{}::{}", self.module, prefix),
+            },
+        };
+        get(&module, name)
+    }
+}
+
+/// Replaces an alias's type parameters with the arguments it is used with.
+struct Substitute<'a>(&'a HashMap<&'a str, &'a Type>);
+
+impl VisitMut for Substitute<'_> {
+    fn visit_type_mut(&mut self, ty: &mut Type) {
+        if let Type::Path(tp) = ty {
+            let bound = tp
+                .path
+                .get_ident()
+                .filter(|_| tp.qself.is_none())
+                .and_then(|ident| self.0.get(ident.to_string().as_str()));
+            if let Some(bound) = bound {
+                *ty = (*bound).clone();
+                return;
+            }
+        }
+        visit_mut::visit_type_mut(self, ty);
+    }
+}
+
+fn path_string(tp: &TypePath) -> String {
+    tp.path
+        .segments
+        .iter()
+        .map(|s| s.ident.to_string())
+        .collect::<Vec<_>>()
+        .join("::")
+}
+
+/// Renders a type compactly for diagrams: paths shortened to their last
+/// segment, everything else as written, e.g. `HashMap<String, Vec<Job>>`,
+/// `(u8, Job)`, `&dyn Handler`, `fn(Job) -> bool`.
+pub fn display_type(ty: &Type) -> String {
+    match ty {
+        Type::Path(tp) if tp.qself.is_none() => {
+            let Some(last) = tp.path.segments.last() else {
+                return tokens_to_string(ty);
+            };
+            let mut out = last.ident.to_string();
+            if let PathArguments::AngleBracketed(args) = &last.arguments {
+                let inner: Vec<String> = args
+                    .args
+                    .iter()
+                    .map(|arg| match arg {
+                        GenericArgument::Type(t) => display_type(t),
+                        GenericArgument::AssocType(a) => {
+                            format!("{} = {}", a.ident, display_type(&a.ty))
+                        }
+                        other => tokens_to_string(other),
+                    })
+                    .collect();
+                out.push_str(&format!("<{}>", inner.join(", ")));
+            } else if !last.arguments.is_none() {
+                out.push_str(&tokens_to_string(&last.arguments));
+            }
+            out
+        }
+        Type::Reference(r) => {
+            let mutability = if r.mutability.is_some() { "mut " } else { "" };
+            format!("&{}{}", mutability, display_type(&r.elem))
+        }
+        Type::Slice(s) => format!("[{}]", display_type(&s.elem)),
+        Type::Array(a) => format!("[{}; {}]", display_type(&a.elem), tokens_to_string(&a.len)),
+        Type::Paren(p) => display_type(&p.elem),
+        Type::Group(g) => display_type(&g.elem),
+        Type::Tuple(t) => {
+            let elems: Vec<String> = t.elems.iter().map(display_type).collect();
+            format!("({})", elems.join(", "))
+        }
+        _ => tokens_to_string(ty),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn names(analyzer: &TypeAnalyzer, source: &str) -> Vec<(String, String)> {
+        let ty: Type = syn::parse_str(source).unwrap();
+        analyzer
+            .analyze(&ty)
+            .into_iter()
+            .map(|r| (r.name.clone(), r.chain()))
+            .collect()
+    }
+
+    #[test]
+    fn test_nested_generics_and_wrappers() {
+        let analyzer = TypeAnalyzer::new();
+        assert_eq!(
+            names(&analyzer, "HashMap<Key, Vec<Foo>>"),
+            [
+                ("Key".to_string(), "HashMap<Key>".to_string()),
+                ("Foo".to_string(), "HashMap<Vec<Foo>>".to_string()),
+            ]
+        );
+        assert_eq!(
+            names(&analyzer, "Arc<Mutex<Vec<Job>>>"),
+            [("Job".to_string(), "Arc<Mutex<Vec<Job>>>".to_string())]
+        );
+        let refs: Vec<String> = names(
+            &analyzer,
+            "(Box<dyn Iterator<Item = Job>>, &[Task; 4], fn(Event) -> Report)",
+        )
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect();
+        assert_eq!(refs, ["Iterator", "Job", "Task", "Event", "Report"]);
+    }
+
+    #[test]
+    fn test_aliases_expand() {
+        let file: syn::File =
+            syn::parse_str("type Jobs = Vec<Job>; type Cyclic = Vec<Cyclic>;").unwrap();
+        let analyzer = TypeAnalyzer::new().with_aliases_from(&file.items);
+        let ty: Type = syn::parse_str("Option<Jobs>").unwrap();
+        let refs = analyzer.analyze(&ty);
+        assert_eq!(refs.len(), 1);
+        assert_eq!(refs[0].name, "Job");
+        assert!(refs[0].is_collection() && refs[0].is_optional());
+        assert!(names(&analyzer, "Cyclic").is_empty());
+    }
+
+    #[test]
+    fn test_generic_aliases_bind_their_arguments() {
+        let source = r#"
+            pub type Result<T> = std::result::Result<T, Error>;
+            pub mod net {
+                use crate::Result;
+                pub type Handle = Arc<Conn>;
+                pub fn open() -> Result<Config> { todo!() }
+            }
+            pub mod jobs {
+                use std::io::Result;
+                pub type Handle = Vec<Task>;
+            }
+        "#;
+        let model = CrateModel::from_source(Path::new("lib.rs"), source).unwrap();
+        let analyzer = TypeAnalyzer::from_model(&model);
+        let pairs = |pairs: &[(&str, &str)]| -> Vec<(String, String)> {
+            pairs
+                .iter()
+                .map(|(name, chain)| (name.to_string(), chain.to_string()))
+                .collect()
+        };
+
+        let net = analyzer.in_module("crate::net");
+        let expected = pairs(&[("Config", "Result<Config>"), ("Error", "Result<Error>")]);
+        assert_eq!(names(&net, "Result<Config>"), expected);
+        assert_eq!(names(&analyzer, "Result<Config>"), expected);
+        // std's two-argument `Result`, or one imported from elsewhere, is not the alias
+        assert_eq!(
+            names(&net, "Result<Config, Other>"),
+            pairs(&[("Config", "Result<Config>"), ("Other", "Result<Other>")])
+        );
+        assert_eq!(
+            names(&analyzer.in_module("crate::jobs"), "Result<Config>"),
+            pairs(&[("Config", "Result<Config>")])
+        );
+
+        // Each module sees its own alias; a name declared twice is left alone elsewhere
+        assert_eq!(names(&net, "Handle"), pairs(&[("Conn", "Arc<Conn>")]));
+        assert_eq!(
+            names(&analyzer.in_module("crate::jobs"), "Handle"),
+            pairs(&[("Task", "Vec<Task>")])
+        );
+        assert_eq!(
+            names(&analyzer, "jobs::Handle"),
+            pairs(&[("Task", "Vec<Task>")])
+        );
+        assert_eq!(names(&analyzer, "Handle"), pairs(&[("Handle", "Handle")]));
+    }
+
+    #[test]
+    fn test_associated_types_of_generic_parameters() {
+        let sig: Signature =
+            syn::parse_str("fn run<T: Task>(task: T, out: T::Output) -> Self::Report").unwrap();
+        let refs: Vec<String> = TypeAnalyzer::new()
+            .analyze_signature(&sig)
+            .into_iter()
+            .map(|r| r.name)
+            .collect();
+        assert_eq!(refs, ["T"]);
+
+        let analyzer = TypeAnalyzer::new().with_params(&["T".to_string()]);
+        assert!(names(&analyzer, "Vec<T::Output>").is_empty());
+        // Not a parameter, so a path to a type
+        assert_eq!(names(&analyzer, "io::Error")[0].0, "Error");
+    }
+
+    #[test]
+    fn test_display_type() {
+        let ty: Type =
+            syn::parse_str("std::collections::HashMap<String, (u8, &[crate::Job])>").unwrap();
+        assert_eq!(display_type(&ty), "HashMap<String, (u8, &[Job])>");
+    }
+}
@@ -0,0 +1,3 @@
+//! A small shop used by the golden-file tests.
+
+pub mod modules;
@@ -0,0 +1,38 @@
+//! Invoices and payments.
+
+/// How invoices are numbered.
+pub struct Config {
+    pub prefix: String,
+}
+
+pub struct Invoice {
+    pub id: u64,
+    pub order_id: u64,
+    pub amount: u64,
+    pub payments: Vec<Payment>,
+}
+
+pub struct Payment {
+    pub invoice_id: u64,
+    pub method: Method,
+}
+
+pub enum Method {
+    Card,
+    Transfer,
+}
+
+pub struct Ledger {
+    config: Config,
+    invoices: Vec<Invoice>,
+}
+
+impl Ledger {
+    pub fn invoice(&self, id: u64) -> Option<Invoice> {
+        None
+    }
+
+    pub fn record(&mut self, payment: Payment) -> Ledger {
+        todo!()
+    }
+}
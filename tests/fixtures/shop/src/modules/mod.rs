@@ -0,0 +1,5 @@
+//! Orders, billing and the workers connecting them.
+
+pub mod billing;
+pub mod orders;
+pub mod workers;
@@ -0,0 +1,94 @@
+//! Orders and the customers placing them.
+
+use crate::modules::billing::{Invoice, Ledger};
+use std::collections::{HashMap, HashSet};
+
+/// How orders are stored.
+pub struct Config {
+    pub capacity: usize,
+    pub tags: HashSet<String>,
+}
+
+pub struct Customer {
+    pub id: u64,
+    pub name: String,
+    pub orders: Vec<Order>,
+}
+
+pub struct Order {
+    pub id: u64,
+    pub customer_id: u64,
+    pub lines: Vec<OrderLine>,
+    pub status: Status,
+    pub invoice: Option<Invoice>,
+}
+
+pub struct OrderLine {
+    pub sku: String,
+    pub quantity: u32,
+}
+
+pub enum Status {
+    Open,
+    Paid { invoice: Invoice },
+    Shipped(Shipment),
+}
+
+pub struct Shipment {
+    pub carrier: String,
+}
+
+pub struct Store {
+    config: Config,
+    customers: HashMap<u64, Customer>,
+}
+
+pub trait Priced {
+    fn total(&self) -> u64;
+}
+
+impl Store {
+    pub fn new(config: Config) -> Self {
+        Store {
+            config,
+            customers: HashMap::new(),
+        }
+    }
+
+    pub fn customer(&self, id: u64) -> Option<&Customer> {
+        self.customers.get(&id)
+    }
+
+    pub fn place(&mut self, customer: Customer) -> Order {
+        todo!()
+    }
+
+    pub fn checkout(&mut self, customer: Customer, ledger: &Ledger) -> Option<Order> {
+        let mut order = self.place(customer);
+        if let Some(invoice) = ledger.invoice(order.id) {
+            order.pay(invoice);
+        }
+        Some(order)
+    }
+}
+
+impl Order {
+    pub fn pay(&mut self, invoice: Invoice) {
+        if self.status == Status::Open {
+            self.status = Status::Paid { invoice };
+        }
+    }
+
+    pub fn ship(&mut self, shipment: Shipment) {
+        self.status = match self.status {
+            Status::Paid { .. } => Status::Shipped(shipment),
+            _ => return,
+        };
+    }
+}
+
+impl Priced for Order {
+    fn total(&self) -> u64 {
+        0
+    }
+}
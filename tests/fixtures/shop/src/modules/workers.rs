@@ -0,0 +1,29 @@
+//! Background tasks passing orders to billing.
+
+use crate::modules::billing::Invoice;
+use crate::modules::orders::Order;
+use tokio::sync::mpsc;
+
+pub async fn run() {
+    let (orders_tx, orders_rx) = mpsc::channel::<Order>(16);
+    let (invoices_tx, invoices_rx) = mpsc::channel::<Invoice>(16);
+    tokio::spawn(bill(orders_rx, invoices_tx));
+    tokio::spawn(notify(invoices_rx));
+    accept(orders_tx).await;
+}
+
+async fn accept(tx: mpsc::Sender<Order>) {
+    let _ = tx;
+}
+
+async fn bill(mut rx: mpsc::Receiver<Order>, tx: mpsc::Sender<Invoice>) {
+    while let Some(order) = rx.recv().await {
+        let _ = (order, &tx);
+    }
+}
+
+async fn notify(mut rx: mpsc::Receiver<Invoice>) {
+    while let Some(invoice) = rx.recv().await {
+        let _ = invoice;
+    }
+}
@@ -0,0 +1,276 @@
+//! Golden-file tests for the generators.
+//!
+//! Each test runs generators twice, in separate processes, on fresh copies of
+//! the crate in `tests/fixtures/shop`. Both runs must write the same bytes as
+//! the files under `tests/golden`. Set `UPDATE_GOLDEN=1` to rewrite the golden
+//! files after an intended change to the output.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use walkdir::WalkDir;
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/shop");
+const GOLDEN: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden");
+
+/// A fresh copy of the fixture crate.
+fn fixture_copy(name: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = fs::remove_dir_all(&dir);
+    for entry in WalkDir::new(FIXTURE) {
+        let entry = entry.unwrap();
+        let target = dir.join(entry.path().strip_prefix(FIXTURE).unwrap());
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&target).unwrap();
+        } else {
+            fs::copy(entry.path(), &target).unwrap();
+        }
+    }
+    dir
+}
+
+fn run(dir: &Path, bin: &str, args: &[&str]) {
+    let output = Command::new(bin)
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{} {:?} failed:\n{}",
+        bin,
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+/// Runs `generate` twice and compares each of `outputs`, relative to the
+/// crate root, between the runs and with its golden copy.
+fn check(name: &str, outputs: &[&str], generate: impl Fn(&Path)) {
+    let runs: Vec<Vec<String>> = (1..=2)
+        .map(|run| {
+            let dir = fixture_copy(&format!("{}_{}", name, run));
+            generate(&dir);
+            outputs
+                .iter()
+                .map(|output| {
+                    fs::read_to_string(dir.join(output))
+                        .unwrap_or_else(|e| panic!("{} was not written: {}", output, e))
+                })
+                .collect()
+        })
+        .collect();
+
+    for (i, output) in outputs.iter().enumerate() {
+        assert_eq!(runs[0][i], runs[1][i], "{} differs between runs", output);
+
+        let golden = Path::new(GOLDEN).join(output);
+        if env::var_os("UPDATE_GOLDEN").is_some() {
+            fs::create_dir_all(golden.parent().unwrap()).unwrap();
+            fs::write(&golden, &runs[0][i]).unwrap();
+            continue;
+        }
+        let expected = fs::read_to_string(&golden).unwrap_or_else(|_| {
+            panic!(
+                "{} is missing; run with UPDATE_GOLDEN=1 to create it",
+                golden.display()
+            )
+        });
+        assert_eq!(
+            runs[0][i],
+            expected,
+            "{} does not match {}",
+            output,
+            golden.display()
+        );
+    }
+}
+
+#[test]
+fn test_class_diagram_golden() {
+    check(
+        "class_diagram",
+        &["diagrams/class_diagram.mermaid"],
+        |dir| {
+            run(dir, env!("CARGO_BIN_EXE_class_diagram"), &[]);
+        },
+    );
+}
+
+#[test]
+fn test_architecture_and_connections_golden() {
+    check(
+        "architecture",
+        &[
+            "architecture/prelude.rs",
+            "architecture/structs.rs",
+            "architecture/impls.rs",
+            "diagrams/connections.mermaid",
+            "diagrams/structs.mermaid",
+        ],
+        |dir| {
+            run(dir, env!("CARGO_BIN_EXE_architecture"), &["."]);
+            run(dir, env!("CARGO_BIN_EXE_connections"), &[]);
+            run(dir, env!("CARGO_BIN_EXE_diagram"), &["structs"]);
+        },
+    );
+}
+
+#[test]
+fn test_filtered_connections_golden() {
+    check(
+        "filtered_connections",
+        &["diagrams/connections_filtered.mermaid"],
+        |dir| {
+            run(dir, env!("CARGO_BIN_EXE_architecture"), &["."]);
+            run(
+                dir,
+                env!("CARGO_BIN_EXE_connections"),
+                &["--exclude", "crate::modules::billing::Config"],
+            );
+            // Renamed so the golden copy does not clash with the unfiltered one
+            fs::rename(
+                dir.join("diagrams/connections.mermaid"),
+                dir.join("diagrams/connections_filtered.mermaid"),
+            )
+            .unwrap();
+        },
+    );
+}
+
+#[test]
+fn test_er_diagram_golden() {
+    check("er_diagram", &["diagrams/orders.mermaid"], |dir| {
+        run(dir, env!("CARGO_BIN_EXE_not_state_diagram"), &["orders.rs"]);
+    });
+}
+
+#[test]
+fn test_async_topology_golden() {
+    check(
+        "async_topology",
+        &["diagrams/async_topology.mermaid"],
+        |dir| {
+            run(dir, env!("CARGO_BIN_EXE_async_topology"), &[]);
+        },
+    );
+}
+
+#[test]
+fn test_docs_golden() {
+    check(
+        "docs",
+        &[
+            "docs/SUMMARY.md",
+            "docs/crate.modules.billing.md",
+            "docs/crate.modules.orders.md",
+        ],
+        |dir| {
+            run(dir, env!("CARGO_BIN_EXE_docs"), &["--mdbook"]);
+        },
+    );
+}
+
+#[test]
+fn test_state_diagram_golden() {
+    check(
+        "state_diagram",
+        &["diagrams/state_diagram_Status.mermaid"],
+        |dir| {
+            run(dir, env!("CARGO_BIN_EXE_state_diagram"), &["Status"]);
+        },
+    );
+}
+
+#[test]
+fn test_sequence_golden() {
+    check(
+        "sequence",
+        &["diagrams/sequence_Store_checkout.mermaid"],
+        |dir| {
+            run(dir, env!("CARGO_BIN_EXE_sequence"), &["Store::checkout"]);
+        },
+    );
+}
+
+#[test]
+fn test_split_and_merge_golden() {
+    check(
+        "split_merge",
+        &[
+            "diagrams/connections/index.mermaid",
+            "diagrams/connections/part_1.mermaid",
+            "diagrams/connections/part_2.mermaid",
+            "diagrams/connections/part_3.mermaid",
+            "diagrams/parts.mermaid",
+        ],
+        |dir| {
+            run(dir, env!("CARGO_BIN_EXE_architecture"), &["."]);
+            run(dir, env!("CARGO_BIN_EXE_connections"), &[]);
+            run(
+                dir,
+                env!("CARGO_BIN_EXE_split_mermaid"),
+                &[
+                    "--by",
+                    "size",
+                    "--max-nodes",
+                    "4",
+                    "diagrams/connections.mermaid",
+                ],
+            );
+            // Merging the parts again, without the index, joins the stubs
+            // back up with the nodes they stand for
+            let parts = dir.join("diagrams/parts");
+            fs::create_dir_all(&parts).unwrap();
+            for part in ["part_1", "part_2", "part_3"] {
+                let name = format!("{}.mermaid", part);
+                fs::copy(
+                    dir.join("diagrams/connections").join(&name),
+                    parts.join(&name),
+                )
+                .unwrap();
+            }
+            run(
+                dir,
+                env!("CARGO_BIN_EXE_merge_mermaids"),
+                &["diagrams/parts"],
+            );
+        },
+    );
+}
+
+#[test]
+fn test_palette_golden() {
+    check(
+        "palette",
+        &[
+            "diagrams/themed.palette.mermaid",
+            "diagrams/themed.mermaid",
+            "colors.json",
+        ],
+        |dir| {
+            run(
+                dir,
+                env!("CARGO_BIN_EXE_class_diagram"),
+                &["--theme", "light"],
+            );
+            // Renamed so the golden copy does not clash with the unthemed one
+            fs::rename(
+                dir.join("diagrams/class_diagram.mermaid"),
+                dir.join("diagrams/themed.mermaid"),
+            )
+            .unwrap();
+            run(
+                dir,
+                env!("CARGO_BIN_EXE_palette"),
+                &["diagrams/themed.mermaid"],
+            );
+            run(
+                dir,
+                env!("CARGO_BIN_EXE_palette"),
+                &["--apply", "diagrams/themed.mermaid"],
+            );
+        },
+    );
+}
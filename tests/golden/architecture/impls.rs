@@ -0,0 +1,14 @@
+use prelude::*;
+
+impl Ledger {
+    pub fn invoice(&self, id: u64) -> Option<Invoice>;
+    pub fn record(&mut self, payment: Payment) -> Ledger;
+}
+impl Store {
+    pub fn customer(&self, id: u64) -> Option<&Customer>;
+    pub fn place(&mut self, customer: Customer) -> Order;
+    pub fn checkout(&mut self, customer: Customer, ledger: &Ledger) -> Option<Order>;
+    pub fn pay(&mut self, invoice: Invoice);
+    pub fn ship(&mut self, shipment: Shipment);
+    fn total(&self) -> u64;
+}
@@ -0,0 +1,56 @@
+use prelude::*;
+
+// module: crate::modules::billing
+pub struct Config {
+    pub prefix: String,
+}
+// module: crate::modules::billing
+pub struct Invoice {
+    pub id: u64,
+    pub order_id: u64,
+    pub amount: u64,
+    pub payments: Vec<Payment>,
+}
+// module: crate::modules::billing
+pub struct Ledger {
+    config: Config,
+    invoices: Vec<Invoice>,
+}
+// module: crate::modules::billing
+pub struct Payment {
+    pub invoice_id: u64,
+    pub method: Method,
+}
+// module: crate::modules::orders
+pub struct Config {
+    pub capacity: usize,
+    pub tags: HashSet<String>,
+}
+// module: crate::modules::orders
+pub struct Customer {
+    pub id: u64,
+    pub name: String,
+    pub orders: Vec<Order>,
+}
+// module: crate::modules::orders
+pub struct Order {
+    pub id: u64,
+    pub customer_id: u64,
+    pub lines: Vec<OrderLine>,
+    pub status: Status,
+    pub invoice: Option<Invoice>,
+}
+// module: crate::modules::orders
+pub struct OrderLine {
+    pub sku: String,
+    pub quantity: u32,
+}
+// module: crate::modules::orders
+pub struct Shipment {
+    pub carrier: String,
+}
+// module: crate::modules::orders
+pub struct Store {
+    config: Config,
+    customers: HashMap<u64, Customer>,
+}